@@ -0,0 +1,645 @@
+use crate::workspace::Workspace;
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing_core::{
+    callsite::Identifier, field::FieldSet, metadata::Kind, subscriber::Interest, Level, Metadata,
+};
+use tracing_line_filter::{
+    source::{Callsite, CallsiteKind, FindingKind},
+    Directive, FilterConfig, LineFilter, LintKind,
+};
+
+/// Checks the directives in the config file at `path` against the source
+/// code in `workspace`, printing a diagnostic for each problem found.
+///
+/// Returns the number of problems found.
+pub fn check(workspace: &Workspace, path: &Path) -> Result<usize, Box<dyn Error>> {
+    let src = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read '{}': {}", path.display(), e))?;
+    let callsites = workspace.callsites(&[])?;
+    let mut config = Config {
+        path: path.display().to_string(),
+        problems: 0,
+        diagnostics: Vec::new(),
+    };
+    check_src(&mut config, &src, workspace.root(), &callsites)?;
+    for diagnostic in &config.diagnostics {
+        println!("{}", diagnostic);
+    }
+    Ok(config.problems)
+}
+
+/// Checks the directives in `src` against the `callsites` in the workspace
+/// at `root`, reporting each problem found to `config`.
+fn check_src(
+    config: &mut Config,
+    src: &str,
+    root: &Path,
+    callsites: &[Callsite],
+) -> Result<(), Box<dyn Error>> {
+    // Parse the config file, remembering which line each directive is on.
+    let mut entries = Vec::new();
+    // A filter with only the config's directives that enable spans and
+    // events without being line entries (such as `EnvFilter` directives and
+    // module prefixes), for finding entries that they already enable.
+    let mut env = LineFilter::new();
+    // Each of those directives, with the line it is on and a filter with only
+    // that directive, for saying which of them enables an entry.
+    let mut enablers = Vec::new();
+    // Every deny directive, for finding the ones that deny nothing.
+    let mut denies = Vec::new();
+    // Every unconditional directive, for linting the config as a whole.
+    let mut unconditional = Vec::new();
+    // The `[profile.NAME]` section that each entry is in, if any. Entries in
+    // different profiles may overlap, since they aren't necessarily active
+    // at the same time.
+    let mut profile = None;
+    // Whether the rest of the section only applies in some environments.
+    let mut section_conditional = false;
+    for (i, line) in src.lines().enumerate() {
+        let line_num = i + 1;
+        let line = line.split('#').next().unwrap_or_default();
+        if line.trim().starts_with('[') && line.trim().ends_with(']') {
+            match profile_name(line) {
+                Some(name) => profile = Some(name.to_owned()),
+                None => config.error(line_num, "expected `[profile.NAME]`", None),
+            }
+            section_conditional = false;
+            continue;
+        }
+        // Entries are checked whether or not their `when` clause is met
+        // here, since they are meant to apply somewhere.
+        let (line, conditional) = strip_when(line);
+        if conditional && line.trim().is_empty() {
+            section_conditional = true;
+        }
+        let conditional = conditional || section_conditional;
+        // Span fields aren't directives, and are checked when the config is
+        // parsed.
+        if is_fields_line(line) {
+            continue;
+        }
+        for directive in line.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let directive = directive.parse::<Directive>();
+            match directive {
+                Ok(Directive::Env(_)) if profile.is_some() => {}
+                Ok(ref directive) if !conditional => {
+                    unconditional.push((line_num, profile.clone(), directive.clone()))
+                }
+                _ => {}
+            }
+            match directive {
+                Ok(directive @ (Directive::Module { .. } | Directive::File { .. })) => {
+                    entries.push((line_num, profile.clone(), directive))
+                }
+                // Denies are checked against the source code wherever they
+                // are, like entries.
+                Ok(Directive::Deny(denied)) => denies.push((line_num, *denied)),
+                Ok(Directive::Env(_)) if profile.is_some() => config.error(
+                    line_num,
+                    "profiles may not contain `EnvFilter` directives",
+                    None,
+                ),
+                // Other directives in profiles only apply while the profile
+                // is active, and conditional ones only in some environments.
+                Ok(_) if profile.is_some() || conditional => {}
+                Ok(directive) => {
+                    env.add_directive(directive.clone())?;
+                    let mut only = LineFilter::new();
+                    only.add_directive(directive.clone())?;
+                    enablers.push((line_num, directive, only));
+                }
+                Err(e) => config.error(line_num, &e.to_string(), None),
+            }
+        }
+    }
+
+    let mut filter = LineFilter::new();
+    for (_, _, directive) in &entries {
+        filter.add_directive(directive.clone())?;
+    }
+    let findings = filter
+        .validate_sources(root)?
+        .into_iter()
+        .map(|finding| {
+            let key = format!("{}@{}", finding.location(), finding.line());
+            (key, finding.kind().clone())
+        })
+        .collect::<HashMap<_, _>>();
+
+    // The first entry seen for each directive, and for each callsite.
+    let mut seen = HashMap::new();
+    let mut enabled_by = HashMap::<(Option<String>, PathBuf, u32), (usize, String)>::new();
+    for (line_num, profile, directive) in entries {
+        // Line hashes don't change what an entry enables, so leave them out.
+        let key = match directive {
+            Directive::Module { line, .. } | Directive::File { line, .. } => {
+                format!("{}@{}", location(&directive), line)
+            }
+            _ => {
+                unreachable!("only line directives are entries")
+            }
+        };
+        let first = seen
+            .get(&(None, key.clone()))
+            .or_else(|| seen.get(&(profile.clone(), key.clone())));
+        if let Some(first) = first {
+            config.warn(
+                line_num,
+                &format!("duplicate entry `{}` (first on line {})", key, first),
+                Some("remove this entry"),
+            );
+            continue;
+        }
+        seen.insert((profile.clone(), key.clone()), line_num);
+
+        match findings.get(&key) {
+            Some(FindingKind::UnknownModule) => {
+                let help = suggest(&directive, callsites, root);
+                config.warn(
+                    line_num,
+                    &format!(
+                        "unreachable entry `{}`: no spans or events found in this module",
+                        key
+                    ),
+                    Some(help.as_deref().unwrap_or("check the module path for typos")),
+                );
+                continue;
+            }
+            Some(FindingKind::MissingFile) => {
+                let help = suggest(&directive, callsites, root);
+                config.warn(
+                    line_num,
+                    &format!("unreachable entry `{}`: file does not exist", key),
+                    help.as_deref(),
+                );
+                continue;
+            }
+            Some(FindingKind::Nearby { line }) => {
+                config.warn(
+                    line_num,
+                    &format!("stale entry `{}`: no span or event on this line", key),
+                    Some(&format!(
+                        "the nearest is `{}@{}`",
+                        location(&directive),
+                        line
+                    )),
+                );
+                continue;
+            }
+            Some(&FindingKind::Drifted { line }) => {
+                let help = line.map(|line| {
+                    format!("it may have moved to `{}@{}`", location(&directive), line)
+                });
+                config.warn(
+                    line_num,
+                    &format!(
+                        "drifted entry `{}`: the line has changed since this entry was written",
+                        key
+                    ),
+                    Some(
+                        help.as_deref()
+                            .unwrap_or("check that this entry still points at the right line"),
+                    ),
+                );
+                continue;
+            }
+            Some(FindingKind::NoCallsite) => {
+                config.warn(
+                    line_num,
+                    &format!(
+                        "stale entry `{}`: no span or event on or near this line",
+                        key
+                    ),
+                    Some("remove this entry"),
+                );
+                continue;
+            }
+            None => {}
+        }
+
+        let matched = callsites
+            .iter()
+            .filter(|callsite| matches(&directive, callsite, root));
+        for callsite in matched {
+            let id = (callsite.file().to_path_buf(), callsite.line());
+            let first = enabled_by
+                .get(&(None, id.0.clone(), id.1))
+                .or_else(|| enabled_by.get(&(profile.clone(), id.0.clone(), id.1)));
+            if let Some((first_line, first)) = first {
+                config.warn(
+                    line_num,
+                    &format!(
+                        "shadowed entry `{}`: already enabled by `{}` on line {}",
+                        key, first, first_line
+                    ),
+                    Some("remove this entry"),
+                );
+                break;
+            }
+            if env_enables(&env, callsite) {
+                let by = enablers
+                    .iter()
+                    .find(|(_, _, only)| env_enables(only, callsite));
+                let message = match by {
+                    Some((first_line, first, _)) => format!(
+                        "shadowed entry `{}`: already enabled by `{}` on line {}",
+                        key, first, first_line
+                    ),
+                    // No one directive enables the entry, but together they
+                    // do.
+                    None => {
+                        let lines = enablers
+                            .iter()
+                            .map(|(line_num, _, _)| line_num.to_string())
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        let plural = if enablers.len() == 1 { "" } else { "s" };
+                        format!(
+                            "shadowed entry `{}`: already enabled by the directives on line{} {}",
+                            key, plural, lines,
+                        )
+                    }
+                };
+                config.warn(line_num, &message, Some("remove this entry"));
+                break;
+            }
+            enabled_by.insert((profile.clone(), id.0, id.1), (line_num, key.clone()));
+        }
+    }
+
+    // Denies that match no callsite in the source code deny nothing,
+    // whatever else the config enables.
+    let mut unused_denies = Vec::new();
+    for (line_num, denied) in denies {
+        if !matches!(
+            denied,
+            Directive::Module { .. } | Directive::File { .. } | Directive::ModulePrefix { .. }
+        ) || callsites
+            .iter()
+            .any(|callsite| matches(&denied, callsite, root))
+        {
+            continue;
+        }
+        let help = suggest(&denied, callsites, root);
+        config.warn(
+            line_num,
+            &format!(
+                "unused deny directive `!{}`: no spans or events match it",
+                denied
+            ),
+            Some(
+                help.as_deref()
+                    .unwrap_or("remove this directive, or check it for typos"),
+            ),
+        );
+        unused_denies.push(line_num);
+    }
+
+    lint(config, &unconditional, &unused_denies)?;
+    Ok(())
+}
+
+/// Reports the problems that [`FilterConfig::lint`] finds with the config's
+/// directives, other than its entries, which are checked against the source
+/// code instead, and the deny directives on the `unused_denies` lines, which
+/// have already been reported.
+fn lint(
+    config: &mut Config,
+    directives: &[(usize, Option<String>, Directive)],
+    unused_denies: &[usize],
+) -> Result<(), Box<dyn Error>> {
+    let mut src = String::new();
+    let mut profile = None;
+    for (_, section, directive) in directives {
+        if section != &profile {
+            if let Some(name) = section {
+                src.push_str(&format!("[profile.{}]\n", name));
+            }
+            profile = section.clone();
+        }
+        src.push_str(&format!("{}\n", directive));
+    }
+    let lints = src.parse::<FilterConfig>()?.lint();
+
+    // The directives that a problem has already been reported for.
+    let mut reported = vec![false; directives.len()];
+    for lint in lints {
+        if matches!(
+            lint.directive(),
+            Directive::Module { .. } | Directive::File { .. }
+        ) {
+            continue;
+        }
+        if lint.kind() == &LintKind::UnusedDeny
+            && directives.iter().any(|(line_num, _, directive)| {
+                directive == lint.directive() && unused_denies.contains(line_num)
+            })
+        {
+            continue;
+        }
+        let mut matching = (0..directives.len()).filter(|&i| {
+            let (_, profile, directive) = &directives[i];
+            !reported[i] && profile.as_deref() == lint.profile() && directive == lint.directive()
+        });
+        // A duplicate is reported on a later line than the directive it
+        // duplicates.
+        let i = match lint.kind() {
+            LintKind::Duplicate { .. } => matching.next_back(),
+            _ => matching.next(),
+        };
+        let help = match lint.kind() {
+            LintKind::UnusedDeny => "remove this directive, or check it for typos",
+            _ => "remove this directive",
+        };
+        if let Some(i) = i {
+            reported[i] = true;
+            config.warn(directives[i].0, &lint.to_string(), Some(help));
+        }
+    }
+    Ok(())
+}
+
+struct Config {
+    path: String,
+    problems: usize,
+    /// The diagnostics reported so far, to print once the config has been
+    /// checked.
+    diagnostics: Vec<String>,
+}
+
+impl Config {
+    fn error(&mut self, line: usize, message: &str, help: Option<&str>) {
+        self.report("error", line, message, help)
+    }
+
+    fn warn(&mut self, line: usize, message: &str, help: Option<&str>) {
+        self.report("warning", line, message, help)
+    }
+
+    fn report(&mut self, severity: &str, line: usize, message: &str, help: Option<&str>) {
+        self.problems += 1;
+        let mut diagnostic = format!("{}:{}: {}: {}", self.path, line, severity, message);
+        if let Some(help) = help {
+            diagnostic.push_str(&format!("\n    help: {}", help));
+        }
+        self.diagnostics.push(diagnostic);
+    }
+}
+
+/// Returns the module or file path of a `Module` or `File` directive.
+fn location(directive: &Directive) -> &str {
+    match directive {
+        Directive::Module { module, .. } => module,
+        Directive::File { file, .. } => file,
+        _ => {
+            unreachable!("only line directives are entries")
+        }
+    }
+}
+
+/// Splits a `when = { ... }` clause off the end of a line, returning the
+/// rest of the line and whether there was a clause.
+fn strip_when(line: &str) -> (&str, bool) {
+    let mut search = 0;
+    while let Some(i) = line[search..].find("when") {
+        let start = search + i;
+        search = start + "when".len();
+        let preceded = line[..start]
+            .chars()
+            .next_back()
+            .is_none_or(|c| c.is_whitespace() || c == ',');
+        let followed = line[search..]
+            .trim_start()
+            .strip_prefix('=')
+            .is_some_and(|rest| rest.trim_start().starts_with('{'));
+        if preceded && followed {
+            return (&line[..start], true);
+        }
+    }
+    (line, false)
+}
+
+/// Returns `true` if `line` is a `fields = { ... }` line.
+fn is_fields_line(line: &str) -> bool {
+    line.trim()
+        .strip_prefix("fields")
+        .and_then(|rest| rest.trim_start().strip_prefix('='))
+        .is_some_and(|rest| rest.trim_start().starts_with('{'))
+}
+
+/// Returns the name in a `[profile.NAME]` header line.
+fn profile_name(line: &str) -> Option<&str> {
+    let name = line
+        .trim()
+        .strip_prefix("[profile.")?
+        .strip_suffix(']')?
+        .trim();
+    if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == ',') {
+        return None;
+    }
+    Some(name)
+}
+
+/// Returns `true` if `directive` enables `callsite`.
+fn matches(directive: &Directive, callsite: &Callsite, root: &Path) -> bool {
+    match directive {
+        Directive::ModulePrefix { prefix } => callsite
+            .module_path()
+            .strip_prefix(prefix.as_str())
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with("::")),
+        Directive::Module { module, line, .. } => {
+            callsite.module_path() == module && callsite.line() == *line
+        }
+        Directive::File { file, line, .. } => {
+            let file = Path::new(file);
+            callsite.file() == file.strip_prefix(root).unwrap_or(file) && callsite.line() == *line
+        }
+        _ => false,
+    }
+}
+
+/// Returns a suggestion naming the module or file with callsites whose path
+/// is closest to the one in `directive`, if one is close enough to be a typo.
+fn suggest(directive: &Directive, callsites: &[Callsite], root: &Path) -> Option<String> {
+    let (path, paths, suffix) = match directive {
+        Directive::Module { module, line, .. } => (
+            module.as_str(),
+            callsites
+                .iter()
+                .map(|callsite| callsite.module_path().to_owned())
+                .collect::<Vec<_>>(),
+            format!("@{}", line),
+        ),
+        Directive::ModulePrefix { prefix } => (
+            prefix.as_str(),
+            callsites
+                .iter()
+                .map(|callsite| callsite.module_path().to_owned())
+                .collect(),
+            "::**".to_owned(),
+        ),
+        Directive::File { file, line, .. } => (
+            file.as_str(),
+            callsites
+                .iter()
+                .map(|callsite| root.join(callsite.file()).to_string_lossy().into_owned())
+                .collect(),
+            format!("@{}", line),
+        ),
+        _ => return None,
+    };
+    let (distance, closest) = paths
+        .iter()
+        .filter(|candidate| candidate.as_str() != path)
+        .map(|candidate| (edit_distance(path, candidate), candidate))
+        .min()?;
+    if distance > path.chars().count() / 3 + 1 {
+        return None;
+    }
+    Some(format!("did you mean `{}{}`?", closest, suffix))
+}
+
+/// Returns the number of single-character insertions, deletions, and
+/// substitutions needed to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &b) in b.iter().enumerate() {
+            let substituted = diagonal + usize::from(a != b);
+            diagonal = row[j + 1];
+            row[j + 1] = substituted.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Returns `true` if the directives in `env` always enable `callsite`.
+fn env_enables(env: &LineFilter, callsite: &Callsite) -> bool {
+    struct Probe;
+    static PROBE: Probe = Probe;
+    static PROBE_META: Metadata<'static> = tracing_core::metadata! {
+        name: "probe",
+        target: module_path!(),
+        level: Level::TRACE,
+        fields: &[],
+        callsite: &PROBE,
+        kind: Kind::EVENT,
+    };
+    impl tracing_core::Callsite for Probe {
+        fn set_interest(&self, _: Interest) {}
+        fn metadata(&self) -> &Metadata<'_> {
+            &PROBE_META
+        }
+    }
+
+    // `LineFilter::explain` only accepts `'static` metadata, so leak it. We only do
+    // this once per entry, and the process exits shortly afterwards.
+    fn leak(s: &str) -> &'static str {
+        Box::leak(s.to_owned().into_boxed_str())
+    }
+    let module_path = leak(callsite.module_path());
+    let kind = match callsite.kind() {
+        CallsiteKind::Span => Kind::SPAN,
+        CallsiteKind::Event => Kind::EVENT,
+    };
+    let metadata = Box::leak(Box::new(Metadata::new(
+        leak(callsite.message().unwrap_or("event")),
+        module_path,
+        // If the level is unknown, assume the worst.
+        callsite.level().unwrap_or(Level::TRACE),
+        Some(leak(&callsite.file().to_string_lossy())),
+        Some(callsite.line()),
+        Some(module_path),
+        FieldSet::new(&[], Identifier(&PROBE)),
+        kind,
+    )));
+    env.explain(metadata).is_enabled()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_line_filter::source;
+
+    /// Checks `src` against the callsites in this repository, whose
+    /// `examples/basic.rs` has events on lines 5, 6, 24, 25, and 27.
+    fn check(src: &str) -> Vec<String> {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+        let callsites = source::scan_workspace(root).unwrap();
+        let mut config = Config {
+            path: "lines.conf".to_owned(),
+            problems: 0,
+            diagnostics: Vec::new(),
+        };
+        check_src(&mut config, src, root, &callsites).unwrap();
+        assert_eq!(config.problems, config.diagnostics.len());
+        config.diagnostics
+    }
+
+    #[test]
+    fn shadowing_is_attributed_to_the_enabling_directive() {
+        let diagnostics = check("!basic@24\nbasic::**\nbasic::some_module@6\n");
+        assert_eq!(
+            diagnostics,
+            vec![
+                "lines.conf:3: warning: shadowed entry `basic::some_module@6`: already enabled \
+                 by `basic::**` on line 2\n    help: remove this entry"
+            ]
+        );
+    }
+
+    #[test]
+    fn denies_do_not_shadow_entries() {
+        let diagnostics = check("basic@info\n!basic@24\nbasic@25\n");
+        assert_eq!(diagnostics, Vec::<String>::new());
+    }
+
+    #[test]
+    fn denies_are_checked_against_the_source() {
+        let diagnostics = check("basic@25\n!basic@99\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].starts_with(
+            "lines.conf:2: warning: unused deny directive `!basic@99`: no spans or events match it"
+        ));
+
+        let diagnostics = check("basic::**\n!bsaic::**\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].ends_with("help: did you mean `basic::**`?"));
+    }
+
+    #[test]
+    fn unknown_modules_suggest_close_matches() {
+        let diagnostics = check("bsaic@25\nbasic::some_modul@6\nnothing_like_it@1\n");
+        assert_eq!(diagnostics.len(), 3);
+        assert!(diagnostics[0].ends_with("help: did you mean `basic@25`?"));
+        assert!(diagnostics[1].ends_with("help: did you mean `basic::some_module@6`?"));
+        assert!(diagnostics[2].ends_with("help: check the module path for typos"));
+    }
+
+    #[test]
+    fn missing_files_suggest_close_matches() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+        let diagnostics = check(&format!("{}/examples/bsaic.rs@25\n", root.display()));
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].ends_with(&format!(
+            "help: did you mean `{}/examples/basic.rs@25`?",
+            root.display()
+        )));
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("basic", "basic"), 0);
+        assert_eq!(edit_distance("bsaic", "basic"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+}
@@ -0,0 +1,64 @@
+use std::{
+    env,
+    error::Error,
+    path::{Path, PathBuf},
+    process::{self, Command},
+};
+
+/// A temporary `git worktree` with a revision checked out, which is removed
+/// when dropped.
+pub struct Worktree {
+    repo: PathBuf,
+    path: PathBuf,
+}
+
+impl Worktree {
+    /// Checks out `rev` of the git repository containing `dir`.
+    pub fn checkout(dir: &Path, rev: &str) -> Result<Self, Box<dyn Error>> {
+        let repo = PathBuf::from(git(dir, &["rev-parse", "--show-toplevel"])?);
+        let path = env::temp_dir().join(format!(
+            "cargo-line-filter-{}-{}",
+            process::id(),
+            rev.replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+        ));
+        let path_str = path.to_str().ok_or("temporary directory is not UTF-8")?;
+        git(&repo, &["worktree", "add", "--detach", path_str, rev])?;
+        Ok(Self { repo, path })
+    }
+
+    /// Returns the path in this worktree corresponding to `dir` in the
+    /// repository it was checked out from.
+    pub fn join(&self, dir: &Path) -> PathBuf {
+        let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        let repo = self
+            .repo
+            .canonicalize()
+            .unwrap_or_else(|_| self.repo.clone());
+        match dir.strip_prefix(repo) {
+            Ok(relative) => self.path.join(relative),
+            Err(_) => self.path.clone(),
+        }
+    }
+}
+
+impl Drop for Worktree {
+    fn drop(&mut self) {
+        if let Some(path) = self.path.to_str() {
+            let _ = git(&self.repo, &["worktree", "remove", "--force", path]);
+        }
+    }
+}
+
+/// Runs `git` in `dir`, returning its trimmed standard output.
+fn git(dir: &Path, args: &[&str]) -> Result<String, Box<dyn Error>> {
+    let output = Command::new("git").arg("-C").arg(dir).args(args).output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "`git {}` failed:\n{}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr)
+        )
+        .into());
+    }
+    Ok(String::from_utf8(output.stdout)?.trim().to_owned())
+}
@@ -0,0 +1,302 @@
+//! A Cargo subcommand for discovering `tracing` callsites to enable with
+//! `tracing-line-filter`.
+use std::{collections::HashMap, env, error::Error, fs, path::PathBuf, process};
+use tracing_line_filter::source::{self, Callsite};
+
+mod check;
+mod git;
+mod reanchor;
+#[cfg(feature = "tui")]
+mod tui;
+mod workspace;
+
+use self::workspace::Workspace;
+
+const USAGE: &str = "\
+Discover `tracing` callsites to enable with `tracing-line-filter`.
+
+USAGE:
+    cargo line-filter <COMMAND> [OPTIONS]
+
+COMMANDS:
+    list               List every `tracing` span and event callsite in the workspace
+    check <CONFIG>     Check a file of directives for unreachable, stale, duplicate,
+                       or shadowed entries and unused deny directives, exiting
+                       with an error if any are found
+    reanchor <CONFIG>  Update the line numbers in a file of directives after the
+                       source code changes, by finding the text of each entry's line
+                       from the `--old` source in the current source
+    tui <ADDR>         Toggle the entries of a running program serving the JSON-RPC
+                       control protocol on a TCP address, watching their hits live,
+                       authenticating with the token in `LINE_FILTER_TOKEN`
+                       (requires the `tui` feature)
+
+OPTIONS:
+        --format <FORMAT>       Output format: `text` or `json` [default: text]
+        --hashes                Include line hashes in the directives printed by `list`
+        --manifest-path <PATH>  Path to the workspace's Cargo.toml
+    -p, --package <NAME>        Only scan the given package (may be repeated), or, for
+                                `tui`, only list callsites in modules starting with NAME
+        --old <DIR|REV|RANGE>   The source the config refers to, for `reanchor`: a
+                                directory, a git revision, or a git range `OLD..NEW`
+    -h, --help                  Print this message
+";
+
+#[derive(Debug)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Debug)]
+struct Args {
+    command: String,
+    config: Option<PathBuf>,
+    addr: Option<String>,
+    format: Format,
+    hashes: bool,
+    manifest_path: Option<PathBuf>,
+    packages: Vec<String>,
+    old: Option<String>,
+}
+
+fn main() {
+    let args = match Args::parse() {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            print!("{}", USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = run(args) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    if matches!(args.command.as_str(), "check" | "reanchor") && args.config.is_none() {
+        return Err(format!("`{}` requires a config file", args.command).into());
+    }
+    if args.command == "tui" {
+        let addr = args
+            .addr
+            .ok_or("`tui` requires an address, such as `127.0.0.1:7070`")?;
+        return tui(&addr, &args.packages);
+    }
+
+    let workspace = Workspace::load(args.manifest_path.as_deref())?;
+    match args.command.as_str() {
+        "list" => {
+            let callsites = workspace.callsites(&args.packages)?;
+            let hashes = line_hashes(&callsites);
+            match args.format {
+                Format::Text => print_text(&callsites, args.hashes.then_some(&hashes[..])),
+                Format::Json => print_json(&callsites, &hashes)?,
+            }
+            Ok(())
+        }
+        "check" => {
+            // `Workspace::callsites` changes the current directory, so
+            // resolve the config path first.
+            let config = args.config.expect("checked above").canonicalize()?;
+            match check::check(&workspace, &config)? {
+                0 => Ok(()),
+                1 => Err("found 1 problem".into()),
+                n => Err(format!("found {} problems", n).into()),
+            }
+        }
+        "reanchor" => {
+            let old = args
+                .old
+                .ok_or("`reanchor` requires `--old <DIR|REV|RANGE>`")?;
+            let config = args.config.expect("checked above");
+            match reanchor::reanchor(&workspace, &config, &old)? {
+                0 => Ok(()),
+                1 => Err("1 entry could not be re-anchored".into()),
+                n => Err(format!("{} entries could not be re-anchored", n).into()),
+            }
+        }
+        cmd => Err(format!("unknown command `{}`", cmd).into()),
+    }
+}
+
+#[cfg(feature = "tui")]
+fn tui(addr: &str, packages: &[String]) -> Result<(), Box<dyn Error>> {
+    match packages {
+        [] => tui::run(addr, ""),
+        [prefix] => tui::run(addr, prefix),
+        _ => Err("`tui` takes at most one `--package`".into()),
+    }
+}
+
+#[cfg(not(feature = "tui"))]
+fn tui(_: &str, _: &[String]) -> Result<(), Box<dyn Error>> {
+    Err("`tui` requires the `tui` feature: `cargo install cargo-line-filter --features tui`".into())
+}
+
+/// Returns the line hash of each callsite, or `None` if its file could not
+/// be read.
+///
+/// Callsite file paths are relative to the workspace root, which
+/// `Workspace::callsites` makes the current directory.
+fn line_hashes(callsites: &[Callsite]) -> Vec<Option<u32>> {
+    let mut files = HashMap::new();
+    callsites
+        .iter()
+        .map(|callsite| {
+            let src = files
+                .entry(callsite.file())
+                .or_insert_with(|| fs::read_to_string(callsite.file()).ok());
+            let line = src
+                .as_deref()?
+                .lines()
+                .nth(callsite.line().checked_sub(1)? as usize)?;
+            Some(source::line_hash(line))
+        })
+        .collect()
+}
+
+fn print_text(callsites: &[Callsite], hashes: Option<&[Option<u32>]>) {
+    let rows = callsites
+        .iter()
+        .enumerate()
+        .map(|(i, callsite)| {
+            let hash = hashes
+                .and_then(|hashes| hashes[i])
+                .map(|hash| format!(":{:08x}", hash))
+                .unwrap_or_default();
+            [
+                format!("{}@{}{}", callsite.module_path(), callsite.line(), hash),
+                callsite
+                    .level()
+                    .map(|level| level.to_string())
+                    .unwrap_or_else(|| "?".to_owned()),
+                callsite.kind().to_string(),
+                format!("{}:{}", callsite.file().display(), callsite.line()),
+                callsite
+                    .message()
+                    .map(|message| format!("{:?}", message))
+                    .unwrap_or_default(),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    let header = ["DIRECTIVE", "LEVEL", "KIND", "LOCATION", "MESSAGE"];
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, col) in widths.iter_mut().zip(row) {
+            *width = (*width).max(col.len());
+        }
+    }
+
+    let print_row = |row: [&str; 5]| {
+        println!(
+            "{:<w0$}  {:<w1$}  {:<w2$}  {:<w3$}  {}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            row[4],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+            w3 = widths[3],
+        );
+    };
+    print_row(header);
+    for row in &rows {
+        print_row([&row[0], &row[1], &row[2], &row[3], &row[4]]);
+    }
+}
+
+fn print_json(callsites: &[Callsite], hashes: &[Option<u32>]) -> Result<(), Box<dyn Error>> {
+    let callsites = callsites
+        .iter()
+        .zip(hashes)
+        .map(|(callsite, hash)| {
+            serde_json::json!({
+                "kind": callsite.kind().as_str(),
+                "level": callsite.level().map(|level| level.as_str()),
+                "module_path": callsite.module_path(),
+                "file": callsite.file(),
+                "line": callsite.line(),
+                "hash": hash.map(|hash| format!("{:08x}", hash)),
+                "message": callsite.message(),
+            })
+        })
+        .collect::<Vec<_>>();
+    println!("{}", serde_json::to_string_pretty(&callsites)?);
+    Ok(())
+}
+
+// === impl Args ===
+
+impl Args {
+    /// Parses command-line arguments, returning `None` if help was requested.
+    fn parse() -> Result<Option<Self>, Box<dyn Error>> {
+        let mut args = env::args().skip(1).peekable();
+        // When run as `cargo line-filter`, Cargo passes the subcommand name as
+        // the first argument.
+        if args.peek().map(String::as_str) == Some("line-filter") {
+            args.next();
+        }
+
+        let mut command = None;
+        let mut config = None;
+        let mut addr = None;
+        let mut format = Format::Text;
+        let mut hashes = false;
+        let mut manifest_path = None;
+        let mut packages = Vec::new();
+        let mut old = None;
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| format!("`{}` requires a value", name))
+            };
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "--format" => {
+                    format = match value("--format")?.as_str() {
+                        "text" => Format::Text,
+                        "json" => Format::Json,
+                        f => return Err(format!("unknown format `{}`", f).into()),
+                    }
+                }
+                "--hashes" => hashes = true,
+                "--manifest-path" => manifest_path = Some(value("--manifest-path")?.into()),
+                "-p" | "--package" => packages.push(value("--package")?),
+                "--old" => old = Some(value("--old")?),
+                arg if arg.starts_with('-') => {
+                    return Err(format!("unknown option `{}`", arg).into())
+                }
+                _ if command.is_none() => command = Some(arg),
+                _ if matches!(command.as_deref(), Some("check" | "reanchor"))
+                    && config.is_none() =>
+                {
+                    config = Some(arg.into())
+                }
+                _ if command.as_deref() == Some("tui") && addr.is_none() => addr = Some(arg),
+                arg => return Err(format!("unexpected argument `{}`", arg).into()),
+            }
+        }
+
+        let command = command.ok_or("missing command")?;
+        Ok(Some(Self {
+            command,
+            config,
+            addr,
+            format,
+            hashes,
+            manifest_path,
+            packages,
+            old,
+        }))
+    }
+}
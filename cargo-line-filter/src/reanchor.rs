@@ -0,0 +1,60 @@
+use crate::{git::Worktree, workspace::Workspace};
+use std::{error::Error, fs, path::Path};
+use tracing_line_filter::source;
+
+/// Re-anchors the entries in the config file at `path` to the current source
+/// code in `workspace`, updating the file in place.
+///
+/// `old` is the source tree the config's line numbers refer to: either a
+/// directory containing the old version of the workspace, a git revision,
+/// or a git range (`OLD..NEW`), in which case entries are re-anchored to
+/// `NEW` rather than to the working tree.
+///
+/// Returns the number of entries that could not be re-anchored.
+pub fn reanchor(workspace: &Workspace, path: &Path, old: &str) -> Result<usize, Box<dyn Error>> {
+    let root = workspace.root();
+    let mut worktrees = Vec::new();
+    let (old_root, new_root) = if Path::new(old).is_dir() {
+        (Path::new(old).to_path_buf(), root.to_path_buf())
+    } else if let Some((old, new)) = old.split_once("..") {
+        let old = Worktree::checkout(root, old)?;
+        let new = Worktree::checkout(root, new)?;
+        let roots = (old.join(root), new.join(root));
+        worktrees.extend([old, new]);
+        roots
+    } else {
+        let old = Worktree::checkout(root, old)?;
+        let roots = (old.join(root), root.to_path_buf());
+        worktrees.push(old);
+        roots
+    };
+
+    let src = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read '{}': {}", path.display(), e))?;
+    let reanchored = source::reanchor(&src, &old_root, &new_root)?;
+    drop(worktrees);
+
+    let mut lost = 0;
+    for entry in reanchored.entries() {
+        let severity = match entry.after() {
+            Some(_) => "note",
+            None => {
+                lost += 1;
+                "warning"
+            }
+        };
+        println!(
+            "{}:{}: {}: {}",
+            path.display(),
+            entry.config_line(),
+            severity,
+            entry
+        );
+    }
+
+    if reanchored.config() != src {
+        fs::write(path, reanchored.config())
+            .map_err(|e| format!("failed to write '{}': {}", path.display(), e))?;
+    }
+    Ok(lost)
+}
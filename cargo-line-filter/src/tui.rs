@@ -0,0 +1,388 @@
+//! A terminal UI for toggling the entries of a running program's filter.
+//!
+//! The UI connects to a program serving the `tracing-line-filter` JSON-RPC
+//! control protocol on a TCP socket (see `jsonrpc::Server::serve_listener`),
+//! lists the callsites the program has recorded, and refreshes their hit
+//! counts every second. Entries are toggled with single keystrokes, so the
+//! terminal is switched out of line-buffered mode with `stty` while the UI
+//! runs. The program's token is read from the `LINE_FILTER_TOKEN`
+//! environment variable, as the server reads it.
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    env,
+    error::Error,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    process::{Command, Stdio},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+/// How often the callsites and their hit counts are fetched again.
+const REFRESH: Duration = Duration::from_secs(1);
+
+const HELP: &str = "j/k or arrows: move  space/enter: toggle entry  q: quit";
+
+/// Runs the UI against the program serving JSON-RPC on `addr`, listing the
+/// callsites in modules starting with `prefix`, until `q` is pressed.
+pub(crate) fn run(addr: &str, prefix: &str) -> Result<(), Box<dyn Error>> {
+    let mut client =
+        Client::connect(addr).map_err(|e| format!("could not connect to {}: {}", addr, e))?;
+    if let Ok(token) = env::var("LINE_FILTER_TOKEN") {
+        client
+            .call("filter/authenticate", json!({ "token": token }))
+            .map_err(|e| format!("could not authenticate to {}: {}", addr, e))?;
+    }
+    let _raw = RawMode::enable()?;
+    let keys = spawn_keys();
+
+    let mut tui = Tui::default();
+    let stdout = io::stdout();
+    loop {
+        tui.refresh(&mut client, prefix)?;
+        tui.draw(&mut stdout.lock())?;
+        let key = match keys.recv_timeout(REFRESH) {
+            Ok(key) => key,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        match key {
+            Key::Up => tui.selected = tui.selected.saturating_sub(1),
+            Key::Down => tui.selected = (tui.selected + 1).min(tui.rows.len().saturating_sub(1)),
+            Key::Toggle => tui.toggle(&mut client)?,
+            Key::Quit => break,
+        }
+    }
+    // Leave the shell below the UI's last frame.
+    println!();
+    Ok(())
+}
+
+/// A JSON-RPC client for the control protocol.
+struct Client {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    id: u64,
+}
+
+/// A recorded callsite, as listed by `filter/callsites`.
+#[derive(Debug, PartialEq)]
+struct Row {
+    directive: String,
+    kind: String,
+    name: String,
+    level: String,
+    enabled: bool,
+    /// The hits of the filter's entry for the callsite's line, if it has one.
+    hits: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+struct Tui {
+    rows: Vec<Row>,
+    selected: usize,
+    /// The output of the last toggle, or the error it failed with.
+    status: String,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Key {
+    Up,
+    Down,
+    Toggle,
+    Quit,
+}
+
+/// Restores the terminal's settings when dropped.
+struct RawMode {
+    saved: String,
+}
+
+/// Reads keystrokes from standard input on another thread, so that the UI
+/// can refresh while it waits for them.
+fn spawn_keys() -> mpsc::Receiver<Key> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut bytes = io::stdin().lock().bytes().map_while(Result::ok);
+        while let Some(key) = read_key(&mut bytes) {
+            let quit = key == Key::Quit;
+            if tx.send(key).is_err() || quit {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// Reads the next key the UI understands from `bytes`, skipping others,
+/// or returns `None` at the end of the input.
+fn read_key(bytes: &mut impl Iterator<Item = u8>) -> Option<Key> {
+    loop {
+        let key = match bytes.next()? {
+            b'k' => Key::Up,
+            b'j' => Key::Down,
+            b' ' | b'\n' | b'\r' => Key::Toggle,
+            b'q' => Key::Quit,
+            // Arrow keys are sent as `ESC [ A` through `ESC [ D`.
+            0x1b => match (bytes.next()?, bytes.next()?) {
+                (b'[', b'A') => Key::Up,
+                (b'[', b'B') => Key::Down,
+                _ => continue,
+            },
+            _ => continue,
+        };
+        return Some(key);
+    }
+}
+
+/// Returns the rows for a `filter/callsites` result, with the hits of the
+/// entries in a `filter/stats` result.
+fn rows(callsites: &Value, stats: &Value) -> Vec<Row> {
+    let hits = stats["entries"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| Some((entry["directive"].as_str()?, entry["hits"].as_u64()?)))
+        .collect::<HashMap<_, _>>();
+    let string = |value: &Value| value.as_str().unwrap_or_default().to_owned();
+    callsites
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|callsite| {
+            // Callsites without a line number can't be toggled.
+            let directive = callsite["directive"].as_str()?;
+            Some(Row {
+                directive: directive.to_owned(),
+                kind: string(&callsite["kind"]),
+                name: string(&callsite["name"]),
+                level: string(&callsite["level"]),
+                enabled: callsite["enabled"].as_bool().unwrap_or_default(),
+                hits: hits.get(directive).copied(),
+            })
+        })
+        .collect()
+}
+
+// === impl Client ===
+
+impl Client {
+    fn connect(addr: &str) -> io::Result<Self> {
+        let writer = TcpStream::connect(addr)?;
+        let reader = BufReader::new(writer.try_clone()?);
+        Ok(Self {
+            reader,
+            writer,
+            id: 0,
+        })
+    }
+
+    /// Calls `method`, returning its result, or its error message as an
+    /// error.
+    fn call(&mut self, method: &str, params: Value) -> Result<Value, Box<dyn Error>> {
+        self.id += 1;
+        let request =
+            json!({ "jsonrpc": "2.0", "id": self.id, "method": method, "params": params });
+        writeln!(self.writer, "{}", request)?;
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err("the program closed the connection".into());
+            }
+            let mut response = serde_json::from_str::<Value>(&line)?;
+            // Skip notifications, which have no `id`.
+            if response["id"] != self.id {
+                continue;
+            }
+            if let Some(message) = response["error"]["message"].as_str() {
+                return Err(message.into());
+            }
+            return Ok(response["result"].take());
+        }
+    }
+}
+
+// === impl Tui ===
+
+impl Tui {
+    fn refresh(&mut self, client: &mut Client, prefix: &str) -> Result<(), Box<dyn Error>> {
+        let callsites = client.call("filter/callsites", json!({ "prefix": prefix }))?;
+        let stats = client.call("filter/stats", Value::Null)?;
+        self.rows = rows(&callsites, &stats);
+        self.selected = self.selected.min(self.rows.len().saturating_sub(1));
+        Ok(())
+    }
+
+    /// Adds an entry for the selected callsite's line, or removes the entry
+    /// it has.
+    fn toggle(&mut self, client: &mut Client) -> Result<(), Box<dyn Error>> {
+        let row = match self.rows.get(self.selected) {
+            Some(row) => row,
+            None => return Ok(()),
+        };
+        let method = if row.hits.is_some() {
+            "filter/remove"
+        } else {
+            "filter/add"
+        };
+        let params = json!({ "directive": row.directive });
+        self.status = match client.call(method, params) {
+            Ok(output) => output.as_str().unwrap_or_default().to_owned(),
+            Err(e) => format!("error: {}", e),
+        };
+        Ok(())
+    }
+
+    fn draw(&self, out: &mut impl Write) -> io::Result<()> {
+        // Clear the screen and move the cursor to its top left corner. The
+        // terminal doesn't translate newlines while it isn't line-buffered.
+        write!(out, "\x1b[2J\x1b[H{}\r\n\r\n", HELP)?;
+        if self.rows.is_empty() {
+            write!(
+                out,
+                "no callsites recorded; the program must call `LineFilter::record_callsites`\r\n"
+            )?;
+        }
+        let width = self
+            .rows
+            .iter()
+            .map(|row| row.directive.len())
+            .max()
+            .unwrap_or(0);
+        for (i, row) in self.rows.iter().enumerate() {
+            let cursor = if i == self.selected { '>' } else { ' ' };
+            let entry = match row.hits {
+                Some(hits) => format!("[x] {:>6} hits", hits),
+                None => "[ ]".to_owned(),
+            };
+            let enabled = if row.enabled { "enabled" } else { "disabled" };
+            write!(
+                out,
+                "{} {:<width$}  {:<5}  {:<5}  {:<8}  {:<15}  {}\r\n",
+                cursor,
+                row.directive,
+                row.level,
+                row.kind,
+                enabled,
+                entry,
+                row.name,
+                width = width,
+            )?;
+        }
+        write!(out, "\r\n{}", self.status)?;
+        out.flush()
+    }
+}
+
+// === impl RawMode ===
+
+impl RawMode {
+    /// Stops the terminal from buffering and echoing input, remembering its
+    /// settings so that they can be restored.
+    fn enable() -> Result<Self, Box<dyn Error>> {
+        let saved = stty(&["-g"]).map_err(|e| format!("the TUI needs a terminal: {}", e))?;
+        stty(&["-icanon", "-echo", "min", "1"])?;
+        Ok(Self {
+            saved: saved.trim().to_owned(),
+        })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+        return Err(io::Error::other(error));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn reads_keys() {
+        let mut bytes = b"jx\x1b[A \x1b[Cq".iter().copied();
+        let keys = std::iter::from_fn(|| read_key(&mut bytes)).collect::<Vec<_>>();
+        assert_eq!(keys, [Key::Down, Key::Up, Key::Toggle, Key::Quit]);
+    }
+
+    #[test]
+    fn rows_have_their_entries_hits() {
+        let callsites = json!([
+            {"directive": "my_crate::db@142", "kind": "event", "name": "event src/db.rs:142",
+             "level": "DEBUG", "file": "src/db.rs", "fields": ["message"], "enabled": true},
+            {"directive": "my_crate::db@200", "kind": "span", "name": "query",
+             "level": "INFO", "file": "src/db.rs", "fields": [], "enabled": false},
+            {"directive": null, "kind": "event", "name": "log event",
+             "level": "INFO", "file": null, "fields": [], "enabled": true},
+        ]);
+        let stats = json!({ "entries": [{"directive": "my_crate::db@142", "hits": 3}] });
+        let rows = rows(&callsites, &stats);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].hits, Some(3));
+        assert!(rows[0].enabled);
+        assert_eq!(rows[1].directive, "my_crate::db@200");
+        assert_eq!(rows[1].hits, None);
+    }
+
+    #[test]
+    fn toggling_adds_and_removes_entries() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut stream = stream;
+            let mut methods = Vec::new();
+            for _ in 0..2 {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let request = serde_json::from_str::<Value>(&line).unwrap();
+                methods.push(request["method"].as_str().unwrap().to_owned());
+                // A notification comes first, and must be skipped.
+                writeln!(stream, r#"{{"jsonrpc":"2.0","method":"filter/changed"}}"#).unwrap();
+                let response = json!({ "jsonrpc": "2.0", "id": request["id"], "result": "ok" });
+                writeln!(stream, "{}", response).unwrap();
+            }
+            methods
+        });
+
+        let mut client = Client::connect(&addr).unwrap();
+        let row = |hits| Row {
+            directive: "my_crate::db@142".to_owned(),
+            kind: "event".to_owned(),
+            name: "query".to_owned(),
+            level: "DEBUG".to_owned(),
+            enabled: false,
+            hits,
+        };
+        let mut tui = Tui {
+            rows: vec![row(None)],
+            ..Tui::default()
+        };
+        tui.toggle(&mut client).unwrap();
+        assert_eq!(tui.status, "ok");
+        tui.rows = vec![row(Some(0))];
+        tui.toggle(&mut client).unwrap();
+        assert_eq!(server.join().unwrap(), ["filter/add", "filter/remove"]);
+
+        let mut out = Vec::new();
+        tui.draw(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("> my_crate::db@142  DEBUG  event  disabled  [x]      0 hits"));
+    }
+}
@@ -0,0 +1,103 @@
+use serde_json::Value;
+use std::{
+    env,
+    error::Error,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use tracing_line_filter::source::{self, Callsite};
+
+/// The crates in a Cargo workspace, as reported by `cargo metadata`.
+pub struct Workspace {
+    root: PathBuf,
+    targets: Vec<Target>,
+}
+
+struct Target {
+    package: String,
+    crate_name: String,
+    src_path: PathBuf,
+}
+
+impl Workspace {
+    pub fn load(manifest_path: Option<&Path>) -> Result<Self, Box<dyn Error>> {
+        let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+        let mut cmd = Command::new(cargo);
+        cmd.args(["metadata", "--format-version", "1", "--no-deps"]);
+        if let Some(path) = manifest_path {
+            cmd.arg("--manifest-path").arg(path);
+        }
+
+        let output = cmd.output()?;
+        if !output.status.success() {
+            return Err(format!(
+                "`cargo metadata` failed:\n{}",
+                String::from_utf8_lossy(&output.stderr)
+            )
+            .into());
+        }
+
+        let metadata: Value = serde_json::from_slice(&output.stdout)?;
+        let root = metadata["workspace_root"]
+            .as_str()
+            .ok_or("`cargo metadata` output is missing `workspace_root`")?
+            .into();
+
+        let mut targets = Vec::new();
+        for package in metadata["packages"].as_array().into_iter().flatten() {
+            let package_name = package["name"].as_str().unwrap_or_default();
+            for target in package["targets"].as_array().into_iter().flatten() {
+                let is_build_script = target["kind"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .any(|kind| kind == "custom-build");
+                let (name, src_path) = match (target["name"].as_str(), target["src_path"].as_str())
+                {
+                    (Some(name), Some(src_path)) if !is_build_script => (name, src_path),
+                    _ => continue,
+                };
+                targets.push(Target {
+                    package: package_name.to_owned(),
+                    crate_name: name.replace('-', "_"),
+                    src_path: src_path.into(),
+                });
+            }
+        }
+
+        Ok(Self { root, targets })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Scans all crates in the workspace (or only those in `packages`, if it
+    /// is not empty) for callsites.
+    ///
+    /// This changes the current directory to the workspace root, since
+    /// `file!()` paths for workspace crates are relative to it.
+    pub fn callsites(&self, packages: &[String]) -> Result<Vec<Callsite>, Box<dyn Error>> {
+        env::set_current_dir(&self.root)?;
+
+        let mut callsites = Vec::new();
+        for target in &self.targets {
+            if !packages.is_empty() && !packages.contains(&target.package) {
+                continue;
+            }
+            let src_path = target
+                .src_path
+                .strip_prefix(&self.root)
+                .unwrap_or(&target.src_path);
+            callsites.extend(source::scan_crate(&target.crate_name, src_path)?);
+        }
+
+        fn key(c: &Callsite) -> (&str, u32, &Path, &str, Option<&str>) {
+            let kind = c.kind().as_str();
+            (c.module_path(), c.line(), c.file(), kind, c.message())
+        }
+        callsites.sort_by(|a, b| key(a).cmp(&key(b)));
+        callsites.dedup();
+        Ok(callsites)
+    }
+}
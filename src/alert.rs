@@ -0,0 +1,116 @@
+use crate::{Directive, LineFilter};
+use std::{fmt, sync::Mutex};
+use tracing_core::Metadata;
+
+/// The callbacks registered with [`LineFilter::on_match`].
+#[derive(Default)]
+pub(crate) struct Alerts {
+    alerts: Vec<Alert>,
+}
+
+struct Alert {
+    directive: Directive,
+    callback: Mutex<Callback>,
+}
+
+type Callback = Box<dyn FnMut(&Metadata<'_>) + Send>;
+
+impl LineFilter {
+    /// Registers a callback that is called whenever the entry with
+    /// `directive` matches a span or event that is recorded.
+    ///
+    /// This lets a program react when a rare pinned line finally runs, such
+    /// as by paging someone, spending an error budget, or dumping its state.
+    /// The callback is called with the metadata of the matched span or
+    /// event, each time a span is created or an event is recorded, unless
+    /// the filter's entries have [expired] or are [disabled]. `directive`
+    /// should be a `Module`, `File`, `ModuleLevel`, or `ModulePrefix`
+    /// directive; a callback is only called when its entry is the most
+    /// specific one that enables a span or event, and never for any other
+    /// kind of directive. Source line hashes are ignored.
+    ///
+    /// A callback is not removed when its entry is, so that it is called
+    /// again if the entry is added back. Callbacks are called while the
+    /// span or event is being recorded, so they should be quick, and
+    /// shouldn't record spans or events themselves. If a callback is already
+    /// running when its entry matches again (such as on another thread), it
+    /// isn't called a second time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{
+    ///     atomic::{AtomicUsize, Ordering},
+    ///     Arc,
+    /// };
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::prelude::*;
+    ///
+    /// let directive = format!("{}@{}", module_path!(), line!() + 12);
+    /// let mut filter: LineFilter = directive.parse().unwrap();
+    ///
+    /// let fired = Arc::new(AtomicUsize::new(0));
+    /// let counter = fired.clone();
+    /// filter.on_match(directive.parse().unwrap(), move |metadata| {
+    ///     assert_eq!(metadata.name(), "rare_failure");
+    ///     counter.fetch_add(1, Ordering::Relaxed);
+    /// });
+    ///
+    /// let subscriber = tracing_subscriber::registry().with(filter);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info!(name: "rare_failure", "the rare failure happened");
+    /// });
+    ///
+    /// assert_eq!(fired.load(Ordering::Relaxed), 1);
+    /// ```
+    ///
+    /// [expired]: Self::expire_at
+    /// [disabled]: Self::disable_all
+    pub fn on_match(
+        &mut self,
+        directive: Directive,
+        callback: impl FnMut(&Metadata<'_>) + Send + 'static,
+    ) -> &mut Self {
+        self.alerts.alerts.push(Alert {
+            directive,
+            callback: Mutex::new(Box::new(callback)),
+        });
+        self
+    }
+
+    /// Removes all of the callbacks registered with [`on_match`].
+    ///
+    /// [`on_match`]: Self::on_match
+    pub fn clear_alerts(&mut self) -> &mut Self {
+        self.alerts.alerts.clear();
+        self
+    }
+
+    /// Calls the callbacks registered for the entry that enables `metadata`,
+    /// if there are any.
+    pub(crate) fn alert(&self, metadata: &Metadata<'_>) {
+        if self.alerts.alerts.is_empty() || self.decide(metadata) != Some(true) {
+            return;
+        }
+        let entry = match self.allow_match(metadata) {
+            Some((_, entry)) => entry,
+            None => return,
+        };
+        for alert in &self.alerts.alerts {
+            if !entry.is(&alert.directive) {
+                continue;
+            }
+            if let Ok(mut callback) = alert.callback.try_lock() {
+                (callback)(metadata);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Alerts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.alerts.iter().map(|alert| &alert.directive))
+            .finish()
+    }
+}
@@ -0,0 +1,170 @@
+use crate::{time::Instant, LineFilter};
+use tracing_core::{
+    callsite::Callsite, field::Value, metadata::Kind, subscriber::Interest, Dispatch, Event, Level,
+    Metadata,
+};
+
+/// What to include in the event recorded by [`LineFilter::announce_config`].
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Announce {
+    pub(crate) enabled: bool,
+    sources: Vec<String>,
+    endpoints: Vec<String>,
+}
+
+struct ConfigCallsite;
+
+static CALLSITE: ConfigCallsite = ConfigCallsite;
+
+static METADATA: Metadata<'static> = tracing_core::metadata! {
+    name: "tracing_line_filter config",
+    target: "tracing_line_filter",
+    level: Level::INFO,
+    fields: &[
+        "message",
+        "entries",
+        "modules",
+        "files",
+        "expires_in_secs",
+        "env",
+        "sources",
+        "endpoints",
+    ],
+    callsite: &CALLSITE,
+    kind: Kind::EVENT,
+};
+
+impl LineFilter {
+    /// Records an `INFO` event describing this filter's configuration when
+    /// it is added to a subscriber, so that logs always show what filtering
+    /// was in force.
+    ///
+    /// The event has the `tracing_line_filter` target and the following
+    /// fields:
+    ///
+    /// * `entries`, `modules`, and `files`: the number of `(module, line)`
+    ///   and `(file, line)` entries.
+    /// * `expires_in_secs`: how long until the entries [expire], if they
+    ///   will.
+    /// * `env`: the [`EnvFilter`] directives, if there are any.
+    /// * `sources`: the files the filter's directives were read from (see
+    ///   [`config_source`]), separated by commas.
+    /// * `endpoints`: the control endpoints that can change the filter (see
+    ///   [`control_endpoint`]), separated by commas.
+    ///
+    /// The event is recorded as soon as the subscriber is built, such as by
+    /// [`init`], before it is used to record anything else. It is sent
+    /// directly to the subscriber's layers, so it is recorded regardless of
+    /// this filter's directives.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::prelude::*;
+    ///
+    /// let mut filter: LineFilter = "my_crate::db@142,warn".parse().unwrap();
+    /// filter
+    ///     .config_source("/etc/my-app/trace-lines")
+    ///     .control_endpoint("jsonrpc on stdio")
+    ///     .announce_config();
+    ///
+    /// // Records an event like:
+    /// // INFO tracing_line_filter: line filter configured entries=1 modules=1
+    /// //     files=0 env="warn" sources="/etc/my-app/trace-lines"
+    /// //     endpoints="jsonrpc on stdio"
+    /// tracing_subscriber::registry()
+    ///     .with(tracing_subscriber::fmt::layer())
+    ///     .with(filter)
+    ///     .init();
+    /// ```
+    ///
+    /// [expire]: LineFilter::expire_at
+    /// [`EnvFilter`]: tracing_subscriber::EnvFilter
+    /// [`config_source`]: Self::config_source
+    /// [`control_endpoint`]: Self::control_endpoint
+    /// [`init`]: tracing_subscriber::util::SubscriberInitExt::init
+    pub fn announce_config(&mut self) -> &mut Self {
+        self.announce.enabled = true;
+        self
+    }
+
+    /// Notes that this filter's directives were read from `source`, such as
+    /// the path of a config file, for the event recorded by
+    /// [`announce_config`].
+    ///
+    /// `LineFilterArgs` does this for each `--trace-line-file` (when the
+    /// `clap` feature flag is enabled).
+    ///
+    /// [`announce_config`]: Self::announce_config
+    pub fn config_source(&mut self, source: impl Into<String>) -> &mut Self {
+        self.announce.sources.push(source.into());
+        self
+    }
+
+    /// Notes that this filter can be changed by a control endpoint, such as
+    /// a [`Command`] socket, described by `endpoint`, for the event recorded
+    /// by [`announce_config`].
+    ///
+    /// [`Command`]: crate::Command
+    /// [`announce_config`]: Self::announce_config
+    pub fn control_endpoint(&mut self, endpoint: impl Into<String>) -> &mut Self {
+        self.announce.endpoints.push(endpoint.into());
+        self
+    }
+}
+
+// === impl Announce ===
+
+impl Announce {
+    /// Records the configuration event to the subscriber that `filter` was
+    /// added to.
+    pub(crate) fn announce(&self, filter: &LineFilter, dispatch: &Dispatch) {
+        // `tracing` is in the middle of registering the subscriber, so
+        // recording the event with a macro (which would register its
+        // callsite) would deadlock.
+        let fields = METADATA.fields();
+        let field = |name| fields.field(name).expect("field is in METADATA");
+        let modules = filter.by_module.len() as u64;
+        let files = filter.by_file.len() as u64;
+        let expires_in = filter
+            .expires_at()
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()).as_secs());
+        let env = filter
+            .fallback
+            .as_ref()
+            .map(ToString::to_string)
+            .filter(|env| !env.is_empty());
+        let sources = Some(self.sources.join(",")).filter(|s| !s.is_empty());
+        let endpoints = Some(self.endpoints.join(",")).filter(|s| !s.is_empty());
+
+        let message = format_args!("line filter configured");
+        let values = [
+            (&field("message"), Some(&message as &dyn Value)),
+            (&field("entries"), Some(&(modules + files) as &dyn Value)),
+            (&field("modules"), Some(&modules as &dyn Value)),
+            (&field("files"), Some(&files as &dyn Value)),
+            (
+                &field("expires_in_secs"),
+                expires_in.as_ref().map(|v| v as &dyn Value),
+            ),
+            (&field("env"), env.as_ref().map(|v| v as &dyn Value)),
+            (&field("sources"), sources.as_ref().map(|v| v as &dyn Value)),
+            (
+                &field("endpoints"),
+                endpoints.as_ref().map(|v| v as &dyn Value),
+            ),
+        ];
+        dispatch.event(&Event::new(&METADATA, &fields.value_set(&values)));
+    }
+}
+
+// === impl ConfigCallsite ===
+
+impl Callsite for ConfigCallsite {
+    fn set_interest(&self, _: Interest) {}
+
+    fn metadata(&self) -> &Metadata<'_> {
+        &METADATA
+    }
+}
@@ -0,0 +1,231 @@
+use crate::{Directive, FilterConfig, LevelSet, LineFilter};
+use ::arbitrary::{Arbitrary, Result, Unstructured};
+use std::collections::BTreeMap;
+use tracing_core::Level;
+use tracing_subscriber::filter::LevelFilter;
+
+const LEVELS: [LevelFilter; 6] = [
+    LevelFilter::TRACE,
+    LevelFilter::DEBUG,
+    LevelFilter::INFO,
+    LevelFilter::WARN,
+    LevelFilter::ERROR,
+    LevelFilter::OFF,
+];
+
+/// Generates arbitrary directives that can be formatted and parsed back into
+/// the same directive, for property-testing parsers and filters.
+///
+/// Module paths have between one and three segments of lowercase letters,
+/// digits, and underscores, and file paths are absolute paths to `.rs` files,
+/// so every directive is valid for use in a [`LineFilter`]. `Env` directives
+/// (when the `env-filter` feature flag is enabled) set a level, either for
+/// every target or for a module path. `Deny` directives deny a `Module`,
+/// `File`, or `ModulePrefix` directive.
+///
+/// This is only available when the `arbitrary` feature flag is enabled.
+///
+/// # Examples
+///
+/// Checking that directives survive a round trip through their string form:
+///
+/// ```
+/// use arbitrary::{Arbitrary, Unstructured};
+/// use tracing_line_filter::Directive;
+///
+/// // In a real property test, these bytes would come from a fuzzer or a
+/// // random number generator.
+/// let bytes = (0..4096u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8);
+/// let bytes = bytes.collect::<Vec<_>>();
+/// let mut u = Unstructured::new(&bytes);
+/// while !u.is_empty() {
+///     let directive = Directive::arbitrary(&mut u).unwrap();
+///     let parsed: Directive = directive.to_string().parse().unwrap();
+///     assert_eq!(parsed, directive);
+/// }
+/// ```
+impl<'a> Arbitrary<'a> for Directive {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let kinds = if cfg!(feature = "env-filter") { 6 } else { 5 };
+        Ok(match u.choose_index(kinds)? {
+            0 => Directive::Module {
+                module: module(u)?,
+                line: u.arbitrary()?,
+                hash: u.arbitrary()?,
+                levels: levels(u)?,
+            },
+            1 => Directive::File {
+                file: file(u)?,
+                line: u.arbitrary()?,
+                hash: u.arbitrary()?,
+                levels: levels(u)?,
+            },
+            2 => Directive::ModuleLevel {
+                module: module(u)?,
+                level: *u.choose(&LEVELS)?,
+            },
+            3 => Directive::ModulePrefix { prefix: module(u)? },
+            4 => Directive::Deny(Box::new(match u.choose_index(3)? {
+                0 => Directive::Module {
+                    module: module(u)?,
+                    line: u.arbitrary()?,
+                    hash: None,
+                    levels: None,
+                },
+                1 => Directive::File {
+                    file: file(u)?,
+                    line: u.arbitrary()?,
+                    hash: None,
+                    levels: None,
+                },
+                _ => Directive::ModulePrefix { prefix: module(u)? },
+            })),
+            #[cfg(feature = "env-filter")]
+            _ => {
+                let level = u.choose(&LEVELS)?.to_string().to_lowercase();
+                let directive = if u.arbitrary()? {
+                    format!("{}={}", module(u)?, level)
+                } else {
+                    level
+                };
+                Directive::Env(directive.parse().expect("generated directives are valid"))
+            }
+            #[cfg(not(feature = "env-filter"))]
+            _ => unreachable!(),
+        })
+    }
+}
+
+/// Generates arbitrary configs, made of [arbitrary directives], up to two
+/// named profiles, and up to two span fields, that can be formatted and
+/// parsed back into the same config.
+///
+/// This is only available when the `arbitrary` feature flag is enabled.
+///
+/// # Examples
+///
+/// ```
+/// use arbitrary::{Arbitrary, Unstructured};
+/// use tracing_line_filter::FilterConfig;
+///
+/// let bytes = (0..4096u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 7) as u8);
+/// let bytes = bytes.collect::<Vec<_>>();
+/// let mut u = Unstructured::new(&bytes);
+/// while !u.is_empty() {
+///     let config = FilterConfig::arbitrary(&mut u).unwrap();
+///     let parsed: FilterConfig = config.to_string().parse().unwrap();
+///     assert_eq!(parsed, config);
+/// }
+/// ```
+///
+/// [arbitrary directives]: Directive#impl-Arbitrary<'a>-for-Directive
+impl<'a> Arbitrary<'a> for FilterConfig {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let directives = arbitrary_directives(u, false)?;
+        let mut profiles = BTreeMap::new();
+        for _ in 0..u.int_in_range(0..=2)? {
+            let name = format!("{}-{}", segment(u)?, profiles.len());
+            profiles.insert(name, arbitrary_directives(u, true)?);
+        }
+        let mut fields = Vec::new();
+        for i in 0..u.int_in_range(0..=2)? {
+            let name = format!("{}{}", segment(u)?, i);
+            let value = format!("{} {}", segment(u)?, u.int_in_range(0..=9999u32)?);
+            fields.push((name, value));
+        }
+        Ok(FilterConfig::from_parts(directives, profiles, fields))
+    }
+}
+
+/// Generates filters with the entries and profiles of an [arbitrary config],
+/// as if by [`LineFilter::set_config`].
+///
+/// This is only available when the `arbitrary` feature flag is enabled.
+///
+/// [arbitrary config]: FilterConfig#impl-Arbitrary<'a>-for-FilterConfig
+impl<'a> Arbitrary<'a> for LineFilter {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let config = FilterConfig::arbitrary(u)?;
+        let mut filter = LineFilter::new();
+        filter
+            .set_config(&config)
+            .expect("generated paths are valid");
+        Ok(filter)
+    }
+}
+
+fn arbitrary_directives(u: &mut Unstructured<'_>, in_profile: bool) -> Result<Vec<Directive>> {
+    let mut directives = Vec::new();
+    for _ in 0..u.int_in_range(0..=8)? {
+        let directive = Directive::arbitrary(u)?;
+        // Profiles may not contain `EnvFilter` directives.
+        if in_profile && is_env(&directive) {
+            continue;
+        }
+        directives.push(directive);
+    }
+    Ok(directives)
+}
+
+#[cfg(feature = "env-filter")]
+fn is_env(directive: &Directive) -> bool {
+    matches!(directive, Directive::Env(_))
+}
+
+#[cfg(not(feature = "env-filter"))]
+fn is_env(_: &Directive) -> bool {
+    false
+}
+
+fn module(u: &mut Unstructured<'_>) -> Result<String> {
+    let mut module = segment(u)?;
+    for _ in 0..u.int_in_range(0..=2)? {
+        module.push_str("::");
+        module.push_str(&segment(u)?);
+    }
+    Ok(module)
+}
+
+fn file(u: &mut Unstructured<'_>) -> Result<String> {
+    let mut file = String::from(if cfg!(windows) { "C:" } else { "" });
+    for _ in 0..u.int_in_range(1..=3)? {
+        file.push('/');
+        file.push_str(&segment(u)?);
+    }
+    file.push_str(".rs");
+    Ok(file)
+}
+
+/// Generates a level set for a line entry, or `None`.
+fn levels(u: &mut Unstructured<'_>) -> Result<Option<LevelSet>> {
+    if !u.arbitrary()? {
+        return Ok(None);
+    }
+    let mut levels = LevelSet::new();
+    for level in [
+        Level::TRACE,
+        Level::DEBUG,
+        Level::INFO,
+        Level::WARN,
+        Level::ERROR,
+    ] {
+        if u.arbitrary()? {
+            levels.insert(level);
+        }
+    }
+    if levels.is_empty() {
+        levels.insert(Level::INFO);
+    }
+    Ok(Some(levels))
+}
+
+/// Generates an identifier of up to eight characters.
+fn segment(u: &mut Unstructured<'_>) -> Result<String> {
+    const FIRST: &[u8] = b"abcdefghijklmnopqrstuvwxyz_";
+    const REST: &[u8] = b"abcdefghijklmnopqrstuvwxyz_0123456789";
+    let mut segment = String::from(*u.choose(FIRST)? as char);
+    for _ in 0..u.int_in_range(0..=7)? {
+        segment.push(*u.choose(REST)? as char);
+    }
+    Ok(segment)
+}
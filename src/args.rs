@@ -0,0 +1,154 @@
+use crate::{directive, Directive, LineFilter, ParseError};
+use std::{error::Error, fmt, fs, io, path::PathBuf};
+use tracing_subscriber::filter;
+
+/// Command-line arguments for configuring a [`LineFilter`], for use with
+/// [`clap`]'s derive API.
+///
+/// Adding these arguments to a command with `#[command(flatten)]` adds the
+/// following flags, each of which may be passed more than once:
+///
+/// * `--trace-line <DIRECTIVE>` enables spans and events on a line, like
+///   `--trace-line my_crate::db@142`. Any directive accepted by [the
+///   `FromStr` implementation for `LineFilter`][syntax] may be used.
+/// * `--trace-line-file <PATH>` reads directives from a file, in the same
+///   format, with one or more directives per line.
+/// * `--trace-env <DIRECTIVES>` adds comma-separated [`EnvFilter`]
+///   directives, like `--trace-env error,my_crate=debug`.
+///
+/// This type is only available when the `clap` feature flag is enabled.
+///
+/// # Examples
+///
+/// ```
+/// use clap::Parser;
+/// use tracing_line_filter::LineFilterArgs;
+///
+/// #[derive(Parser)]
+/// struct Cli {
+///     #[command(flatten)]
+///     trace: LineFilterArgs,
+/// }
+///
+/// let cli = Cli::parse_from([
+///     "my-app",
+///     "--trace-line",
+///     "my_crate::db@142",
+///     "--trace-env",
+///     "error",
+/// ]);
+/// let filter = cli.trace.into_filter().unwrap();
+/// assert_eq!(filter.to_string(), "my_crate::db@142,error");
+/// ```
+///
+/// [`clap`]: https://docs.rs/clap
+/// [syntax]: crate::LineFilter#impl-FromStr-for-LineFilter
+/// [`EnvFilter`]: tracing_subscriber::EnvFilter
+#[derive(Clone, Debug, Default, clap::Args)]
+// Don't use this documentation as the help text of commands that flatten
+// these arguments.
+#[command(about = None, long_about = None)]
+pub struct LineFilterArgs {
+    /// Enable spans and events matching a directive, such as
+    /// `my_crate::db@142`.
+    #[arg(long = "trace-line", value_name = "DIRECTIVE")]
+    pub trace_line: Vec<Directive>,
+
+    /// Read directives to enable from a file.
+    #[arg(long = "trace-line-file", value_name = "PATH")]
+    pub trace_line_file: Vec<PathBuf>,
+
+    /// Enable spans and events matching comma-separated `EnvFilter`
+    /// directives, such as `error,my_crate=debug`.
+    #[arg(long = "trace-env", value_name = "DIRECTIVES", value_delimiter = ',')]
+    pub trace_env: Vec<filter::Directive>,
+}
+
+/// Indicates a [`LineFilterArgs`] could not be converted into a
+/// [`LineFilter`].
+#[derive(Debug)]
+pub struct ArgsError {
+    path: PathBuf,
+    kind: ErrorKind,
+}
+
+#[derive(Debug)]
+enum ErrorKind {
+    Io(io::Error),
+    Parse(ParseError),
+}
+
+impl LineFilterArgs {
+    /// Builds a [`LineFilter`] from these arguments.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if a `--trace-line-file` cannot be read, or
+    /// contains an invalid directive.
+    pub fn into_filter(self) -> Result<LineFilter, ArgsError> {
+        let mut filter = LineFilter::new();
+        for path in self.trace_line_file {
+            let config = match fs::read_to_string(&path) {
+                Ok(config) => config,
+                Err(e) => return Err(ArgsError::new(path, ErrorKind::Io(e))),
+            };
+            filter.config_source(path.display().to_string());
+            let sections = match directive::parse_sections(&config) {
+                Ok(sections) => sections,
+                Err(e) => return Err(ArgsError::new(path, ErrorKind::Parse(e))),
+            };
+            sections.record_warnings();
+            for directive in sections.directives {
+                add(&mut filter, directive);
+            }
+            for (name, directives) in sections.profiles {
+                filter
+                    .define_profile(name, directives)
+                    .expect("profiles are validated by `parse_sections`");
+            }
+            for (name, value) in sections.fields {
+                filter.add_span_field(name, value);
+            }
+        }
+
+        for directive in self.trace_line {
+            add(&mut filter, directive);
+        }
+        for directive in self.trace_env {
+            add(&mut filter, Directive::Env(directive));
+        }
+        Ok(filter)
+    }
+}
+
+fn add(filter: &mut LineFilter, directive: Directive) {
+    filter
+        .add_directive(directive)
+        .expect("paths are validated by `Directive::from_str`");
+}
+
+// === impl ArgsError ===
+
+impl ArgsError {
+    fn new(path: PathBuf, kind: ErrorKind) -> Self {
+        Self { path, kind }
+    }
+}
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ErrorKind::Io(ref e) => write!(f, "failed to read '{}': {}", self.path.display(), e),
+            ErrorKind::Parse(ref e) => write!(f, "{}: {}", self.path.display(), e),
+        }
+    }
+}
+
+impl Error for ArgsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self.kind {
+            ErrorKind::Io(ref e) => Some(e),
+            ErrorKind::Parse(ref e) => Some(e),
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use crate::{time::Instant, Directive, LineFilter};
+use std::{
+    fmt,
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+
+/// The target of audit events, which are always enabled by a `LineFilter`.
+pub(crate) const TARGET: &str = "tracing_line_filter::audit";
+
+/// Describes a change made to a filter by a control endpoint, which is
+/// recorded as an audit event once the change has been made.
+pub(crate) struct Change<'a> {
+    /// The kind of endpoint that made the change, such as `jsonrpc`.
+    pub(crate) transport: &'a str,
+    /// Who made the change, such as a client's address, if it is known.
+    pub(crate) peer: Option<&'a str>,
+    /// What was changed.
+    pub(crate) change: &'a dyn fmt::Display,
+    pub(crate) entries_before: usize,
+    pub(crate) entries_after: usize,
+    /// The entries that were evicted to make room for the change, if the
+    /// filter has a [maximum number of entries](LineFilter::max_entries).
+    pub(crate) evicted: &'a [Directive],
+}
+
+/// Describes a command received by a control endpoint, which is recorded as
+/// an audit event if it doesn't change the filter.
+pub(crate) struct Request<'a> {
+    /// The kind of endpoint that received the command, such as `jsonrpc`.
+    pub(crate) transport: &'a str,
+    /// Who sent the command, such as a client's address, if it is known.
+    pub(crate) peer: Option<&'a str>,
+    pub(crate) command: &'a dyn fmt::Display,
+}
+
+/// The limit on how many commands a filter's control endpoints may run in
+/// each period, set by [`LineFilter::limit_commands`].
+#[derive(Debug)]
+pub(crate) struct RateLimit {
+    max: u32,
+    per: Duration,
+    /// When the current period started, and how many commands have run in
+    /// it.
+    period: Mutex<Option<(Instant, u32)>>,
+}
+
+impl LineFilter {
+    /// Limits the control endpoints that change this filter to running `max`
+    /// commands in each `per` period.
+    ///
+    /// Every command run by [`Command::apply`] rebuilds `tracing`'s cache of
+    /// enabled callsites, so automation that sends commands in a tight loop
+    /// can slow down the whole program. Once `max` commands have run in the
+    /// current period, further commands fail without being run until the
+    /// period ends, and each of them is recorded as a `WARN` event with the
+    /// `tracing_line_filter::audit` target. The [`jsonrpc`] and [`dap`]
+    /// servers are also limited, but commands that only read the filter
+    /// without rebuilding the cache, such as the JSON-RPC `filter/list`
+    /// method, are not.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tracing_line_filter::{Command, LineFilter};
+    /// use tracing_subscriber::{prelude::*, reload};
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter.limit_commands(2, Duration::from_secs(60));
+    /// let (filter, handle) = reload::Layer::new(filter);
+    /// let _subscriber = tracing_subscriber::registry().with(filter);
+    ///
+    /// let mut run = |command: &str| {
+    ///     command
+    ///         .parse::<Command>()
+    ///         .and_then(|command| command.apply(&handle, "socket", None))
+    /// };
+    /// run("enable my_crate::db@142").unwrap();
+    /// run("enable my_crate::db@200").unwrap();
+    ///
+    /// let error = run("enable my_crate::db@250").unwrap_err();
+    /// assert!(error.is_rate_limited());
+    /// assert!(error.to_string().starts_with("too many commands; try again in"));
+    /// ```
+    ///
+    /// [`Command::apply`]: crate::Command::apply
+    /// [`jsonrpc`]: https://docs.rs/tracing-line-filter/latest/tracing_line_filter/jsonrpc/index.html
+    /// [`dap`]: https://docs.rs/tracing-line-filter/latest/tracing_line_filter/dap/index.html
+    pub fn limit_commands(&mut self, max: u32, per: Duration) -> &mut Self {
+        self.command_limit = Some(RateLimit {
+            max,
+            per,
+            period: Mutex::new(None),
+        });
+        self
+    }
+
+    /// Removes the limit set by [`limit_commands`].
+    ///
+    /// [`limit_commands`]: Self::limit_commands
+    pub fn clear_command_limit(&mut self) -> &mut Self {
+        self.command_limit = None;
+        self
+    }
+
+    /// Only allows control endpoints to run commands that inspect this
+    /// filter, if `read_only` is `true`.
+    ///
+    /// In environments where changes to a program's filter must go through
+    /// its config file, control endpoints can still be used to observe the
+    /// filter. While the filter is read-only, [`Command::apply`] only runs
+    /// the commands for which [`Command::is_read_only`] returns `true`, such
+    /// as `list`, `stats`, and `explain`, and fails without running any
+    /// others. Each rejected command is recorded as a `WARN` event with the
+    /// `tracing_line_filter::audit` target. The [`jsonrpc`] server's
+    /// `filter/list`, `filter/stats`, and `filter/health` methods still
+    /// work, but its other methods fail, and the [`dap`] bridge fails to set
+    /// logpoints.
+    ///
+    /// Setting the `LINE_FILTER_READ_ONLY` environment variable to `1` (or
+    /// `true`) makes every `LineFilter` in the process read-only in the
+    /// same way. The variable is read once, the first time a command is run.
+    ///
+    /// The filter can still be changed by the program itself, such as by
+    /// [`set_config`] or `LineFilter::from_watch` (when the `tokio` feature
+    /// flag is enabled).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::{Command, LineFilter};
+    /// use tracing_subscriber::{prelude::*, reload};
+    ///
+    /// let mut filter: LineFilter = "my_crate::db@142".parse().unwrap();
+    /// filter.read_only_commands(true);
+    /// let (filter, handle) = reload::Layer::new(filter);
+    /// let _subscriber = tracing_subscriber::registry().with(filter);
+    ///
+    /// let mut run = |command: &str| {
+    ///     command
+    ///         .parse::<Command>()
+    ///         .and_then(|command| command.apply(&handle, "socket", None))
+    /// };
+    /// assert_eq!(run("list").unwrap(), "my_crate::db@142  # never hit");
+    ///
+    /// let error = run("disable my_crate::db@142").unwrap_err();
+    /// assert!(error.is_read_only());
+    /// assert_eq!(
+    ///     error.to_string(),
+    ///     "cannot run 'disable my_crate::db@142': the filter is read-only",
+    /// );
+    /// ```
+    ///
+    /// [`Command::apply`]: crate::Command::apply
+    /// [`Command::is_read_only`]: crate::Command::is_read_only
+    /// [`jsonrpc`]: https://docs.rs/tracing-line-filter/latest/tracing_line_filter/jsonrpc/index.html
+    /// [`dap`]: https://docs.rs/tracing-line-filter/latest/tracing_line_filter/dap/index.html
+    /// [`set_config`]: Self::set_config
+    pub fn read_only_commands(&mut self, read_only: bool) -> &mut Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Returns `true` if control endpoints may only run commands that
+    /// inspect this filter, either because of [`read_only_commands`] or the
+    /// `LINE_FILTER_READ_ONLY` environment variable.
+    ///
+    /// [`read_only_commands`]: Self::read_only_commands
+    pub fn is_read_only(&self) -> bool {
+        static READ_ONLY: OnceLock<bool> = OnceLock::new();
+        self.read_only
+            || *READ_ONLY.get_or_init(|| {
+                matches!(
+                    std::env::var("LINE_FILTER_READ_ONLY").as_deref(),
+                    Ok("1") | Ok("true")
+                )
+            })
+    }
+
+    /// Counts a command against this filter's command limit, or returns how
+    /// long until another command may run if the limit has been reached.
+    pub(crate) fn check_command_limit(&self) -> Result<(), Duration> {
+        match self.command_limit {
+            Some(ref limit) => limit.check(),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the number of `(module, line)` and `(file, line)` entries.
+    pub(crate) fn entry_count(&self) -> usize {
+        self.by_module.len() + self.by_file.len()
+    }
+}
+
+// === impl Change ===
+
+impl Change<'_> {
+    /// Records an `INFO` event describing this change, followed by an `INFO`
+    /// event for each entry that it evicted.
+    ///
+    /// This must not be called while the filter is being modified, since
+    /// the event can't be filtered until the modification is done.
+    pub(crate) fn record(&self) {
+        tracing::info!(
+            target: TARGET,
+            transport = self.transport,
+            peer = self.peer,
+            change = %self.change,
+            entries.before = self.entries_before as u64,
+            entries.after = self.entries_after as u64,
+            "filter changed",
+        );
+        for directive in self.evicted {
+            tracing::info!(
+                target: TARGET,
+                transport = self.transport,
+                peer = self.peer,
+                %directive,
+                "filter entry evicted",
+            );
+        }
+    }
+}
+
+// === impl Request ===
+
+impl Request<'_> {
+    /// Records a `DEBUG` event for a command that didn't change the filter,
+    /// either because it only inspects the filter, or because it failed with
+    /// `error`.
+    pub(crate) fn record(&self, error: Option<&dyn fmt::Display>) {
+        tracing::debug!(
+            target: TARGET,
+            transport = self.transport,
+            peer = self.peer,
+            command = %self.command,
+            error = error.map(tracing::field::display),
+            "filter command received",
+        );
+    }
+
+    /// Records a `WARN` event for a command that wasn't run because the
+    /// filter is read-only.
+    pub(crate) fn record_rejected(&self) {
+        tracing::warn!(
+            target: TARGET,
+            transport = self.transport,
+            peer = self.peer,
+            command = %self.command,
+            "filter command rejected: the filter is read-only",
+        );
+    }
+
+    /// Records a `WARN` event for a request that wasn't served because it
+    /// didn't have the control server's token.
+    #[cfg(feature = "jsonrpc")]
+    pub(crate) fn record_unauthorized(&self) {
+        tracing::warn!(
+            target: TARGET,
+            transport = self.transport,
+            peer = self.peer,
+            command = %self.command,
+            "filter command rejected: the client is not authenticated",
+        );
+    }
+
+    /// Records a `WARN` event for a command that wasn't run because the
+    /// filter's command limit was reached.
+    pub(crate) fn record_limited(&self, retry_after: Duration) {
+        tracing::warn!(
+            target: TARGET,
+            transport = self.transport,
+            peer = self.peer,
+            command = %self.command,
+            retry_after_ms = retry_after.as_millis() as u64,
+            "filter command rate limited",
+        );
+    }
+}
+
+// === impl RateLimit ===
+
+impl RateLimit {
+    /// Returns a limit with the same settings, which no commands have been
+    /// counted against.
+    pub(crate) fn unused(&self) -> Self {
+        Self {
+            max: self.max,
+            per: self.per,
+            period: Mutex::new(None),
+        }
+    }
+
+    fn check(&self) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut period = self.period.lock().unwrap_or_else(|e| e.into_inner());
+        let (start, count) = match *period {
+            Some((start, count)) if now.duration_since(start) < self.per => (start, count),
+            _ => (now, 0),
+        };
+        if count >= self.max {
+            return Err(self.per.saturating_sub(now.duration_since(start)));
+        }
+        *period = Some((start, count + 1));
+        Ok(())
+    }
+}
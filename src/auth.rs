@@ -0,0 +1,64 @@
+use std::{env, fmt};
+
+/// The environment variable that control servers read their token from,
+/// unless one is set with their `token` method.
+pub(crate) const TOKEN_VAR: &str = "LINE_FILTER_TOKEN";
+
+/// A secret that the clients of a control server must present.
+///
+/// The secret is left out of the token's `Debug` output, so that it isn't
+/// logged along with the server that holds it.
+#[derive(Clone)]
+pub(crate) struct Token(String);
+
+impl Token {
+    pub(crate) fn new(token: impl Into<String>) -> Self {
+        Self(token.into())
+    }
+
+    /// Returns the token in the `LINE_FILTER_TOKEN` environment variable, if
+    /// it is set and not empty.
+    pub(crate) fn from_env() -> Option<Self> {
+        env::var(TOKEN_VAR)
+            .ok()
+            .filter(|token| !token.is_empty())
+            .map(Self)
+    }
+
+    /// Returns `true` if `candidate` is this token.
+    ///
+    /// This takes time that depends only on the lengths of the two, so that
+    /// how long a comparison takes doesn't reveal how much of a guess was
+    /// right.
+    pub(crate) fn matches(&self, candidate: &str) -> bool {
+        self.0.len() == candidate.len()
+            && self
+                .0
+                .bytes()
+                .zip(candidate.bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+impl fmt::Debug for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Token(..)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_only_the_whole_token() {
+        let token = Token::new("hunter2");
+        assert!(token.matches("hunter2"));
+        assert!(!token.matches("hunter"));
+        assert!(!token.matches("hunter22"));
+        assert!(!token.matches("Hunter2"));
+        assert!(!token.matches(""));
+        assert_eq!(format!("{:?}", token), "Token(..)");
+    }
+}
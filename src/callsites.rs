@@ -0,0 +1,225 @@
+use crate::{Directive, LineFilter};
+use std::{borrow::Cow, collections::BTreeSet};
+use tracing_core::{span, Metadata, Subscriber};
+use tracing_subscriber::{registry::LookupSpan, reload, Registry};
+
+impl LineFilter {
+    /// Enables the callsite of the live span with `id`, in the filter behind
+    /// a [`reload`] handle, returning the directive for the entry that was
+    /// added.
+    ///
+    /// This is for tools that find an interesting span at runtime (such as in
+    /// a trace, or through the span context of an event) and want to see more
+    /// spans like it, without working out where it was created. The span's
+    /// metadata is looked up in the current default subscriber, which must be
+    /// built on a [`Registry`], and a `module@line` entry is added for its
+    /// callsite, or a `file@line` entry if the callsite has no module path.
+    ///
+    /// This returns `Ok(None)` if the span isn't open in the default
+    /// subscriber, or if its callsite has no line number.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the subscriber containing the filter has
+    /// been dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::{prelude::*, reload, EnvFilter};
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter.with_env_filter(EnvFilter::new("warn"));
+    /// let (filter, handle) = reload::Layer::new(filter);
+    /// let _guard = tracing_subscriber::registry().with(filter).set_default();
+    ///
+    /// let created_on = line!() + 1;
+    /// let span = tracing::warn_span!("slow request");
+    ///
+    /// // Later, once a tool has found the span interesting...
+    /// let id = span.id().unwrap();
+    /// let directive = LineFilter::enable_callsite_of(&handle, &id).unwrap().unwrap();
+    /// assert_eq!(directive.to_string(), format!("{}@{}", module_path!(), created_on));
+    /// # assert!(handle.with_current(|filter| filter.directives().any(|d| d == directive)).unwrap());
+    /// ```
+    ///
+    /// [`reload`]: tracing_subscriber::reload
+    /// [`Registry`]: tracing_subscriber::Registry
+    pub fn enable_callsite_of<S>(
+        handle: &reload::Handle<Self, S>,
+        id: &span::Id,
+    ) -> Result<Option<Directive>, reload::Error>
+    where
+        S: Subscriber,
+    {
+        let metadata = match span_metadata(id) {
+            Some(metadata) => metadata,
+            None => return Ok(None),
+        };
+        let mut directive = None;
+        handle.modify(|filter| directive = filter.enable_callsite(metadata))?;
+        Ok(directive)
+    }
+
+    /// Enables every recorded callsite that is similar to `callsite`,
+    /// returning the directives for their entries.
+    ///
+    /// This is for going from one interesting span or event to every place
+    /// in the program that emits ones like it. Callsites are similar if:
+    ///
+    /// - they are both spans with the same name, or
+    /// - they are both events with the same field names, other than
+    ///   `message`. `tracing` doesn't keep an event's message template in
+    ///   its callsite's metadata, so events with no fields other than their
+    ///   message are only similar to themselves.
+    ///
+    /// Only the callsites recorded since [`record_callsites`] was called are
+    /// searched, so this does nothing if callsites aren't being recorded. A
+    /// `module@line` entry is added for each similar callsite (including
+    /// `callsite` itself, if it has been recorded), or a `file@line` entry if
+    /// the callsite has no module path. The directives are returned in
+    /// order, whether or not their entries were already enabled.
+    ///
+    /// The `enable-similar` [`Command`] does the same for the callsites at a
+    /// `module@line` or `file@line` location.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::{prelude::*, reload, EnvFilter};
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter
+    ///     .with_env_filter(EnvFilter::new("warn"))
+    ///     .record_callsites();
+    /// let (filter, handle) = reload::Layer::new(filter);
+    /// let _guard = tracing_subscriber::registry().with(filter).set_default();
+    ///
+    /// let first = line!() + 1;
+    /// let _ = tracing::debug_span!("connect", attempt = 1);
+    /// tracing::debug!(attempt = 1, "connecting");
+    /// let span = tracing::warn_span!("connect", reconnect = true);
+    /// tracing::warn!(attempt = 2, "connection lost; reconnecting");
+    /// tracing::warn!("giving up");
+    ///
+    /// // Starting from the span that was seen, enable every `connect` span.
+    /// let mut spans = Vec::new();
+    /// handle
+    ///     .modify(|filter| spans = filter.enable_similar(span.metadata().unwrap()))
+    ///     .unwrap();
+    /// let lines = spans.iter().map(ToString::to_string).collect::<Vec<_>>();
+    /// assert_eq!(
+    ///     lines,
+    ///     [
+    ///         format!("{}@{}", module_path!(), first),
+    ///         format!("{}@{}", module_path!(), first + 2),
+    ///     ],
+    /// );
+    /// ```
+    ///
+    /// [`record_callsites`]: Self::record_callsites
+    /// [`Command`]: crate::Command
+    pub fn enable_similar(&mut self, callsite: &Metadata<'_>) -> Vec<Directive> {
+        let fields = |metadata: &Metadata<'_>| {
+            metadata
+                .fields()
+                .iter()
+                .map(|field| field.name())
+                .filter(|&name| name != "message")
+                .collect::<BTreeSet<_>>()
+        };
+        let similar = |metadata: &Metadata<'_>| {
+            if metadata.callsite() == callsite.callsite() {
+                return true;
+            }
+            match (callsite.is_span(), metadata.is_span()) {
+                (true, true) => metadata.name() == callsite.name(),
+                (false, false) => {
+                    let wanted = fields(callsite);
+                    !wanted.is_empty() && fields(metadata) == wanted
+                }
+                _ => false,
+            }
+        };
+
+        let mut callsites = match self.recorder {
+            Some(ref recorder) => recorder.callsites(),
+            None => return Vec::new(),
+        };
+        callsites.retain(|metadata| similar(metadata));
+        callsites
+            .sort_by_key(|metadata| (metadata.module_path(), metadata.file(), metadata.line()));
+        let mut directives = Vec::new();
+        for metadata in callsites {
+            if let Some(directive) = self.enable_callsite(metadata) {
+                directives.push(directive);
+            }
+        }
+        // Several callsites may be on the same line.
+        directives.dedup();
+        directives
+    }
+
+    /// Returns the callsites recorded since [`record_callsites`] was called
+    /// at the location of a `Module` or `File` directive.
+    ///
+    /// [`record_callsites`]: Self::record_callsites
+    pub(crate) fn recorded_at(&self, directive: &Directive) -> Vec<&'static Metadata<'static>> {
+        let recorder = match self.recorder {
+            Some(ref recorder) => recorder,
+            None => return Vec::new(),
+        };
+        recorder
+            .callsites()
+            .into_iter()
+            .filter(|metadata| match directive {
+                Directive::Module { module, line, .. } => {
+                    metadata.line() == Some(*line)
+                        && crate::module_keys(metadata).any(|key| key == module)
+                }
+                Directive::File { file, line, .. } => {
+                    metadata.line() == Some(*line) && metadata.file() == Some(file.as_str())
+                }
+                _ => false,
+            })
+            .collect()
+    }
+
+    /// Adds an entry for the line of the callsite with `metadata`, returning
+    /// its directive, or `None` if the callsite has no line number.
+    fn enable_callsite(&mut self, metadata: &'static Metadata<'static>) -> Option<Directive> {
+        let line = metadata.line()?;
+        match (metadata.module_path(), metadata.file()) {
+            (Some(module), _) => {
+                self.enable_by_mod(module, line);
+                Some(Directive::Module {
+                    module: module.to_owned(),
+                    line,
+                    hash: None,
+                    levels: None,
+                })
+            }
+            (None, Some(file)) => {
+                self.insert_file((Cow::Borrowed(file), line));
+                Some(Directive::File {
+                    file: file.to_owned(),
+                    line,
+                    hash: None,
+                    levels: None,
+                })
+            }
+            (None, None) => None,
+        }
+    }
+}
+
+/// Returns the metadata of the span with `id` in the default subscriber, if
+/// it is built on a [`Registry`] and the span is open.
+fn span_metadata(id: &span::Id) -> Option<&'static Metadata<'static>> {
+    tracing::dispatcher::get_default(|dispatch| {
+        let registry = dispatch.downcast_ref::<Registry>()?;
+        registry.span(id).map(|span| span.metadata())
+    })
+}
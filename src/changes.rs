@@ -0,0 +1,132 @@
+use crate::{Directive, LineFilter};
+use futures_channel::mpsc;
+use futures_core::Stream;
+use std::{
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll},
+};
+
+/// A change to a [`LineFilter`], as reported by [`LineFilter::changes`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FilterChange {
+    /// A directive was added to the filter.
+    Added(Directive),
+    /// A `Module` or `File` directive was removed from the filter.
+    Removed(Directive),
+    /// The filter's `(module, line)` and `(file, line)` entries expired. See
+    /// [`LineFilter::expire_at`] for details.
+    ///
+    /// This is reported when the filter first notices that its deadline has
+    /// passed, which is the next time it checks a span or event.
+    Expired,
+    /// The filter's directives were replaced by a new config, using
+    /// [`LineFilter::set_config`]. This is reported after the `Added` and
+    /// `Removed` changes that the new config made. Since the filter's
+    /// [`EnvFilter`] is rebuilt from scratch, the config's `Env` directives
+    /// are always reported as `Added`.
+    ///
+    /// [`EnvFilter`]: tracing_subscriber::EnvFilter
+    Reloaded,
+}
+
+/// A [`Stream`] of the changes made to a [`LineFilter`], returned by
+/// [`LineFilter::changes`].
+#[derive(Debug)]
+#[must_use = "streams do nothing unless polled"]
+pub struct Changes {
+    rx: mpsc::UnboundedReceiver<FilterChange>,
+}
+
+/// The senders for a filter's [`Changes`] streams.
+#[derive(Debug, Default)]
+pub(crate) struct Subscribers {
+    txs: Mutex<Vec<mpsc::UnboundedSender<FilterChange>>>,
+}
+
+impl LineFilter {
+    /// Returns a [`Stream`] of the changes made to this filter from now on.
+    ///
+    /// Each call returns a new stream, which receives every change made after
+    /// it was created: directives being added or removed, the line entries
+    /// expiring, and new configs being applied. Changes made to a filter in
+    /// a running program are often made on a different task or thread than
+    /// the one that consumes them, such as by a control protocol, so this
+    /// gives dashboards and audit logs one place to find out about all of
+    /// them.
+    ///
+    /// The stream ends when the filter is dropped. This includes when a
+    /// filter inside a [`reload`] layer is replaced using
+    /// [`Handle::reload`], so prefer [`Handle::modify`] with
+    /// [`LineFilter::set_config`] when consumers should keep receiving
+    /// changes.
+    ///
+    /// This method is only available when the `futures` feature flag is
+    /// enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use futures::StreamExt;
+    /// use tracing_line_filter::{Directive, FilterChange, LineFilter};
+    /// use tracing_subscriber::{prelude::*, reload};
+    ///
+    /// let (filter, handle) = reload::Layer::new(LineFilter::default());
+    /// let _subscriber = tracing_subscriber::registry().with(filter);
+    ///
+    /// let mut changes = handle.with_current(LineFilter::changes).unwrap();
+    /// handle
+    ///     .modify(|filter| {
+    ///         filter.enable_by_mod("my_crate::db", 142);
+    ///     })
+    ///     .unwrap();
+    ///
+    /// let change = futures::executor::block_on(changes.next());
+    /// let directive: Directive = "my_crate::db@142".parse().unwrap();
+    /// assert_eq!(change, Some(FilterChange::Added(directive)));
+    /// ```
+    ///
+    /// [`reload`]: tracing_subscriber::reload
+    /// [`Handle::reload`]: tracing_subscriber::reload::Handle::reload
+    /// [`Handle::modify`]: tracing_subscriber::reload::Handle::modify
+    pub fn changes(&self) -> Changes {
+        let (tx, rx) = mpsc::unbounded();
+        self.changes
+            .txs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(tx);
+        Changes { rx }
+    }
+}
+
+// === impl Subscribers ===
+
+impl Subscribers {
+    /// Sends the change returned by `change` to every stream, if there are
+    /// any.
+    pub(crate) fn notify(&self, change: impl FnOnce() -> FilterChange) {
+        let mut txs = self.txs.lock().unwrap_or_else(|e| e.into_inner());
+        if txs.is_empty() {
+            return;
+        }
+        let change = change();
+        // Streams that have been dropped are removed.
+        txs.retain(|tx| tx.unbounded_send(change.clone()).is_ok());
+    }
+}
+
+// === impl Changes ===
+
+impl Stream for Changes {
+    type Item = FilterChange;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.rx).poll_next(cx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.rx.size_hint()
+    }
+}
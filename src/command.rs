@@ -0,0 +1,1100 @@
+use crate::{
+    audit, note, publish, record,
+    time::{Instant, SystemTime},
+    Directive, LineFilter, ParseError, ProfileError, Rollup,
+};
+use std::{error::Error, fmt, fs, io, path::PathBuf, str::FromStr, time::Duration};
+use tracing_core::Subscriber;
+use tracing_subscriber::reload;
+
+/// A command for inspecting or changing a [`LineFilter`] in a running
+/// program.
+///
+/// Commands are written as a command name followed by its argument, if it
+/// takes one. This is a small text language intended to be sent to a program
+/// over a control channel (such as a Unix socket or named pipe) by a human
+/// or a script, one command per line:
+///
+/// | Command                      | Description                                          |
+/// |------------------------------|------------------------------------------------------|
+/// | `enable <DIRECTIVE>`         | Adds a [`Directive`] to the filter.                  |
+/// | `disable <DIRECTIVE>`        | Removes a `module@line` or `file@line` directive.    |
+/// | `list`                       | Lists the filter's directives and their last hits.   |
+/// | `stats`                      | Summarizes the filter's entries and their hits.      |
+/// | `explain <DIRECTIVE>`        | Explains why the callsites on a line are enabled.    |
+/// | `save <PATH>`                | Writes the filter's directives to a file.            |
+/// | `expire <DURATION>`          | Disables all line entries after a duration.          |
+/// | `disable-all`                | Disables all entries, leaving only the `EnvFilter`.  |
+/// | `reenable-all`               | Undoes `disable-all`.                                |
+/// | `activate <PROFILE>`         | Adds a named profile's directives to the filter.     |
+/// | `deactivate <PROFILE>`       | Removes a named profile's directives.                |
+/// | `profiles`                   | Lists the filter's profiles and which are active.    |
+/// | `complete [PREFIX]`          | Completes a module path or `module@line` directive.  |
+/// | `callsites [PREFIX]`         | Lists the recorded spans and events in some modules. |
+/// | `enable-similar <DIRECTIVE>` | Enables the callsites similar to those on a line.    |
+/// | `help`                       | Describes the available commands.                    |
+///
+/// Durations are written as a number followed by a unit (`ms`, `s`, `m`, or
+/// `h`), such as `30s` or `5m`, and may combine several units, such as
+/// `1h30m`. `expire never` removes the deadline. See
+/// [`LineFilter::expire_at`] for details.
+///
+/// `list` prints one directive per line. Each `module@line` and `file@line`
+/// directive is followed by a comment saying how many times it has been hit
+/// and how long ago the last hit was (see [`LineFilter::stats`]), such as
+/// `my_crate::db@142  # 3 hits, last 5s ago`. Comments are ignored when
+/// directives are parsed, so the output can still be used as a filter
+/// config.
+///
+/// Commands are executed using [`Command::execute`], which returns the text
+/// to send back to whoever sent the command.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_line_filter::{Command, LineFilter};
+///
+/// let mut filter = LineFilter::default();
+///
+/// let output = "enable my_crate::db@142"
+///     .parse::<Command>()
+///     .unwrap()
+///     .execute(&mut filter)
+///     .unwrap();
+/// assert_eq!(output, "enabled my_crate::db@142");
+///
+/// let output = "list".parse::<Command>().unwrap().execute(&mut filter).unwrap();
+/// assert_eq!(output, "my_crate::db@142  # never hit");
+/// ```
+///
+/// Executing commands against the filter in a running program, using a
+/// [`reload`] layer:
+///
+/// ```
+/// use tracing_line_filter::{Command, LineFilter};
+/// use tracing_subscriber::{prelude::*, reload};
+///
+/// let (filter, handle) = reload::Layer::new(LineFilter::default());
+/// tracing_subscriber::registry()
+///     .with(tracing_subscriber::fmt::layer())
+///     .with(filter)
+///     .init();
+///
+/// // Later, when a line is received from the control channel...
+/// let line = "enable my_crate::db@142";
+/// let response = line
+///     .parse::<Command>()
+///     .and_then(|command| command.apply(&handle, "socket", None));
+///
+/// match response {
+///     Ok(output) => println!("{}", output),
+///     Err(error) => println!("error: {}", error),
+/// }
+/// ```
+///
+/// [`reload`]: tracing_subscriber::reload
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Adds a directive to the filter (`enable <DIRECTIVE>`).
+    Enable(Directive),
+    /// Removes a `Module` or `File` directive from the filter
+    /// (`disable <DIRECTIVE>`).
+    Disable(Directive),
+    /// Lists the filter's directives and when they were last hit (`list`).
+    List,
+    /// Summarizes the filter's entries and their hits, the modules whose
+    /// entries have the most hits (see [`LineFilter::noisiest`]), and its
+    /// overhead if it is being measured (`stats`).
+    Stats,
+    /// Explains why the callsites at a `module@line` or `file@line`
+    /// location are enabled or disabled (`explain <DIRECTIVE>`). See
+    /// [`LineFilter::explain`] for details.
+    ///
+    /// This uses the callsites recorded since
+    /// [`LineFilter::record_callsites`] was called.
+    Explain(Directive),
+    /// Writes the filter's directives and profiles to a file (`save <PATH>`).
+    ///
+    /// The file starts with a line recording the version of the config
+    /// format it is written in; see [`FilterConfig::VERSION`].
+    ///
+    /// [`FilterConfig::VERSION`]: crate::FilterConfig::VERSION
+    Save(PathBuf),
+    /// Sets or removes the filter's expiry deadline (`expire <DURATION>` or
+    /// `expire never`).
+    Expire(Option<Duration>),
+    /// Disables all of the filter's entries (`disable-all`). See
+    /// [`LineFilter::disable_all`] for details.
+    DisableAll,
+    /// Re-enables the filter's entries after `disable-all`
+    /// (`reenable-all`).
+    ReenableAll,
+    /// Activates a named profile (`activate <PROFILE>`). See
+    /// [`LineFilter::activate_profile`] for details.
+    Activate(String),
+    /// Deactivates a named profile (`deactivate <PROFILE>`). See
+    /// [`LineFilter::deactivate_profile`] for details.
+    Deactivate(String),
+    /// Lists the filter's profiles, and whether each is active (`profiles`).
+    Profiles,
+    /// Lists completions for a partially-typed directive
+    /// (`complete [PREFIX]`). See [`LineFilter::complete`] for details.
+    Complete(String),
+    /// Lists the recorded callsites in the modules whose paths start with a
+    /// prefix, or in every module (`callsites [PREFIX]`). See
+    /// [`LineFilter::known_callsites`] for details.
+    ///
+    /// Each callsite is listed as a `module@line` directive, followed by a
+    /// comment describing the span or event, and whether the filter enables
+    /// it, such as ``my_crate::db@142  # INFO span `query` at src/db.rs:142,
+    /// enabled``.
+    Callsites(String),
+    /// Enables every recorded callsite that is similar to one at a
+    /// `module@line` or `file@line` location (`enable-similar <DIRECTIVE>`).
+    /// See [`LineFilter::enable_similar`] for details.
+    ///
+    /// This uses the callsites recorded since
+    /// [`LineFilter::record_callsites`] was called.
+    EnableSimilar(Directive),
+    /// Describes the available commands (`help`).
+    Help,
+}
+
+/// Indicates a [`Command`] could not be parsed or executed.
+#[derive(Debug)]
+pub struct CommandError {
+    kind: ErrorKind,
+}
+
+#[derive(Debug)]
+enum ErrorKind {
+    Unknown(String),
+    Usage(&'static str),
+    Duration(String),
+    TooLong(Duration),
+    Directive(ParseError),
+    Filter(crate::Error),
+    #[cfg(feature = "env-filter")]
+    NotRemovable(Directive),
+    Io(PathBuf, io::Error),
+    Profile(ProfileError),
+    Reload(reload::Error),
+    RateLimited(Duration),
+    ReadOnly(String),
+}
+
+const HELP: &str = "\
+enable <DIRECTIVE>   enable spans and events matching a directive
+disable <DIRECTIVE>  disable a `module@line` or `file@line` directive
+list                 list enabled directives and when they were last hit
+stats                summarize enabled entries
+explain <DIRECTIVE>  explain why the recorded callsites at a `module@line` or `file@line` are enabled
+save <PATH>          write enabled directives to a file
+expire <DURATION>    disable all line entries after a duration (such as `5m`), or `never`
+disable-all          disable all entries, leaving only the EnvFilter
+reenable-all         re-enable entries after `disable-all`
+activate <PROFILE>   enable the directives in a named profile
+deactivate <PROFILE> disable the directives in a named profile
+profiles             list named profiles and whether they are active
+complete [PREFIX]    list recorded module paths or `module@line` directives starting with a prefix
+callsites [PREFIX]   list recorded spans and events in modules starting with a prefix
+enable-similar <DIRECTIVE> enable recorded spans with the same name, or events with the same fields, as those at a `module@line` or `file@line`
+help                 print this message";
+
+impl Command {
+    /// Executes this command against `filter`, returning the command's output.
+    ///
+    /// The output is human-readable text, which may span multiple lines but
+    /// does not end with a newline.
+    ///
+    /// Changes to a filter that is already part of a subscriber only take
+    /// effect once `tracing`'s cache of enabled callsites is rebuilt. When
+    /// the filter is wrapped in a [`reload`] layer, executing commands inside
+    /// [`Handle::modify`] takes care of this.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if an `Enable` command's file path is invalid,
+    /// if an `Expire` command's duration is too long to set a deadline with,
+    /// if a `Disable` command names an [`EnvFilter`] directive, or if a
+    /// `Save` command fails to write its file, or if an `Activate` or
+    /// `Deactivate` command names a profile that isn't defined.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::{Command, LineFilter};
+    ///
+    /// let mut filter = LineFilter::default();
+    /// let mut run = |command: &str| {
+    ///     command
+    ///         .parse::<Command>()
+    ///         .and_then(|command| command.execute(&mut filter))
+    /// };
+    ///
+    /// run("enable my_crate::db@142").unwrap();
+    /// run("enable my_crate::db@200").unwrap();
+    /// run("enable warn").unwrap();
+    /// assert_eq!(run("disable my_crate::db@200").unwrap(), "disabled my_crate::db@200");
+    /// assert_eq!(run("disable my_crate::db@200").unwrap(), "my_crate::db@200 was not enabled");
+    ///
+    /// assert_eq!(run("expire 1h30m").unwrap(), "line entries expire in 1h30m");
+    /// assert!(run("stats")
+    ///     .unwrap()
+    ///     .starts_with("1 entry (1 module, 0 file), expiring in 1h29m"));
+    ///
+    /// // `EnvFilter` directives cannot be disabled.
+    /// assert!(run("disable warn").is_err());
+    /// // Neither can misspelled commands.
+    /// assert!(run("disbale my_crate::db@142").is_err());
+    /// ```
+    ///
+    /// [`reload`]: tracing_subscriber::reload
+    /// [`Handle::modify`]: tracing_subscriber::reload::Handle::modify
+    /// [`EnvFilter`]: tracing_subscriber::EnvFilter
+    /// [`apply`]: Self::apply
+    pub fn execute(&self, filter: &mut LineFilter) -> Result<String, CommandError> {
+        if let Some(output) = self.inspect(filter) {
+            return Ok(output);
+        }
+        match self {
+            Command::Enable(directive) => {
+                filter
+                    .add_directive(directive.clone())
+                    .map_err(|e| CommandError::new(ErrorKind::Filter(e)))?;
+                Ok(format!("enabled {}", directive))
+            }
+            #[cfg(feature = "env-filter")]
+            Command::Disable(directive @ Directive::Env(_)) => Err(CommandError::new(
+                ErrorKind::NotRemovable(directive.clone()),
+            )),
+            Command::Disable(directive) => {
+                if filter.remove_directive(directive) {
+                    Ok(format!("disabled {}", directive))
+                } else {
+                    Ok(format!("{} was not enabled", directive))
+                }
+            }
+            Command::Save(path) => {
+                let count = format!("{:#}", filter).lines().count();
+                fs::write(path, filter.saved_config())
+                    .map_err(|e| CommandError::new(ErrorKind::Io(path.clone(), e)))?;
+                Ok(format!(
+                    "saved {} to '{}'",
+                    plural(count, "directive"),
+                    path.display()
+                ))
+            }
+            Command::Expire(Some(ttl)) => {
+                let deadline = Instant::now()
+                    .checked_add(*ttl)
+                    .ok_or_else(|| CommandError::new(ErrorKind::TooLong(*ttl)))?;
+                filter.expire_at(deadline);
+                Ok(format!("line entries expire in {}", FmtDuration(*ttl)))
+            }
+            Command::Expire(None) => {
+                filter.expire_at(None);
+                Ok("line entries never expire".to_owned())
+            }
+            Command::DisableAll => {
+                filter.disable_all();
+                Ok("all entries disabled".to_owned())
+            }
+            Command::ReenableAll => {
+                filter.reenable_all();
+                if filter.is_disabled() {
+                    Ok("entries re-enabled, but LINE_FILTER_DISABLE is set".to_owned())
+                } else {
+                    Ok("entries re-enabled".to_owned())
+                }
+            }
+            Command::Activate(name) => {
+                if filter.is_profile_active(name) {
+                    return Ok(format!("profile '{}' is already active", name));
+                }
+                filter
+                    .activate_profile(name)
+                    .map_err(|e| CommandError::new(ErrorKind::Profile(e)))?;
+                Ok(format!("activated profile '{}'", name))
+            }
+            Command::Deactivate(name) => {
+                if filter
+                    .profiles()
+                    .any(|profile| profile == (name.as_str(), false))
+                {
+                    return Ok(format!("profile '{}' is not active", name));
+                }
+                filter
+                    .deactivate_profile(name)
+                    .map_err(|e| CommandError::new(ErrorKind::Profile(e)))?;
+                Ok(format!("deactivated profile '{}'", name))
+            }
+            Command::EnableSimilar(directive) => Ok(enable_similar(filter, directive)),
+            Command::List
+            | Command::Stats
+            | Command::Explain(_)
+            | Command::Profiles
+            | Command::Complete(_)
+            | Command::Callsites(_)
+            | Command::Help => unreachable!("read-only commands are run by `inspect`"),
+        }
+    }
+
+    /// Runs this command if it is [read-only](Self::is_read_only), returning
+    /// its output, or `None` if it isn't.
+    fn inspect(&self, filter: &LineFilter) -> Option<String> {
+        let output = match self {
+            Command::List => {
+                let list = list(filter);
+                if list.is_empty() {
+                    "no directives enabled".to_owned()
+                } else {
+                    list
+                }
+            }
+            Command::Stats => stats(filter),
+            Command::Explain(directive) => explain(filter, directive),
+            Command::Profiles => {
+                let profiles = filter
+                    .profiles()
+                    .map(|(name, active)| {
+                        if active {
+                            format!("{}  # active", name)
+                        } else {
+                            name.to_owned()
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                if profiles.is_empty() {
+                    "no profiles defined".to_owned()
+                } else {
+                    profiles.join("\n")
+                }
+            }
+            Command::Complete(prefix) => filter.complete(prefix).join("\n"),
+            Command::Callsites(prefix) => callsites(filter, prefix),
+            Command::Help => HELP.to_owned(),
+            _ => return None,
+        };
+        Some(output)
+    }
+
+    /// Returns `true` if this command only inspects the filter, so that it
+    /// may be run while the filter is [read-only].
+    ///
+    /// `save` isn't read-only, even though it doesn't change the filter,
+    /// since it writes a file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::Command;
+    ///
+    /// let is_read_only = |command: &str| command.parse::<Command>().unwrap().is_read_only();
+    /// assert!(is_read_only("list"));
+    /// assert!(is_read_only("explain my_crate::db@142"));
+    /// assert!(!is_read_only("enable my_crate::db@142"));
+    /// assert!(!is_read_only("save filter.txt"));
+    /// ```
+    ///
+    /// [read-only]: LineFilter::read_only_commands
+    pub fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            Command::List
+                | Command::Stats
+                | Command::Explain(_)
+                | Command::Profiles
+                | Command::Complete(_)
+                | Command::Callsites(_)
+                | Command::Help
+        )
+    }
+
+    /// Returns `true` if this command changes the filter when it succeeds.
+    fn changes_filter(&self) -> bool {
+        matches!(
+            self,
+            Command::Enable(_)
+                | Command::Disable(_)
+                | Command::Expire(_)
+                | Command::DisableAll
+                | Command::ReenableAll
+                | Command::Activate(_)
+                | Command::Deactivate(_)
+                | Command::EnableSimilar(_)
+        )
+    }
+
+    /// Executes this command against the filter behind a [`reload`]
+    /// handle, returning the command's output.
+    ///
+    /// This executes the command inside [`Handle::modify`], which also
+    /// rebuilds `tracing`'s cache of enabled callsites, so that changes take
+    /// effect immediately. Commands that only inspect the filter (see
+    /// [`is_read_only`]) take the handle's read lock instead, and leave the
+    /// cache alone. Once a command that changes the filter (`enable`,
+    /// `disable`, `expire`, `disable-all`, `reenable-all`, `activate`,
+    /// `deactivate`, or `enable-similar`) has succeeded, an `INFO` event is
+    /// recorded with the `tracing_line_filter::audit` target, so that changes
+    /// to a program's logging are themselves logged. `LineFilter`s always
+    /// enable these events, regardless of their directives. The event's
+    /// fields are:
+    ///
+    /// * `transport`: how the command was received, such as `socket`.
+    /// * `peer`: who sent the command, such as a client's address, if
+    ///   `peer` is `Some`.
+    /// * `change`: the command.
+    /// * `entries.before` and `entries.after`: the number of
+    ///   `(module, line)` and `(file, line)` entries before and after the
+    ///   change.
+    ///
+    /// If the filter has a [maximum number of entries], each entry that the
+    /// change evicted is reported by a further `INFO` event with the same
+    /// target, `transport`, and `peer`, and the evicted `directive`.
+    ///
+    /// Every other command, including one that fails, is recorded as a
+    /// `DEBUG` event with the same target, `transport`, `peer`, and
+    /// `command` fields, and the `error` it failed with, if it did. If the
+    /// filter is [read-only] and the command isn't, or if the filter has a
+    /// [command limit] that was already reached, the command isn't run,
+    /// and a `WARN` event records it instead. For a command limit, the event
+    /// has a `retry_after_ms` field saying how long until commands may run
+    /// again.
+    ///
+    /// The [`jsonrpc`] and [`dap`] servers (and `LineFilter::from_watch`,
+    /// when the `tokio` feature flag is enabled) record the same events for
+    /// the changes they make.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the command fails, if the filter is
+    /// read-only (see [`CommandError::is_read_only`]), if the filter's
+    /// command limit has been reached (see
+    /// [`CommandError::is_rate_limited`]), or if the subscriber containing
+    /// the filter has been dropped.
+    ///
+    /// [`reload`]: tracing_subscriber::reload
+    /// [`Handle::modify`]: tracing_subscriber::reload::Handle::modify
+    /// [maximum number of entries]: LineFilter::max_entries
+    /// [read-only]: LineFilter::read_only_commands
+    /// [`is_read_only`]: Self::is_read_only
+    /// [command limit]: LineFilter::limit_commands
+    /// [`jsonrpc`]: https://docs.rs/tracing-line-filter/latest/tracing_line_filter/jsonrpc/index.html
+    /// [`dap`]: https://docs.rs/tracing-line-filter/latest/tracing_line_filter/dap/index.html
+    pub fn apply<S>(
+        &self,
+        handle: &reload::Handle<LineFilter, S>,
+        transport: &str,
+        peer: Option<&str>,
+    ) -> Result<String, CommandError>
+    where
+        S: Subscriber,
+    {
+        let request = audit::Request {
+            transport,
+            peer,
+            command: self,
+        };
+        let reload = |e| CommandError::new(ErrorKind::Reload(e));
+        if !self.is_read_only()
+            && handle
+                .with_current(LineFilter::is_read_only)
+                .map_err(reload)?
+        {
+            request.record_rejected();
+            return Err(CommandError::new(ErrorKind::ReadOnly(self.to_string())));
+        }
+        let limited = handle
+            .with_current(LineFilter::check_command_limit)
+            .map_err(reload)?;
+        if let Err(retry_after) = limited {
+            request.record_limited(retry_after);
+            return Err(CommandError::new(ErrorKind::RateLimited(retry_after)));
+        }
+
+        if self.is_read_only() {
+            let output = handle
+                .with_current(|filter| self.inspect(filter))
+                .map_err(reload)?
+                .expect("read-only commands are run by `inspect`");
+            request.record(None);
+            return Ok(output);
+        }
+
+        let mut result = None;
+        let mut entries = (0, 0);
+        let mut evicted = Vec::new();
+        handle
+            .modify(|filter| {
+                entries.0 = filter.entry_count();
+                result = Some(self.execute(filter));
+                #[cfg(feature = "metrics")]
+                if self.changes_filter() && matches!(result, Some(Ok(_))) {
+                    filter.metrics.record_reload(filter);
+                }
+                entries.1 = filter.entry_count();
+                evicted = filter.take_evicted();
+            })
+            .map_err(reload)?;
+        let output = match result.expect("`modify` calls the closure") {
+            Ok(output) => output,
+            Err(error) => {
+                request.record(Some(&error));
+                return Err(error);
+            }
+        };
+
+        if self.changes_filter() {
+            audit::Change {
+                transport,
+                peer,
+                change: self,
+                entries_before: entries.0,
+                entries_after: entries.1,
+                evicted: &evicted,
+            }
+            .record();
+            publish::publish(handle, transport);
+        } else {
+            request.record(None);
+        }
+        Ok(output)
+    }
+}
+
+impl FromStr for Command {
+    type Err = CommandError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (name, arg) = match s.find(char::is_whitespace) {
+            Some(i) => (&s[..i], s[i..].trim_start()),
+            None => (s, ""),
+        };
+
+        let usage = |usage| Err(CommandError::new(ErrorKind::Usage(usage)));
+        let directive = |arg: &str| {
+            arg.parse::<Directive>()
+                .map_err(|e| CommandError::new(ErrorKind::Directive(e)))
+        };
+        match (name, arg) {
+            ("enable", "") => usage("enable <DIRECTIVE>"),
+            ("enable", arg) => Ok(Command::Enable(directive(arg)?)),
+            ("disable", "") => usage("disable <DIRECTIVE>"),
+            ("disable", arg) => Ok(Command::Disable(directive(arg)?)),
+            ("list", "") => Ok(Command::List),
+            ("list", _) => usage("list"),
+            ("stats", "") => Ok(Command::Stats),
+            ("stats", _) => usage("stats"),
+            ("explain", "") => usage("explain <DIRECTIVE>"),
+            ("explain", arg) => match directive(arg)? {
+                Directive::ModuleLevel { .. }
+                | Directive::ModulePrefix { .. }
+                | Directive::Deny(_) => usage("explain <module@line|file@line>"),
+                #[cfg(feature = "env-filter")]
+                Directive::Env(_) => usage("explain <module@line|file@line>"),
+                directive => Ok(Command::Explain(directive)),
+            },
+            ("save", "") => usage("save <PATH>"),
+            ("save", arg) => Ok(Command::Save(PathBuf::from(arg))),
+            ("expire", "") => usage("expire <DURATION>"),
+            ("expire", "never") => Ok(Command::Expire(None)),
+            ("expire", arg) => {
+                let ttl = parse_duration(arg)
+                    .ok_or_else(|| CommandError::new(ErrorKind::Duration(arg.to_owned())))?;
+                if Instant::now().checked_add(ttl).is_none() {
+                    return Err(CommandError::new(ErrorKind::TooLong(ttl)));
+                }
+                Ok(Command::Expire(Some(ttl)))
+            }
+            ("disable-all", "") => Ok(Command::DisableAll),
+            ("disable-all", _) => usage("disable-all"),
+            ("reenable-all", "") => Ok(Command::ReenableAll),
+            ("reenable-all", _) => usage("reenable-all"),
+            ("activate", "") => usage("activate <PROFILE>"),
+            ("activate", arg) => Ok(Command::Activate(arg.to_owned())),
+            ("deactivate", "") => usage("deactivate <PROFILE>"),
+            ("deactivate", arg) => Ok(Command::Deactivate(arg.to_owned())),
+            ("profiles", "") => Ok(Command::Profiles),
+            ("profiles", _) => usage("profiles"),
+            ("complete", prefix) => Ok(Command::Complete(prefix.to_owned())),
+            ("callsites", prefix) => Ok(Command::Callsites(prefix.to_owned())),
+            ("enable-similar", "") => usage("enable-similar <DIRECTIVE>"),
+            ("enable-similar", arg) => match directive(arg)? {
+                directive @ (Directive::Module { .. } | Directive::File { .. }) => {
+                    Ok(Command::EnableSimilar(directive))
+                }
+                _ => usage("enable-similar <module@line|file@line>"),
+            },
+            ("help", "") => Ok(Command::Help),
+            ("help", _) => usage("help"),
+            _ => Err(CommandError::new(ErrorKind::Unknown(name.to_owned()))),
+        }
+    }
+}
+
+/// Formats a `Command` in the syntax it is parsed from, such as
+/// `enable my_crate::db@142`.
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Enable(directive) => write!(f, "enable {}", directive),
+            Command::Disable(directive) => write!(f, "disable {}", directive),
+            Command::List => f.write_str("list"),
+            Command::Stats => f.write_str("stats"),
+            Command::Explain(directive) => write!(f, "explain {}", directive),
+            Command::Save(path) => write!(f, "save {}", path.display()),
+            Command::Expire(Some(ttl)) => write!(f, "expire {}", FmtDuration(*ttl)),
+            Command::Expire(None) => f.write_str("expire never"),
+            Command::DisableAll => f.write_str("disable-all"),
+            Command::ReenableAll => f.write_str("reenable-all"),
+            Command::Activate(name) => write!(f, "activate {}", name),
+            Command::Deactivate(name) => write!(f, "deactivate {}", name),
+            Command::Profiles => f.write_str("profiles"),
+            Command::Complete(prefix) if prefix.is_empty() => f.write_str("complete"),
+            Command::Complete(prefix) => write!(f, "complete {}", prefix),
+            Command::Callsites(prefix) if prefix.is_empty() => f.write_str("callsites"),
+            Command::Callsites(prefix) => write!(f, "callsites {}", prefix),
+            Command::EnableSimilar(directive) => write!(f, "enable-similar {}", directive),
+            Command::Help => f.write_str("help"),
+        }
+    }
+}
+
+/// Lists the filter's directives, with their notes, followed by comments
+/// describing when each entry was last hit. The comments are ignored when
+/// the list is parsed.
+pub(crate) fn list(filter: &LineFilter) -> String {
+    let now = SystemTime::now();
+    let noted = |directive: &Directive| match filter.note(directive) {
+        Some(note) => format!("{} {}", directive, note::fmt_clause(note)),
+        None => directive.to_string(),
+    };
+    let modules = filter
+        .mod_prefix_directives()
+        .into_iter()
+        .chain(filter.mod_level_directives())
+        .map(|directive| noted(&directive));
+    let entries = filter.stats().into_iter().map(|entry| {
+        let last_hit = entry
+            .last_hit()
+            .map(|last_hit| now.duration_since(last_hit).unwrap_or_default());
+        match last_hit {
+            Some(ago) => format!(
+                "{}  # {}, last {} ago",
+                noted(entry.directive()),
+                plural(entry.hits() as usize, "hit"),
+                FmtDuration(ago),
+            ),
+            None => format!("{}  # never hit", noted(entry.directive())),
+        }
+    });
+    let denied = filter
+        .deny_directives()
+        .into_iter()
+        .map(|directive| noted(&directive));
+    let mut lines = modules.chain(entries).chain(denied).collect::<Vec<_>>();
+    if let Some(ref fallback) = filter.fallback {
+        let env = fallback.to_string();
+        if !env.is_empty() {
+            lines.push(env);
+        }
+    }
+    lines.join("\n")
+}
+
+/// Explains each recorded callsite at the location of a `Module` or `File`
+/// directive.
+fn explain(filter: &LineFilter, directive: &Directive) -> String {
+    if filter.recorder.is_none() {
+        return "callsites are not being recorded".to_owned();
+    }
+    let mut callsites = filter.recorded_at(directive);
+    if callsites.is_empty() {
+        return format!("no callsites recorded at {}", directive);
+    }
+
+    callsites.sort_by_key(|metadata| (metadata.is_span(), metadata.name()));
+    callsites
+        .into_iter()
+        .map(|metadata| {
+            let kind = if metadata.is_span() {
+                format!("span `{}`", metadata.name())
+            } else {
+                "event".to_owned()
+            };
+            format!(
+                "{} {}: {}",
+                metadata.level(),
+                kind,
+                filter.explain(metadata)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn callsites(filter: &LineFilter, prefix: &str) -> String {
+    if filter.recorder.is_none() {
+        return "callsites are not being recorded".to_owned();
+    }
+    let callsites = filter
+        .known_callsites()
+        .filter_map(|metadata| {
+            let module = crate::module_keys(metadata).find(|key| key.starts_with(prefix))?;
+            let mut callsite = format!(
+                "{}@{}  # {}",
+                module,
+                metadata.line()?,
+                record::describe(metadata)
+            );
+            if filter.explain(metadata).is_enabled() {
+                callsite.push_str(", enabled");
+            }
+            Some(callsite)
+        })
+        .collect::<Vec<_>>();
+    if callsites.is_empty() {
+        return "no callsites recorded".to_owned();
+    }
+    callsites.join("\n")
+}
+
+fn enable_similar(filter: &mut LineFilter, directive: &Directive) -> String {
+    if filter.recorder.is_none() {
+        return "callsites are not being recorded".to_owned();
+    }
+    let callsites = filter.recorded_at(directive);
+    if callsites.is_empty() {
+        return format!("no callsites recorded at {}", directive);
+    }
+    let mut enabled = Vec::new();
+    for metadata in callsites {
+        enabled.extend(filter.enable_similar(metadata));
+    }
+    enabled.sort_by_key(|directive| match directive {
+        Directive::Module { module, line, .. } => (module.clone(), *line),
+        Directive::File { file, line, .. } => (file.clone(), *line),
+        directive => (directive.to_string(), 0),
+    });
+    enabled.dedup();
+    let mut output = format!(
+        "enabled {} similar to {}",
+        plural(enabled.len(), "directive"),
+        directive
+    );
+    for directive in enabled {
+        output.push_str(&format!("\n{}", directive));
+    }
+    output
+}
+
+fn stats(filter: &LineFilter) -> String {
+    let modules = filter.by_module.len();
+    let files = filter.by_file.len();
+    let mut stats = format!(
+        "{} ({} module, {} file)",
+        plural(modules + files, "entry"),
+        modules,
+        files,
+    );
+
+    if let Some(deadline) = filter.expires_at() {
+        match deadline.checked_duration_since(Instant::now()) {
+            Some(ttl) if !ttl.is_zero() => {
+                stats.push_str(&format!(", expiring in {}", FmtDuration(ttl)))
+            }
+            _ => stats.push_str(", expired"),
+        }
+    }
+    if filter.is_disabled() {
+        stats.push_str(", disabled");
+    }
+    if filter.is_starting_up() {
+        stats.push_str(", starting up");
+    }
+
+    for entry in filter.stats() {
+        stats.push_str(&format!(
+            "\n{}: {}",
+            entry.directive(),
+            plural(entry.hits() as usize, "hit")
+        ));
+        if let Some(note) = entry.note() {
+            stats.push_str(&format!(" ({})", note));
+        }
+    }
+    let noisiest = filter
+        .noisiest(Rollup::Module, 3)
+        .into_iter()
+        .filter(|module| module.hits() > 0)
+        .map(|module| {
+            format!(
+                "{} ({})",
+                module.location(),
+                plural(module.hits() as usize, "hit")
+            )
+        })
+        .collect::<Vec<_>>();
+    if !noisiest.is_empty() {
+        stats.push_str(&format!("\nnoisiest modules: {}", noisiest.join(", ")));
+    }
+
+    if let Some(ref fallback) = filter.fallback {
+        let env = fallback.to_string();
+        if !env.is_empty() {
+            stats.push_str(&format!("\nEnvFilter: {}", env));
+        }
+    }
+
+    if let Some(overhead) = filter.overhead() {
+        stats.push_str(&format!(
+            "\nregister_callsite: {}\nenabled: {}",
+            overhead.register_callsite(),
+            overhead.enabled()
+        ));
+    }
+    stats
+}
+
+/// Parses durations like `30s`, `5m`, or `1h30m`.
+pub(crate) fn parse_duration(s: &str) -> Option<Duration> {
+    let mut total = Duration::ZERO;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let n = rest[..digits].parse::<u64>().ok()?;
+        rest = &rest[digits..];
+        let units = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let duration = match &rest[..units] {
+            "ms" => Duration::from_millis(n),
+            "s" => Duration::from_secs(n),
+            "m" => Duration::from_secs(n.checked_mul(60)?),
+            "h" => Duration::from_secs(n.checked_mul(60 * 60)?),
+            _ => return None,
+        };
+        rest = &rest[units..];
+        total = total.checked_add(duration)?;
+    }
+    Some(total)
+}
+
+fn plural(n: usize, noun: &str) -> String {
+    match (n, noun.strip_suffix('y')) {
+        (1, _) => format!("1 {}", noun),
+        (n, Some(stem)) => format!("{} {}ies", n, stem),
+        (n, None) => format!("{} {}s", n, noun),
+    }
+}
+
+/// Formats a duration like `1h30m` or `4m59s`, to the nearest second.
+pub(crate) struct FmtDuration(pub(crate) Duration);
+
+impl fmt::Display for FmtDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.0.as_secs();
+        if secs == 0 {
+            return write!(f, "{}ms", self.0.as_millis());
+        }
+
+        let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+        if h > 0 {
+            write!(f, "{}h", h)?;
+        }
+        if m > 0 {
+            write!(f, "{}m", m)?;
+        }
+        if s > 0 {
+            write!(f, "{}s", s)?;
+        }
+        Ok(())
+    }
+}
+
+// === impl CommandError ===
+
+impl CommandError {
+    /// Returns `true` if the command wasn't run because the filter is
+    /// [read-only].
+    ///
+    /// [read-only]: LineFilter::read_only_commands
+    pub fn is_read_only(&self) -> bool {
+        matches!(self.kind, ErrorKind::ReadOnly(_))
+    }
+
+    /// Returns `true` if the command wasn't run because the filter's
+    /// [command limit] was reached.
+    ///
+    /// [command limit]: LineFilter::limit_commands
+    pub fn is_rate_limited(&self) -> bool {
+        matches!(self.kind, ErrorKind::RateLimited(_))
+    }
+
+    /// Returns `true` if the filter could not be changed because its
+    /// subscriber was dropped.
+    #[cfg(feature = "jsonrpc")]
+    pub(crate) fn is_reload(&self) -> bool {
+        matches!(self.kind, ErrorKind::Reload(_))
+    }
+
+    fn new(kind: ErrorKind) -> Self {
+        Self { kind }
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ErrorKind::Unknown(ref name) => {
+                write!(f, "unknown command '{}'; try 'help'", name)
+            }
+            ErrorKind::Usage(usage) => write!(f, "usage: {}", usage),
+            ErrorKind::Duration(ref duration) => write!(
+                f,
+                "invalid duration '{}': expected a number followed by 'ms', 's', 'm', or 'h'",
+                duration
+            ),
+            ErrorKind::TooLong(duration) => {
+                write!(f, "duration '{}' is too long", FmtDuration(duration))
+            }
+            ErrorKind::Directive(ref e) => fmt::Display::fmt(e, f),
+            ErrorKind::Filter(ref e) => fmt::Display::fmt(e, f),
+            #[cfg(feature = "env-filter")]
+            ErrorKind::NotRemovable(ref directive) => write!(
+                f,
+                "cannot disable '{}': `EnvFilter` directives cannot be removed",
+                directive
+            ),
+            ErrorKind::Io(ref path, ref e) => {
+                write!(f, "failed to write '{}': {}", path.display(), e)
+            }
+            ErrorKind::Profile(ref e) => fmt::Display::fmt(e, f),
+            ErrorKind::Reload(ref e) => write!(f, "failed to change the filter: {}", e),
+            ErrorKind::ReadOnly(ref command) => {
+                write!(f, "cannot run '{}': the filter is read-only", command)
+            }
+            ErrorKind::RateLimited(retry_after) => write!(
+                f,
+                "too many commands; try again in {}",
+                FmtDuration(retry_after)
+            ),
+        }
+    }
+}
+
+impl Error for CommandError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self.kind {
+            ErrorKind::Directive(ref e) => Some(e),
+            ErrorKind::Filter(ref e) => Some(e),
+            ErrorKind::Io(_, ref e) => Some(e),
+            ErrorKind::Profile(ref e) => Some(e),
+            ErrorKind::Reload(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_err(command: &str) -> String {
+        command
+            .parse::<Command>()
+            .expect_err("command should not parse")
+            .to_string()
+    }
+
+    #[test]
+    fn empty_commands_are_unknown() {
+        assert_eq!(parse_err(""), "unknown command ''; try 'help'");
+        assert_eq!(parse_err("   \t"), "unknown command ''; try 'help'");
+    }
+
+    #[test]
+    fn unknown_verbs_are_rejected() {
+        assert_eq!(
+            parse_err("disbale a@1"),
+            "unknown command 'disbale'; try 'help'"
+        );
+        assert_eq!(parse_err("LIST"), "unknown command 'LIST'; try 'help'");
+    }
+
+    #[test]
+    fn missing_arguments_print_usage() {
+        assert_eq!(parse_err("enable"), "usage: enable <DIRECTIVE>");
+        assert_eq!(parse_err("expire"), "usage: expire <DURATION>");
+        assert_eq!(parse_err("activate   "), "usage: activate <PROFILE>");
+    }
+
+    #[test]
+    fn trailing_garbage_is_rejected() {
+        assert_eq!(parse_err("list all"), "usage: list");
+        assert_eq!(parse_err("stats now"), "usage: stats");
+        assert_eq!(parse_err("help me"), "usage: help");
+        assert!("enable my_crate@142 extra".parse::<Command>().is_err());
+        assert!("expire 5m later".parse::<Command>().is_err());
+        assert!("expire 5x".parse::<Command>().is_err());
+        assert!("expire 5".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn overflowing_durations_are_rejected() {
+        assert!(parse_err("expire 18446744073709551615s").contains("too long"));
+        assert!(parse_err("expire 18446744073709551615h").starts_with("invalid duration"));
+        assert!(parse_err("expire 18446744073709551616s").starts_with("invalid duration"));
+        assert!(parse_err("expire 9223372036854775807s9223372036854775807s").contains("too long"));
+    }
+
+    #[test]
+    fn overflowing_expire_does_not_panic() {
+        let mut filter = LineFilter::default();
+        filter.expire_after(Duration::from_secs(60));
+        let deadline = filter.expires_at();
+
+        let error = Command::Expire(Some(Duration::MAX))
+            .execute(&mut filter)
+            .expect_err("the deadline overflows");
+        assert!(error.to_string().contains("too long"));
+        assert_eq!(filter.expires_at(), deadline);
+
+        filter.expire_after(Duration::MAX);
+        assert_eq!(filter.expires_at(), None);
+    }
+
+    #[test]
+    fn durations_round_trip() {
+        for command in ["expire 1h30m", "expire 45s", "expire 250ms", "expire never"] {
+            let parsed = command.parse::<Command>().unwrap();
+            assert_eq!(parsed.to_string(), command);
+        }
+    }
+
+    #[test]
+    fn read_only_commands_take_the_read_lock() {
+        use tracing_subscriber::prelude::*;
+
+        let filter = format!("{}@{}", module_path!(), line!()).parse::<LineFilter>();
+        let (filter, handle) = reload::Layer::new(filter.unwrap());
+        let _subscriber = tracing_subscriber::registry().with(filter);
+
+        // Running a command that needed the write lock here would deadlock.
+        let listed = handle
+            .with_current(|_| Command::List.apply(&handle, "test", None))
+            .unwrap()
+            .unwrap();
+        assert!(listed.starts_with(module_path!()));
+        assert!(Command::Stats.apply(&handle, "test", None).is_ok());
+
+        let enable = format!("enable {}@1", module_path!()).parse::<Command>();
+        assert!(enable.unwrap().apply(&handle, "test", None).is_ok());
+        let listed = Command::List.apply(&handle, "test", None).unwrap();
+        assert_eq!(listed.lines().count(), 2);
+    }
+}
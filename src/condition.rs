@@ -0,0 +1,208 @@
+use crate::LineFilter;
+use std::{env, fs, path::Path, sync::RwLock};
+
+/// The instance ID set by [`LineFilter::set_instance_id`].
+static INSTANCE_ID: RwLock<Option<String>> = RwLock::new(None);
+
+/// A `when = { ... }` clause, which makes the directives it applies to
+/// conditional on the environment the program is running in.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct Condition {
+    env: Option<String>,
+    equals: Option<String>,
+    hostname: Option<String>,
+    process: Option<String>,
+    instance: Option<String>,
+    /// Keys that this version of the crate doesn't know about, which are
+    /// never met.
+    unknown: Vec<String>,
+}
+
+impl LineFilter {
+    /// Sets an ID for this instance of the program, such as a replica's
+    /// name, which `when = { instance = "ID" }` clauses in configs are
+    /// matched against.
+    ///
+    /// A fleet of processes running different binaries, or several copies of
+    /// one binary, can then share a single config file, with each entry
+    /// scoped to the processes it is relevant to (see [the `FromStr`
+    /// implementation for `LineFilter`][syntax]). The ID applies to the
+    /// whole process, and to configs parsed after it is set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    ///
+    /// LineFilter::set_instance_id("web-3");
+    ///
+    /// let config = r#"
+    /// my_crate::db@142 when = { instance = "web-3" }
+    /// my_crate::db@200 when = { instance = "web-4" }
+    /// "#;
+    /// let filter: LineFilter = config.parse().unwrap();
+    /// assert_eq!(filter.to_string(), "my_crate::db@142");
+    /// ```
+    ///
+    /// [syntax]: crate::LineFilter#impl-FromStr-for-LineFilter
+    pub fn set_instance_id(id: impl Into<String>) {
+        let mut instance = INSTANCE_ID.write().unwrap_or_else(|e| e.into_inner());
+        *instance = Some(id.into());
+    }
+}
+
+/// Splits a `when = { ... }` clause off the end of a line of directives,
+/// returning the rest of the line and the clause's text, if there is one.
+pub(crate) fn split(line: &str) -> (&str, Option<&str>) {
+    let mut search = 0;
+    while let Some(i) = line[search..].find("when") {
+        let start = search + i;
+        search = start + "when".len();
+        let preceded = line[..start]
+            .chars()
+            .next_back()
+            .is_none_or(|c| c.is_ascii_whitespace() || c == ',');
+        let rest = line[search..].trim_start();
+        let followed = rest
+            .strip_prefix('=')
+            .is_some_and(|rest| rest.trim_start().starts_with('{'));
+        if preceded && followed {
+            return (&line[..start], Some(&line[start..]));
+        }
+    }
+    (line, None)
+}
+
+// === impl Condition ===
+
+impl Condition {
+    /// Parses a `when = { key = "value", ... }` clause.
+    pub(crate) fn parse(clause: &str) -> Result<Self, &'static str> {
+        let table = clause
+            .trim()
+            .strip_prefix("when")
+            .and_then(|rest| rest.trim_start().strip_prefix('='))
+            .and_then(|rest| rest.trim().strip_prefix('{'))
+            .and_then(|rest| rest.strip_suffix('}'))
+            .ok_or("expected `when = { ... }` at the end of the line")?;
+
+        let mut condition = Self::default();
+        for pair in table
+            .split(',')
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+        {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or("expected `key = \"value\"` in `when` clause")?;
+            let value = value
+                .trim()
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .filter(|value| !value.contains('"'))
+                .ok_or("`when` clause values must be quoted strings")?
+                .to_owned();
+            let slot = match key.trim() {
+                "env" => &mut condition.env,
+                "equals" => &mut condition.equals,
+                "hostname" => &mut condition.hostname,
+                "process" => &mut condition.process,
+                "instance" => &mut condition.instance,
+                key => {
+                    condition.unknown.push(key.to_owned());
+                    continue;
+                }
+            };
+            if slot.replace(value).is_some() {
+                return Err("duplicate key in `when` clause");
+            }
+        }
+
+        if condition.equals.is_some() && condition.env.is_none() {
+            return Err("`equals` requires an `env` key in the same `when` clause");
+        }
+        if condition == Self::default() {
+            return Err(
+                "`when` clauses must have an `env`, `hostname`, `process`, or `instance` key",
+            );
+        }
+        Ok(condition)
+    }
+
+    /// Returns the keys in this condition that this version of the crate
+    /// doesn't know about.
+    pub(crate) fn unknown_keys(&self) -> &[String] {
+        &self.unknown
+    }
+
+    /// Returns `true` if the program's environment meets this condition.
+    ///
+    /// Conditions with unknown keys are never met, since the directives they
+    /// apply to were meant for environments this version of the crate can't
+    /// recognize.
+    pub(crate) fn is_met(&self) -> bool {
+        if !self.unknown.is_empty() {
+            return false;
+        }
+        if let Some(ref var) = self.env {
+            let value = env::var(var).ok();
+            let met = match self.equals {
+                Some(ref equals) => value.as_ref() == Some(equals),
+                None => value.is_some_and(|value| !value.is_empty()),
+            };
+            if !met {
+                return false;
+            }
+        }
+
+        let patterns = [
+            (&self.hostname, hostname as fn() -> Option<String>),
+            (&self.process, process_name),
+            (&self.instance, instance_id),
+        ];
+        patterns.iter().all(|(pattern, value)| match pattern {
+            Some(pattern) => matches(pattern, &value().unwrap_or_default()),
+            None => true,
+        })
+    }
+}
+
+/// Returns `true` if `value` is `pattern`, or starts with `pattern`'s prefix
+/// if it ends in `*`.
+fn matches(pattern: &str, value: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => value.starts_with(prefix),
+        None => value == pattern,
+    }
+}
+
+/// Returns the name of the machine the program is running on, if it can be
+/// found.
+fn hostname() -> Option<String> {
+    let from_file = ["/proc/sys/kernel/hostname", "/etc/hostname"]
+        .iter()
+        .find_map(|path| fs::read_to_string(path).ok());
+    from_file
+        .or_else(|| env::var("HOSTNAME").ok())
+        .or_else(|| env::var("COMPUTERNAME").ok())
+        .map(|hostname| hostname.trim().to_owned())
+        .filter(|hostname| !hostname.is_empty())
+}
+
+/// Returns the file name of the program's executable, without an
+/// extension.
+fn process_name() -> Option<String> {
+    let exe = env::current_exe()
+        .ok()
+        .or_else(|| env::args_os().next().map(Into::into))?;
+    Path::new(&exe)
+        .file_stem()
+        .map(|name| name.to_string_lossy().into_owned())
+}
+
+fn instance_id() -> Option<String> {
+    INSTANCE_ID
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
@@ -0,0 +1,294 @@
+use crate::{directive, fields, note, startup, Directive, Error, LineFilter, ParseError, Warning};
+use std::{
+    collections::BTreeMap, fmt, iter::FromIterator, path::Path, str::FromStr, time::Duration,
+};
+
+/// A snapshot of a [`LineFilter`]'s directives, which can be applied to an
+/// existing filter with [`LineFilter::set_config`].
+///
+/// Unlike a `LineFilter`, a `FilterConfig` is plain data that can be cloned
+/// and compared, so it can be distributed to the parts of a program that
+/// own filters, such as over a [`tokio::sync::watch`] channel using
+/// `LineFilter::from_watch` (when the `tokio` feature flag is enabled).
+///
+/// A `FilterConfig` can be parsed from the same directive syntax as a
+/// `LineFilter` (see [the `FromStr` implementation for
+/// `LineFilter`][syntax]), or collected from [`Directive`]s.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_line_filter::{FilterConfig, LineFilter};
+///
+/// let config: FilterConfig = "my_crate::db@142, warn".parse().unwrap();
+///
+/// let mut filter = LineFilter::default();
+/// filter.enable_by_mod("my_crate::db", 200);
+/// filter.set_config(&config).unwrap();
+/// assert_eq!(filter.to_string(), "my_crate::db@142,warn");
+/// ```
+///
+/// [`tokio::sync::watch`]: https://docs.rs/tokio/latest/tokio/sync/watch/index.html
+/// [syntax]: crate::LineFilter#impl-FromStr-for-LineFilter
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FilterConfig {
+    directives: Vec<Directive>,
+    profiles: BTreeMap<String, Vec<Directive>>,
+    span_fields: Vec<(String, String)>,
+    notes: BTreeMap<String, String>,
+    startup: Vec<Directive>,
+    startup_window: Option<Duration>,
+}
+
+impl FilterConfig {
+    /// Returns a new, empty `FilterConfig`, which disables all spans and
+    /// events when applied to a filter.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn from_parts(
+        directives: Vec<Directive>,
+        profiles: BTreeMap<String, Vec<Directive>>,
+        span_fields: Vec<(String, String)>,
+    ) -> Self {
+        Self {
+            directives,
+            profiles,
+            span_fields,
+            notes: BTreeMap::new(),
+            startup: Vec::new(),
+            startup_window: None,
+        }
+    }
+
+    /// Returns the directives in this config, in the order they were added.
+    pub fn directives(&self) -> &[Directive] {
+        &self.directives
+    }
+
+    /// Returns the names and directives of the profiles defined in this
+    /// config's `[profile.NAME]` sections, sorted by name.
+    ///
+    /// See [`LineFilter::define_profile`] for details on profiles.
+    pub fn profiles(&self) -> impl Iterator<Item = (&str, &[Directive])> + '_ {
+        self.profiles
+            .iter()
+            .map(|(name, directives)| (name.as_str(), directives.as_slice()))
+    }
+
+    /// Parses a config from a directive string, returning the [`Warning`]s
+    /// found while parsing it instead of recording them as events.
+    ///
+    /// See [`LineFilter::parse_with_warnings`] for details.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if `s` can't be parsed.
+    pub fn parse_with_warnings(s: &str) -> Result<(Self, Vec<Warning>), ParseError> {
+        let sections = directive::parse_sections(s)?;
+        let config = Self {
+            directives: sections.directives,
+            profiles: sections.profiles,
+            span_fields: sections.fields,
+            notes: sections.notes,
+            startup: sections.startup,
+            startup_window: sections.startup_window,
+        };
+        Ok((config, sections.warnings))
+    }
+
+    /// Returns the names and values of the fields set by this config's
+    /// `fields = { ... }` lines.
+    ///
+    /// See [`LineFilter::add_span_field`] for details on span fields.
+    pub fn span_fields(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+        self.span_fields
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Returns the note attached to `directive` by a `note = "..."` clause
+    /// in this config, if it has one.
+    ///
+    /// See [`LineFilter::set_note`] for details on notes.
+    pub fn note(&self, directive: &Directive) -> Option<&str> {
+        note::get(&self.notes, directive)
+    }
+
+    /// Returns the directives in this config's `[startup]` section, in the
+    /// order they were added.
+    ///
+    /// See [`LineFilter::enable_during_startup`] for details on startup
+    /// entries.
+    pub fn startup(&self) -> &[Directive] {
+        &self.startup
+    }
+
+    /// Returns the `window = "..."` of this config's `[startup]` section, if
+    /// it has one.
+    pub fn startup_window(&self) -> Option<Duration> {
+        self.startup_window
+    }
+}
+
+impl FromStr for FilterConfig {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (config, warnings) = Self::parse_with_warnings(s)?;
+        for warning in &warnings {
+            warning.record();
+        }
+        Ok(config)
+    }
+}
+
+impl FromIterator<Directive> for FilterConfig {
+    fn from_iter<I: IntoIterator<Item = Directive>>(iter: I) -> Self {
+        Self {
+            directives: iter.into_iter().collect(),
+            ..Self::default()
+        }
+    }
+}
+
+impl From<Vec<Directive>> for FilterConfig {
+    fn from(directives: Vec<Directive>) -> Self {
+        Self {
+            directives,
+            ..Self::default()
+        }
+    }
+}
+
+/// Formats a `FilterConfig` as a comma-separated list of directives, which
+/// can be parsed back into an equivalent config. Directives with notes are
+/// each on a line of their own, followed by their `note = "..."` clause. The
+/// config's span fields follow on a `fields = { ... }` line, its startup
+/// entries follow after a `[startup]` header, and each profile follows on its
+/// own lines, after a `[profile.NAME]` header.
+impl fmt::Display for FilterConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        note::fmt_directives(f, &self.directives, &self.notes, ",")?;
+        let mut empty = self.directives.is_empty();
+        if !self.span_fields.is_empty() {
+            if !empty {
+                f.write_str("\n")?;
+            }
+            f.write_str(&fields::fmt_line(&self.span_fields))?;
+            empty = false;
+        }
+        if !self.startup.is_empty() {
+            if !empty {
+                f.write_str("\n")?;
+            }
+            empty = false;
+            writeln!(f, "{}", startup::fmt_header(self.startup_window))?;
+            note::fmt_directives(f, &self.startup, &self.notes, ",")?;
+        }
+        for (name, directives) in &self.profiles {
+            if !empty {
+                f.write_str("\n")?;
+            }
+            empty = false;
+            writeln!(f, "[profile.{}]", name)?;
+            note::fmt_directives(f, directives, &self.notes, ",")?;
+        }
+        Ok(())
+    }
+}
+
+impl LineFilter {
+    /// Replaces this filter's `(module, line)` and `(file, line)` entries and
+    /// its [`EnvFilter`] with the directives in `config`.
+    ///
+    /// The filter's [span fields], [notes], and [startup entries] are replaced
+    /// by the config's. Its profiles are replaced by the profiles defined in
+    /// `config`, and the profiles that were active before, and are still
+    /// defined, are activated again. Other settings, such as the filter's
+    /// expiry deadline, are left unchanged. When the filter is wrapped in a
+    /// [`reload`] layer, calling this inside [`Handle::modify`] applies the
+    /// new config to a running program.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if one of the config's `File` directives has a
+    /// path that is not valid for use in a `LineFilter`, in which case the
+    /// filter is not changed. Configs parsed from strings have already had
+    /// their paths validated, so this never fails for those configs.
+    ///
+    /// [`EnvFilter`]: tracing_subscriber::EnvFilter
+    /// [span fields]: LineFilter::add_span_field
+    /// [notes]: LineFilter::set_note
+    /// [startup entries]: LineFilter::enable_during_startup
+    /// [`reload`]: tracing_subscriber::reload
+    /// [`Handle::modify`]: tracing_subscriber::reload::Handle::modify
+    pub fn set_config(&mut self, config: &FilterConfig) -> Result<&mut Self, Error> {
+        let profiled = config.profiles.values().flatten();
+        let all = config
+            .directives
+            .iter()
+            .chain(&config.startup)
+            .chain(profiled);
+        for directive in all {
+            if let Directive::File { file, .. } = directive {
+                if let Err(error) = self.file_key(Path::new(file)).map_err(Error::from) {
+                    self.reloaded(Some(error.to_string()));
+                    return Err(error);
+                }
+            }
+        }
+
+        // The old startup entries are removed along with the other entries
+        // that aren't in the new config, and the config's profiles and
+        // startup entries are laid over its directives afresh.
+        self.startup = Default::default();
+        self.clear_overlays();
+
+        // Only remove the entries that aren't in the new config, so that
+        // the filter's `changes` streams see what actually changed.
+        let removed = self
+            .directives()
+            .filter(|directive| !config.directives.contains(directive))
+            .collect::<Vec<_>>();
+        for directive in &removed {
+            self.remove_directive(directive);
+        }
+
+        self.span_fields = config.span_fields.clone();
+        self.notes = config.notes.clone();
+        self.fallback = None;
+        for directive in &config.directives {
+            self.add_directive(directive.clone())
+                .expect("paths were validated");
+        }
+
+        // The removed directives included the active profiles' directives,
+        // so the old profiles can be dropped without deactivating them.
+        let active = self
+            .profiles()
+            .filter(|&(_, active)| active)
+            .map(|(name, _)| name.to_owned())
+            .collect::<Vec<_>>();
+        self.profiles.clear();
+        for (name, directives) in &config.profiles {
+            self.define_profile(name.clone(), directives.iter().cloned())
+                .expect("profiles in configs are validated by `parse_sections`");
+        }
+        for name in active {
+            if self.profiles.contains_key(&name) {
+                self.activate_profile(&name).expect("profile is defined");
+            }
+        }
+        self.enable_during_startup(config.startup.iter().cloned(), config.startup_window)
+            .expect("startup entries in configs are validated by `parse_sections`");
+
+        self.reloaded(None);
+        #[cfg(feature = "metrics")]
+        self.metrics.record_reload(self);
+        #[cfg(feature = "futures")]
+        self.changes.notify(|| crate::FilterChange::Reloaded);
+        Ok(self)
+    }
+}
@@ -0,0 +1,356 @@
+//! Following a config stored in Consul's key/value store.
+//!
+//! [`LineFilter::from_consul`] watches a key with blocking queries, made
+//! over plain HTTP/1.0 with `std::net`, so that this doesn't need an HTTP
+//! client or an async runtime. Each value is applied with the helpers that
+//! `from_watch` and `from_file` use, and recorded with the `consul`
+//! transport.
+use crate::{watch, LineFilter};
+use std::{
+    env,
+    io::{self, Read, Write},
+    net::TcpStream,
+    path::Path,
+    thread,
+    time::Duration,
+};
+use tracing_core::Subscriber;
+use tracing_subscriber::reload;
+
+/// The transport that changes made by `from_consul` are recorded with.
+const TRANSPORT: &str = "consul";
+
+/// The environment variable that the ACL token is read from.
+const TOKEN_VAR: &str = "CONSUL_HTTP_TOKEN";
+
+/// How long each blocking query waits for the key to change.
+const WAIT: Duration = Duration::from_secs(300);
+
+/// How long to wait before querying again after a query fails.
+const RETRY: Duration = Duration::from_secs(5);
+
+/// The largest response that is read, in bytes. Consul's own limit on the
+/// size of a value is 512 KiB.
+const MAX_RESPONSE_LEN: u64 = 1024 * 1024;
+
+impl LineFilter {
+    /// Returns a filter whose directives follow the config stored under
+    /// `key` in [Consul]'s key/value store, using the HTTP API of the Consul
+    /// agent at `agent` (such as `127.0.0.1:8500`).
+    ///
+    /// The filter starts out with no directives. This spawns a thread that
+    /// watches the key with [blocking queries], so that each new config is
+    /// applied as soon as the key changes, as if it were sent to
+    /// [`from_watch`]. This lets a whole fleet's filters be driven from one
+    /// key. Changes are recorded as audit events in the same way as for
+    /// [`from_file`], with the `consul` transport and the key as the
+    /// `path`, and the same goes for
+    /// values that can't be parsed and for a key that is deleted once a
+    /// config has been read from it: the filter keeps the last config that
+    /// could be applied. If the agent can't be reached, the failure is
+    /// recorded, and the query is retried every few seconds.
+    ///
+    /// If the `CONSUL_HTTP_TOKEN` environment variable is set, it is sent as
+    /// the ACL token for each query; a token with control characters in it
+    /// is recorded as a failed query instead of being sent. The agent is queried over plain HTTP,
+    /// so it should be the local agent, rather than a server across the
+    /// network. Other coordination stores, such as etcd, can drive a filter
+    /// by forwarding their updates into the channel given to `from_watch`.
+    ///
+    /// The thread stops once a query returns after the subscriber containing
+    /// the filter has been dropped.
+    ///
+    /// This method is only available when the `consul` feature flag is
+    /// enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::prelude::*;
+    ///
+    /// tracing_subscriber::registry()
+    ///     .with(tracing_subscriber::fmt::layer())
+    ///     .with(LineFilter::from_consul("127.0.0.1:8500", "my-app/lines"))
+    ///     .init();
+    /// ```
+    ///
+    /// [Consul]: https://developer.hashicorp.com/consul/api-docs/kv
+    /// [blocking queries]: https://developer.hashicorp.com/consul/api-docs/features/blocking
+    /// [`from_watch`]: Self::from_watch
+    /// [`from_file`]: Self::from_file
+    pub fn from_consul<S>(
+        agent: impl Into<String>,
+        key: impl Into<String>,
+    ) -> reload::Layer<Self, S>
+    where
+        S: Subscriber + 'static,
+    {
+        let agent = agent.into();
+        let key = key.into();
+        let (layer, handle) = reload::Layer::new(Self::new());
+
+        thread::spawn(move || {
+            let path = Path::new(&key);
+            let mut index = 0;
+            let mut last = None;
+            // Only the first of several failed queries in a row is recorded.
+            let mut failing = false;
+            loop {
+                if handle.with_current(|_| ()).is_err() {
+                    // The subscriber was dropped.
+                    break;
+                }
+                let text = query(&agent, &key, index).and_then(|(text, next)| {
+                    // The index only goes backwards if the store was reset,
+                    // which means starting over. It is advanced past a
+                    // deletion too, so that the next query blocks until the
+                    // key is written again.
+                    index = if next < index { 0 } else { next };
+                    match text {
+                        None if last.is_some() => Err(io::Error::new(
+                            io::ErrorKind::NotFound,
+                            "the key was deleted",
+                        )),
+                        text => Ok(text),
+                    }
+                });
+                let text = match text {
+                    Ok(text) => text,
+                    Err(error) => {
+                        if !failing && !watch::reject(&handle, TRANSPORT, Some(path), &error, None)
+                        {
+                            break;
+                        }
+                        failing = true;
+                        thread::sleep(RETRY);
+                        continue;
+                    }
+                };
+                let recovered = std::mem::replace(&mut failing, false);
+                if text == last {
+                    if recovered && handle.modify(|filter| filter.reloaded(None)).is_err() {
+                        break;
+                    }
+                    continue;
+                }
+                let applied = match text.as_deref().map(watch::parse_config).transpose() {
+                    Ok(config) => {
+                        watch::apply(&handle, TRANSPORT, &config.unwrap_or_default(), Some(path))
+                    }
+                    Err(error) => watch::reject(
+                        &handle,
+                        TRANSPORT,
+                        Some(path),
+                        &error,
+                        Some(error.directive()),
+                    ),
+                };
+                if !applied {
+                    // The subscriber was dropped.
+                    break;
+                }
+                last = text;
+            }
+        });
+
+        layer
+    }
+}
+
+/// Makes a blocking query for `key`, returning its value (or `None` if it
+/// doesn't exist) once its modify index is past `index`, along with the
+/// index to make the next query with.
+fn query(agent: &str, key: &str, index: u64) -> io::Result<(Option<String>, u64)> {
+    let token = token_header(env::var(TOKEN_VAR).ok().as_deref())?;
+    let mut stream = TcpStream::connect(agent)?;
+    // Consul adds up to `WAIT / 16` to each wait, so that clients don't all
+    // query again at once.
+    stream.set_read_timeout(Some(WAIT + WAIT / 8))?;
+    // An HTTP/1.0 request means the body is neither chunked nor followed by
+    // another response, so it ends when the agent closes the connection.
+    write!(
+        stream,
+        "GET /v1/kv/{}?raw&index={}&wait={}s HTTP/1.0\r\nHost: {}\r\n{}\r\n",
+        encode_key(key),
+        index,
+        WAIT.as_secs(),
+        agent,
+        token,
+    )?;
+    let mut response = Vec::new();
+    stream
+        .take(MAX_RESPONSE_LEN + 1)
+        .read_to_end(&mut response)?;
+    if response.len() as u64 > MAX_RESPONSE_LEN {
+        return Err(invalid_data("the response is larger than 1 MiB"));
+    }
+    parse_response(&response)
+}
+
+/// Returns the header that sends `token` as the ACL token, or nothing if
+/// there is no token.
+///
+/// Tokens with control characters in them are rejected, since a line break
+/// would end the header and let the rest of the token add headers of its
+/// own.
+fn token_header(token: Option<&str>) -> io::Result<String> {
+    match token {
+        Some(token) if token.chars().any(char::is_control) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "the {} environment variable contains control characters",
+                TOKEN_VAR
+            ),
+        )),
+        Some(token) => Ok(format!("X-Consul-Token: {}\r\n", token)),
+        None => Ok(String::new()),
+    }
+}
+
+/// Parses the response to a `?raw` query for a key.
+fn parse_response(response: &[u8]) -> io::Result<(Option<String>, u64)> {
+    let split = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or_else(|| invalid_data("the response has no end of headers"))?;
+    let head = std::str::from_utf8(&response[..split])
+        .map_err(|_| invalid_data("the response headers are not UTF-8"))?;
+    let body = &response[split + 4..];
+
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .ok_or_else(|| invalid_data("the response has no status"))?;
+    let index = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case("x-consul-index"))
+        .and_then(|(_, value)| value.trim().parse::<u64>().ok())
+        .ok_or_else(|| invalid_data("the response has no X-Consul-Index header"))?;
+    match status {
+        "200" => String::from_utf8(body.to_vec())
+            .map(|text| (Some(text), index))
+            .map_err(|_| invalid_data("the value is not UTF-8")),
+        "404" => Ok((None, index)),
+        status => Err(io::Error::other(format!(
+            "the Consul agent responded with status {}: {}",
+            status,
+            String::from_utf8_lossy(body).trim(),
+        ))),
+    }
+}
+
+/// Percent-encodes the characters of `key` that can't appear in a URL path,
+/// other than the `/`s that separate its parts.
+fn encode_key(key: &str) -> String {
+    let mut encoded = String::with_capacity(key.len());
+    for byte in key.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            byte => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn invalid_data(error: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::BufRead, net::TcpListener};
+    use tracing_subscriber::{prelude::*, Registry};
+
+    #[test]
+    fn parses_responses() {
+        let response = b"HTTP/1.0 200 OK\r\nX-Consul-Index: 42\r\n\r\nm@1\n";
+        let (text, index) = parse_response(response).unwrap();
+        assert_eq!(text.as_deref(), Some("m@1\n"));
+        assert_eq!(index, 42);
+
+        let response = b"HTTP/1.0 404 Not Found\r\nx-consul-index: 7\r\n\r\n";
+        assert_eq!(parse_response(response).unwrap(), (None, 7));
+
+        let response = b"HTTP/1.0 403 Forbidden\r\nX-Consul-Index: 7\r\n\r\nACL not found";
+        let error = parse_response(response).unwrap_err().to_string();
+        assert!(error.contains("403: ACL not found"), "{}", error);
+        let response = b"HTTP/1.0 200 OK\r\n\r\nm@1";
+        assert!(parse_response(response).is_err());
+    }
+
+    #[test]
+    fn rejects_tokens_with_control_characters() {
+        assert_eq!(token_header(None).unwrap(), "");
+        assert_eq!(
+            token_header(Some("secret")).unwrap(),
+            "X-Consul-Token: secret\r\n"
+        );
+        let error = token_header(Some("secret\r\nX-Evil: 1")).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(token_header(Some("secret\0")).is_err());
+    }
+
+    #[test]
+    fn encodes_keys() {
+        assert_eq!(encode_key("my-app/lines"), "my-app/lines");
+        assert_eq!(encode_key("my app/lines?"), "my%20app/lines%3F");
+    }
+
+    #[test]
+    fn follows_the_key() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let agent = listener.local_addr().unwrap().to_string();
+        // Answers each query with the next value, and then stops answering.
+        let (queries, rx) = std::sync::mpsc::channel();
+        thread::spawn(move || {
+            let values = [(1, "m@1"), (2, "m@2"), (3, "m@2,m@")];
+            for (index, value) in values {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = io::BufReader::new(&stream).lines();
+                queries.send(request.next().unwrap().unwrap()).unwrap();
+                // Read the rest of the request before answering it.
+                request.find(|line| line.as_ref().unwrap().is_empty());
+                write!(
+                    stream,
+                    "HTTP/1.0 200 OK\r\nX-Consul-Index: {}\r\n\r\n{}",
+                    index, value
+                )
+                .unwrap();
+            }
+            // Keep the next query waiting.
+            let (next, _) = listener.accept().unwrap();
+            let mut request = io::BufReader::new(&next).lines();
+            queries.send(request.next().unwrap().unwrap()).unwrap();
+            thread::park();
+        });
+
+        let mut queries = Vec::new();
+        {
+            let layer = LineFilter::from_consul::<Registry>(agent, "my-app/lines");
+            let handle = layer.handle();
+            let _subscriber = tracing_subscriber::registry().with(layer);
+            // The fourth query is only made once the third value has been
+            // handled.
+            for _ in 0..4 {
+                queries.push(rx.recv_timeout(Duration::from_secs(5)).unwrap());
+            }
+
+            let current = handle.with_current(|filter| filter.to_string()).unwrap();
+            assert_eq!(current, "m@2");
+            // The last value couldn't be parsed, so the filter kept the one
+            // before it.
+            let health = handle.with_current(LineFilter::health_snapshot).unwrap();
+            assert!(health.last_reload().unwrap().error().is_some());
+        }
+
+        assert!(queries[0].starts_with("GET /v1/kv/my-app/lines?raw&index=0&wait=300s "));
+        assert!(queries[1].contains("index=1&"));
+        assert!(queries[2].contains("index=2&"));
+        assert!(queries[3].contains("index=3&"));
+    }
+}
@@ -0,0 +1,137 @@
+use crate::{BadPath, Error, LineFilter};
+use std::{collections::HashMap, path::Path};
+use tracing_core::Metadata;
+
+impl LineFilter {
+    /// Enable a span or event on line `line` of the file at `path` in the
+    /// crate named `crate_name`, such as `src/db.rs` in `my_crate`, wherever
+    /// the crate is checked out.
+    ///
+    /// The file paths that [`std::file!()`] reports depend on how a crate was
+    /// built: they are relative to the workspace root for workspace crates
+    /// (`my_crate/src/db.rs`, or `src/db.rs` for a single package), and
+    /// absolute for dependencies (such as
+    /// `/home/me/.cargo/registry/src/.../my_crate-1.2.3/src/db.rs`). Rather
+    /// than the whole path, this entry matches any file path that ends with
+    /// `path` inside a directory named after the crate (with or without a
+    /// version), or that is `path` itself in a span or event from the crate,
+    /// so the same config works on every machine that builds the crate.
+    /// Dashes and underscores in the crate's name are interchangeable.
+    ///
+    /// Since these entries aren't directives, they aren't included in the
+    /// filter's [`Display`] output or [statistics].
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if `path` is absolute, or isn't a Rust source
+    /// code file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    ///
+    /// let mut filter = LineFilter::default();
+    /// // Matches `my-crate/src/db.rs` in a workspace, `src/db.rs` when
+    /// // `my-crate` is the only package, and
+    /// // `/home/me/.cargo/registry/src/.../my-crate-1.2.3/src/db.rs`.
+    /// filter.enable_in_crate_file("my-crate", "src/db.rs", 42).unwrap();
+    ///
+    /// assert!(filter.enable_in_crate_file("my-crate", "/src/db.rs", 42).is_err());
+    /// ```
+    ///
+    /// [`Display`]: std::fmt::Display
+    /// [statistics]: Self::stats
+    pub fn enable_in_crate_file(
+        &mut self,
+        crate_name: &str,
+        path: impl AsRef<Path>,
+        line: u32,
+    ) -> Result<&mut Self, Error> {
+        let path = path.as_ref();
+        if path.is_absolute() {
+            return Err(Error::Path(BadPath::new(
+                path,
+                "crate file paths must be relative to the crate's root",
+            )));
+        }
+        if path.extension().and_then(std::ffi::OsStr::to_str) != Some("rs") {
+            return Err(Error::Path(BadPath::new(
+                path,
+                "files must be Rust source code files",
+            )));
+        }
+        let path = path
+            .to_str()
+            .ok_or_else(|| BadPath::new(path, "file paths must be valid utf-8"))?;
+        let entry = (crate_ident(crate_name), normalize(path));
+        let entries = self.crate_files.entry(line).or_default();
+        if !entries.contains(&entry) {
+            entries.push(entry);
+        }
+        Ok(self)
+    }
+
+    /// Returns `true` if `metadata` is on the line of an entry added by
+    /// [`enable_in_crate_file`].
+    ///
+    /// [`enable_in_crate_file`]: Self::enable_in_crate_file
+    pub(crate) fn is_in_crate_file(&self, metadata: &Metadata<'_>) -> bool {
+        if self.crate_files.is_empty() {
+            return false;
+        }
+        let entries = match metadata.line().and_then(|line| self.crate_files.get(&line)) {
+            Some(entries) => entries,
+            None => return false,
+        };
+        let file = match metadata.file() {
+            Some(file) => normalize(file),
+            None => return false,
+        };
+        entries
+            .iter()
+            .any(|(crate_name, path)| matches(crate_name, path, &file, metadata))
+    }
+}
+
+/// The entries added by [`LineFilter::enable_in_crate_file`], with the crate
+/// names and relative paths of the entries on each line.
+pub(crate) type CrateFiles = HashMap<u32, Vec<(String, String)>>;
+
+/// Returns `true` if `file` is the file at `path` in the crate named
+/// `crate_name`.
+fn matches(crate_name: &str, path: &str, file: &str, metadata: &Metadata<'_>) -> bool {
+    let dir = match file.strip_suffix(path) {
+        Some("") => {
+            // The crate is the root package of the workspace it was built in.
+            return crate::module_keys(metadata).any(|module| {
+                let krate = module.split("::").next().unwrap_or(module);
+                crate_ident(krate) == crate_name
+            });
+        }
+        Some(dir) => match dir.strip_suffix('/') {
+            Some(dir) => dir,
+            None => return false,
+        },
+        None => return false,
+    };
+    let name = dir.rsplit('/').next().unwrap_or(dir);
+    if crate_ident(name) == crate_name {
+        return true;
+    }
+    // A dependency unpacked into `my_crate-1.2.3`.
+    name.rsplit_once('-').is_some_and(|(name, version)| {
+        crate_ident(name) == crate_name && version.starts_with(|c: char| c.is_ascii_digit())
+    })
+}
+
+/// Returns a crate's name, as it appears in module paths.
+fn crate_ident(name: &str) -> String {
+    name.replace('-', "_")
+}
+
+/// Returns `path` with forward slashes, so that paths from builds on Windows
+/// match.
+fn normalize(path: &str) -> String {
+    path.replace('\\', "/")
+}
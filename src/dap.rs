@@ -0,0 +1,500 @@
+//! A [Debug Adapter Protocol][dap] bridge for enabling lines from an editor.
+//!
+//! Editors that support the Debug Adapter Protocol (DAP), such as VS Code,
+//! let users set *logpoints* by clicking in the gutter next to a line of
+//! code. This module implements just enough of the protocol to receive
+//! those logpoints from an editor over a socket and turn them into
+//! `file@line` entries in a running program's [`LineFilter`], so that any
+//! span or event on that line is enabled.
+//!
+//! The following requests are supported:
+//!
+//! * `setBreakpoints`, which replaces the logpoints set in a source file.
+//!   Breakpoints without a `logMessage` are reported as unverified, since
+//!   this filter cannot pause the program, and the text of a `logMessage` is
+//!   ignored: the span or event on the line is recorded as it is written.
+//! * `initialize`, `attach`, `launch`, `configurationDone`, `threads`, and
+//!   `disconnect`, which are answered so that editors will connect.
+//!
+//! Other requests fail with an "unsupported request" error. Messages larger
+//! than 1 MiB, which no editor sends for these requests, end the connection
+//! rather than being read into memory.
+//!
+//! Since this requires a JSON parser, it is only available when the `dap`
+//! feature flag is enabled.
+//!
+//! # Paths
+//!
+//! Editors send absolute paths, but the file paths in the metadata of spans
+//! and events in a Cargo workspace's own crates are relative to the
+//! workspace root. When a [`Bridge`] is given the workspace root with
+//! [`Bridge::workspace_root`], paths inside it are made relative before they
+//! are added to the filter. Such relative `file@line` entries cannot be
+//! parsed from a config file, so they are only useful in a running program.
+//!
+//! Each time an editor changes its logpoints, an audit event is recorded with
+//! the `dap` transport and the editor's address; see [`Command::apply`] for
+//! details. Each `setBreakpoints` request counts against the filter's
+//! [command limit], and fails once the limit has been reached, or if the
+//! filter is [read-only], without changing the logpoints.
+//!
+//! # Examples
+//!
+//! Serving logpoints on a TCP port, to be used with the `debugServer`
+//! option of a VS Code `launch.json` configuration:
+//!
+//! ```no_run
+//! use std::net::TcpListener;
+//! use tracing_line_filter::{dap, LineFilter};
+//! use tracing_subscriber::{prelude::*, reload};
+//!
+//! let (filter, handle) = reload::Layer::new(LineFilter::default());
+//! tracing_subscriber::registry()
+//!     .with(tracing_subscriber::fmt::layer())
+//!     .with(filter)
+//!     .init();
+//!
+//! std::thread::spawn(move || {
+//!     let listener = TcpListener::bind("127.0.0.1:4711").unwrap();
+//!     dap::Bridge::new(handle)
+//!         .workspace_root(env!("CARGO_MANIFEST_DIR"))
+//!         .serve(&listener)
+//! });
+//! ```
+//!
+//! [dap]: https://microsoft.github.io/debug-adapter-protocol/
+//! [`Command::apply`]: crate::Command::apply
+//! [command limit]: crate::LineFilter::limit_commands
+//! [read-only]: crate::LineFilter::read_only_commands
+use crate::{audit, command::FmtDuration, publish, Directive, LineFilter};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    path::{Path, PathBuf},
+};
+use tracing_core::Subscriber;
+use tracing_subscriber::reload;
+
+/// Translates DAP logpoints into [`LineFilter`] entries.
+///
+/// See the [module-level documentation](self) for details.
+#[derive(Debug)]
+pub struct Bridge<S> {
+    handle: reload::Handle<LineFilter, S>,
+    root: Option<PathBuf>,
+    /// The entries added for each source file's logpoints, so that they can
+    /// be removed when the logpoints are replaced.
+    logpoints: HashMap<String, Vec<Directive>>,
+    /// The address of the editor being served, if it is known.
+    peer: Option<String>,
+    seq: u64,
+}
+
+impl<S> Bridge<S>
+where
+    S: Subscriber,
+{
+    /// Returns a new `Bridge` that changes the [`LineFilter`] behind
+    /// `handle`.
+    pub fn new(handle: reload::Handle<LineFilter, S>) -> Self {
+        Self {
+            handle,
+            root: None,
+            logpoints: HashMap::new(),
+            peer: None,
+            seq: 0,
+        }
+    }
+
+    /// Sets the root of the Cargo workspace the program was built from.
+    ///
+    /// Logpoints in files under `root` are added to the filter with paths
+    /// relative to `root`, matching the file paths that `tracing` records for
+    /// the workspace's own crates.
+    pub fn workspace_root(&mut self, root: impl Into<PathBuf>) -> &mut Self {
+        self.root = Some(root.into());
+        self
+    }
+
+    /// Accepts connections from editors on `listener`, serving them one at a
+    /// time.
+    ///
+    /// This only returns if accepting a connection fails, or if the
+    /// subscriber containing the filter has been dropped.
+    pub fn serve(&mut self, listener: &TcpListener) -> io::Result<()> {
+        loop {
+            let (stream, addr) = listener.accept()?;
+            let reader = BufReader::new(stream.try_clone()?);
+            self.peer = Some(addr.to_string());
+            let result = self.serve_connection(reader, stream);
+            self.peer = None;
+            if let Err(e) = result {
+                // A misbehaving editor shouldn't stop the bridge, but there's
+                // no point in serving without a filter to change.
+                if self.handle.with_current(|_| ()).is_err() {
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// Serves a single editor connection, reading DAP messages from `reader`
+    /// and writing responses to `writer`.
+    ///
+    /// Once the editor disconnects, the entries added for its logpoints are
+    /// removed from the filter.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if reading or writing a message fails, if a
+    /// message is malformed, or if the subscriber containing the filter has
+    /// been dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::{dap, LineFilter};
+    /// use tracing_subscriber::{prelude::*, reload};
+    ///
+    /// let (filter, handle) = reload::Layer::new(LineFilter::default());
+    /// let _subscriber = tracing_subscriber::registry().with(filter);
+    ///
+    /// let mut input = Vec::new();
+    /// for message in [
+    ///     r#"{"seq":1,"type":"request","command":"initialize","arguments":{}}"#,
+    ///     r#"{"seq":2,"type":"request","command":"setBreakpoints","arguments":{
+    ///         "source":{"path":"/home/eliza/my_crate/src/db.rs"},
+    ///         "breakpoints":[{"line":142,"logMessage":"query"}]
+    ///     }}"#,
+    /// ] {
+    ///     input.extend(format!("Content-Length: {}\r\n\r\n{}", message.len(), message).bytes());
+    /// }
+    ///
+    /// let mut output = Vec::new();
+    /// dap::Bridge::new(handle.clone())
+    ///     .workspace_root("/home/eliza/my_crate")
+    ///     .serve_connection(&input[..], &mut output)
+    ///     .unwrap();
+    ///
+    /// let output = String::from_utf8(output).unwrap();
+    /// assert!(output.contains(r#""verified":true"#));
+    ///
+    /// // The logpoint is removed once the editor disconnects.
+    /// let list = handle.with_current(|filter| filter.to_string()).unwrap();
+    /// assert_eq!(list, "");
+    /// ```
+    pub fn serve_connection(
+        &mut self,
+        mut reader: impl BufRead,
+        mut writer: impl Write,
+    ) -> io::Result<()> {
+        let result = self.serve_messages(&mut reader, &mut writer);
+        let sources = self.logpoints.keys().cloned().collect::<Vec<_>>();
+        for source in sources {
+            self.set_logpoints(&source, Vec::new())?;
+        }
+        result
+    }
+
+    fn serve_messages(
+        &mut self,
+        reader: &mut impl BufRead,
+        writer: &mut impl Write,
+    ) -> io::Result<()> {
+        while let Some(request) = read_message(reader)? {
+            if request["type"] != "request" {
+                continue;
+            }
+            let command = request["command"].as_str().unwrap_or_default();
+            let args = &request["arguments"];
+            let body = match command {
+                "initialize" => Ok(json!({
+                    "supportsConfigurationDoneRequest": true,
+                    "supportsLogPoints": true,
+                })),
+                "setBreakpoints" => match self.check_allowed(args)? {
+                    Ok(()) => Ok(self.set_breakpoints(args)?),
+                    Err(message) => Err(message),
+                },
+                "threads" => Ok(json!({ "threads": [] })),
+                "attach" | "launch" | "configurationDone" | "disconnect" => Ok(json!({})),
+                _ => Err(format!("unsupported request '{}'", command)),
+            };
+
+            let mut response = json!({
+                "type": "response",
+                "request_seq": request["seq"],
+                "command": command,
+                "success": body.is_ok(),
+            });
+            match body {
+                Ok(body) => response["body"] = body,
+                Err(message) => response["message"] = message.into(),
+            }
+            self.write_message(writer, response)?;
+
+            match command {
+                "initialize" => {
+                    let event = json!({ "type": "event", "event": "initialized" });
+                    self.write_message(writer, event)?;
+                }
+                "disconnect" => break,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that a `setBreakpoints` request may change the filter, and
+    /// counts it against the filter's command limit, returning the error
+    /// message to respond with if it may not.
+    fn check_allowed(&self, args: &Value) -> io::Result<Result<(), String>> {
+        let (read_only, limited) = self
+            .handle
+            .with_current(|filter| {
+                if filter.is_read_only() {
+                    return (true, Ok(()));
+                }
+                (false, filter.check_command_limit())
+            })
+            .map_err(io::Error::other)?;
+        let source = args["source"]["path"].as_str().unwrap_or_default();
+        let request = audit::Request {
+            transport: "dap",
+            peer: self.peer.as_deref(),
+            command: &format_args!("set logpoints in {}", source),
+        };
+        if read_only {
+            request.record_rejected();
+            return Ok(Err(
+                "logpoints can't be set: the filter is read-only".to_owned()
+            ));
+        }
+        if let Err(retry_after) = limited {
+            request.record_limited(retry_after);
+            return Ok(Err(format!(
+                "too many changes; try again in {}",
+                FmtDuration(retry_after)
+            )));
+        }
+        Ok(Ok(()))
+    }
+
+    fn set_breakpoints(&mut self, args: &Value) -> io::Result<Value> {
+        let path = match args["source"]["path"].as_str() {
+            Some(path) => path,
+            None => return Ok(json!({ "breakpoints": [] })),
+        };
+        let file = match self.root {
+            Some(ref root) => Path::new(path)
+                .strip_prefix(root)
+                .unwrap_or(Path::new(path)),
+            None => Path::new(path),
+        };
+        let file = file.to_string_lossy().into_owned();
+
+        let mut directives = Vec::new();
+        let mut breakpoints = Vec::new();
+        for breakpoint in args["breakpoints"].as_array().into_iter().flatten() {
+            let line = breakpoint["line"]
+                .as_u64()
+                .and_then(|line| u32::try_from(line).ok());
+            let breakpoint = match line {
+                Some(line) if breakpoint.get("logMessage").is_some() => {
+                    directives.push(Directive::File {
+                        file: file.clone(),
+                        line,
+                        hash: None,
+                        levels: None,
+                    });
+                    json!({ "verified": true, "line": line })
+                }
+                _ => json!({
+                    "verified": false,
+                    "line": breakpoint["line"],
+                    "message": "only logpoints are supported",
+                }),
+            };
+            breakpoints.push(breakpoint);
+        }
+
+        self.set_logpoints(path, directives)?;
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    /// Replaces the entries for the logpoints in `source` with `directives`.
+    fn set_logpoints(&mut self, source: &str, directives: Vec<Directive>) -> io::Result<()> {
+        let old = self.logpoints.remove(source).unwrap_or_default();
+        if old.is_empty() && directives.is_empty() {
+            return Ok(());
+        }
+        let mut entries = (0, 0);
+        let mut evicted = Vec::new();
+        self.handle
+            .modify(|filter| {
+                entries.0 = filter.entry_count();
+                for directive in &old {
+                    filter.remove_directive(directive);
+                }
+                // Editor paths may be relative to the workspace root, so
+                // they are added without `enable_by_file`'s validation.
+                for directive in &directives {
+                    if let Directive::File { file, line, .. } = directive {
+                        filter.insert_file((file.clone().into(), *line));
+                    }
+                }
+                #[cfg(feature = "metrics")]
+                filter.metrics.record_reload(filter);
+                entries.1 = filter.entry_count();
+                evicted = filter.take_evicted();
+            })
+            .map_err(io::Error::other)?;
+
+        let lines = directives
+            .iter()
+            .filter_map(|directive| match directive {
+                Directive::File { line, .. } => Some(line.to_string()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let change = if lines.is_empty() {
+            format!("removed logpoints in {}", source)
+        } else {
+            format!("set logpoints in {} on lines {}", source, lines.join(", "))
+        };
+        audit::Change {
+            transport: "dap",
+            peer: self.peer.as_deref(),
+            change: &change,
+            entries_before: entries.0,
+            entries_after: entries.1,
+            evicted: &evicted,
+        }
+        .record();
+        publish::publish(&self.handle, "dap");
+        if !directives.is_empty() {
+            self.logpoints.insert(source.to_owned(), directives);
+        }
+        Ok(())
+    }
+
+    fn write_message(&mut self, writer: &mut impl Write, mut message: Value) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = self.seq.into();
+        let message = message.to_string();
+        write!(
+            writer,
+            "Content-Length: {}\r\n\r\n{}",
+            message.len(),
+            message
+        )?;
+        writer.flush()
+    }
+}
+
+/// The largest message body that is read, in bytes.
+const MAX_MESSAGE_LEN: usize = 1024 * 1024;
+
+/// The longest header line that is read, in bytes.
+const MAX_HEADER_LEN: u64 = 1024;
+
+/// Reads a `Content-Length`-framed JSON message, or `None` at the end of
+/// the stream.
+///
+/// Since the peer decides how long messages are, this fails with
+/// [`io::ErrorKind::InvalidData`] rather than reading a header line longer
+/// than [`MAX_HEADER_LEN`] or a body longer than [`MAX_MESSAGE_LEN`].
+fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut len = None;
+    let mut header = String::new();
+    loop {
+        header.clear();
+        if reader
+            .by_ref()
+            .take(MAX_HEADER_LEN)
+            .read_line(&mut header)?
+            == 0
+        {
+            return Ok(None);
+        }
+        if !header.ends_with('\n') && header.len() as u64 == MAX_HEADER_LEN {
+            return Err(invalid_data("message header is too long"));
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                len = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let len = len.ok_or_else(|| invalid_data("message is missing a Content-Length header"))?;
+    if len > MAX_MESSAGE_LEN {
+        return Err(invalid_data(format!(
+            "message is {} bytes long, but at most {} bytes are allowed",
+            len, MAX_MESSAGE_LEN
+        )));
+    }
+    let mut body = Vec::new();
+    reader.by_ref().take(len as u64).read_to_end(&mut body)?;
+    if body.len() < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(invalid_data)
+}
+
+fn invalid_data(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(message: &[u8]) -> io::Result<Option<Value>> {
+        read_message(&mut io::Cursor::new(message))
+    }
+
+    #[test]
+    fn reads_framed_messages() {
+        let message = read(b"Content-Length: 13\r\n\r\n{\"seq\": 1}   ").unwrap();
+        assert_eq!(message, Some(json!({ "seq": 1 })));
+        assert!(read(b"").unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_huge_content_lengths() {
+        let error = read(b"Content-Length: 99999999999999\r\n\r\n{}").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let error = read(format!("Content-Length: {}\r\n\r\n", MAX_MESSAGE_LEN + 1).as_bytes())
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_huge_headers() {
+        let header = vec![b'x'; MAX_HEADER_LEN as usize * 2];
+        let error = read(&header).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_truncated_bodies() {
+        let error = read(b"Content-Length: 100\r\n\r\n{}").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn rejects_missing_content_lengths() {
+        let error = read(b"Content-Type: json\r\n\r\n{}").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
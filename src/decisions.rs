@@ -0,0 +1,158 @@
+use crate::{Directive, LineFilter};
+use std::{cell::Cell, fmt};
+use tracing_core::Metadata;
+
+/// The target of the events recorded by [`LineFilter::trace_decisions`].
+pub(crate) const TARGET: &str = "tracing_line_filter::decision";
+
+thread_local! {
+    /// Set while a decision event is being recorded, so that the filter
+    /// doesn't trace its decision about the decision event itself.
+    static RECORDING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// The rule that decided whether a span or event is enabled.
+enum Rule {
+    Entry(String),
+    Deny(String),
+    ExpiredEntry(String),
+    DisabledEntry(String),
+    /// The filter's `EnvFilter` or fallback function, by name.
+    Fallback(&'static str),
+    NoMatch,
+}
+
+impl LineFilter {
+    /// Records a `TRACE` event describing each decision this filter makes
+    /// about a span or event, for debugging a filter that doesn't behave as
+    /// expected.
+    ///
+    /// `tracing` caches the filter's decision about most callsites, so
+    /// decisions are only traced for spans and events that the filter has to
+    /// check each time they are reached: those with entries that will
+    /// [expire], those with [`EnvFilter`] directives that depend on the
+    /// current span or on field values, and [`log` records] (when the `log`
+    /// feature flag is enabled).
+    ///
+    /// Each event has the `tracing_line_filter::decision` target, with the
+    /// checked span or event's module path, name, and line number, whether it was
+    /// enabled, and the rule that decided it, such as
+    /// ``entry `my_crate::db@142` ``. Decision events are always enabled by
+    /// this filter, and the filter doesn't trace its decisions about them.
+    ///
+    /// # Notes
+    ///
+    /// Decision events are recorded while the filter is checking a span or
+    /// event, so they are only recorded when the filter is part of the
+    /// global default subscriber: `tracing` drops events recorded while a
+    /// scoped subscriber (set using [`with_default`] or [`set_default`]) is
+    /// in use. Since this adds an event for every decision, it should only
+    /// be enabled while debugging.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::prelude::*;
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter
+    ///     .enable_by_mod(module_path!(), line!() + 10)
+    ///     .expire_after(Duration::from_secs(60 * 60))
+    ///     .trace_decisions();
+    ///
+    /// tracing_subscriber::registry()
+    ///     .with(tracing_subscriber::fmt::layer())
+    ///     .with(filter)
+    ///     .init();
+    ///
+    /// // Records a decision event saying this was enabled by an entry.
+    /// tracing::info!("pinned");
+    /// ```
+    ///
+    /// [expire]: LineFilter::expire_at
+    /// [`EnvFilter`]: tracing_subscriber::EnvFilter
+    /// [`log` records]: LineFilter::normalize_log_metadata
+    /// [`with_default`]: tracing::subscriber::with_default
+    /// [`set_default`]: tracing::subscriber::set_default
+    pub fn trace_decisions(&mut self) -> &mut Self {
+        self.trace_decisions = true;
+        self
+    }
+
+    /// Records a decision event for `metadata`, if decision tracing is
+    /// enabled.
+    pub(crate) fn trace_decision(&self, metadata: &Metadata<'_>, enabled: bool) {
+        if !self.trace_decisions || is_recording() {
+            return;
+        }
+        let rule = self.rule(metadata);
+        RECORDING.with(|recording| recording.set(true));
+        tracing::trace!(
+            target: TARGET,
+            enabled,
+            rule = %rule,
+            callsite.module = crate::module_key(metadata),
+            callsite.name = metadata.name(),
+            callsite.line = metadata.line(),
+            "filter decision",
+        );
+        RECORDING.with(|recording| recording.set(false));
+    }
+
+    fn rule(&self, metadata: &Metadata<'_>) -> Rule {
+        let allow = self.allow_match(metadata);
+        let deny = self.deny_match(metadata);
+        let entry_wins = self.resolve(
+            allow.as_ref().map(|(specificity, _)| *specificity),
+            deny.as_ref().map(|(specificity, _)| *specificity),
+        );
+        let entry = match (allow, deny, entry_wins) {
+            (Some((_, entry)), _, Some(true)) => entry.to_directive().to_string(),
+            (_, Some((_, deny)), _) => {
+                let deny = Directive::Deny(Box::new(deny.to_directive())).to_string();
+                if self.is_disabled() {
+                    return Rule::DisabledEntry(deny);
+                }
+                if self.is_expired() {
+                    return Rule::ExpiredEntry(deny);
+                }
+                return Rule::Deny(deny);
+            }
+            _ => {
+                return match self.fallback {
+                    Some(ref fallback) => Rule::Fallback(fallback.name()),
+                    None => Rule::NoMatch,
+                }
+            }
+        };
+        if self.is_disabled() {
+            Rule::DisabledEntry(entry)
+        } else if self.is_expired() {
+            Rule::ExpiredEntry(entry)
+        } else {
+            Rule::Entry(entry)
+        }
+    }
+}
+
+/// Returns `true` if a decision event is being recorded on this thread.
+pub(crate) fn is_recording() -> bool {
+    RECORDING.with(Cell::get)
+}
+
+// === impl Rule ===
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rule::Entry(entry) => write!(f, "entry `{}`", entry),
+            Rule::Deny(entry) => write!(f, "deny entry `{}`", entry),
+            Rule::ExpiredEntry(entry) => write!(f, "expired entry `{}`", entry),
+            Rule::DisabledEntry(entry) => write!(f, "disabled entry `{}`", entry),
+            Rule::Fallback(name) => f.write_str(name),
+            Rule::NoMatch => f.write_str("no entry matches, and there is no EnvFilter"),
+        }
+    }
+}
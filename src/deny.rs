@@ -0,0 +1,621 @@
+use crate::{module_key, module_keys, prefix::PrefixTrie, Directive, Error, LineFilter};
+use std::{borrow::Cow, collections::HashSet, path::Path};
+use tracing_core::{LevelFilter, Metadata};
+
+/// How a [`LineFilter`] decides whether to enable a span or event that both
+/// an entry and a deny entry match.
+///
+/// Set using [`LineFilter::with_precedence`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Precedence {
+    /// Deny entries always win, so a span or event that any deny entry
+    /// matches is disabled. This is the default.
+    #[default]
+    DenyWins,
+    /// The more specific entry wins: a `module@line` or `file@line` entry is
+    /// more specific than a `module@level` entry, which is more specific
+    /// than a `module::**` prefix, and longer prefixes are more specific
+    /// than shorter ones. If both entries are equally specific, the deny
+    /// entry wins.
+    MostSpecific,
+}
+
+/// A filter's deny entries.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Deny {
+    by_module: HashSet<(Cow<'static, str>, u32)>,
+    by_file: HashSet<(Cow<'static, str>, u32)>,
+    prefixes: PrefixTrie,
+}
+
+/// An entry or deny entry that matches a span or event.
+pub(crate) enum Matched<'a> {
+    Module(&'a str, u32),
+    File(&'a str, u32),
+    Level(&'a str, LevelFilter),
+    Prefix(&'a str),
+}
+
+/// How specific an entry that matches a span or event is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Specificity {
+    /// A `module::**` prefix, with its number of path segments.
+    Prefix(usize),
+    Level,
+    Line,
+}
+
+impl LineFilter {
+    /// Disable a span or event in the Rust module `module` on line `line`,
+    /// even if it is enabled by another entry or by this filter's
+    /// [`EnvFilter`].
+    ///
+    /// Deny entries carve exceptions out of broader entries, such as a noisy
+    /// event in a module that is enabled by a [prefix]. If an entry also
+    /// matches the span or event, this filter's [`Precedence`] decides which
+    /// one wins. The directive syntax for a deny entry is the entry's syntax
+    /// preceded by `!`, such as `!my_crate::db@142`. Like the filter's other
+    /// entries, deny entries are disabled once the filter's entries
+    /// [expire].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::prelude::*;
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter
+    ///     .enable_by_mod_prefix(module_path!())
+    ///     .deny_by_mod(module_path!(), line!() + 4);
+    /// let subscriber = tracing_subscriber::registry().with(filter);
+    ///
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     assert!(tracing::info_span!("noisy").is_disabled());
+    ///     assert!(!tracing::info_span!("enabled").is_disabled());
+    /// });
+    /// ```
+    ///
+    /// [`EnvFilter`]: tracing_subscriber::EnvFilter
+    /// [prefix]: Self::enable_by_mod_prefix
+    /// [expire]: Self::expire_at
+    pub fn deny_by_mod(&mut self, module: impl Into<Cow<'static, str>>, line: u32) -> &mut Self {
+        let entry = (module.into(), line);
+        #[cfg(feature = "futures")]
+        if !self.deny.by_module.contains(&entry) {
+            self.changes.notify(|| {
+                crate::FilterChange::Added(Directive::Deny(Box::new(Directive::Module {
+                    module: entry.0.to_string(),
+                    line,
+                    hash: None,
+                    levels: None,
+                })))
+            });
+        }
+        self.deny.by_module.insert(entry);
+        self
+    }
+
+    /// Disable a span or event in the file `file` on line `line`, even if it
+    /// is enabled by another entry or by this filter's [`EnvFilter`].
+    ///
+    /// File paths are validated in the same way as by [`enable_by_file`].
+    /// See [`deny_by_mod`] for details on deny entries.
+    ///
+    /// # Errors
+    ///
+    /// This returns [`Error::Path`] if `file` is not valid for use in a
+    /// `LineFilter`.
+    ///
+    /// [`EnvFilter`]: tracing_subscriber::EnvFilter
+    /// [`enable_by_file`]: Self::enable_by_file
+    /// [`deny_by_mod`]: Self::deny_by_mod
+    pub fn deny_by_file(&mut self, file: impl AsRef<Path>, line: u32) -> Result<&mut Self, Error> {
+        let entry = (Cow::Owned(self.file_key(file.as_ref())?.into_owned()), line);
+        #[cfg(feature = "futures")]
+        if !self.deny.by_file.contains(&entry) {
+            self.changes.notify(|| {
+                crate::FilterChange::Added(Directive::Deny(Box::new(Directive::File {
+                    file: entry.0.to_string(),
+                    line,
+                    hash: None,
+                    levels: None,
+                })))
+            });
+        }
+        self.deny.by_file.insert(entry);
+        Ok(self)
+    }
+
+    /// Disable a span or event in the file `file` on line `line`, panicking
+    /// if `file` is not valid for use in a `LineFilter`.
+    ///
+    /// This is like [`deny_by_file`], for quick scripts and tests whose paths
+    /// are known to be valid.
+    ///
+    /// # Panics
+    ///
+    /// If `file` is not valid for use in a `LineFilter`.
+    ///
+    /// [`deny_by_file`]: Self::deny_by_file
+    #[track_caller]
+    pub fn deny_by_file_or_panic(&mut self, file: impl AsRef<Path>, line: u32) -> &mut Self {
+        self.deny_by_file(file, line)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Disable every span and event in the Rust module `module` and in all of
+    /// its submodules, even if they are enabled by another entry or by this
+    /// filter's [`EnvFilter`].
+    ///
+    /// Prefixes match whole path segments, as with
+    /// [`enable_by_mod_prefix`]. See [`deny_by_mod`] for details on deny
+    /// entries.
+    ///
+    /// [`EnvFilter`]: tracing_subscriber::EnvFilter
+    /// [`enable_by_mod_prefix`]: Self::enable_by_mod_prefix
+    /// [`deny_by_mod`]: Self::deny_by_mod
+    pub fn deny_by_mod_prefix(&mut self, module: &str) -> &mut Self {
+        if self.deny.prefixes.insert(module) {
+            #[cfg(feature = "futures")]
+            self.changes.notify(|| {
+                crate::FilterChange::Added(Directive::Deny(Box::new(Directive::ModulePrefix {
+                    prefix: module.to_owned(),
+                })))
+            });
+        }
+        self
+    }
+
+    /// Sets how this filter decides whether to enable a span or event that
+    /// both an entry and a [deny entry] match.
+    ///
+    /// By default, [deny entries always win](Precedence::DenyWins).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::{LineFilter, Precedence};
+    /// use tracing_subscriber::prelude::*;
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter
+    ///     .deny_by_mod_prefix(module_path!())
+    ///     .enable_by_mod(module_path!(), line!() + 6)
+    ///     .with_precedence(Precedence::MostSpecific);
+    /// let subscriber = tracing_subscriber::registry().with(filter);
+    ///
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     // The `module@line` entry is more specific than the deny prefix.
+    ///     assert!(!tracing::info_span!("enabled").is_disabled());
+    ///     assert!(tracing::info_span!("denied").is_disabled());
+    /// });
+    /// ```
+    ///
+    /// [deny entry]: Self::deny_by_mod
+    pub fn with_precedence(&mut self, precedence: Precedence) -> &mut Self {
+        self.precedence = precedence;
+        self
+    }
+
+    /// Returns this filter's deny directives, sorted in the same order as
+    /// its other directives.
+    pub(crate) fn deny_directives(&self) -> Vec<Directive> {
+        let mut modules = self.deny.by_module.iter().collect::<Vec<_>>();
+        modules.sort();
+        let mut files = self.deny.by_file.iter().collect::<Vec<_>>();
+        files.sort();
+
+        let prefixes = self
+            .deny
+            .prefixes
+            .to_vec()
+            .into_iter()
+            .map(|prefix| Directive::ModulePrefix { prefix });
+        let modules = modules.into_iter().map(|(module, line)| Directive::Module {
+            module: module.to_string(),
+            line: *line,
+            hash: None,
+            levels: None,
+        });
+        let files = files.into_iter().map(|(file, line)| Directive::File {
+            file: file.to_string(),
+            line: *line,
+            hash: None,
+            levels: None,
+        });
+        prefixes
+            .chain(modules)
+            .chain(files)
+            .map(|directive| Directive::Deny(Box::new(directive)))
+            .collect()
+    }
+
+    /// Removes a deny entry for the `Module`, `File`, or `ModulePrefix`
+    /// directive `directive`, returning `true` if there was one.
+    pub(crate) fn remove_deny(&mut self, directive: &Directive) -> bool {
+        let removed = match directive {
+            Directive::Module { module, line, .. } => self
+                .deny
+                .by_module
+                .remove(&(Cow::Owned(module.clone()), *line)),
+            Directive::File { file, line, .. } => {
+                self.deny.by_file.remove(&(Cow::Owned(file.clone()), *line))
+            }
+            Directive::ModulePrefix { prefix } => self.deny.prefixes.remove(prefix),
+            _ => false,
+        };
+        #[cfg(feature = "futures")]
+        if removed {
+            self.changes.notify(|| {
+                crate::FilterChange::Removed(Directive::Deny(Box::new(directive.clone())))
+            });
+        }
+        removed
+    }
+
+    /// Returns whether this filter's entries enable (`Some(true)`) or
+    /// disable (`Some(false)`) a span or event, or `None` if no entry
+    /// matches it, so that it is up to the `EnvFilter`.
+    ///
+    /// An entry that matches but doesn't enable anything right now, because
+    /// its session has expired, startup is over, or its span conditions
+    /// aren't met, doesn't hide a broader kind of entry that matches too:
+    /// if a `module@line` entry is inactive, the `module@level` entry or
+    /// `module::**` prefix that matches decides instead.
+    pub(crate) fn decide(&self, metadata: &Metadata<'_>) -> Option<bool> {
+        self.decide_match(metadata).0
+    }
+
+    /// Returns what [`decide`](Self::decide) decides about `metadata`, along
+    /// with the entry that enables it, if the decision is to enable it
+    /// because of an entry.
+    pub(crate) fn decide_match<'a>(
+        &'a self,
+        metadata: &Metadata<'a>,
+    ) -> (Option<bool>, Option<Matched<'a>>) {
+        if self.is_disabled() || self.is_expired() {
+            return (None, None);
+        }
+        let is_active = |(_, entry): &(Specificity, Matched<'_>)| {
+            !self.is_session_expired(entry)
+                && !self.is_startup_over(entry)
+                && self.is_span_condition_met(entry)
+        };
+        let allow = self
+            .allow_line_match(metadata)
+            .filter(is_active)
+            .or_else(|| self.allow_level_match(metadata).filter(is_active))
+            .or_else(|| self.allow_prefix_match(metadata).filter(is_active));
+        let deny = self.deny_specificity(metadata);
+        match allow {
+            Some((specificity, entry)) => match self.resolve(Some(specificity), deny) {
+                Some(true) => (Some(true), Some(entry)),
+                decision => (decision, None),
+            },
+            None => (self.resolve(None, deny), None),
+        }
+    }
+
+    /// Returns `true` if an entry that matches `metadata` has span
+    /// conditions, so whether it is enabled depends on the spans that are
+    /// entered.
+    pub(crate) fn has_conditional_match(&self, metadata: &Metadata<'_>) -> bool {
+        let is_conditional = |(_, entry): (Specificity, Matched<'_>)| self.is_conditional(&entry);
+        self.allow_line_match(metadata).is_some_and(is_conditional)
+            || self.allow_level_match(metadata).is_some_and(is_conditional)
+            || self
+                .allow_prefix_match(metadata)
+                .is_some_and(is_conditional)
+    }
+
+    /// Decides between the most specific matching entry and deny entry.
+    pub(crate) fn resolve(
+        &self,
+        allow: Option<Specificity>,
+        deny: Option<Specificity>,
+    ) -> Option<bool> {
+        match (allow, deny) {
+            (None, None) => None,
+            (Some(_), None) => Some(true),
+            (None, Some(_)) => Some(false),
+            (Some(allow), Some(deny)) => {
+                Some(self.precedence == Precedence::MostSpecific && allow > deny)
+            }
+        }
+    }
+
+    /// Returns the most specific entry that enables `metadata`, along with how
+    /// specific it is.
+    pub(crate) fn allow_match<'a>(
+        &'a self,
+        metadata: &Metadata<'a>,
+    ) -> Option<(Specificity, Matched<'a>)> {
+        self.allow_line_match(metadata)
+            .or_else(|| self.allow_level_match(metadata))
+            .or_else(|| self.allow_prefix_match(metadata))
+    }
+
+    /// Returns the `module@line` or `file@line` entry that enables
+    /// `metadata`, if there is one.
+    fn allow_line_match<'a>(
+        &'a self,
+        metadata: &Metadata<'a>,
+    ) -> Option<(Specificity, Matched<'a>)> {
+        if let Some(line) = metadata.line() {
+            // Entries with level sets only match spans and events at those
+            // levels.
+            let allows = |location: &str| self.entry_allows(location, line, metadata.level());
+            for key in module_keys(metadata) {
+                if self.by_module.contains(&(Cow::Borrowed(key), line)) && allows(key) {
+                    return Some((Specificity::Line, Matched::Module(key, line)));
+                }
+            }
+            if let Some(file) = metadata.file() {
+                if self.by_file.contains(&(Cow::Borrowed(file), line)) && allows(file) {
+                    return Some((Specificity::Line, Matched::File(file, line)));
+                }
+            }
+            let mapped = self.mapped_match(&self.by_module, &self.by_file, metadata);
+            if let Some(matched) = mapped.filter(|matched| match *matched {
+                Matched::Module(location, _) | Matched::File(location, _) => allows(location),
+                _ => true,
+            }) {
+                return Some((Specificity::Line, matched));
+            }
+            if self.is_instrumented_fn(metadata) {
+                return Some((
+                    Specificity::Line,
+                    Matched::Module(module_key(metadata), line),
+                ));
+            }
+            if let Some(file) = metadata.file().filter(|_| self.is_in_crate_file(metadata)) {
+                return Some((Specificity::Line, Matched::File(file, line)));
+            }
+        }
+        None
+    }
+
+    /// Returns the `module@level` entry that enables `metadata`, if there is
+    /// one.
+    fn allow_level_match<'a>(
+        &'a self,
+        metadata: &Metadata<'a>,
+    ) -> Option<(Specificity, Matched<'a>)> {
+        for key in module_keys(metadata) {
+            if let Some(&level) = self.mod_levels.get(key) {
+                if level >= *metadata.level() {
+                    return Some((Specificity::Level, Matched::Level(key, level)));
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the longest `module::**` prefix that enables `metadata`, if
+    /// there is one.
+    fn allow_prefix_match<'a>(
+        &'a self,
+        metadata: &Metadata<'a>,
+    ) -> Option<(Specificity, Matched<'a>)> {
+        let prefix = module_keys(metadata).find_map(|key| self.mod_prefixes.longest_match(key))?;
+        let segments = prefix.split("::").count();
+        Some((Specificity::Prefix(segments), Matched::Prefix(prefix)))
+    }
+
+    /// Returns how specific the most specific deny entry that matches
+    /// `metadata` is, ignoring expiry.
+    pub(crate) fn deny_specificity(&self, metadata: &Metadata<'_>) -> Option<Specificity> {
+        self.deny_match(metadata)
+            .map(|(specificity, _)| specificity)
+    }
+
+    /// Returns the most specific deny entry that matches `metadata`, along
+    /// with how specific it is.
+    pub(crate) fn deny_match<'a>(
+        &'a self,
+        metadata: &Metadata<'a>,
+    ) -> Option<(Specificity, Matched<'a>)> {
+        if let Some(line) = metadata.line() {
+            for key in module_keys(metadata) {
+                if self.deny.by_module.contains(&(Cow::Borrowed(key), line)) {
+                    return Some((Specificity::Line, Matched::Module(key, line)));
+                }
+            }
+            if let Some(file) = metadata.file() {
+                if self.deny.by_file.contains(&(Cow::Borrowed(file), line)) {
+                    return Some((Specificity::Line, Matched::File(file, line)));
+                }
+            }
+            if let Some(matched) =
+                self.mapped_match(&self.deny.by_module, &self.deny.by_file, metadata)
+            {
+                return Some((Specificity::Line, matched));
+            }
+        }
+        let prefix = module_keys(metadata).find_map(|key| self.deny.prefixes.longest_match(key))?;
+        let segments = prefix.split("::").count();
+        Some((Specificity::Prefix(segments), Matched::Prefix(prefix)))
+    }
+
+    /// Returns the `module@line` entry in `by_module` for the module that the
+    /// filter's [`ModuleMap`] maps `metadata`'s file to, or the `file@line`
+    /// entry in `by_file` for the file it maps `metadata`'s module to.
+    ///
+    /// [`ModuleMap`]: crate::ModuleMap
+    fn mapped_match<'a>(
+        &'a self,
+        by_module: &HashSet<(Cow<'static, str>, u32)>,
+        by_file: &HashSet<(Cow<'static, str>, u32)>,
+        metadata: &Metadata<'a>,
+    ) -> Option<Matched<'a>> {
+        if self.module_map.is_empty() {
+            return None;
+        }
+        let line = metadata.line()?;
+        let mapped = metadata
+            .file()
+            .and_then(|file| self.module_map.mapped_module(by_module, file, line))
+            .map(|module| Matched::Module(module, line));
+        mapped.or_else(|| {
+            let module = metadata.module_path()?;
+            self.module_map
+                .mapped_file(by_file, module, line)
+                .map(|file| Matched::File(file, line))
+        })
+    }
+}
+
+// === impl Matched ===
+
+impl Matched<'_> {
+    /// Returns `true` if `directive` is the directive for this entry,
+    /// ignoring its line hash.
+    pub(crate) fn is(&self, directive: &Directive) -> bool {
+        match (directive, self) {
+            (Directive::Module { module, line, .. }, Matched::Module(m, l)) => {
+                module == m && line == l
+            }
+            (Directive::File { file, line, .. }, Matched::File(f, l)) => file == f && line == l,
+            (Directive::ModuleLevel { module, level }, Matched::Level(m, l)) => {
+                module == m && level == l
+            }
+            (Directive::ModulePrefix { prefix }, Matched::Prefix(p)) => prefix == p,
+            _ => false,
+        }
+    }
+
+    /// Returns the directive for this entry, without a line hash.
+    pub(crate) fn to_directive(&self) -> Directive {
+        match *self {
+            Matched::Module(module, line) => Directive::Module {
+                module: module.to_owned(),
+                line,
+                hash: None,
+                levels: None,
+            },
+            Matched::File(file, line) => Directive::File {
+                file: file.to_owned(),
+                line,
+                hash: None,
+                levels: None,
+            },
+            Matched::Level(module, level) => Directive::ModuleLevel {
+                module: module.to_owned(),
+                level,
+            },
+            Matched::Prefix(prefix) => Directive::ModulePrefix {
+                prefix: prefix.to_owned(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CallsiteDesc;
+    use std::time::Duration;
+    use tracing_core::Level;
+    use tracing_subscriber::{reload, Registry};
+
+    fn event(line: u32) -> &'static Metadata<'static> {
+        CallsiteDesc::event("my_crate::db", line, Level::INFO).metadata()
+    }
+
+    fn decide(directives: &str, precedence: Precedence, line: u32) -> Option<bool> {
+        let mut filter = directives.parse::<LineFilter>().unwrap();
+        filter.with_precedence(precedence);
+        filter.decide(event(line))
+    }
+
+    #[test]
+    fn deny_entries_win_by_default() {
+        let precedence = Precedence::DenyWins;
+        assert_eq!(
+            decide("my_crate::db@1,!my_crate::**", precedence, 1),
+            Some(false)
+        );
+        assert_eq!(
+            decide("my_crate::**,!my_crate::db@1", precedence, 1),
+            Some(false)
+        );
+        assert_eq!(
+            decide("my_crate::**,!my_crate::db@1", precedence, 2),
+            Some(true)
+        );
+        assert_eq!(decide("!my_crate::db@1", precedence, 1), Some(false));
+        assert_eq!(decide("my_crate::db@2", precedence, 1), None);
+    }
+
+    #[test]
+    fn the_most_specific_entry_wins() {
+        let precedence = Precedence::MostSpecific;
+        assert_eq!(
+            decide("my_crate::db@1,!my_crate::**", precedence, 1),
+            Some(true)
+        );
+        assert_eq!(
+            decide("my_crate::db@1,!my_crate::**", precedence, 2),
+            Some(false)
+        );
+        assert_eq!(
+            decide("my_crate::**,!my_crate::db@1", precedence, 1),
+            Some(false)
+        );
+        assert_eq!(
+            decide("my_crate::db@info,!my_crate::**", precedence, 1),
+            Some(true)
+        );
+        assert_eq!(
+            decide("my_crate::db::**,!my_crate::**", precedence, 1),
+            Some(true)
+        );
+        assert_eq!(
+            decide("my_crate::**,!my_crate::db::**", precedence, 1),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn equally_specific_deny_entries_win() {
+        let precedence = Precedence::MostSpecific;
+        assert_eq!(
+            decide("my_crate::db@1,!my_crate::db@1", precedence, 1),
+            Some(false)
+        );
+        assert_eq!(
+            decide("my_crate::**,!my_crate::**", precedence, 1),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn inactive_entries_dont_hide_broader_ones() {
+        let decide = |directives: &str, precedence| {
+            let mut filter = directives.parse::<LineFilter>().unwrap();
+            filter.with_precedence(precedence);
+            let (_layer, handle) = reload::Layer::<_, Registry>::new(filter);
+            let line = "my_crate::db@1".parse().unwrap();
+            let _session =
+                LineFilter::start_session(&handle, "expired", [line], Duration::ZERO).unwrap();
+            handle
+                .with_current(|filter| filter.decide(event(1)))
+                .unwrap()
+        };
+
+        // The session's line entry has expired, so the prefix decides.
+        assert_eq!(decide("my_crate::**", Precedence::DenyWins), Some(true));
+        assert_eq!(
+            decide("my_crate::db@info", Precedence::DenyWins),
+            Some(true)
+        );
+        assert_eq!(decide("", Precedence::DenyWins), None);
+        // An expired line entry doesn't win over a deny prefix, either.
+        assert_eq!(
+            decide("my_crate::**,!my_crate::**", Precedence::MostSpecific),
+            Some(false)
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use crate::{Directive, LineFilter};
+use std::{collections::BTreeMap, fmt};
+
+/// The differences between two [`LineFilter`]s' directives, returned by
+/// [`LineFilter::diff`].
+///
+/// A directive that only one of the filters has is either added or removed.
+/// A directive whose location is in both filters, but with a different value,
+/// is changed: a `module@line` or `file@line` entry whose line hash changed,
+/// a `module@level` entry whose level changed, or an `EnvFilter` directive
+/// whose level changed.
+///
+/// The `Display` implementation lists the differences on one line, such as
+/// `+my_crate::db@200, -my_crate::db@142, my_crate=info -> my_crate=debug`,
+/// or `no changes` if there aren't any. When the `serde` feature flag is
+/// enabled, this implements `serde::Serialize`, with each directive
+/// serialized as a string.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FilterDiff {
+    added: Vec<Directive>,
+    removed: Vec<Directive>,
+    changed: Vec<DirectiveChange>,
+}
+
+/// A directive whose value differs between two filters, as reported by
+/// [`FilterDiff::changed`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DirectiveChange {
+    from: Directive,
+    to: Directive,
+}
+
+impl LineFilter {
+    /// Returns the differences between this filter's directives and
+    /// `other`'s, as if `self` were replaced by `other`.
+    ///
+    /// Both filters' entries, deny entries, and `EnvFilter` directives are
+    /// compared, in the order of their [`directives`](Self::directives).
+    /// Profiles, span fields, and settings such as the filter's [maximum
+    /// number of entries](Self::max_entries) are not.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    ///
+    /// let before: LineFilter = "my_crate::db@142, my_crate::auth@info".parse().unwrap();
+    /// let after: LineFilter = "my_crate::db@200, my_crate::auth@debug".parse().unwrap();
+    ///
+    /// let diff = before.diff(&after);
+    /// assert_eq!(diff.added()[0].to_string(), "my_crate::db@200");
+    /// assert_eq!(diff.removed()[0].to_string(), "my_crate::db@142");
+    /// assert_eq!(diff.changed()[0].from().to_string(), "my_crate::auth@info");
+    /// assert_eq!(diff.changed()[0].to().to_string(), "my_crate::auth@debug");
+    /// assert_eq!(
+    ///     diff.to_string(),
+    ///     "+my_crate::db@200, -my_crate::db@142, my_crate::auth@info -> my_crate::auth@debug",
+    /// );
+    ///
+    /// assert!(after.diff(&after).is_empty());
+    /// ```
+    pub fn diff(&self, other: &LineFilter) -> FilterDiff {
+        FilterDiff::between(self.diffable_directives(), other.diffable_directives())
+    }
+
+    /// Returns this filter's directives, followed by its `EnvFilter`
+    /// directives, if it has any.
+    pub(crate) fn diffable_directives(&self) -> Vec<Directive> {
+        self.directives().chain(self.env_directives()).collect()
+    }
+
+    #[cfg(feature = "env-filter")]
+    fn env_directives(&self) -> Vec<Directive> {
+        let env = match self.fallback {
+            Some(ref fallback) => fallback.to_string(),
+            None => return Vec::new(),
+        };
+        split_env(&env)
+            .filter_map(|directive| directive.parse().ok())
+            .map(Directive::Env)
+            .collect()
+    }
+
+    #[cfg(not(feature = "env-filter"))]
+    fn env_directives(&self) -> Vec<Directive> {
+        Vec::new()
+    }
+}
+
+// === impl FilterDiff ===
+
+impl FilterDiff {
+    /// Returns the differences between two lists of directives.
+    pub(crate) fn between(
+        before: impl IntoIterator<Item = Directive>,
+        after: impl IntoIterator<Item = Directive>,
+    ) -> Self {
+        let mut before = before
+            .into_iter()
+            .map(|directive| (key(&directive), directive))
+            .collect::<BTreeMap<_, _>>();
+        let mut diff = Self::default();
+        for directive in after {
+            match before.remove(&key(&directive)) {
+                None => diff.added.push(directive),
+                Some(from) if from != directive => diff.changed.push(DirectiveChange {
+                    from,
+                    to: directive,
+                }),
+                Some(_) => {}
+            }
+        }
+        diff.removed.extend(before.into_values());
+        diff
+    }
+
+    /// Returns the directives that were added.
+    pub fn added(&self) -> &[Directive] {
+        &self.added
+    }
+
+    /// Returns the directives that were removed.
+    pub fn removed(&self) -> &[Directive] {
+        &self.removed
+    }
+
+    /// Returns the directives whose values changed.
+    pub fn changed(&self) -> &[DirectiveChange] {
+        &self.changed
+    }
+
+    /// Returns `true` if the filters' directives are the same.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl fmt::Display for FilterDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("no changes");
+        }
+        let added = self.added.iter().map(|d| format!("+{}", d));
+        let removed = self.removed.iter().map(|d| format!("-{}", d));
+        let changed = self.changed.iter().map(ToString::to_string);
+        let diff = added.chain(removed).chain(changed).collect::<Vec<_>>();
+        f.write_str(&diff.join(", "))
+    }
+}
+
+// === impl DirectiveChange ===
+
+impl DirectiveChange {
+    /// Returns the directive as it was before the change.
+    pub fn from(&self) -> &Directive {
+        &self.from
+    }
+
+    /// Returns the directive as it is after the change.
+    pub fn to(&self) -> &Directive {
+        &self.to
+    }
+}
+
+impl fmt::Display for DirectiveChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} -> {}", self.from, self.to)
+    }
+}
+
+/// Returns the location a directive applies to, which identifies it in a
+/// diff regardless of its value.
+pub(crate) fn key(directive: &Directive) -> String {
+    match directive {
+        Directive::Module { module, line, .. } => format!("{}@{}", module, line),
+        Directive::File { file, line, .. } => format!("{}@{}", file, line),
+        // Module paths never end in `@`, so this can't be mistaken for the
+        // other keys.
+        Directive::ModuleLevel { module, .. } => format!("{}@", module),
+        #[cfg(feature = "env-filter")]
+        Directive::Env(directive) => {
+            let directive = directive.to_string();
+            let target = directive.rsplit_once('=').map_or("", |(target, _)| target);
+            format!("={}", target)
+        }
+        directive => directive.to_string(),
+    }
+}
+
+/// Splits an `EnvFilter`'s string form into its directives, without
+/// splitting at commas inside a directive's span and field filters.
+#[cfg(feature = "env-filter")]
+fn split_env(env: &str) -> impl Iterator<Item = &str> {
+    let mut depth = 0usize;
+    env.split(move |c| {
+        match c {
+            '[' | '{' => depth += 1,
+            ']' | '}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        c == ',' && depth == 0
+    })
+    .filter(|directive| !directive.is_empty())
+}
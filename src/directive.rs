@@ -0,0 +1,1310 @@
+use crate::{
+    condition::{self, Condition},
+    fields, levels, note, startup, BadPath, FilterConfig, LevelSet, LineFilter, Validation,
+    Warning, WarningKind,
+};
+use std::{collections::BTreeMap, error::Error, fmt, str::FromStr, time::Duration};
+use tracing_subscriber::filter::LevelFilter;
+#[cfg(feature = "env-filter")]
+use tracing_subscriber::filter::{self, EnvFilter};
+
+/// Indicates a directive string could not be parsed.
+#[derive(Debug)]
+pub struct ParseError {
+    directive: String,
+    kind: ErrorKind,
+}
+
+#[derive(Debug)]
+enum ErrorKind {
+    Syntax(&'static str),
+    Path(BadPath),
+    #[cfg(feature = "env-filter")]
+    Env(filter::ParseError),
+}
+
+/// A single filter directive, such as `my_crate::db@142` or `my_crate=debug`.
+///
+/// See [the `FromStr` implementation for `LineFilter`][syntax] for the
+/// directive syntax. Directives can be added to a [`LineFilter`] using
+/// [`LineFilter::add_directive`].
+///
+/// # Examples
+///
+/// ```
+/// use tracing_line_filter::Directive;
+///
+/// let directive: Directive = "my_crate::db@142".parse().unwrap();
+/// assert_eq!(
+///     directive,
+///     Directive::Module {
+///         module: "my_crate::db".to_owned(),
+///         line: 142,
+///         hash: None,
+///         levels: None,
+///     }
+/// );
+/// assert_eq!(directive.to_string(), "my_crate::db@142");
+/// ```
+///
+/// [syntax]: crate::LineFilter#impl-FromStr-for-LineFilter
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Directive {
+    /// Enables spans and events in a module on a line, such as
+    /// `my_crate::db@142`.
+    Module {
+        /// The module's path.
+        module: String,
+        /// The line number.
+        line: u32,
+        /// The hash of the source line the directive points at, if the
+        /// directive includes one (such as `my_crate::db@142:1a2b3c4d`).
+        hash: Option<u32>,
+        /// The levels of the spans and events to enable on the line, if the
+        /// directive only enables some of them (such as
+        /// `my_crate::db@142=trace+debug`).
+        levels: Option<LevelSet>,
+    },
+    /// Enables spans and events in a file on a line, such as
+    /// `/home/eliza/my_crate/src/db.rs@142`.
+    File {
+        /// The file's path.
+        file: String,
+        /// The line number.
+        line: u32,
+        /// The hash of the source line the directive points at, if the
+        /// directive includes one.
+        hash: Option<u32>,
+        /// The levels of the spans and events to enable on the line, if the
+        /// directive only enables some of them.
+        levels: Option<LevelSet>,
+    },
+    /// Enables spans and events in a module at a level and all levels
+    /// above it, such as `my_crate::db@info`.
+    ///
+    /// This is a default for the whole module: `Module` directives for the
+    /// same module enable the spans and events on their lines regardless of
+    /// their levels.
+    ModuleLevel {
+        /// The module's path.
+        module: String,
+        /// The most verbose level to enable.
+        level: LevelFilter,
+    },
+    /// Enables spans and events on any line of a module and all of its
+    /// submodules, such as `my_crate::db::**`.
+    ModulePrefix {
+        /// The module's path, without the trailing `::**`.
+        prefix: String,
+    },
+    /// Disables the spans and events that a `Module`, `File`, or
+    /// `ModulePrefix` directive would enable, such as `!my_crate::db@142`.
+    ///
+    /// See [`LineFilter::deny_by_mod`] for details. Other kinds of
+    /// directives can't be denied, and [`LineFilter::add_directive`] ignores
+    /// a `Deny` directive containing one.
+    Deny(Box<Directive>),
+    /// An [`EnvFilter`] directive, such as `error` or `my_crate::db=debug`.
+    ///
+    /// This is only available when the `env-filter` feature flag is
+    /// enabled, which it is by default.
+    ///
+    /// [`EnvFilter`]: tracing_subscriber::EnvFilter
+    #[cfg(feature = "env-filter")]
+    Env(filter::Directive),
+}
+
+/// Parses and validates a directive string at compile time.
+///
+/// This expands to the directive string itself, so it can be used anywhere a
+/// directive string is expected. If the string contains a syntax error, the
+/// build fails with a message describing the error. See [the `FromStr`
+/// implementation for `LineFilter`][syntax] for the directive syntax.
+///
+/// Since the [`EnvFilter`] syntax is quite flexible, only the `target@line`
+/// directives are fully validated at compile time. Other directives are
+/// checked for invalid level names, and are otherwise parsed by [`EnvFilter`]
+/// when the string is parsed into a [`LineFilter`].
+///
+/// # Examples
+///
+/// ```
+/// use tracing_line_filter::{line_directives, LineFilter};
+///
+/// const DIRECTIVES: &str = line_directives!("my_crate::db@142,error");
+///
+/// let filter: LineFilter = DIRECTIVES.parse().unwrap();
+/// ```
+///
+/// Typos are caught at compile time:
+///
+/// ```compile_fail
+/// use tracing_line_filter::line_directives;
+///
+/// // This won't compile: `14two` is not a line number!
+/// let directives = line_directives!("my_crate::db@14two,error");
+/// ```
+///
+/// ```compile_fail
+/// use tracing_line_filter::line_directives;
+///
+/// // This won't compile: `degub` is not a level!
+/// let directives = line_directives!("my_crate::db@142,my_crate=degub");
+/// ```
+///
+/// [syntax]: crate::LineFilter#impl-FromStr-for-LineFilter
+/// [`EnvFilter`]: tracing_subscriber::EnvFilter
+#[macro_export]
+macro_rules! line_directives {
+    ($directives:expr) => {{
+        const DIRECTIVES: &str = $directives;
+        const _: () = $crate::__validate_directives(DIRECTIVES);
+        DIRECTIVES
+    }};
+}
+
+#[doc(hidden)]
+pub const fn __validate_directives(directives: &str) {
+    let bytes = directives.as_bytes();
+    let mut start = 0;
+    while start <= bytes.len() {
+        let mut end = start;
+        while end < bytes.len() && !matches!(bytes[end], b',' | b'\n' | b'#') {
+            end += 1;
+        }
+        let (d_start, d_end) = trim(bytes, start, end);
+        if d_start < d_end {
+            if let Err(msg) = check(bytes, d_start, d_end) {
+                panic!("{}", msg);
+            }
+        }
+        // Skip comments.
+        if end < bytes.len() && bytes[end] == b'#' {
+            while end < bytes.len() && bytes[end] != b'\n' {
+                end += 1;
+            }
+        }
+        start = end + 1;
+    }
+}
+
+/// Parses a list of directives.
+///
+/// Directives take one of six forms:
+///
+/// * `module::path@line` enables spans and events in the module with the
+///   given path on the given line, like [`LineFilter::enable_by_mod`].
+/// * `module::path@level` enables spans and events in the module with the
+///   given path at the given level (such as `info`) or above, like
+///   [`LineFilter::enable_by_mod_level`].
+/// * `module::path::**` enables every span and event in the module with the
+///   given path and in all of its submodules, like
+///   [`LineFilter::enable_by_mod_prefix`].
+/// * A `module::path@line`, `/path/to/file.rs@line`, or `module::path::**`
+///   directive preceded by `!` (such as `!my_crate::db@142`) disables the
+///   spans and events that the directive would enable, like
+///   [`LineFilter::deny_by_mod`].
+/// * `/path/to/file.rs@line` enables spans and events in the given file on
+///   the given line, like [`LineFilter::enable_by_file`]. A directive is
+///   treated as a file path if it contains a path separator or ends in `.rs`.
+/// * Any other directive, such as `error` or `my_crate::db=debug`, is an
+///   [`EnvFilter`] directive. If any of these are present, they are combined
+///   into an [`EnvFilter`] that is composed with the line filter, as though
+///   by [`LineFilter::with_env_filter`].
+///
+/// A `module::path@line` or `/path/to/file.rs@line` directive may also end in
+/// a colon followed by a hash of the text of the source line it points at,
+/// written as 8 hexadecimal digits (such as `my_crate::db@142:1a2b3c4d`).
+/// Hashes don't affect which spans and events are enabled, but they can be
+/// used to detect entries whose line numbers are out of date after the source
+/// code changes. See `LineFilter::validate_sources` (when the `source`
+/// feature flag is enabled) for details.
+///
+/// A `module::path@line` or `/path/to/file.rs@line` directive may also end
+/// in `=` and a set of levels separated by `+`, such as
+/// `my_crate::db@142=trace+debug`, after its hash if it has one. It then
+/// only enables the spans and events on its line at those levels, so that
+/// an `INFO` event sharing a line with the `DEBUG` event being debugged is
+/// left to the rest of the filter (see [`LevelSet`]).
+///
+/// Directives are separated by commas or newlines, and whitespace around
+/// directives is ignored. A `#` starts a comment, which continues until the
+/// end of the line. This means that a list of directives can also be kept in
+/// a file, with one directive per line.
+///
+/// A line containing only a `[profile.NAME]` header starts a named profile:
+/// the directives after it, up to the next header, are not added to the
+/// filter, but are defined as a profile that can be switched on as a unit
+/// later (see [`LineFilter::define_profile`]). Profiles may not contain
+/// [`EnvFilter`] directives.
+///
+/// A line containing only a `[startup]` header starts the startup section,
+/// whose directives are only enabled while the program is starting up (see
+/// [`LineFilter::enable_during_startup`]). A `window = "DURATION"` line in
+/// the section, such as `window = "30s"`, ends startup once that much time
+/// has passed; otherwise it lasts until [`LineFilter::mark_ready`] is
+/// called. The startup section may not contain [`EnvFilter`] or deny
+/// directives.
+///
+/// A line of directives may end in a `when = { ... }` clause, so that one
+/// shared config file can include directives that only apply in some
+/// environments. The directives on the line are skipped unless every key in
+/// the clause matches:
+///
+/// * `env = "VAR"` matches if the environment variable `VAR` is set to a
+///   non-empty value. With `equals = "VALUE"`, it only matches if `VAR` is
+///   set to `VALUE`.
+/// * `hostname = "NAME"` matches if the machine's hostname is `NAME`.
+/// * `process = "NAME"` matches if the program's executable is named `NAME`
+///   (without an extension such as `.exe`), so a config shared by several
+///   binaries can scope entries to one of them.
+/// * `instance = "ID"` matches if the program's instance ID, set using
+///   [`LineFilter::set_instance_id`], is `ID`.
+///
+/// A trailing `*` in a `hostname`, `process`, or `instance` pattern matches
+/// any value starting with the rest of the pattern, such as `web-*`. A
+/// clause with any other key never matches, so that configs using keys
+/// added in newer versions of this crate can still be parsed; the unknown
+/// key is reported as a [warning], which is recorded as a `WARN` event.
+///
+/// A line containing only a `when` clause applies to the rest of its
+/// section, so a clause right after a `[profile.NAME]` header makes the
+/// whole profile conditional. Conditions are checked when the directives
+/// are parsed, so skipped directives are left out of a filter's (or a
+/// [`FilterConfig`]'s) directives entirely.
+///
+/// A `fields = { name = "value", ... }` line, outside of any profile, sets
+/// fields that are attached to the spans that the filter's entries match
+/// (see [`LineFilter::add_span_field`]). Like directives, it may end in a
+/// `when` clause.
+///
+/// A line of directives may also end in a `note = "..."` clause, before its
+/// `when` clause if it has one, which attaches a free-form note to each of
+/// the line's directives, such as why they were added and when they can be
+/// removed (see [`LineFilter::set_note`]). Notes are written back along with
+/// their directives when a filter is formatted or saved. A `#` inside a note
+/// doesn't start a comment, and `\"`, `\\`, and `\n` stand for a quote, a
+/// backslash, and a newline.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_line_filter::LineFilter;
+///
+/// let filter: LineFilter = "my_crate::db@142,my_crate::db@200,warn,my_crate=info"
+///     .parse()
+///     .expect("directives should be valid");
+/// ```
+///
+/// Enabling a module at the `INFO` level, with a few lines at any level:
+///
+/// ```
+/// use tracing_line_filter::LineFilter;
+///
+/// let filter: LineFilter = "my_crate::db@info,my_crate::db@142,my_crate::db@200"
+///     .parse()
+///     .expect("directives should be valid");
+/// ```
+///
+/// Parsing a file of directives:
+///
+/// ```
+/// use tracing_line_filter::LineFilter;
+///
+/// let config = r#"
+/// # Enable the database query events.
+/// my_crate::db@142
+/// my_crate::db@200
+///
+/// # Enable all warnings, and INFO in `my_crate`.
+/// warn, my_crate=info
+/// "#;
+///
+/// let filter: LineFilter = config.parse().expect("directives should be valid");
+/// ```
+///
+/// Defining profiles for debugging parts of a program:
+///
+/// ```
+/// use tracing_line_filter::LineFilter;
+///
+/// let config = r#"
+/// warn
+///
+/// [profile.db-debug]
+/// my_crate::db@142
+/// my_crate::db@200
+///
+/// [profile.auth-debug]
+/// my_crate::auth::**
+/// "#;
+///
+/// let mut filter: LineFilter = config.parse().expect("directives should be valid");
+/// assert_eq!(filter.to_string(), "warn");
+///
+/// filter.activate_profile("db-debug").unwrap();
+/// assert_eq!(filter.to_string(), "my_crate::db@142,my_crate::db@200,warn");
+/// ```
+///
+/// Recording why lines were pinned:
+///
+/// ```
+/// use tracing_line_filter::LineFilter;
+///
+/// let config = r#"
+/// my_crate::db@142, my_crate::db@200 note = "added for #4521; remove after the fix ships"
+/// my_crate::auth@88
+/// "#;
+///
+/// let filter: LineFilter = config.parse().expect("directives should be valid");
+/// let db = "my_crate::db@200".parse().unwrap();
+/// assert_eq!(filter.note(&db), Some("added for #4521; remove after the fix ships"));
+/// assert_eq!(filter.note(&"my_crate::auth@88".parse().unwrap()), None);
+/// ```
+///
+/// Enabling only the `DEBUG` event on a line that has two:
+///
+/// ```
+/// use tracing_line_filter::LineFilter;
+/// use tracing_subscriber::{prelude::*, reload};
+///
+/// let directive = format!("{}@{}=debug", module_path!(), line!() + 5);
+/// let filter: LineFilter = directive.parse().unwrap();
+/// let (filter, handle) = reload::Layer::new(filter);
+/// let _guard = tracing_subscriber::registry().with(filter).set_default();
+///
+/// tracing::debug!("cache miss"); tracing::info!("fetched row");
+///
+/// let stats = handle.with_current(LineFilter::stats).unwrap();
+/// assert_eq!(stats[0].directive().to_string(), directive);
+/// assert_eq!(stats[0].hits(), 1);
+/// ```
+///
+/// Pinning lines only in some environments:
+///
+/// ```
+/// use tracing_line_filter::LineFilter;
+///
+/// std::env::set_var("DEPLOY_ENV", "staging");
+///
+/// let config = r#"
+/// my_crate::db@142
+/// my_crate::db@200 when = { env = "DEPLOY_ENV", equals = "staging" }
+/// my_crate::db@300 when = { env = "DEPLOY_ENV", equals = "production" }
+///
+/// [profile.canary]
+/// when = { hostname = "canary-*" }
+/// my_crate::**
+/// "#;
+///
+/// let filter: LineFilter = config.parse().expect("directives should be valid");
+/// assert_eq!(filter.to_string(), "my_crate::db@142,my_crate::db@200");
+/// ```
+///
+/// Logging verbosely while the program starts up:
+///
+/// ```
+/// use std::time::Duration;
+/// use tracing_line_filter::FilterConfig;
+///
+/// let config = r#"
+/// my_crate::server@info
+///
+/// [startup]
+/// window = "1m30s"
+/// my_crate::config::**
+/// "#;
+///
+/// let config: FilterConfig = config.parse().expect("directives should be valid");
+/// assert_eq!(config.startup()[0].to_string(), "my_crate::config::**");
+/// assert_eq!(config.startup_window(), Some(Duration::from_secs(90)));
+/// ```
+///
+/// Marking the spans recorded during a debugging session:
+///
+/// ```
+/// use tracing_line_filter::LineFilter;
+///
+/// let config = r#"
+/// fields = { debug_session = "ticket-1234" }
+/// my_crate::db@142
+/// "#;
+///
+/// let filter: LineFilter = config.parse().expect("directives should be valid");
+/// let fields = filter.span_fields().collect::<Vec<_>>();
+/// assert_eq!(fields, [("debug_session", "ticket-1234")]);
+/// ```
+///
+/// The [`line_directives!`](crate::line_directives) macro can be used to
+/// check hard-coded directive strings at compile time.
+///
+/// [`EnvFilter`]: tracing_subscriber::EnvFilter
+/// [`FilterConfig`]: crate::FilterConfig
+/// [warning]: LineFilter::parse_with_warnings
+impl FromStr for LineFilter {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (filter, warnings) = Self::parse_with_warnings(s)?;
+        for warning in &warnings {
+            warning.record();
+        }
+        Ok(filter)
+    }
+}
+
+impl LineFilter {
+    /// Parses a filter from a directive string, like [its `FromStr`
+    /// implementation][syntax], returning the [`Warning`]s found while
+    /// parsing it instead of recording them as events.
+    ///
+    /// Warnings are about problems that don't stop the config from being
+    /// parsed, but that the config's author should probably know about:
+    /// syntax from an older version of the config format, keys that this
+    /// version of the crate doesn't know about, and file paths that probably
+    /// never match. A program that loads a config file can show them to its
+    /// user, or refuse a config with warnings in CI.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if `s` can't be parsed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::{LineFilter, WarningKind};
+    ///
+    /// let config = r#"
+    /// my_crate::db@142
+    /// my_crate::db@200 when = { cluster = "us-east" }
+    /// /home/me/my_crate/src/../src/db.rs@300
+    /// "#;
+    ///
+    /// let (filter, warnings) = LineFilter::parse_with_warnings(config).unwrap();
+    /// assert_eq!(filter.to_string(), "my_crate::db@142,/home/me/my_crate/src/../src/db.rs@300");
+    /// assert_eq!(warnings.len(), 2);
+    /// assert_eq!(warnings[0].kind(), WarningKind::UnknownKey);
+    /// assert_eq!(
+    ///     warnings[0].to_string(),
+    ///     "line 3: unknown `when` key `cluster`; the line is skipped",
+    /// );
+    /// assert_eq!(warnings[1].kind(), WarningKind::SuspiciousPath);
+    /// ```
+    ///
+    /// [syntax]: LineFilter#impl-FromStr-for-LineFilter
+    pub fn parse_with_warnings(s: &str) -> Result<(Self, Vec<Warning>), ParseError> {
+        let sections = parse_sections(s)?;
+        let mut filter = LineFilter::new();
+        #[cfg(feature = "env-filter")]
+        let mut env = Vec::new();
+        for directive in sections.directives {
+            match directive {
+                // Collect `EnvFilter` directives, so that only one `EnvFilter`
+                // is constructed.
+                #[cfg(feature = "env-filter")]
+                Directive::Env(directive) => env.push(directive),
+                directive => {
+                    filter
+                        .add_directive(directive)
+                        .expect("paths are validated by `Directive::from_str`");
+                }
+            }
+        }
+
+        #[cfg(feature = "env-filter")]
+        if !env.is_empty() {
+            let env = env
+                .into_iter()
+                .fold(EnvFilter::default(), EnvFilter::add_directive);
+            filter.with_env_filter(env);
+        }
+
+        for (name, directives) in sections.profiles {
+            filter
+                .define_profile(name, directives)
+                .expect("profiles are validated by `parse_sections`");
+        }
+        for (name, value) in sections.fields {
+            filter.add_span_field(name, value);
+        }
+        filter
+            .enable_during_startup(sections.startup, sections.startup_window)
+            .expect("startup entries are validated by `parse_sections`");
+        filter.notes = sections.notes;
+
+        Ok((filter, sections.warnings))
+    }
+}
+
+/// Formats a `LineFilter` as a list of directives that can be parsed back
+/// into an equivalent filter.
+///
+/// `module::**` and `module@level` directives are listed first, followed by
+/// `module@line` directives and `file@line` directives, each sorted by path
+/// and line number, followed by deny directives and the [`EnvFilter`]'s
+/// directives. Directives are separated by commas, or by newlines when
+/// formatted with the alternate flag (`{:#}`), so that the output can be
+/// written to a file. [Startup entries] are left out, since they are saved
+/// in a `[startup]` section instead. Since the order doesn't depend on the
+/// order that entries were added in, equivalent filters are always
+/// formatted the same way, and saved configs can be diffed.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_line_filter::LineFilter;
+///
+/// let mut filter = LineFilter::default();
+/// filter
+///     .enable_by_mod("my_crate::db", 200)
+///     .enable_by_mod("my_crate::db", 142);
+///
+/// assert_eq!(filter.to_string(), "my_crate::db@142,my_crate::db@200");
+/// assert_eq!(format!("{:#}", filter), "my_crate::db@142\nmy_crate::db@200");
+///
+/// let parsed: LineFilter = filter.to_string().parse().unwrap();
+/// assert_eq!(parsed.to_string(), filter.to_string());
+/// ```
+///
+/// [`EnvFilter`]: tracing_subscriber::EnvFilter
+/// [Startup entries]: LineFilter::enable_during_startup
+impl fmt::Display for LineFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sep = if f.alternate() { "\n" } else { "," };
+        let directives = self
+            .directives()
+            .filter_map(|directive| self.without_startup(directive))
+            .collect::<Vec<_>>();
+        let last_noted = note::fmt_directives(f, &directives, &self.notes, sep)?;
+
+        if let Some(ref fallback) = self.fallback {
+            let env = fallback.to_string();
+            if !env.is_empty() {
+                match last_noted {
+                    Some(true) => f.write_str("\n")?,
+                    Some(false) => f.write_str(sep)?,
+                    None => {}
+                }
+                f.write_str(&env)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The directives parsed from a directive string, along with the directives
+/// in each of its `[profile.NAME]` sections and its `[startup]` section.
+#[derive(Debug, Default)]
+pub(crate) struct Sections {
+    pub(crate) directives: Vec<Directive>,
+    pub(crate) profiles: BTreeMap<String, Vec<Directive>>,
+    /// The directives in the `[startup]` section.
+    pub(crate) startup: Vec<Directive>,
+    /// The `window = "..."` of the `[startup]` section, if it has one.
+    pub(crate) startup_window: Option<Duration>,
+    /// The fields set by `fields = { ... }` lines.
+    pub(crate) fields: Vec<(String, String)>,
+    /// The notes set by `note = "..."` clauses, keyed by their directives'
+    /// locations.
+    pub(crate) notes: BTreeMap<String, String>,
+    pub(crate) warnings: Vec<Warning>,
+}
+
+// === impl Sections ===
+
+impl Sections {
+    /// Records the warnings found while parsing these sections as events.
+    pub(crate) fn record_warnings(&self) {
+        for warning in &self.warnings {
+            warning.record();
+        }
+    }
+}
+
+/// Parses a directive string, skipping comments.
+///
+/// A line containing only a `[profile.NAME]` or `[startup]` header starts a
+/// profile or the startup section, and the directives that follow it belong
+/// to that section, until the next header. A line's directives may be
+/// followed by a `note = "..."` clause, which is kept for each of them, and
+/// by a `when = { ... }` clause, and are skipped unless its condition is
+/// met; a line containing only a `when` clause applies it to the rest of
+/// the section.
+///
+/// Configs written in older versions of the format are migrated first.
+pub(crate) fn parse_sections(s: &str) -> Result<Sections, ParseError> {
+    parse_sections_with(s, Validation::Strict)
+}
+
+/// Parses a config like [`parse_sections`], checking its file paths with
+/// `validation`.
+pub(crate) fn parse_sections_with(s: &str, validation: Validation) -> Result<Sections, ParseError> {
+    let migration = FilterConfig::migrate(s)?;
+    let is_space = |c: char| c.is_ascii_whitespace();
+    let mut sections = Sections {
+        warnings: migration.warnings().to_vec(),
+        ..Sections::default()
+    };
+    let mut profile = None;
+    // Whether `profile` is the `[startup]` section.
+    let mut in_startup = false;
+    // Whether the section's `when` clauses so far have been met.
+    let mut section_met = true;
+    for (i, line) in migration.config().lines().enumerate() {
+        let line_num = match migration.original_line(i + 1) {
+            Some(line_num) => line_num,
+            // The version line, which was checked by `migrate`.
+            None => continue,
+        };
+        if line.trim_start().starts_with("#!") {
+            sections.warnings.push(Warning::new(
+                line_num,
+                WarningKind::UnknownKey,
+                format!(
+                    "ignoring `{}`, since only the first line may be a `#!` line",
+                    line.trim()
+                ),
+            ));
+            continue;
+        }
+        let line = strip_comment(line);
+        let header = line.trim_matches(is_space);
+        if header == "[startup]" {
+            profile = Some(&mut sections.startup);
+            in_startup = true;
+            section_met = true;
+            continue;
+        }
+        if header.starts_with('[') && header.ends_with(']') {
+            let name = header
+                .strip_prefix("[profile.")
+                .and_then(|rest| rest.strip_suffix(']'))
+                .map(|name| name.trim_matches(is_space))
+                .ok_or_else(|| {
+                    ParseError::syntax(header, "expected `[profile.NAME]` or `[startup]`")
+                })?;
+            if name.is_empty() || name.contains(|c: char| is_space(c) || c == ',') {
+                return Err(ParseError::syntax(header, "expected a profile name"));
+            }
+            profile = Some(sections.profiles.entry(name.to_owned()).or_default());
+            in_startup = false;
+            section_met = true;
+            continue;
+        }
+        if let Some(window) = startup::parse_window(line) {
+            let window = window.map_err(|msg| ParseError::syntax(header, msg))?;
+            if !in_startup {
+                return Err(ParseError::syntax(
+                    header,
+                    "`window` may only be set in the `[startup]` section",
+                ));
+            }
+            sections.startup_window = Some(window);
+            continue;
+        }
+
+        let (line, clause) = condition::split(line);
+        let met = match clause {
+            Some(clause) => {
+                let condition = Condition::parse(clause)
+                    .map_err(|msg| ParseError::syntax(clause.trim_matches(is_space), msg))?;
+                for key in condition.unknown_keys() {
+                    sections.warnings.push(Warning::new(
+                        line_num,
+                        WarningKind::UnknownKey,
+                        format!("unknown `when` key `{}`; the line is skipped", key),
+                    ));
+                }
+                condition.is_met()
+            }
+            None => true,
+        };
+        let (line, note) = note::split(line)
+            .map_err(|msg| ParseError::syntax(line.trim_matches(is_space), msg))?;
+        if line.trim_matches(is_space).is_empty() {
+            if note.is_some() {
+                return Err(ParseError::syntax(
+                    header,
+                    "a `note` clause must follow the directives it describes",
+                ));
+            }
+            section_met &= met;
+            continue;
+        }
+        if let Some(fields) = fields::parse_line(line) {
+            let text = line.trim_matches(is_space);
+            let fields = fields.map_err(|msg| ParseError::syntax(text, msg))?;
+            if note.is_some() {
+                return Err(ParseError::syntax(
+                    text,
+                    "a `note` clause must follow the directives it describes",
+                ));
+            }
+            if profile.is_some() {
+                return Err(ParseError::syntax(
+                    text,
+                    "`fields` may only be set outside of profiles and `[startup]`",
+                ));
+            }
+            if met && section_met {
+                for (name, value) in fields {
+                    fields::set(&mut sections.fields, name, value);
+                }
+            }
+            continue;
+        }
+
+        let directives = line
+            .split(',')
+            .map(|directive| directive.trim_matches(is_space))
+            .filter(|directive| !directive.is_empty());
+        for text in directives {
+            let directive = Directive::parse_with(text, validation)?;
+            if let Some(file) = directive.suspicious_path() {
+                sections.warnings.push(Warning::new(
+                    line_num,
+                    WarningKind::SuspiciousPath,
+                    format!(
+                        "`{}` has `.` or `..` components, which the paths reported by \
+                         `file!()` usually don't",
+                        file
+                    ),
+                ));
+            }
+            if in_startup {
+                if let Some(msg) = startup::unsupported(&directive) {
+                    return Err(ParseError::syntax(text, msg));
+                }
+            }
+            match profile {
+                #[cfg(feature = "env-filter")]
+                Some(_) if matches!(directive, Directive::Env(_)) => {
+                    return Err(ParseError::syntax(
+                        text,
+                        "profiles may not contain `EnvFilter` directives",
+                    ))
+                }
+                _ if !(met && section_met) => continue,
+                _ => {}
+            }
+            if let Some(ref note) = note {
+                note::insert(&mut sections.notes, &directive, note.clone());
+            }
+            match profile {
+                Some(ref mut profile) => profile.push(directive),
+                None => sections.directives.push(directive),
+            }
+        }
+    }
+    Ok(sections)
+}
+
+/// Returns `line` without its comment, if it has one. A `#` inside a quoted
+/// string, such as a note, doesn't start a comment.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+// === impl Directive ===
+
+impl Directive {
+    /// Returns this directive's file path, if it is a `File` directive (or
+    /// denies one) whose path has `.` or `..` components.
+    fn suspicious_path(&self) -> Option<&str> {
+        match self {
+            Directive::File { file, .. } => file
+                .split(['/', '\\'])
+                .any(|component| component == "." || component == "..")
+                .then_some(file.as_str()),
+            Directive::Deny(denied) => denied.suspicious_path(),
+            _ => None,
+        }
+    }
+
+    /// Parses a directive, checking its file path with `validation`.
+    pub(crate) fn parse_with(s: &str, validation: Validation) -> Result<Self, ParseError> {
+        let directive = s.trim_matches(|c: char| c.is_ascii_whitespace());
+        let bytes = directive.as_bytes();
+        if bytes.is_empty() {
+            return Err(ParseError::syntax(directive, "directives may not be empty"));
+        }
+        check(bytes, 0, bytes.len()).map_err(|msg| ParseError::syntax(directive, msg))?;
+
+        if let Some(denied) = directive.strip_prefix('!') {
+            return Self::parse_with(denied, validation)
+                .map(|denied| Directive::Deny(Box::new(denied)));
+        }
+
+        if let Some(prefix) = directive.strip_suffix(PREFIX_SUFFIX) {
+            if line_split(bytes, 0, bytes.len()).is_none() {
+                return Ok(Directive::ModulePrefix {
+                    prefix: prefix.to_owned(),
+                });
+            }
+        }
+
+        let at = match line_split(bytes, 0, bytes.len()) {
+            Some(at) => at,
+            #[cfg(feature = "env-filter")]
+            None => {
+                return directive
+                    .parse()
+                    .map(Directive::Env)
+                    .map_err(|e| ParseError::new(directive, ErrorKind::Env(e)))
+            }
+            #[cfg(not(feature = "env-filter"))]
+            None => {
+                return Err(ParseError::syntax(
+                    directive,
+                    "`EnvFilter` directives require the `env-filter` feature flag",
+                ))
+            }
+        };
+
+        let location = &directive[..at];
+        if is_level_name(bytes, at + 1, bytes.len()) {
+            let level = directive[at + 1..]
+                .parse()
+                .expect("level names are checked by `check`");
+            return Ok(Directive::ModuleLevel {
+                module: location.to_owned(),
+                level,
+            });
+        }
+
+        let (line, levels) = match directive[at + 1..].split_once('=') {
+            Some((line, levels)) => {
+                let levels = levels::parse(levels).expect("level sets are checked by `check`");
+                (line, Some(levels))
+            }
+            None => (&directive[at + 1..], None),
+        };
+        let (line, hash) = match line.split_once(':') {
+            Some((line, hash)) => (line, Some(hash)),
+            None => (line, None),
+        };
+        let line = line
+            .parse::<u32>()
+            .map_err(|_| ParseError::syntax(directive, "invalid line number"))?;
+        let hash = hash
+            .map(|hash| u32::from_str_radix(hash, 16))
+            .transpose()
+            .map_err(|_| ParseError::syntax(directive, "invalid line hash"))?;
+        if is_file(bytes, 0, at) {
+            validation
+                .check_path(location.as_ref())
+                .map_err(|e| ParseError::new(directive, ErrorKind::Path(e)))?;
+            Ok(Directive::File {
+                file: location.to_owned(),
+                line,
+                hash,
+                levels,
+            })
+        } else {
+            Ok(Directive::Module {
+                module: location.to_owned(),
+                line,
+                hash,
+                levels,
+            })
+        }
+    }
+}
+
+impl FromStr for Directive {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_with(s, Validation::Strict)
+    }
+}
+
+/// Serializes a `Directive` as a string, in the syntax it is parsed from.
+///
+/// This is only available when the `serde` feature flag is enabled.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Directive {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl fmt::Display for Directive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Directive::Module {
+                module,
+                line,
+                hash,
+                levels,
+            } => fmt_entry(f, module, *line, *hash, *levels),
+            Directive::File {
+                file,
+                line,
+                hash,
+                levels,
+            } => fmt_entry(f, file, *line, *hash, *levels),
+            Directive::ModuleLevel { module, level } => write!(f, "{}@{}", module, level),
+            Directive::ModulePrefix { prefix } => write!(f, "{}{}", prefix, PREFIX_SUFFIX),
+            Directive::Deny(directive) => write!(f, "!{}", directive),
+            #[cfg(feature = "env-filter")]
+            Directive::Env(directive) => fmt::Display::fmt(directive, f),
+        }
+    }
+}
+
+/// Formats a `location@line` entry, with its line hash and level set, if it
+/// has them.
+pub(crate) fn fmt_entry(
+    f: &mut fmt::Formatter<'_>,
+    location: &str,
+    line: u32,
+    hash: Option<u32>,
+    levels: Option<LevelSet>,
+) -> fmt::Result {
+    write!(f, "{}@{}", location, line)?;
+    if let Some(hash) = hash {
+        write!(f, ":{:08x}", hash)?;
+    }
+    match levels {
+        Some(levels) => write!(f, "={}", levels),
+        None => Ok(()),
+    }
+}
+
+// === impl ParseError ===
+
+impl ParseError {
+    fn new(directive: &str, kind: ErrorKind) -> Self {
+        Self {
+            directive: directive.to_owned(),
+            kind,
+        }
+    }
+
+    pub(crate) fn syntax(directive: &str, message: &'static str) -> Self {
+        Self::new(directive, ErrorKind::Syntax(message))
+    }
+
+    /// Returns the directive that couldn't be parsed, as it was written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::FilterConfig;
+    ///
+    /// let error = "my_crate::db@142, my_crate@@".parse::<FilterConfig>().unwrap_err();
+    /// assert_eq!(error.directive(), "my_crate@@");
+    /// ```
+    pub fn directive(&self) -> &str {
+        &self.directive
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid directive '{}': ", self.directive)?;
+        match self.kind {
+            ErrorKind::Syntax(message) => f.write_str(message),
+            ErrorKind::Path(ref e) => fmt::Display::fmt(e, f),
+            #[cfg(feature = "env-filter")]
+            ErrorKind::Env(ref e) => fmt::Display::fmt(e, f),
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self.kind {
+            ErrorKind::Syntax(_) => None,
+            ErrorKind::Path(ref e) => Some(e),
+            #[cfg(feature = "env-filter")]
+            ErrorKind::Env(ref e) => Some(e),
+        }
+    }
+}
+
+// === const parsing helpers ===
+//
+// These are `const fn`s so that they can be shared between the
+// `line_directives!` macro, which validates directives at compile time, and
+// the runtime parser. Since slicing isn't possible in a `const fn`, they
+// operate on `start..end` index ranges into the directive string's bytes.
+
+const LEVELS: &[&str] = &["trace", "debug", "info", "warn", "error", "off"];
+
+/// The suffix of a `module::**` directive.
+const PREFIX_SUFFIX: &str = "::**";
+
+/// Checks the syntax of a single (trimmed, non-empty) directive.
+const fn check(bytes: &[u8], start: usize, end: usize) -> Result<(), &'static str> {
+    if bytes[start] == b'!' {
+        return check_deny(bytes, start + 1, end);
+    }
+    match line_split(bytes, start, end) {
+        Some(at) => {
+            if at == start {
+                return Err("expected a module or file path before '@'");
+            }
+            if contains_whitespace(bytes, start, at) {
+                return Err("module and file paths may not contain whitespace");
+            }
+            if !is_file(bytes, start, at) && line_split(bytes, start, at).is_some() {
+                return Err("module paths may not contain '@'");
+            }
+            if is_level_name(bytes, at + 1, end) {
+                if is_file(bytes, start, at) {
+                    return Err("levels may only be set for modules, not files");
+                }
+                return Ok(());
+            }
+            let levels = find(bytes, at + 1, end, b'=');
+            if let Err(msg) = check_line(bytes, at + 1, levels) {
+                return Err(msg);
+            }
+            if levels < end {
+                return check_levels(bytes, levels + 1, end);
+            }
+            Ok(())
+        }
+        None if is_prefix(bytes, start, end) => {
+            let prefix_end = end - PREFIX_SUFFIX.len();
+            if prefix_end == start {
+                return Err("expected a module path before '::**'");
+            }
+            if contains_whitespace(bytes, start, prefix_end) {
+                return Err("module paths may not contain whitespace");
+            }
+            if is_file(bytes, start, prefix_end) {
+                return Err("'::**' may only follow a module path");
+            }
+            Ok(())
+        }
+        None => check_env(bytes, start, end),
+    }
+}
+
+/// Checks a deny directive, after its `!`.
+const fn check_deny(bytes: &[u8], start: usize, end: usize) -> Result<(), &'static str> {
+    if start == end || bytes[start] == b'!' {
+        return Err("expected a `module@line`, `file@line`, or `module::**` directive after '!'");
+    }
+    let is_entry = match line_split(bytes, start, end) {
+        Some(at) => !is_level_name(bytes, at + 1, end),
+        None => is_prefix(bytes, start, end),
+    };
+    if !is_entry {
+        return Err("only `module@line`, `file@line`, and `module::**` directives can be denied");
+    }
+    if let Some(at) = line_split(bytes, start, end) {
+        if find(bytes, at + 1, end, b'=') < end {
+            return Err("denied entries may not have level sets");
+        }
+    }
+    check(bytes, start, end)
+}
+
+/// Returns `true` if `start..end` ends in `::**`.
+const fn is_prefix(bytes: &[u8], start: usize, end: usize) -> bool {
+    let suffix = PREFIX_SUFFIX.as_bytes();
+    if end - start < suffix.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < suffix.len() {
+        if bytes[end - suffix.len() + i] != suffix[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Returns the index of the `@` separating a path from a line number, if this
+/// is a `target@line` directive.
+///
+/// `EnvFilter` directives may contain `@`s inside span field values (e.g.
+/// `[span{user=me@example.com}]`), so anything with a span filter is never
+/// treated as a line directive.
+const fn line_split(bytes: &[u8], start: usize, end: usize) -> Option<usize> {
+    let mut at = None;
+    let mut i = start;
+    while i < end {
+        match bytes[i] {
+            b'[' => return None,
+            b'@' => at = Some(i),
+            _ => {}
+        }
+        i += 1;
+    }
+    at
+}
+
+/// Checks a line number, and the line hash following it, if there is one.
+const fn check_line(bytes: &[u8], start: usize, end: usize) -> Result<(), &'static str> {
+    if start == end {
+        return Err("expected a line number after '@'");
+    }
+    let mut line: u64 = 0;
+    let mut i = start;
+    while i < end {
+        let b = bytes[i];
+        if b == b':' {
+            if i == start {
+                return Err("expected a line number after '@'");
+            }
+            return check_hash(bytes, i + 1, end);
+        }
+        if !b.is_ascii_digit() {
+            return Err("line numbers must be decimal integers");
+        }
+        line = line * 10 + (b - b'0') as u64;
+        if line > u32::MAX as u64 {
+            return Err("line number is too large");
+        }
+        i += 1;
+    }
+    Ok(())
+}
+
+/// Returns the index of the first `byte` in `start..end`, or `end` if there
+/// isn't one.
+const fn find(bytes: &[u8], start: usize, end: usize, byte: u8) -> usize {
+    let mut i = start;
+    while i < end && bytes[i] != byte {
+        i += 1;
+    }
+    i
+}
+
+/// Checks a level set, such as `trace+debug`.
+const fn check_levels(bytes: &[u8], start: usize, end: usize) -> Result<(), &'static str> {
+    let mut level = start;
+    let mut i = start;
+    while i <= end {
+        if i == end || bytes[i] == b'+' {
+            let off = i - level == 3 && bytes[level].eq_ignore_ascii_case(&b'o');
+            if !is_level_name(bytes, level, i) || off {
+                return Err("level sets must be levels separated by `+`, such as `trace+debug`");
+            }
+            level = i + 1;
+        }
+        i += 1;
+    }
+    Ok(())
+}
+
+/// Checks a line hash, which must be 8 hexadecimal digits.
+const fn check_hash(bytes: &[u8], start: usize, end: usize) -> Result<(), &'static str> {
+    if end - start != 8 {
+        return Err("line hashes must be 8 hexadecimal digits");
+    }
+    let mut i = start;
+    while i < end {
+        if !bytes[i].is_ascii_hexdigit() {
+            return Err("line hashes must be 8 hexadecimal digits");
+        }
+        i += 1;
+    }
+    Ok(())
+}
+
+/// Performs a cursory check of an `EnvFilter` directive, catching misspelled
+/// level names in `target=level` directives.
+const fn check_env(bytes: &[u8], start: usize, end: usize) -> Result<(), &'static str> {
+    let mut eq = None;
+    let mut i = start;
+    while i < end {
+        match bytes[i] {
+            b'=' => eq = Some(i),
+            // An `=` before the end of a span filter is part of a field.
+            b']' | b'}' => eq = None,
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let level_start = match eq {
+        Some(eq) => eq + 1,
+        None => return Ok(()),
+    };
+    if level_start == end || is_level(bytes, level_start, end) {
+        Ok(())
+    } else {
+        Err("unknown level; expected one of `trace`, `debug`, `info`, `warn`, `error`, `off`, or 0-5")
+    }
+}
+
+const fn is_level(bytes: &[u8], start: usize, end: usize) -> bool {
+    if end - start == 1 && bytes[start] >= b'0' && bytes[start] <= b'5' {
+        return true;
+    }
+    is_level_name(bytes, start, end)
+}
+
+/// Returns `true` if `start..end` is the name of a level, such as `info`.
+///
+/// Unlike [`is_level`], this doesn't accept numeric levels, which would be
+/// ambiguous with line numbers after an `@`.
+const fn is_level_name(bytes: &[u8], start: usize, end: usize) -> bool {
+    let mut l = 0;
+    while l < LEVELS.len() {
+        let level = LEVELS[l].as_bytes();
+        if level.len() == end - start {
+            let mut i = 0;
+            while i < level.len() && bytes[start + i].to_ascii_lowercase() == level[i] {
+                i += 1;
+            }
+            if i == level.len() {
+                return true;
+            }
+        }
+        l += 1;
+    }
+    false
+}
+
+/// Returns `true` if the path in `start..end` should be treated as a file
+/// path rather than a module path.
+const fn is_file(bytes: &[u8], start: usize, end: usize) -> bool {
+    let mut i = start;
+    while i < end {
+        if bytes[i] == b'/' || bytes[i] == b'\\' {
+            return true;
+        }
+        i += 1;
+    }
+    end - start >= 3 && bytes[end - 3] == b'.' && bytes[end - 2] == b'r' && bytes[end - 1] == b's'
+}
+
+const fn contains_whitespace(bytes: &[u8], start: usize, end: usize) -> bool {
+    let mut i = start;
+    while i < end {
+        if bytes[i].is_ascii_whitespace() {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
+const fn trim(bytes: &[u8], mut start: usize, mut end: usize) -> (usize, usize) {
+    while start < end && bytes[start].is_ascii_whitespace() {
+        start += 1;
+    }
+    while end > start && bytes[end - 1].is_ascii_whitespace() {
+        end -= 1;
+    }
+    (start, end)
+}
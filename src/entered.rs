@@ -0,0 +1,348 @@
+use crate::{deny::Matched, Directive, Error, LineFilter};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        RwLock,
+    },
+};
+use tracing_core::{span, Metadata};
+
+thread_local! {
+    /// The spans that filters' conditions point at which are entered on this
+    /// thread, innermost last, along with the ID of the filter that tracks
+    /// each one.
+    ///
+    /// Span IDs are only unique within a subscriber, so each filter only
+    /// looks at the spans it tracked itself; otherwise, a filter attached to
+    /// one subscriber could see another subscriber's span with the same ID.
+    static ENTERED: RefCell<Vec<(u64, span::Id, &'static Metadata<'static>)>> =
+        const { RefCell::new(Vec::new()) };
+}
+
+/// The ID given to the next `SpanConditions`.
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// The conditions added by [`LineFilter::enable_while_entered`], and the
+/// spans they point at that have been created.
+pub(crate) struct SpanConditions {
+    /// Identifies this filter's spans in [`ENTERED`].
+    id: u64,
+    conditions: Vec<SpanCondition>,
+    /// The open spans that a condition points at.
+    spans: RwLock<HashMap<span::Id, &'static Metadata<'static>>>,
+}
+
+#[derive(Clone, Debug)]
+struct SpanCondition {
+    entry: Directive,
+    span: Directive,
+}
+
+impl LineFilter {
+    /// Enable the span or event matched by `entry` only while a span matched
+    /// by `span` is entered on the current thread.
+    ///
+    /// This narrows a pinned line down to the times it runs on behalf of
+    /// another pinned span, such as a low-level networking event that is
+    /// only interesting while a particular request handler is running. The
+    /// entry is added as if by [`add_directive`], and is treated as though it
+    /// didn't match anything while none of the spans that its conditions
+    /// point at are entered, so that deny entries and the filter's
+    /// [`EnvFilter`] decide instead. An entry with several conditions is
+    /// enabled while any of their spans is entered.
+    ///
+    /// `span` should be a `Module` or `File` directive, and the span it
+    /// points at must be enabled, by this filter or another layer, for it to
+    /// be entered. Each filter only tracks the spans entered in the
+    /// subscriber it is attached to, and spans are tracked by the thread
+    /// that enters them, so an entry isn't enabled on another thread (such
+    /// as by work that was spawned onto another thread from inside the span)
+    /// unless the span is entered there too. Like [callbacks], conditions
+    /// aren't removed when their entry is; use [`clear_span_conditions`] to
+    /// remove them.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if `entry` is a `File` directive whose path is
+    /// not valid for use in a `LineFilter`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{
+    ///     atomic::{AtomicUsize, Ordering},
+    ///     Arc,
+    /// };
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::prelude::*;
+    ///
+    /// fn send() {
+    ///     tracing::debug!("sending bytes");
+    /// }
+    /// let net = format!("{}@{}", module_path!(), line!() - 2);
+    /// let server = format!("{}@{}", module_path!(), line!() + 1);
+    /// let handle_request = || tracing::info_span!("request").in_scope(send);
+    ///
+    /// let mut filter: LineFilter = server.parse().unwrap();
+    /// filter
+    ///     .enable_while_entered(net.parse().unwrap(), server.parse().unwrap())
+    ///     .unwrap();
+    /// let sent = Arc::new(AtomicUsize::new(0));
+    /// let counter = sent.clone();
+    /// filter.on_match(net.parse().unwrap(), move |_| {
+    ///     counter.fetch_add(1, Ordering::Relaxed);
+    /// });
+    ///
+    /// let subscriber = tracing_subscriber::registry().with(filter);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     // Not sent on behalf of the request handler.
+    ///     send();
+    ///     handle_request();
+    /// });
+    ///
+    /// assert_eq!(sent.load(Ordering::Relaxed), 1);
+    /// ```
+    ///
+    /// [`add_directive`]: Self::add_directive
+    /// [`EnvFilter`]: https://docs.rs/tracing-subscriber/latest/tracing_subscriber/filter/struct.EnvFilter.html
+    /// [callbacks]: Self::on_match
+    /// [`clear_span_conditions`]: Self::clear_span_conditions
+    pub fn enable_while_entered(
+        &mut self,
+        entry: Directive,
+        span: Directive,
+    ) -> Result<&mut Self, Error> {
+        self.add_directive(entry.clone())?;
+        self.span_conditions
+            .conditions
+            .push(SpanCondition { entry, span });
+        Ok(self)
+    }
+
+    /// Removes all of the conditions added by [`enable_while_entered`],
+    /// leaving their entries enabled whatever spans are entered.
+    ///
+    /// [`enable_while_entered`]: Self::enable_while_entered
+    pub fn clear_span_conditions(&mut self) -> &mut Self {
+        self.span_conditions.conditions.clear();
+        self
+    }
+
+    /// Returns `true` if `entry` has conditions added by
+    /// [`enable_while_entered`].
+    ///
+    /// [`enable_while_entered`]: Self::enable_while_entered
+    pub(crate) fn is_conditional(&self, entry: &Matched<'_>) -> bool {
+        self.span_conditions
+            .conditions
+            .iter()
+            .any(|condition| entry.is(&condition.entry))
+    }
+
+    /// Returns `true` if `entry` has no conditions, or if the span that one
+    /// of them points at is entered on the current thread.
+    pub(crate) fn is_span_condition_met(&self, entry: &Matched<'_>) -> bool {
+        let mut conditions = self
+            .span_conditions
+            .conditions
+            .iter()
+            .filter(|condition| entry.is(&condition.entry))
+            .peekable();
+        if conditions.peek().is_none() {
+            return true;
+        }
+        let id = self.span_conditions.id;
+        ENTERED.with(|entered| {
+            let entered = entered.borrow();
+            conditions.any(|condition| {
+                entered.iter().any(|(filter, _, metadata)| {
+                    *filter == id && points_at(&condition.span, metadata)
+                })
+            })
+        })
+    }
+
+    /// Remembers a newly created span, if a condition points at it.
+    pub(crate) fn span_condition_created(
+        &self,
+        metadata: &'static Metadata<'static>,
+        id: &span::Id,
+    ) {
+        let conditions = &self.span_conditions;
+        if conditions.conditions.is_empty()
+            || !conditions
+                .conditions
+                .iter()
+                .any(|condition| points_at(&condition.span, metadata))
+        {
+            return;
+        }
+        let mut spans = conditions.spans.write().unwrap_or_else(|e| e.into_inner());
+        spans.insert(id.clone(), metadata);
+    }
+
+    /// Tracks that a span was entered on the current thread, if a condition
+    /// points at it.
+    pub(crate) fn span_condition_entered(&self, id: &span::Id) {
+        if let Some(metadata) = self.span_condition_metadata(id) {
+            let filter = self.span_conditions.id;
+            ENTERED.with(|entered| entered.borrow_mut().push((filter, id.clone(), metadata)));
+        }
+    }
+
+    /// Tracks that a span was exited on the current thread, if a condition
+    /// points at it.
+    pub(crate) fn span_condition_exited(&self, id: &span::Id) {
+        if self.span_condition_metadata(id).is_none() {
+            return;
+        }
+        let filter = self.span_conditions.id;
+        ENTERED.with(|entered| {
+            let mut entered = entered.borrow_mut();
+            if let Some(i) = entered
+                .iter()
+                .rposition(|(f, entered, _)| *f == filter && entered == id)
+            {
+                entered.remove(i);
+            }
+        });
+    }
+
+    /// Forgets a span that was closed, so that its ID can be reused.
+    pub(crate) fn span_condition_closed(&self, id: &span::Id) {
+        if self.span_conditions.conditions.is_empty() {
+            return;
+        }
+        let mut spans = self
+            .span_conditions
+            .spans
+            .write()
+            .unwrap_or_else(|e| e.into_inner());
+        spans.remove(id);
+    }
+
+    fn span_condition_metadata(&self, id: &span::Id) -> Option<&'static Metadata<'static>> {
+        if self.span_conditions.conditions.is_empty() {
+            return None;
+        }
+        let spans = self
+            .span_conditions
+            .spans
+            .read()
+            .unwrap_or_else(|e| e.into_inner());
+        spans.get(id).copied()
+    }
+}
+
+/// Returns `true` if the `Module` or `File` directive `span` points at the
+/// span with `metadata`.
+pub(crate) fn points_at(span: &Directive, metadata: &Metadata<'_>) -> bool {
+    let line = match metadata.line() {
+        Some(line) => line,
+        None => return false,
+    };
+    crate::module_keys(metadata).any(|module| Matched::Module(module, line).is(span))
+        || metadata
+            .file()
+            .is_some_and(|file| Matched::File(file, line).is(span))
+}
+
+// === impl SpanConditions ===
+
+impl Default for SpanConditions {
+    fn default() -> Self {
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            conditions: Vec::new(),
+            spans: Default::default(),
+        }
+    }
+}
+
+impl Clone for SpanConditions {
+    fn clone(&self) -> Self {
+        // The spans are the ones the filter has seen since it was attached to
+        // a subscriber, so the clone tracks its own.
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            conditions: self.conditions.clone(),
+            spans: Default::default(),
+        }
+    }
+}
+
+impl fmt::Debug for SpanConditions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(
+                self.conditions
+                    .iter()
+                    .map(|condition| (&condition.entry, &condition.span)),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::prelude::*;
+
+    const SEND: u32 = line!() + 3;
+
+    fn send() -> bool {
+        tracing::event_enabled!(tracing::Level::DEBUG)
+    }
+
+    fn filter(server: &str) -> LineFilter {
+        let net = format!("{}@{}", module_path!(), SEND);
+        let mut filter: LineFilter = server.parse().unwrap();
+        filter
+            .enable_while_entered(net.parse().unwrap(), server.parse().unwrap())
+            .unwrap();
+        filter
+    }
+
+    #[test]
+    fn filters_only_see_spans_they_entered() {
+        let server = format!("{}@{}", module_path!(), line!() + 1);
+        let request = || tracing::info_span!("request");
+
+        let outer = tracing_subscriber::registry().with(filter(&server));
+        let inner = tracing_subscriber::registry().with(filter(&server));
+        tracing::subscriber::with_default(outer, || {
+            request().in_scope(|| {
+                assert!(send());
+                // The other subscriber's span isn't entered in this one.
+                tracing::subscriber::with_default(inner, || assert!(!send()));
+                assert!(send());
+            });
+            assert!(!send());
+        });
+    }
+
+    #[test]
+    fn clones_track_their_own_spans() {
+        let server = format!("{}@{}", module_path!(), line!() + 1);
+        let request = || tracing::info_span!("request");
+
+        let filter = filter(&server);
+        let clone = filter.clone();
+        assert_ne!(filter.span_conditions.id, clone.span_conditions.id);
+        let outer = tracing_subscriber::registry().with(filter);
+        let inner = tracing_subscriber::registry().with(clone);
+        tracing::subscriber::with_default(outer, || {
+            request().in_scope(|| {
+                tracing::subscriber::with_default(inner, || {
+                    assert!(!send());
+                    request().in_scope(|| assert!(send()));
+                    assert!(!send());
+                });
+                assert!(send());
+            });
+        });
+    }
+}
@@ -0,0 +1,301 @@
+use crate::{Directive, LineFilter, ParseError};
+use std::{collections::BTreeMap, env};
+use tracing_core::LevelFilter;
+
+/// The environment variable that [`LineFilter::export_env`] puts a filter's
+/// directives in.
+const DIRECTIVES_VAR: &str = "LINE_FILTER";
+
+/// The environment variable that [`LineFilter::export_env`] puts a filter's
+/// whole config in, when its directives aren't enough.
+const CONFIG_VAR: &str = "LINE_FILTER_CONFIG";
+
+impl LineFilter {
+    /// Returns the `EnvFilter` directives (as used by `RUST_LOG`) that come
+    /// closest to this filter.
+    ///
+    /// This is for passing a filter's intent on to subprocesses and other
+    /// systems that only understand `RUST_LOG`. `EnvFilter` can't enable
+    /// single lines, so each module with `module@line` entries is enabled at
+    /// the `TRACE` level, and `module::**` entries enable their module at the
+    /// `TRACE` level (which, for `EnvFilter`, also enables any module whose
+    /// path starts with the same characters). `module@level` entries are
+    /// kept, and `!module::**` deny entries are turned off unless the same
+    /// module is enabled. `file@line` entries and other deny entries can't be
+    /// expressed, and are left out.
+    ///
+    /// The filter's `EnvFilter` directives come first, followed by the
+    /// downgraded entries sorted by module. If the filter's entries are
+    /// [disabled] or have [expired], only its `EnvFilter` directives are
+    /// returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    ///
+    /// let filter: LineFilter = "error,my_crate::db@142,my_crate::db@200,my_crate::http@debug"
+    ///     .parse()
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     filter.to_env_filter_lossy(),
+    ///     "error,my_crate::db=trace,my_crate::http=debug",
+    /// );
+    ///
+    /// // The result can be used to configure another program.
+    /// let rust_log = filter.to_env_filter_lossy();
+    /// let _child = std::process::Command::new("my-tool").env("RUST_LOG", rust_log);
+    /// ```
+    ///
+    /// [disabled]: Self::disable_all
+    /// [expired]: Self::expire_at
+    pub fn to_env_filter_lossy(&self) -> String {
+        let mut targets = BTreeMap::<String, LevelFilter>::new();
+        if !self.is_disabled() && !self.is_expired() {
+            let mut enable = |target: &str, level| {
+                let max = targets.entry(target.to_owned()).or_insert(LevelFilter::OFF);
+                *max = (*max).max(level);
+            };
+            for (module, _) in &self.by_module {
+                enable(module, LevelFilter::TRACE);
+            }
+            for (module, &level) in &self.mod_levels {
+                enable(module, level);
+            }
+            for prefix in self.mod_prefixes.to_vec() {
+                enable(&prefix, LevelFilter::TRACE);
+            }
+            for directive in self.deny_directives() {
+                if let Directive::Deny(denied) = directive {
+                    if let Directive::ModulePrefix { prefix } = *denied {
+                        targets.entry(prefix).or_insert(LevelFilter::OFF);
+                    }
+                }
+            }
+        }
+
+        let mut directives = Vec::new();
+        if let Some(ref fallback) = self.fallback {
+            let env = fallback.to_string();
+            if !env.is_empty() {
+                directives.push(env);
+            }
+        }
+        directives.extend(
+            targets
+                .into_iter()
+                .map(|(target, level)| format!("{}={}", target, level.to_string().to_lowercase())),
+        );
+        directives.join(",")
+    }
+
+    /// Returns environment variables that pass this filter on to a child
+    /// process, which can rebuild it with [`inherit_from_env`].
+    ///
+    /// Unlike [`to_env_filter_lossy`], which is for programs that only
+    /// understand `RUST_LOG`, this keeps every directive, so that a child
+    /// process that uses a `LineFilter` enables the same lines as its
+    /// parent. The variables are:
+    ///
+    /// - `LINE_FILTER`, with the filter's directives, separated by commas.
+    /// - `LINE_FILTER_CONFIG`, with the filter's whole config, as the `save`
+    ///   [`Command`] writes it, if the filter has span fields, profiles, or
+    ///   [startup entries], which can't be written as a list of directives.
+    /// - `LINE_FILTER_DISABLE`, set to `1`, if the filter's entries are
+    ///   [disabled].
+    ///
+    /// The directives of active profiles are passed on as ordinary
+    /// directives. Other settings, such as when the filter's entries expire,
+    /// aren't passed on.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    ///
+    /// let filter: LineFilter = "warn,my_crate::db@142".parse().unwrap();
+    /// let vars = filter.export_env();
+    /// assert_eq!(vars, [("LINE_FILTER", "my_crate::db@142,warn".to_owned())]);
+    ///
+    /// let _child = std::process::Command::new("my-worker").envs(vars);
+    /// ```
+    ///
+    /// [`inherit_from_env`]: Self::inherit_from_env
+    /// [`to_env_filter_lossy`]: Self::to_env_filter_lossy
+    /// [`Command`]: crate::Command
+    /// [disabled]: Self::disable_all
+    /// [startup entries]: Self::enable_during_startup
+    pub fn export_env(&self) -> Vec<(&'static str, String)> {
+        let mut vars = vec![(DIRECTIVES_VAR, self.to_string())];
+        // The config has more in it than the filter's directives.
+        if self.config_text() != format!("{:#}", self) {
+            vars.push((CONFIG_VAR, self.saved_config()));
+        }
+        if self.is_disabled() {
+            vars.push(("LINE_FILTER_DISABLE", "1".to_owned()));
+        }
+        vars
+    }
+
+    /// Returns a filter built from the environment variables set by a parent
+    /// process's [`export_env`].
+    ///
+    /// The filter is parsed from `LINE_FILTER_CONFIG` if it is set, or
+    /// otherwise from `LINE_FILTER`, as if by [the `FromStr`
+    /// implementation][syntax]. If neither is set, or neither is valid
+    /// Unicode, the filter has no directives. `LINE_FILTER_DISABLE` is
+    /// honoured by every `LineFilter`, so it needs no special handling.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the variable contains an invalid directive.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::prelude::*;
+    ///
+    /// // In a process spawned with the variables from `export_env`:
+    /// # std::env::set_var("LINE_FILTER", "my_crate::db@142,warn");
+    /// let filter = LineFilter::inherit_from_env().unwrap();
+    /// assert_eq!(filter.to_string(), "my_crate::db@142,warn");
+    ///
+    /// tracing_subscriber::registry()
+    ///     .with(tracing_subscriber::fmt::layer())
+    ///     .with(filter)
+    ///     .init();
+    /// ```
+    ///
+    /// [`export_env`]: Self::export_env
+    /// [syntax]: LineFilter#impl-FromStr-for-LineFilter
+    pub fn inherit_from_env() -> Result<Self, ParseError> {
+        match env::var(CONFIG_VAR).or_else(|_| env::var(DIRECTIVES_VAR)) {
+            Ok(config) => config.parse(),
+            Err(_) => Ok(Self::new()),
+        }
+    }
+}
+
+#[cfg(feature = "env-filter")]
+impl LineFilter {
+    /// Returns a filter whose [`EnvFilter`] is made of the `EnvFilter`
+    /// directives in `directives` (such as the value of `RUST_LOG`), along
+    /// with a warning for each directive that was left out.
+    ///
+    /// This is for moving an existing `RUST_LOG` setup into a `LineFilter`,
+    /// which can then have line entries added to it. Unlike parsing the same
+    /// string with [`FromStr`](std::str::FromStr), which fails on the first
+    /// bad directive, the directives that `EnvFilter` doesn't accept are
+    /// skipped with a warning, as are line filter directives (such as
+    /// `my_crate::db@142`), which aren't understood by the other programs
+    /// that read `RUST_LOG`.
+    ///
+    /// This method is only available when the `env-filter` feature flag is
+    /// enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    ///
+    /// let (mut filter, warnings) =
+    ///     LineFilter::from_env_directives("error,my_crate::db=debug,my_crate::http=loud");
+    /// assert_eq!(filter.to_string(), "my_crate::db=debug,error");
+    /// assert_eq!(warnings.len(), 1);
+    /// assert!(warnings[0].starts_with("ignoring `my_crate::http=loud`"));
+    ///
+    /// // Pin some lines on top of the `RUST_LOG` setup.
+    /// filter.enable_by_mod("my_crate::cache", 42);
+    /// ```
+    ///
+    /// [`EnvFilter`]: tracing_subscriber::EnvFilter
+    pub fn from_env_directives(directives: &str) -> (Self, Vec<String>) {
+        let mut filter = Self::new();
+        let mut warnings = Vec::new();
+        let directives = directives
+            .split(',')
+            .map(str::trim)
+            .filter(|directive| !directive.is_empty());
+        for text in directives {
+            match text.parse::<Directive>() {
+                Ok(directive @ Directive::Env(_)) => {
+                    filter
+                        .add_directive(directive)
+                        .expect("`EnvFilter` directives are always valid");
+                }
+                Ok(_) => warnings.push(format!(
+                    "ignoring `{}`: line filter directives can't be used in `RUST_LOG`",
+                    text
+                )),
+                Err(e) => warnings.push(format!("ignoring `{}`: {}", text, e)),
+            }
+        }
+        (filter, warnings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Returns the filter that a child process would inherit from `filter`,
+    /// without changing this process's environment.
+    fn inherit(filter: &LineFilter) -> LineFilter {
+        let vars = filter.export_env().into_iter().collect::<BTreeMap<_, _>>();
+        vars.get(CONFIG_VAR)
+            .or_else(|| vars.get(DIRECTIVES_VAR))
+            .unwrap()
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn directives_are_exported_on_their_own() {
+        let filter = "warn,my_crate::db@142,my_crate::http::**"
+            .parse::<LineFilter>()
+            .unwrap();
+        let vars = filter.export_env();
+        assert_eq!(vars.len(), 1);
+        assert_eq!(inherit(&filter).to_string(), filter.to_string());
+    }
+
+    #[test]
+    fn the_whole_config_round_trips() {
+        let mut filter = "warn,my_crate::db@142,!my_crate::db@150"
+            .parse::<LineFilter>()
+            .unwrap();
+        let boot = "my_crate::init@debug".parse().unwrap();
+        filter
+            .enable_during_startup(vec![boot], Duration::from_secs(30))
+            .unwrap();
+        filter.add_span_field("pinned_by", "oncall");
+        filter
+            .define_profile("slow", vec!["my_crate::db@200".parse().unwrap()])
+            .unwrap();
+        filter.activate_profile("slow").unwrap();
+
+        let vars = filter.export_env();
+        assert!(vars.iter().any(|&(name, _)| name == CONFIG_VAR));
+        let inherited = inherit(&filter);
+        assert_eq!(inherited.saved_config(), filter.saved_config());
+        assert!(inherited.is_starting_up());
+        // Active profiles' directives are passed on as ordinary directives.
+        assert!(inherited.to_string().contains("my_crate::db@200"));
+        assert!(inherited
+            .profiles()
+            .any(|profile| profile == ("slow", false)));
+    }
+
+    #[test]
+    fn startup_entries_are_exported_with_the_config() {
+        let mut filter = LineFilter::default();
+        let boot = "my_crate::init@debug".parse().unwrap();
+        filter.enable_during_startup(vec![boot], None).unwrap();
+
+        // The startup entries aren't in the directives.
+        assert_eq!(filter.to_string(), "");
+        assert_eq!(inherit(&filter).saved_config(), filter.saved_config());
+    }
+}
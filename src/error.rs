@@ -0,0 +1,97 @@
+use crate::{BadModule, BadPath, ParseError};
+use std::{error, fmt, io};
+use tracing_subscriber::reload;
+
+/// An error returned by a [`LineFilter`] method.
+///
+/// Each variant wraps the error for one kind of failure, so that callers can
+/// tell them apart by matching on this type. More variants may be added as
+/// the filter's API grows, so matches on it must include a wildcard arm.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_line_filter::{Error, LineFilter};
+///
+/// let mut filter = LineFilter::default();
+/// match filter.enable_by_file("/home/me/my_crate/src/lib.txt", 42) {
+///     Err(Error::Path(e)) => println!("bad path: {}", e),
+///     Err(e) => println!("something else went wrong: {}", e),
+///     Ok(_) => unreachable!("only Rust source files can be enabled"),
+/// }
+/// ```
+///
+/// [`LineFilter`]: crate::LineFilter
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// A file path was not valid for use in a `LineFilter`.
+    Path(BadPath),
+    /// A module path was not a valid Rust module path.
+    Module(BadModule),
+    /// A directive could not be parsed.
+    Parse(ParseError),
+    /// A filter's config could not be read or written.
+    Io(io::Error),
+    /// A filter behind a [`reload`] handle could not be changed, because the
+    /// subscriber it belongs to was dropped.
+    ///
+    /// [`reload`]: tracing_subscriber::reload
+    Reload(reload::Error),
+}
+
+// === impl Error ===
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Path(e) => fmt::Display::fmt(e, f),
+            Error::Module(e) => fmt::Display::fmt(e, f),
+            Error::Parse(e) => fmt::Display::fmt(e, f),
+            Error::Io(e) => write!(f, "failed to access the filter's config: {}", e),
+            Error::Reload(e) => write!(f, "failed to change the filter: {}", e),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Path(e) => Some(e),
+            Error::Module(e) => Some(e),
+            Error::Parse(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::Reload(e) => Some(e),
+        }
+    }
+}
+
+impl From<BadPath> for Error {
+    fn from(error: BadPath) -> Self {
+        Error::Path(error)
+    }
+}
+
+impl From<BadModule> for Error {
+    fn from(error: BadModule) -> Self {
+        Error::Module(error)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Self {
+        Error::Parse(error)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<reload::Error> for Error {
+    fn from(error: reload::Error) -> Self {
+        Error::Reload(error)
+    }
+}
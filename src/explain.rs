@@ -0,0 +1,244 @@
+use crate::{deny::Matched, time::Instant, Directive, LineFilter, Precedence};
+use std::borrow::Cow;
+use std::fmt;
+use tracing_core::Metadata;
+
+/// Describes why a [`LineFilter`] enables or disables a callsite, returned by
+/// [`LineFilter::explain`].
+///
+/// The `Display` implementation formats a short, human-readable summary,
+/// such as ``enabled by `my_crate::db@142` ``.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Explanation {
+    entry: Option<Directive>,
+    deny: Option<Directive>,
+    /// Whether `entry` wins over `deny`, if both match.
+    entry_wins: bool,
+    precedence: Precedence,
+    expired: bool,
+    disabled: bool,
+    /// What the filter's fallback is called, such as "EnvFilter".
+    fallback: &'static str,
+    env: Option<EnvDecision>,
+}
+
+/// What a [`LineFilter`]'s [`EnvFilter`] decided about a callsite, as
+/// reported by [`Explanation::env`].
+///
+/// [`EnvFilter`]: tracing_subscriber::EnvFilter
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EnvDecision {
+    /// The `EnvFilter` always enables the callsite.
+    Enabled,
+    /// The `EnvFilter` never enables the callsite.
+    Disabled,
+    /// Whether the `EnvFilter` enables the callsite depends on the span it
+    /// is in, or on the values of its fields, so it is decided each time the
+    /// callsite is reached.
+    Dynamic,
+}
+
+impl LineFilter {
+    /// Explains why this filter enables or disables the callsite described
+    /// by `metadata`.
+    ///
+    /// The explanation includes the most specific `module@line`,
+    /// `file@line`, `module@level`, or `module::**` entry that matches the
+    /// callsite (if there is one), the most specific deny entry that matches
+    /// it, which of the two won, whether the filter's entries
+    /// have expired, and what the filter's [`EnvFilter`] decides about the
+    /// callsite. The `EnvFilter` is consulted even when an entry matches, so
+    /// that entries which the `EnvFilter` already enables can be found.
+    ///
+    /// This is intended for tests, and for tools that debug a filter's
+    /// configuration, such as the `explain` [`Command`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::{EnvDecision, LineFilter};
+    /// use tracing_subscriber::{prelude::*, reload, EnvFilter};
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter
+    ///     .enable_by_mod(module_path!(), line!() + 6)
+    ///     .with_env_filter(EnvFilter::new("warn"));
+    /// let (filter, handle) = reload::Layer::new(filter);
+    /// let subscriber = tracing_subscriber::registry().with(filter);
+    ///
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let span = tracing::info_span!("pinned");
+    ///     let metadata = span.metadata().unwrap();
+    ///
+    ///     let explanation = handle.with_current(|filter| filter.explain(metadata)).unwrap();
+    ///     assert!(explanation.is_enabled());
+    ///     assert_eq!(explanation.env(), Some(EnvDecision::Disabled));
+    ///     assert_eq!(
+    ///         explanation.to_string(),
+    ///         format!("enabled by `{}@{}`", module_path!(), metadata.line().unwrap()),
+    ///     );
+    /// });
+    /// ```
+    ///
+    /// [`EnvFilter`]: tracing_subscriber::EnvFilter
+    /// [`Command`]: crate::Command
+    pub fn explain(&self, metadata: &'static Metadata<'static>) -> Explanation {
+        let allow = self.allow_match(metadata);
+        let deny = self.deny_match(metadata);
+        let entry_wins = self
+            .resolve(
+                allow.as_ref().map(|(specificity, _)| *specificity),
+                deny.as_ref().map(|(specificity, _)| *specificity),
+            )
+            .unwrap_or(false);
+        let entry = allow.map(|(_, entry)| match entry {
+            // Include the entry's line hash, if it has one.
+            Matched::Module(module, line) => {
+                self.module_directive(&(Cow::Owned(module.to_owned()), line))
+            }
+            Matched::File(file, line) => self.file_directive(&(Cow::Owned(file.to_owned()), line)),
+            entry => entry.to_directive(),
+        });
+        let deny = deny.map(|(_, entry)| Directive::Deny(Box::new(entry.to_directive())));
+        let expired = self
+            .expires
+            .is_some_and(|deadline| Instant::now() >= deadline);
+        let env = self.fallback.as_ref().map(|fallback| {
+            let interest = fallback.register_callsite(metadata);
+            if interest.is_always() {
+                EnvDecision::Enabled
+            } else if interest.is_never() {
+                EnvDecision::Disabled
+            } else {
+                EnvDecision::Dynamic
+            }
+        });
+        Explanation {
+            entry,
+            deny,
+            entry_wins,
+            precedence: self.precedence,
+            expired,
+            disabled: self.is_disabled(),
+            env,
+            fallback: self.fallback.as_ref().map_or("EnvFilter", |f| f.name()),
+        }
+    }
+}
+
+// === impl Explanation ===
+
+impl Explanation {
+    /// Returns `true` if the callsite is always enabled, either by an entry
+    /// or by the filter's `EnvFilter`.
+    ///
+    /// This returns `false` if the callsite is only enabled in some spans or
+    /// with some field values; see [`env`](Self::env).
+    pub fn is_enabled(&self) -> bool {
+        if !self.expired && !self.disabled {
+            if self.deny.is_some() {
+                return self.entry_wins;
+            }
+            if self.entry.is_some() {
+                return true;
+            }
+        }
+        self.env == Some(EnvDecision::Enabled)
+    }
+
+    /// Returns the `Module`, `File`, `ModuleLevel`, or `ModulePrefix`
+    /// directive for the entry that matches the callsite, if there is one.
+    ///
+    /// An entry is returned even if the filter's entries have expired; see
+    /// [`is_expired`](Self::is_expired).
+    pub fn entry(&self) -> Option<&Directive> {
+        self.entry.as_ref()
+    }
+
+    /// Returns the `Deny` directive for the deny entry that matches the
+    /// callsite, if there is one.
+    ///
+    /// If both an entry and a deny entry match, the filter's [`Precedence`]
+    /// decides which one wins.
+    ///
+    /// [`Precedence`]: crate::Precedence
+    pub fn deny(&self) -> Option<&Directive> {
+        self.deny.as_ref()
+    }
+
+    /// Returns `true` if the filter's entries have expired, so that only
+    /// its `EnvFilter` is consulted.
+    pub fn is_expired(&self) -> bool {
+        self.expired
+    }
+
+    /// Returns `true` if the filter's entries are disabled (see
+    /// [`LineFilter::disable_all`]), so that only its `EnvFilter` is
+    /// consulted.
+    ///
+    /// [`LineFilter::disable_all`]: crate::LineFilter::disable_all
+    pub fn is_disabled(&self) -> bool {
+        self.disabled
+    }
+
+    /// Returns what the filter's `EnvFilter` (or the function passed to
+    /// [`with_fallback`]) decided about the callsite, or `None` if the filter
+    /// doesn't have either.
+    ///
+    /// [`with_fallback`]: LineFilter::with_fallback
+    pub fn env(&self) -> Option<EnvDecision> {
+        self.env
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.expired && !self.disabled {
+            match (&self.entry, &self.deny) {
+                (Some(entry), None) => return write!(f, "enabled by `{}`", entry),
+                (None, Some(deny)) => return write!(f, "disabled by `{}`", deny),
+                (Some(entry), Some(deny)) if self.entry_wins => {
+                    return write!(
+                        f,
+                        "enabled by `{}`, which is more specific than `{}`",
+                        entry, deny
+                    )
+                }
+                (Some(entry), Some(deny)) => {
+                    return match self.precedence {
+                        Precedence::MostSpecific => write!(
+                            f,
+                            "disabled by `{}`, which is at least as specific as `{}`",
+                            deny, entry
+                        ),
+                        Precedence::DenyWins => write!(
+                            f,
+                            "disabled by `{}`, since deny entries take precedence over `{}`",
+                            deny, entry
+                        ),
+                    }
+                }
+                (None, None) => {}
+            }
+        } else if let Some(entry) = self.entry.as_ref().or(self.deny.as_ref()) {
+            if self.disabled {
+                write!(f, "`{}` is disabled; ", entry)?;
+            } else {
+                write!(f, "`{}` has expired; ", entry)?;
+            }
+        }
+        let fallback = self.fallback;
+        match self.env {
+            Some(EnvDecision::Enabled) => write!(f, "enabled by the {}", fallback),
+            Some(EnvDecision::Disabled) => write!(f, "disabled by the {}", fallback),
+            Some(EnvDecision::Dynamic) => write!(
+                f,
+                "enabled by the {} in some spans, or with some field values",
+                fallback
+            ),
+            None if self.entry.is_some() || self.deny.is_some() => f.write_str("disabled"),
+            None => f.write_str("disabled: no entry matches, and there is no EnvFilter"),
+        }
+    }
+}
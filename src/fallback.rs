@@ -0,0 +1,109 @@
+use crate::LineFilter;
+use std::{fmt, sync::Arc};
+use tracing_core::{subscriber::Interest, Metadata, Subscriber};
+use tracing_subscriber::layer;
+#[cfg(feature = "env-filter")]
+use tracing_subscriber::{EnvFilter, Layer, Registry};
+
+/// Decides about the spans and events that none of a filter's entries
+/// match.
+pub(crate) enum Fallback {
+    #[cfg(feature = "env-filter")]
+    Env(Box<EnvFilter>),
+    Fn(Box<dyn Fn(&Metadata<'_>) -> bool + Send + Sync>),
+}
+
+impl LineFilter {
+    /// Composes `self` with a function that decides whether to enable the
+    /// spans and events that aren't matched by any of the filter's entries.
+    ///
+    /// This replaces the filter's [`EnvFilter`], if it has one, and is the
+    /// way to enable spans and events outside of the filter's entries when
+    /// the `env-filter` feature flag (which is enabled by default) is
+    /// disabled. `Env` directives added to the filter later replace the
+    /// function with an `EnvFilter`.
+    ///
+    /// Unlike an `EnvFilter`, the function is only called once for each
+    /// callsite, when the callsite is registered, so it can't filter spans
+    /// and events by the span they are in or by their field values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter
+    ///     .enable_by_mod("my_crate::db", 142)
+    ///     // Also enable all warnings, wherever they are.
+    ///     .with_fallback(|metadata| *metadata.level() <= tracing::Level::WARN);
+    /// ```
+    ///
+    /// [`EnvFilter`]: https://docs.rs/tracing-subscriber/latest/tracing_subscriber/filter/struct.EnvFilter.html
+    pub fn with_fallback(
+        &mut self,
+        fallback: impl Fn(&Metadata<'_>) -> bool + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.fallback = Some(Arc::new(Fallback::Fn(Box::new(fallback))));
+        self
+    }
+}
+
+// === impl Fallback ===
+
+impl Fallback {
+    /// Returns what the fallback is called in explanations, such as
+    /// "enabled by the EnvFilter".
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "env-filter")]
+            Fallback::Env(_) => "EnvFilter",
+            Fallback::Fn(_) => "fallback",
+        }
+    }
+
+    pub(crate) fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        match self {
+            #[cfg(feature = "env-filter")]
+            Fallback::Env(env) => Layer::<Registry>::register_callsite(env, metadata),
+            Fallback::Fn(f) if f(metadata) => Interest::always(),
+            Fallback::Fn(_) => Interest::never(),
+        }
+    }
+
+    pub(crate) fn enabled<S>(&self, metadata: &Metadata<'_>, cx: layer::Context<'_, S>) -> bool
+    where
+        S: Subscriber,
+    {
+        #[cfg(not(feature = "env-filter"))]
+        let _ = cx;
+        match self {
+            #[cfg(feature = "env-filter")]
+            Fallback::Env(env) => Layer::<S>::enabled(env, metadata, cx),
+            Fallback::Fn(f) => f(metadata),
+        }
+    }
+}
+
+/// Formats an `EnvFilter`'s directives, or nothing for a function.
+impl fmt::Display for Fallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        #[cfg(not(feature = "env-filter"))]
+        let _ = f;
+        match self {
+            #[cfg(feature = "env-filter")]
+            Fallback::Env(env) => fmt::Display::fmt(env, f),
+            Fallback::Fn(_) => Ok(()),
+        }
+    }
+}
+
+impl fmt::Debug for Fallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(feature = "env-filter")]
+            Fallback::Env(env) => f.debug_tuple("Env").field(env).finish(),
+            Fallback::Fn(_) => f.debug_tuple("Fn").finish_non_exhaustive(),
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use crate::LineFilter;
+use std::fmt::Write;
+use tracing_core::span;
+use tracing_subscriber::{
+    fmt::{format::DefaultFields, FormattedFields},
+    registry::LookupSpan,
+    Registry,
+};
+
+/// The extra fields attached to a span that one of a [`LineFilter`]'s
+/// entries matched, when the filter has [span fields].
+///
+/// `tracing` has no way to add fields to a span after its callsite is
+/// defined, so the fields are added to the span's [extensions], where other
+/// layers can find them. They are also appended to the span's fields as
+/// formatted by an [`fmt`] layer with the default field formatter, as long
+/// as the `fmt` layer comes before the filter in the subscriber, and added
+/// as attributes by the `otel` module's `PinnedSampler` (when the `otel`
+/// feature flag is enabled).
+///
+/// [span fields]: LineFilter::add_span_field
+/// [extensions]: tracing_subscriber::registry::Extensions
+/// [`fmt`]: tracing_subscriber::fmt
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpanFields {
+    fields: Vec<(String, String)>,
+}
+
+impl LineFilter {
+    /// Adds a field that is attached to every span that one of this filter's
+    /// entries matches, such as `debug_session = "ticket-1234"`.
+    ///
+    /// This makes it clear which spans in a program's output or exported
+    /// traces were only recorded because of a deliberate debugging session.
+    /// Fields are attached when spans are created, as a [`SpanFields`]
+    /// extension. Spans that are only enabled by the filter's
+    /// [`EnvFilter`] don't get them.
+    ///
+    /// Adding a field with the same name as an existing field replaces its
+    /// value. Span fields can also be set by a `fields = { ... }` line in a
+    /// config (see [the `FromStr` implementation for `LineFilter`][syntax]).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::prelude::*;
+    ///
+    /// let mut filter: LineFilter = "my_crate::db@142".parse().unwrap();
+    /// filter.add_span_field("debug_session", "ticket-1234");
+    ///
+    /// tracing_subscriber::registry()
+    ///     .with(tracing_subscriber::fmt::layer())
+    ///     .with(filter)
+    ///     .init();
+    ///
+    /// // The span on line 142 is now formatted like:
+    /// // my_span{id=1 debug_session="ticket-1234"}
+    /// ```
+    ///
+    /// [`EnvFilter`]: tracing_subscriber::EnvFilter
+    /// [syntax]: crate::LineFilter#impl-FromStr-for-LineFilter
+    pub fn add_span_field(
+        &mut self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> &mut Self {
+        set(&mut self.span_fields, name.into(), value.into());
+        self
+    }
+
+    /// Removes all of the fields added by [`add_span_field`].
+    ///
+    /// [`add_span_field`]: Self::add_span_field
+    pub fn clear_span_fields(&mut self) -> &mut Self {
+        self.span_fields.clear();
+        self
+    }
+
+    /// Returns the names and values of the fields attached to spans that
+    /// this filter's entries match, in the order they were added.
+    pub fn span_fields(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+        self.span_fields
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Formats this filter's span fields as a `fields = { ... }` line, to
+    /// follow the filter's own directives in a config file.
+    pub(crate) fn span_fields_line(&self) -> String {
+        if self.span_fields.is_empty() {
+            return String::new();
+        }
+        format!("\n{}", fmt_line(&self.span_fields))
+    }
+
+    /// Attaches this filter's span fields to the span with `attrs`, if one of
+    /// the filter's entries matches it.
+    pub(crate) fn attach_span_fields(&self, attrs: &span::Attributes<'_>, id: &span::Id) {
+        if self.span_fields.is_empty() || self.decide(attrs.metadata()) != Some(true) {
+            return;
+        }
+        tracing::dispatcher::get_default(|dispatch| {
+            let span = dispatch
+                .downcast_ref::<Registry>()
+                .and_then(|registry| registry.span(id));
+            let span = match span {
+                Some(span) => span,
+                None => return,
+            };
+            let mut extensions = span.extensions_mut();
+            if let Some(formatted) = extensions.get_mut::<FormattedFields<DefaultFields>>() {
+                for (name, value) in &self.span_fields {
+                    if !formatted.fields.is_empty() {
+                        formatted.fields.push(' ');
+                    }
+                    let _ = write!(formatted.fields, "{}={:?}", name, value);
+                }
+            }
+            extensions.replace(SpanFields {
+                fields: self.span_fields.clone(),
+            });
+        });
+    }
+}
+
+// === impl SpanFields ===
+
+impl SpanFields {
+    /// Returns the names and values of the fields.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+        self.fields
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+}
+
+/// Sets the field `name` in `fields` to `value`, replacing its old value.
+pub(crate) fn set(fields: &mut Vec<(String, String)>, name: String, value: String) {
+    match fields.iter_mut().find(|(existing, _)| *existing == name) {
+        Some((_, old)) => *old = value,
+        None => fields.push((name, value)),
+    }
+}
+
+/// Parses a `fields = { name = "value", ... }` line, returning `None` if
+/// `line` isn't one.
+pub(crate) fn parse_line(line: &str) -> Option<Result<Vec<(String, String)>, &'static str>> {
+    let rest = line.trim().strip_prefix("fields")?.trim_start();
+    let rest = rest.strip_prefix('=')?.trim_start();
+    if !rest.starts_with('{') {
+        return None;
+    }
+    Some(parse_table(rest))
+}
+
+fn parse_table(table: &str) -> Result<Vec<(String, String)>, &'static str> {
+    let table = table
+        .trim_end()
+        .strip_prefix('{')
+        .and_then(|table| table.strip_suffix('}'))
+        .ok_or("expected `fields = { ... }`")?;
+    let mut fields = Vec::new();
+    for pair in table
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+    {
+        let (name, value) = pair
+            .split_once('=')
+            .ok_or("expected `name = \"value\"` in `fields`")?;
+        let name = name.trim();
+        let valid = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '.';
+        if name.is_empty() || !name.chars().all(valid) {
+            return Err("field names may only contain letters, digits, `_`, and `.`");
+        }
+        let value = value
+            .trim()
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .filter(|value| !value.contains('"'))
+            .ok_or("`fields` values must be quoted strings")?;
+        set(&mut fields, name.to_owned(), value.to_owned());
+    }
+    Ok(fields)
+}
+
+/// Formats `fields` as a `fields = { ... }` line.
+pub(crate) fn fmt_line(fields: &[(String, String)]) -> String {
+    let pairs = fields
+        .iter()
+        .map(|(name, value)| format!("{} = \"{}\"", name, value))
+        .collect::<Vec<_>>();
+    format!("fields = {{ {} }}", pairs.join(", "))
+}
@@ -0,0 +1,365 @@
+use crate::prefix::PrefixTrie;
+use crate::time::Instant;
+use std::{
+    any::TypeId,
+    borrow::Cow,
+    cell::Cell,
+    collections::{HashSet, VecDeque},
+    fmt::{self, Write},
+    sync::{Mutex, PoisonError},
+};
+use tracing_core::{
+    callsite::Identifier,
+    field::{Field, Visit},
+    span,
+    subscriber::Interest,
+    Event, Level, LevelFilter, Metadata, Subscriber,
+};
+use tracing_subscriber::layer::{self, Layer};
+
+/// The target of the events replayed by a [`FlightRecorder`], which it
+/// always enables.
+pub(crate) const TARGET: &str = "tracing_line_filter::flight";
+
+thread_local! {
+    /// The callsite of the event that `enabled` most recently let through
+    /// only so that `event_enabled` can record it.
+    static CAPTURING: Cell<Option<Identifier>> = const { Cell::new(None) };
+}
+
+/// A layer that records recent events that its filter disabled, and replays
+/// them when a trigger line fires.
+///
+/// Rare failures are often hard to diagnose from a program's usual logs,
+/// but enabling verbose logging everywhere to catch them is too expensive.
+/// A `FlightRecorder` wraps a filter (usually a [`LineFilter`], or a
+/// [`reload`] layer containing one), and keeps the most recent events from
+/// a few modules that the filter disabled in a bounded buffer. When an event
+/// on one of its *trigger* lines fires, it replays the buffered events to
+/// the subscriber, so that the trigger is logged along with the verbose
+/// context that led up to it.
+///
+/// Replayed events have the `tracing_line_filter::flight` target, which the
+/// `FlightRecorder` always enables, and their original level. Each has the
+/// following fields:
+///
+/// * `message`: the original event's fields, formatted as `message k=v`.
+/// * `origin.target` and `origin.location`: the original event's target and
+///   `file:line`.
+/// * `age_ms`: how long before the trigger the original event was recorded.
+/// * `trigger`: the trigger line, such as `my_crate::db@142`.
+///
+/// Trigger lines are enabled whether or not the wrapped filter enables
+/// them. Events of recorded modules are checked by the wrapped filter as
+/// usual, and are only buffered if it disables them, so the events on
+/// pinned lines are still logged as they happen. Spans aren't recorded.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_line_filter::{FlightRecorder, LineFilter};
+/// use tracing_subscriber::{prelude::*, reload};
+///
+/// let filter: LineFilter = "warn".parse().unwrap();
+/// let (filter, handle) = reload::Layer::new(filter);
+///
+/// // Keep the last 256 disabled events from `my_crate::db` and its
+/// // submodules, and replay them when the event on line 142 fires.
+/// let mut recorder = FlightRecorder::new(filter, 256);
+/// recorder
+///     .record_mod("my_crate::db")
+///     .trigger_by_mod("my_crate::db", 142);
+///
+/// tracing_subscriber::registry()
+///     .with(tracing_subscriber::fmt::layer())
+///     .with(recorder)
+///     .init();
+/// ```
+///
+/// [`LineFilter`]: crate::LineFilter
+/// [`reload`]: tracing_subscriber::reload
+pub struct FlightRecorder<L> {
+    inner: L,
+    capacity: usize,
+    modules: PrefixTrie,
+    triggers: HashSet<(Cow<'static, str>, u32)>,
+    buffer: Mutex<VecDeque<Record>>,
+}
+
+/// A disabled event, recorded by a [`FlightRecorder`].
+struct Record {
+    metadata: &'static Metadata<'static>,
+    fields: String,
+    at: Instant,
+}
+
+/// Formats an event's fields as `message k=v`.
+struct FieldVisitor<'a>(&'a mut String);
+
+impl<L> FlightRecorder<L> {
+    /// Returns a new `FlightRecorder` that wraps `filter`, and keeps up to
+    /// `capacity` of the most recent events that it disables.
+    ///
+    /// By default, no modules are recorded and there are no trigger lines.
+    pub fn new(filter: L, capacity: usize) -> Self {
+        Self {
+            inner: filter,
+            capacity,
+            modules: PrefixTrie::default(),
+            triggers: HashSet::new(),
+            buffer: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Records the disabled events in the Rust module `module` and all of
+    /// its submodules.
+    ///
+    /// As with [`LineFilter::enable_by_mod_prefix`], this matches whole path
+    /// segments: recording `my_crate::db` records `my_crate::db` and
+    /// `my_crate::db::pool`, but not `my_crate::dbx`.
+    ///
+    /// [`LineFilter::enable_by_mod_prefix`]: crate::LineFilter::enable_by_mod_prefix
+    pub fn record_mod(&mut self, module: &str) -> &mut Self {
+        self.modules.insert(module);
+        self
+    }
+
+    /// Replays the recorded events whenever the event in the Rust module
+    /// `module` on line `line` fires.
+    ///
+    /// Module paths follow the same rules as
+    /// [`LineFilter::enable_by_mod`](crate::LineFilter::enable_by_mod). The
+    /// buffer is emptied each time it is replayed, so an event is only
+    /// replayed once.
+    pub fn trigger_by_mod(&mut self, module: impl Into<Cow<'static, str>>, line: u32) -> &mut Self {
+        self.triggers.insert((module.into(), line));
+        self
+    }
+
+    /// Returns a reference to the wrapped filter.
+    pub fn get_ref(&self) -> &L {
+        &self.inner
+    }
+
+    fn is_trigger(&self, metadata: &Metadata<'_>) -> bool {
+        match metadata.line() {
+            Some(line) => crate::module_keys(metadata)
+                .any(|module| self.triggers.contains(&(Cow::Borrowed(module), line))),
+            None => false,
+        }
+    }
+
+    fn is_recorded(&self, metadata: &Metadata<'_>) -> bool {
+        if self.capacity == 0 || !metadata.is_event() {
+            return false;
+        }
+        crate::module_keys(metadata).any(|module| self.modules.longest_match(module).is_some())
+    }
+
+    fn record(&self, event: &Event<'_>) {
+        let mut fields = String::new();
+        event.record(&mut FieldVisitor(&mut fields));
+        let record = Record {
+            metadata: event.metadata(),
+            fields,
+            at: Instant::now(),
+        };
+        let mut buffer = self.buffer.lock().unwrap_or_else(PoisonError::into_inner);
+        if buffer.len() == self.capacity {
+            buffer.pop_front();
+        }
+        buffer.push_back(record);
+    }
+
+    /// Replays the recorded events, triggered by the event with `trigger`'s
+    /// metadata.
+    fn replay(&self, trigger: &Metadata<'_>) {
+        // Take the records before replaying them, so that the lock isn't
+        // held while the replayed events are recorded.
+        let records =
+            std::mem::take(&mut *self.buffer.lock().unwrap_or_else(PoisonError::into_inner));
+        if records.is_empty() {
+            return;
+        }
+        let trigger = format!(
+            "{}@{}",
+            crate::module_key(trigger),
+            trigger.line().unwrap_or_default(),
+        );
+        let now = Instant::now();
+        for record in records {
+            let metadata = record.metadata;
+            let location = format!(
+                "{}:{}",
+                metadata.file().unwrap_or("<unknown>"),
+                metadata.line().unwrap_or_default()
+            );
+            let age_ms = now.duration_since(record.at).as_millis() as u64;
+            macro_rules! replay {
+                ($level:expr) => {
+                    tracing::event!(
+                        target: TARGET,
+                        $level,
+                        origin.target = metadata.target(),
+                        origin.location = %location,
+                        age_ms,
+                        trigger = %trigger,
+                        "{}",
+                        record.fields,
+                    )
+                };
+            }
+            match *metadata.level() {
+                Level::ERROR => replay!(Level::ERROR),
+                Level::WARN => replay!(Level::WARN),
+                Level::INFO => replay!(Level::INFO),
+                Level::DEBUG => replay!(Level::DEBUG),
+                Level::TRACE => replay!(Level::TRACE),
+            }
+        }
+    }
+}
+
+impl<S, L> Layer<S> for FlightRecorder<L>
+where
+    S: Subscriber,
+    L: Layer<S>,
+{
+    fn on_register_dispatch(&self, subscriber: &tracing_core::Dispatch) {
+        self.inner.on_register_dispatch(subscriber);
+    }
+
+    fn on_layer(&mut self, subscriber: &mut S) {
+        self.inner.on_layer(subscriber);
+    }
+
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        // The wrapped filter is always told about the callsite, even if its
+        // answer is overridden, so that it can record it.
+        let interest = self.inner.register_callsite(metadata);
+        if metadata.target() == TARGET || self.is_trigger(metadata) {
+            return Interest::always();
+        }
+        if self.is_recorded(metadata) && !interest.is_always() {
+            return Interest::sometimes();
+        }
+        interest
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, cx: layer::Context<'_, S>) -> bool {
+        if metadata.target() == TARGET || self.is_trigger(metadata) {
+            return true;
+        }
+        if self.inner.enabled(metadata, cx) {
+            return true;
+        }
+        if self.is_recorded(metadata) {
+            // Let the event through to `event_enabled`, which records it
+            // and disables it once its fields are available.
+            CAPTURING.with(|capturing| capturing.set(Some(metadata.callsite())));
+            return true;
+        }
+        false
+    }
+
+    fn event_enabled(&self, event: &Event<'_>, cx: layer::Context<'_, S>) -> bool {
+        let metadata = event.metadata();
+        // If another event was checked while this event's fields were being
+        // evaluated, this event is treated as enabled by the wrapped filter.
+        let capturing = CAPTURING.with(|capturing| {
+            capturing.take() == Some(metadata.callsite()) && !self.is_trigger(metadata)
+        });
+        if capturing {
+            self.record(event);
+            return false;
+        }
+        self.is_trigger(metadata) || self.inner.event_enabled(event, cx)
+    }
+
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, cx: layer::Context<'_, S>) {
+        self.inner.on_new_span(attrs, id, cx);
+    }
+
+    fn on_record(&self, span: &span::Id, values: &span::Record<'_>, cx: layer::Context<'_, S>) {
+        self.inner.on_record(span, values, cx);
+    }
+
+    fn on_follows_from(&self, span: &span::Id, follows: &span::Id, cx: layer::Context<'_, S>) {
+        self.inner.on_follows_from(span, follows, cx);
+    }
+
+    fn on_event(&self, event: &Event<'_>, cx: layer::Context<'_, S>) {
+        self.inner.on_event(event, cx);
+        if self.is_trigger(event.metadata()) {
+            self.replay(event.metadata());
+        }
+    }
+
+    fn on_enter(&self, id: &span::Id, cx: layer::Context<'_, S>) {
+        self.inner.on_enter(id, cx);
+    }
+
+    fn on_exit(&self, id: &span::Id, cx: layer::Context<'_, S>) {
+        self.inner.on_exit(id, cx);
+    }
+
+    fn on_close(&self, id: span::Id, cx: layer::Context<'_, S>) {
+        self.inner.on_close(id, cx);
+    }
+
+    fn on_id_change(&self, old: &span::Id, new: &span::Id, cx: layer::Context<'_, S>) {
+        self.inner.on_id_change(old, new, cx);
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        let hint = self.inner.max_level_hint();
+        // Recorded events may be more verbose than anything the wrapped
+        // filter enables.
+        if self.capacity > 0 {
+            return None;
+        }
+        hint
+    }
+
+    #[doc(hidden)]
+    unsafe fn downcast_raw(&self, id: TypeId) -> Option<*const ()> {
+        if id == TypeId::of::<Self>() {
+            return Some(self as *const Self as *const ());
+        }
+        self.inner.downcast_raw(id)
+    }
+}
+
+impl<L: fmt::Debug> fmt::Debug for FlightRecorder<L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FlightRecorder")
+            .field("inner", &self.inner)
+            .field("capacity", &self.capacity)
+            .field("modules", &self.modules.to_vec())
+            .field("triggers", &self.triggers)
+            .finish_non_exhaustive()
+    }
+}
+
+// === impl FieldVisitor ===
+
+impl Visit for FieldVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+        let _ = if field.name() == "message" {
+            write!(self.0, "{:?}", value)
+        } else {
+            write!(self.0, "{}={:?}", field.name(), value)
+        };
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.record_debug(field, &format_args!("{}", value))
+        } else {
+            self.record_debug(field, &value)
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use crate::{command, version, LineFilter};
+use std::{fmt, fs, path::PathBuf};
+use tracing_core::Subscriber;
+use tracing_subscriber::reload;
+
+/// The target of the summary events recorded by [`FlushGuard`], which are
+/// always enabled by a `LineFilter`.
+pub(crate) const TARGET: &str = "tracing_line_filter::stats";
+
+/// A guard that reports a [`LineFilter`]'s hit statistics when it is
+/// dropped.
+///
+/// Like [`tracing-appender`]'s `WorkerGuard`, this should be held in `main`
+/// for as long as the program runs, so that short-lived programs (such as
+/// command-line tools) still report which of their pinned lines actually
+/// fired. When the guard is dropped, it either records an `INFO` event
+/// summarizing the filter's [`stats`], or writes them to a file.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_line_filter::{FlushGuard, LineFilter};
+/// use tracing_subscriber::{prelude::*, reload};
+///
+/// let filter: LineFilter = "my_crate::db@142".parse().unwrap();
+/// let (filter, handle) = reload::Layer::new(filter);
+/// tracing_subscriber::registry()
+///     .with(tracing_subscriber::fmt::layer())
+///     .with(filter)
+///     .init();
+///
+/// let _guard = FlushGuard::new(handle);
+///
+/// // When `_guard` is dropped at the end of `main`, it records an event
+/// // like:
+/// // INFO tracing_line_filter::stats: line filter summary entries=1 hit=0 hits=0
+/// //     never_hit="my_crate::db@142"
+/// ```
+///
+/// [`tracing-appender`]: https://docs.rs/tracing-appender
+/// [`stats`]: LineFilter::stats
+#[must_use = "statistics are reported when the guard is dropped"]
+pub struct FlushGuard<S> {
+    handle: reload::Handle<LineFilter, S>,
+    path: Option<PathBuf>,
+}
+
+impl<S> FlushGuard<S>
+where
+    S: Subscriber,
+{
+    /// Returns a guard that records an `INFO` event summarizing the
+    /// statistics of the filter behind `handle` when it is dropped.
+    ///
+    /// The event has the `tracing_line_filter::stats` target, which is
+    /// always enabled by the filter, and the following fields:
+    ///
+    /// * `entries`: the number of `(module, line)` and `(file, line)`
+    ///   entries.
+    /// * `hit`: the number of entries that enabled at least one span or
+    ///   event.
+    /// * `hits`: the total number of spans and events the entries enabled.
+    /// * `never_hit`: the directives for the entries that never enabled
+    ///   anything, separated by commas.
+    pub fn new(handle: reload::Handle<LineFilter, S>) -> Self {
+        Self { handle, path: None }
+    }
+
+    /// Returns a guard that writes the statistics of the filter behind
+    /// `handle` to the file at `path` when it is dropped, instead of
+    /// recording an event.
+    ///
+    /// The file lists the filter's directives in the same format as the
+    /// `list` [`Command`], so that it can also be used as a config: each
+    /// `module@line` and `file@line` directive is followed by a comment
+    /// saying how many times it was hit. If the file can't be written, a
+    /// warning is recorded instead.
+    ///
+    /// [`Command`]: crate::Command
+    pub fn to_file(handle: reload::Handle<LineFilter, S>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            handle,
+            path: Some(path.into()),
+        }
+    }
+}
+
+impl<S> Drop for FlushGuard<S> {
+    fn drop(&mut self) {
+        // Read the filter before recording anything, since the filter can't
+        // be used to filter events while it's being read.
+        if let Some(ref path) = self.path {
+            let list = match self.handle.with_current(command::list) {
+                Ok(list) => list,
+                Err(_) => return,
+            };
+            let contents = format!("{}\n{}\n", version::stamp(), list);
+            if let Err(error) = fs::write(path, contents) {
+                tracing::warn!(
+                    target: "tracing_line_filter",
+                    %error,
+                    "failed to write line filter statistics to '{}'",
+                    path.display(),
+                );
+            }
+            return;
+        }
+
+        let stats = match self.handle.with_current(LineFilter::stats) {
+            Ok(stats) => stats,
+            Err(_) => return,
+        };
+        let hit = stats.iter().filter(|entry| entry.hits() > 0).count();
+        let hits = stats.iter().map(|entry| entry.hits()).sum::<u64>();
+        let never_hit = stats
+            .iter()
+            .filter(|entry| entry.hits() == 0)
+            .map(|entry| entry.directive().to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        tracing::info!(
+            target: TARGET,
+            entries = stats.len() as u64,
+            hit = hit as u64,
+            hits,
+            never_hit = Some(never_hit).filter(|s| !s.is_empty()),
+            "line filter summary",
+        );
+    }
+}
+
+impl<S> fmt::Debug for FlushGuard<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FlushGuard")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
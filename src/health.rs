@@ -0,0 +1,196 @@
+use crate::{time::SystemTime, LineFilter};
+
+/// A summary of a [`LineFilter`]'s state, for checking that the intended
+/// filter is live, returned by [`LineFilter::health_snapshot`].
+///
+/// When the `serde` feature flag is enabled, this implements
+/// `serde::Serialize`, so that it can be exported as JSON (such as by the
+/// `filter/health` method of the `jsonrpc` server).
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct HealthSnapshot {
+    config_hash: String,
+    entries: usize,
+    directives: usize,
+    disabled: bool,
+    expired: bool,
+    last_reload: Option<ReloadStatus>,
+    features: Vec<&'static str>,
+}
+
+/// When a [`LineFilter`]'s config was last replaced, and whether that
+/// succeeded, as reported by [`HealthSnapshot::last_reload`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ReloadStatus {
+    time: SystemTime,
+    error: Option<String>,
+}
+
+/// The feature flags this crate may be built with.
+const FEATURES: &[(&str, bool)] = &[
+    ("env-filter", cfg!(feature = "env-filter")),
+    ("source", cfg!(feature = "source")),
+    ("dap", cfg!(feature = "dap")),
+    ("jsonrpc", cfg!(feature = "jsonrpc")),
+    ("log", cfg!(feature = "log")),
+    ("otel", cfg!(feature = "otel")),
+    ("metrics", cfg!(feature = "metrics")),
+    ("clap", cfg!(feature = "clap")),
+    ("tokio", cfg!(feature = "tokio")),
+    ("serde", cfg!(feature = "serde")),
+    ("futures", cfg!(feature = "futures")),
+    ("signal", cfg!(feature = "signal")),
+    ("test-util", cfg!(feature = "test-util")),
+    ("tracing-mock", cfg!(feature = "tracing-mock")),
+    ("arbitrary", cfg!(feature = "arbitrary")),
+];
+
+impl LineFilter {
+    /// Returns a summary of this filter's state, for orchestration systems
+    /// and dashboards that check that each instance of a program is running
+    /// with the intended filter.
+    ///
+    /// The snapshot includes a hash of the filter's config, the number of
+    /// entries and directives it has, whether its entries are disabled or
+    /// expired, when its config was last replaced by [`set_config`] (such
+    /// as by `LineFilter::from_watch`, when the `tokio` feature flag is
+    /// enabled) and whether that succeeded, and which of this crate's
+    /// feature flags are enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::{FilterConfig, LineFilter};
+    ///
+    /// let mut filter = LineFilter::default();
+    /// let health = filter.health_snapshot();
+    /// assert_eq!(health.entries(), 0);
+    /// assert!(health.last_reload().is_none());
+    ///
+    /// let config: FilterConfig = "my_crate::db@142, my_crate::auth@info".parse().unwrap();
+    /// filter.set_config(&config).unwrap();
+    /// let reloaded = filter.health_snapshot();
+    /// assert_eq!(reloaded.entries(), 1);
+    /// assert_eq!(reloaded.directives(), 2);
+    /// assert!(reloaded.last_reload().unwrap().is_ok());
+    /// assert_ne!(reloaded.config_hash(), health.config_hash());
+    /// ```
+    ///
+    /// [`set_config`]: Self::set_config
+    pub fn health_snapshot(&self) -> HealthSnapshot {
+        HealthSnapshot {
+            config_hash: format!("{:08x}", config_hash(&self.config_text())),
+            entries: self.entry_count(),
+            directives: self.directives().count(),
+            disabled: self.is_disabled(),
+            expired: self.is_expired(),
+            last_reload: self.last_reload.clone(),
+            features: FEATURES
+                .iter()
+                .filter(|&&(_, enabled)| enabled)
+                .map(|&(name, _)| name)
+                .collect(),
+        }
+    }
+
+    /// Remembers that this filter's config was just replaced, or that
+    /// replacing it failed with `error`.
+    pub(crate) fn reloaded(&mut self, error: Option<String>) {
+        self.last_reload = Some(ReloadStatus {
+            time: SystemTime::now(),
+            error,
+        });
+    }
+
+    /// Returns this filter's config, as the `save` command writes it,
+    /// without its version line.
+    pub(crate) fn config_text(&self) -> String {
+        format!(
+            "{:#}{}{}{}",
+            self,
+            self.span_fields_line(),
+            self.startup_section(),
+            self.profile_sections()
+        )
+    }
+}
+
+// === impl HealthSnapshot ===
+
+impl HealthSnapshot {
+    /// Returns a hash of the filter's config, as 8 hexadecimal digits.
+    ///
+    /// The hash covers the filter's directives (including its `EnvFilter`
+    /// directives), span fields, and profiles, as written to a file by the
+    /// `save` [`Command`]. It is 32-bit FNV-1a, like the line hashes in
+    /// directives, so two instances with the same config have the same hash
+    /// on every platform.
+    ///
+    /// [`Command`]: crate::Command
+    pub fn config_hash(&self) -> &str {
+        &self.config_hash
+    }
+
+    /// Returns the number of `(module, line)` and `(file, line)` entries.
+    pub fn entries(&self) -> usize {
+        self.entries
+    }
+
+    /// Returns the number of directives, as listed by
+    /// [`LineFilter::directives`].
+    pub fn directives(&self) -> usize {
+        self.directives
+    }
+
+    /// Returns `true` if the filter's entries are disabled; see
+    /// [`LineFilter::is_disabled`].
+    pub fn is_disabled(&self) -> bool {
+        self.disabled
+    }
+
+    /// Returns `true` if the filter's entries have expired; see
+    /// [`LineFilter::expire_at`].
+    pub fn is_expired(&self) -> bool {
+        self.expired
+    }
+
+    /// Returns when the filter's config was last replaced and whether that
+    /// succeeded, or `None` if it never has been.
+    pub fn last_reload(&self) -> Option<&ReloadStatus> {
+        self.last_reload.as_ref()
+    }
+
+    /// Returns the names of this crate's feature flags that are enabled,
+    /// such as `env-filter`.
+    pub fn features(&self) -> &[&'static str] {
+        &self.features
+    }
+}
+
+// === impl ReloadStatus ===
+
+impl ReloadStatus {
+    /// Returns when the config was replaced, or when replacing it failed.
+    pub fn time(&self) -> SystemTime {
+        self.time
+    }
+
+    /// Returns the error that replacing the config failed with, or `None`
+    /// if it succeeded.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Returns `true` if the config was replaced.
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Returns the 32-bit FNV-1a hash of `config`.
+fn config_hash(config: &str) -> u32 {
+    config.bytes().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
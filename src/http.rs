@@ -0,0 +1,847 @@
+//! An embedded web UI for browsing and toggling a filter's entries.
+//!
+//! [`Server`] serves a small single-page UI over HTTP, listing the callsites
+//! recorded since [`LineFilter::record_callsites`] was called, grouped by
+//! module. Each callsite has a toggle that adds or removes the entry for its
+//! line, and shows how many times the entry has been hit. The page also has
+//! a selector for how long the filter's line entries last (see
+//! [`LineFilter::expire_after`]). Hit counts are polled once a second,
+//! rather than pushed to the page.
+//!
+//! The page is backed by a few endpoints, which can also be used by scripts:
+//!
+//! | Request              | Response                                     |
+//! |----------------------|----------------------------------------------|
+//! | `GET /`              | The UI.                                      |
+//! | `GET /api/callsites` | The [`jsonrpc`] server's `filter/callsites`. |
+//! | `GET /api/stats`     | A [`StatsSnapshot`], as JSON.                |
+//! | `POST /api/command`  | Runs the [`Command`] in the request body.    |
+//!
+//! Only the `enable`, `disable`, and `expire` commands that the page sends
+//! can be run; other commands, such as `save`, are answered with a `403`
+//! status. `POST /api/command` responds with the command's output as plain
+//! text, or with its error and a `400` status if it fails, `403` if the
+//! filter is [read-only], or `429` if the filter's [command limit] has been
+//! reached. Each command is run with [`Command::apply`], so it is recorded
+//! as an audit event with the `http` transport and the client's address.
+//!
+//! # Security
+//!
+//! The `/api` endpoints require the server's [token], sent as an
+//! `Authorization: Bearer TOKEN` or `X-Api-Key: TOKEN` header, and answer
+//! other requests with a `401` status, recording each of them as a `WARN`
+//! audit event. The page asks for the token, or reads it from the URL's
+//! fragment, as in `http://127.0.0.1:7071/#token=TOKEN`, which browsers
+//! don't send to the server. Since the token is sent in a header, web pages
+//! on other sites can't make requests with it without the server's consent,
+//! which it never gives. Requests are also answered with a `403` status if
+//! their `Host` header names anything other than the address the client
+//! connected to (or `localhost`, for a loopback address), or the hosts
+//! added with [`allow_host`], or if their `Origin` header names a different
+//! site than their `Host` header, so that pages on other sites can't reach
+//! the server by pointing their own domain at its address. [`serve`] speaks
+//! plain HTTP, so the token can be read by anyone who can watch its traffic;
+//! to serve the UI over such a network, wrap each connection in a TLS
+//! session with [`serve_with`].
+//!
+//! Each connection is answered with a single response and then closed.
+//! Requests with headers longer than 8 KiB or bodies longer than 64 KiB are
+//! answered with a `413` status, without being buffered. Connections that
+//! don't send a whole request, or don't read their response, within 5
+//! seconds are closed, so that an idle client doesn't stop the server from
+//! answering others.
+//!
+//! Since this requires a JSON encoder, it is only available when the `http`
+//! feature flag is enabled.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::net::TcpListener;
+//! use tracing_line_filter::{http, LineFilter};
+//! use tracing_subscriber::{prelude::*, reload};
+//!
+//! let mut filter = LineFilter::default();
+//! filter.record_callsites();
+//! let (filter, handle) = reload::Layer::new(filter);
+//! tracing_subscriber::registry()
+//!     .with(tracing_subscriber::fmt::layer())
+//!     .with(filter)
+//!     .init();
+//!
+//! std::thread::spawn(move || {
+//!     let listener = TcpListener::bind("127.0.0.1:7071").unwrap();
+//!     // The token is read from the `LINE_FILTER_TOKEN` environment
+//!     // variable. Open http://127.0.0.1:7071/#token=TOKEN to use the UI.
+//!     http::Server::new(handle).serve(&listener)
+//! });
+//! ```
+//!
+//! [`jsonrpc`]: crate::jsonrpc
+//! [`StatsSnapshot`]: crate::StatsSnapshot
+//! [`Command`]: crate::Command
+//! [`Command::apply`]: crate::Command::apply
+//! [read-only]: crate::LineFilter::read_only_commands
+//! [command limit]: crate::LineFilter::limit_commands
+//! [token]: Server::token
+//! [`allow_host`]: Server::allow_host
+//! [`serve`]: Server::serve
+//! [`serve_with`]: Server::serve_with
+use crate::{audit, auth::Token, jsonrpc, Command, LineFilter};
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{IpAddr, SocketAddr, TcpListener, TcpStream},
+    time::Duration,
+};
+use tracing_core::Subscriber;
+use tracing_subscriber::reload;
+
+/// The page served at `/`.
+const INDEX: &str = include_str!("http/index.html");
+
+/// The longest request line or header that is read, in bytes.
+const MAX_HEADER_LEN: u64 = 8 * 1024;
+
+/// The largest request body that is read, in bytes.
+const MAX_BODY_LEN: usize = 64 * 1024;
+
+/// How long a connection may take to send its request, or to read its
+/// response.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Serves the web UI for a [`LineFilter`].
+///
+/// See the [module-level documentation](self) for details.
+#[derive(Debug)]
+pub struct Server<S> {
+    handle: reload::Handle<LineFilter, S>,
+    token: Option<Token>,
+    /// The hosts that requests may be addressed to, other than the address
+    /// of the connection being served.
+    hosts: Vec<String>,
+    /// The address of the client being served, if it is known.
+    peer: Option<String>,
+    /// The address that the client being served connected to, if it is
+    /// known.
+    local: Option<SocketAddr>,
+}
+
+/// A response's status, content type, and body.
+type Response = (&'static str, &'static str, String);
+
+/// The parts of a request that are used.
+struct Request {
+    method: String,
+    path: String,
+    host: Option<String>,
+    origin: Option<String>,
+    /// The token from the request's `Authorization` or `X-Api-Key` header,
+    /// if it has one.
+    token: Option<String>,
+    body: String,
+}
+
+impl<S> Server<S>
+where
+    S: Subscriber,
+{
+    /// Returns a new `Server` for the [`LineFilter`] behind `handle`.
+    ///
+    /// The server's [token] is read from the `LINE_FILTER_TOKEN` environment
+    /// variable, if it is set. Otherwise, the server has no token until one
+    /// is set, so it won't serve its endpoints.
+    ///
+    /// [token]: Self::token
+    pub fn new(handle: reload::Handle<LineFilter, S>) -> Self {
+        Self {
+            handle,
+            token: Token::from_env(),
+            hosts: Vec::new(),
+            peer: None,
+            local: None,
+        }
+    }
+
+    /// Sets the token that requests to the `/api` endpoints must send, as
+    /// an `Authorization: Bearer TOKEN` or `X-Api-Key: TOKEN` header,
+    /// replacing the one read from the `LINE_FILTER_TOKEN` environment
+    /// variable.
+    ///
+    /// The token should be a long random string, such as one generated for
+    /// each deployment, since anyone who has it can change the filter.
+    pub fn token(&mut self, token: impl Into<String>) -> &mut Self {
+        self.token = Some(Token::new(token));
+        self
+    }
+
+    /// Allows requests addressed to `host`, as named by their `Host`
+    /// header, such as `logs.example.com` or `logs.example.com:8080`.
+    ///
+    /// This is needed when clients reach the server through a name other
+    /// than the address they connect to, such as through a proxy. Requests
+    /// addressed to the address the client connected to are always allowed.
+    pub fn allow_host(&mut self, host: impl Into<String>) -> &mut Self {
+        self.hosts.push(host.into());
+        self
+    }
+
+    /// Accepts connections on `listener`, answering them one at a time.
+    ///
+    /// This only returns if accepting a connection fails, or if the
+    /// subscriber containing the filter has been dropped.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error right away if the server has no [token].
+    ///
+    /// [token]: Self::token
+    pub fn serve(&mut self, listener: &TcpListener) -> io::Result<()> {
+        self.serve_with(listener, Ok)
+    }
+
+    /// Accepts connections on `listener`, passing each one to `accept` and
+    /// answering a request on the stream it returns, one at a time.
+    ///
+    /// This is like [`serve`], except that `accept` can wrap each connection
+    /// in another stream, such as a TLS session, so that the UI can be
+    /// served over a network that others can watch. The connection's
+    /// timeouts are set before it is passed to `accept`, so they also limit
+    /// how long a handshake can take. If `accept` fails, such as because a
+    /// TLS handshake failed, the connection is closed, and the server keeps
+    /// accepting others.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error right away if the server has no [token].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use std::{io, net::TcpStream};
+    /// # fn handshake(stream: TcpStream) -> io::Result<TcpStream> { Ok(stream) }
+    /// use std::net::TcpListener;
+    /// use tracing_line_filter::{http, LineFilter};
+    /// use tracing_subscriber::{prelude::*, reload};
+    ///
+    /// let (filter, handle) = reload::Layer::new(LineFilter::default());
+    /// tracing_subscriber::registry().with(filter).init();
+    ///
+    /// let listener = TcpListener::bind("0.0.0.0:7071").unwrap();
+    /// // `handshake` returns the connection wrapped in a TLS session, such
+    /// // as a `rustls::StreamOwned`.
+    /// http::Server::new(handle)
+    ///     .allow_host("logs.example.com")
+    ///     .serve_with(&listener, handshake)
+    ///     .unwrap();
+    /// ```
+    ///
+    /// [`serve`]: Self::serve
+    /// [token]: Self::token
+    pub fn serve_with<T>(
+        &mut self,
+        listener: &TcpListener,
+        mut accept: impl FnMut(TcpStream) -> io::Result<T>,
+    ) -> io::Result<()>
+    where
+        T: Read + Write,
+    {
+        if self.token.is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the web UI can't be served without a token",
+            ));
+        }
+        loop {
+            let (stream, addr) = listener.accept()?;
+            stream.set_read_timeout(Some(TIMEOUT))?;
+            stream.set_write_timeout(Some(TIMEOUT))?;
+            let local = stream.local_addr().ok();
+            let stream = match accept(stream) {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            self.peer = Some(addr.to_string());
+            self.local = local;
+            let result = self.serve_stream(stream);
+            self.peer = None;
+            self.local = None;
+            if let Err(e) = result {
+                // A client that goes away shouldn't stop the server, but
+                // there's no point in serving without a filter to change.
+                if self.handle.with_current(|_| ()).is_err() {
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// Reads a single request from `reader`, and writes its response to
+    /// `writer`.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if reading or writing fails, or if the
+    /// subscriber containing the filter has been dropped. Malformed requests
+    /// are answered with an error status instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::{http, LineFilter};
+    /// use tracing_subscriber::{prelude::*, reload};
+    ///
+    /// let (filter, handle) = reload::Layer::new(LineFilter::default());
+    /// let _subscriber = tracing_subscriber::registry().with(filter);
+    ///
+    /// let body = "enable my_crate::db@142";
+    /// let request = format!(
+    ///     "POST /api/command HTTP/1.1\r\n\
+    ///      Authorization: Bearer hunter2\r\n\
+    ///      Content-Length: {}\r\n\r\n{}",
+    ///     body.len(),
+    ///     body,
+    /// );
+    /// let mut response = Vec::new();
+    /// http::Server::new(handle.clone())
+    ///     .token("hunter2")
+    ///     .serve_connection(request.as_bytes(), &mut response)
+    ///     .unwrap();
+    ///
+    /// let response = String::from_utf8(response).unwrap();
+    /// assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    /// assert!(response.ends_with("\r\n\r\nenabled my_crate::db@142"));
+    /// let list = handle.with_current(|filter| filter.to_string()).unwrap();
+    /// assert_eq!(list, "my_crate::db@142");
+    /// ```
+    pub fn serve_connection(
+        &mut self,
+        mut reader: impl BufRead,
+        writer: impl Write,
+    ) -> io::Result<()> {
+        let response = self.answer(&mut reader)?;
+        write_response(writer, response)
+    }
+
+    /// Reads a single request from `stream`, and writes its response back
+    /// to it.
+    ///
+    /// This is like [`serve_connection`], for streams that can't be split
+    /// into a reader and a writer, such as TLS sessions. Since the address
+    /// that the client connected to isn't known, requests are only answered
+    /// if their `Host` header names a host added with [`allow_host`], or if
+    /// they don't have one.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error in the same cases as [`serve_connection`].
+    ///
+    /// [`serve_connection`]: Self::serve_connection
+    /// [`allow_host`]: Self::allow_host
+    pub fn serve_stream(&mut self, stream: impl Read + Write) -> io::Result<()> {
+        let mut stream = BufReader::new(stream);
+        let response = self.answer(&mut stream)?;
+        write_response(stream.get_mut(), response)
+    }
+
+    /// Reads a single request from `reader`, returning its response.
+    fn answer(&self, reader: &mut impl BufRead) -> io::Result<Response> {
+        match read_request(reader)? {
+            Ok(request) => self.respond(&request),
+            Err(response) => Ok(response),
+        }
+    }
+
+    fn respond(&self, request: &Request) -> io::Result<Response> {
+        if let Some(ref host) = request.host {
+            if !self.is_own_host(host) {
+                return Ok(text("403 Forbidden", "unknown host"));
+            }
+        }
+        if let Some(ref origin) = request.origin {
+            if !is_same_origin(origin, request.host.as_deref()) {
+                return Ok(text(
+                    "403 Forbidden",
+                    "cross-origin requests are not allowed",
+                ));
+            }
+        }
+
+        // Query strings aren't used.
+        let path = request.path.split('?').next().unwrap_or_default();
+        if path.starts_with("/api/") && !self.is_authorized(request.token.as_deref()) {
+            audit::Request {
+                transport: "http",
+                peer: self.peer.as_deref(),
+                command: &format_args!("{} {}", request.method, path),
+            }
+            .record_unauthorized();
+            return Ok(text("401 Unauthorized", "a valid token is required"));
+        }
+        let json = |value: serde_json::Value| ("200 OK", "application/json", value.to_string());
+        Ok(match (request.method.as_str(), path) {
+            ("GET", "/") => ("200 OK", "text/html; charset=utf-8", INDEX.to_owned()),
+            ("GET", "/api/callsites") => json(
+                self.handle
+                    .with_current(|filter| jsonrpc::callsites(filter, ""))
+                    .map_err(io::Error::other)?,
+            ),
+            ("GET", "/api/stats") => {
+                let snapshot = self
+                    .handle
+                    .with_current(LineFilter::stats_snapshot)
+                    .map_err(io::Error::other)?;
+                json(serde_json::to_value(snapshot).map_err(io::Error::other)?)
+            }
+            ("POST", "/api/command") => self.command(&request.body)?,
+            (_, "/" | "/api/callsites" | "/api/stats" | "/api/command") => {
+                text("405 Method Not Allowed", "method not allowed")
+            }
+            _ => text("404 Not Found", "not found"),
+        })
+    }
+
+    fn command(&self, body: &str) -> io::Result<Response> {
+        let command = match body.trim().parse::<Command>() {
+            Ok(command) => command,
+            Err(e) => return Ok(text("400 Bad Request", e)),
+        };
+        // Only the commands that the page sends can be run, so that a
+        // client can't, for example, `save` the filter over any file that
+        // the program can write to.
+        if !matches!(
+            command,
+            Command::Enable(_) | Command::Disable(_) | Command::Expire(_)
+        ) {
+            return Ok(text(
+                "403 Forbidden",
+                "only `enable`, `disable`, and `expire` commands can be run over HTTP",
+            ));
+        }
+        Ok(
+            match command.apply(&self.handle, "http", self.peer.as_deref()) {
+                Ok(output) => text("200 OK", output),
+                // There's no filter left to change.
+                Err(e) if e.is_reload() => return Err(io::Error::other(e)),
+                Err(e) if e.is_read_only() => text("403 Forbidden", e),
+                Err(e) if e.is_rate_limited() => text("429 Too Many Requests", e),
+                Err(e) => text("400 Bad Request", e),
+            },
+        )
+    }
+
+    fn is_authorized(&self, token: Option<&str>) -> bool {
+        match (&self.token, token) {
+            (Some(expected), Some(token)) => expected.matches(token),
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if `host`, a request's `Host` header, names the
+    /// address that the client connected to, or a host allowed with
+    /// [`allow_host`](Self::allow_host).
+    fn is_own_host(&self, host: &str) -> bool {
+        if self
+            .hosts
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(host))
+        {
+            return true;
+        }
+        let local = match self.local {
+            Some(local) => local,
+            None => return false,
+        };
+        let (name, port) = match host
+            .rsplit_once(':')
+            .and_then(|(name, port)| Some((name, port.parse::<u16>().ok()?)))
+        {
+            Some((name, port)) => (name, port),
+            None => (host, 80),
+        };
+        let name = name.trim_start_matches('[').trim_end_matches(']');
+        port == local.port()
+            && (name.parse::<IpAddr>().ok() == Some(local.ip())
+                || (name.eq_ignore_ascii_case("localhost") && local.ip().is_loopback()))
+    }
+}
+
+/// Returns `true` if `origin`, a request's `Origin` header, names the same
+/// site as `host`, its `Host` header.
+fn is_same_origin(origin: &str, host: Option<&str>) -> bool {
+    let host = match host {
+        Some(host) => host,
+        None => return false,
+    };
+    let authority = origin
+        .strip_prefix("http://")
+        .or_else(|| origin.strip_prefix("https://"));
+    authority.is_some_and(|authority| authority.eq_ignore_ascii_case(host))
+}
+
+fn write_response(mut writer: impl Write, response: Response) -> io::Result<()> {
+    let (status, content_type, body) = response;
+    let challenge = if status.starts_with("401") {
+        "WWW-Authenticate: Bearer\r\n"
+    } else {
+        ""
+    };
+    write!(
+        writer,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        challenge,
+        body,
+    )?;
+    writer.flush()
+}
+
+fn text(status: &'static str, body: impl ToString) -> Response {
+    (status, "text/plain; charset=utf-8", body.to_string())
+}
+
+/// Reads a request, or returns the error response to send if it is
+/// malformed or too large.
+fn read_request(reader: &mut impl BufRead) -> io::Result<Result<Request, Response>> {
+    let too_large = || Ok(Err(text("413 Content Too Large", "request is too large")));
+    let bad_request = |message| Ok(Err(text("400 Bad Request", message)));
+
+    let mut line = String::new();
+    let mut read_line = |line: &mut String| {
+        line.clear();
+        let read = reader.by_ref().take(MAX_HEADER_LEN).read_line(line)?;
+        io::Result::Ok(read == 0 || line.ends_with('\n'))
+    };
+    if !read_line(&mut line)? {
+        return too_large();
+    }
+    let mut parts = line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(path), Some(version)) if version.starts_with("HTTP/1.") => {
+            (method.to_owned(), path.to_owned())
+        }
+        _ => return bad_request("malformed request line"),
+    };
+
+    let mut len = 0;
+    let (mut host, mut origin, mut token) = (None, None, None);
+    loop {
+        if !read_line(&mut line)? {
+            return too_large();
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                len = match value.parse::<usize>() {
+                    Ok(len) => len,
+                    Err(_) => return bad_request("malformed Content-Length header"),
+                };
+            } else if name.eq_ignore_ascii_case("host") {
+                host = Some(value.to_owned());
+            } else if name.eq_ignore_ascii_case("origin") {
+                origin = Some(value.to_owned());
+            } else if name.eq_ignore_ascii_case("authorization") {
+                token = value
+                    .split_once(' ')
+                    .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+                    .map(|(_, token)| token.trim().to_owned());
+            } else if name.eq_ignore_ascii_case("x-api-key") {
+                token = Some(value.to_owned());
+            }
+        }
+    }
+
+    if len > MAX_BODY_LEN {
+        return too_large();
+    }
+    let mut body = Vec::with_capacity(len);
+    reader.take(len as u64).read_to_end(&mut body)?;
+    if body.len() < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    match String::from_utf8(body) {
+        Ok(body) => Ok(Ok(Request {
+            method,
+            path,
+            host,
+            origin,
+            token,
+            body,
+        })),
+        Err(_) => bad_request("request body is not UTF-8"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::prelude::*;
+
+    const TOKEN: &str = "hunter2";
+
+    fn server<S: Subscriber>(handle: &reload::Handle<LineFilter, S>) -> Server<S> {
+        let mut server = Server::new(handle.clone());
+        server.token(TOKEN);
+        server.local = Some(([127, 0, 0, 1], 7071).into());
+        server
+    }
+
+    fn serve(server: &mut Server<impl Subscriber>, request: &str) -> String {
+        let mut response = Vec::new();
+        server
+            .serve_connection(request.as_bytes(), &mut response)
+            .unwrap();
+        String::from_utf8(response).unwrap()
+    }
+
+    fn request(handle: &reload::Handle<LineFilter, impl Subscriber>, request: &str) -> String {
+        serve(&mut server(handle), request)
+    }
+
+    fn get(handle: &reload::Handle<LineFilter, impl Subscriber>, path: &str) -> String {
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: 127.0.0.1:7071\r\nAuthorization: Bearer {}\r\n\r\n",
+            path, TOKEN,
+        );
+        serve(&mut server(handle), &request)
+    }
+
+    fn post(handle: &reload::Handle<LineFilter, impl Subscriber>, command: &str) -> String {
+        let head = format!(
+            "POST /api/command HTTP/1.1\r\nHost: localhost:7071\r\n\
+             Origin: http://localhost:7071\r\nAuthorization: Bearer {}\r\n\
+             Content-Length: {}\r\n\r\n",
+            TOKEN,
+            command.len(),
+        );
+        request(handle, &(head + command))
+    }
+
+    #[test]
+    fn serves_the_page_and_its_endpoints() {
+        let mut filter: LineFilter = "my_crate::db@142".parse().unwrap();
+        filter.record_callsites();
+        let (filter, handle) = reload::Layer::new(filter);
+        let _subscriber = tracing_subscriber::registry().with(filter);
+
+        let page = request(&handle, "GET / HTTP/1.1\r\n\r\n");
+        assert!(page.starts_with("HTTP/1.1 200 OK\r\nContent-Type: text/html"));
+        assert!(page.contains("/api/callsites"));
+
+        let callsites = get(&handle, "/api/callsites");
+        assert!(callsites.ends_with("\r\n\r\n[]"));
+
+        let stats = get(&handle, "/api/stats?now");
+        let body = stats.split("\r\n\r\n").nth(1).unwrap();
+        let stats = serde_json::from_str::<serde_json::Value>(body).unwrap();
+        assert_eq!(stats["entries"][0]["directive"], "my_crate::db@142");
+
+        let missing = request(&handle, "GET /nope HTTP/1.1\r\n\r\n");
+        assert!(missing.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        let wrong = request(
+            &handle,
+            "DELETE /api/stats HTTP/1.1\r\nAuthorization: Bearer hunter2\r\n\r\n",
+        );
+        assert!(wrong.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+    }
+
+    #[test]
+    fn api_requests_need_the_token() {
+        let (filter, handle) = reload::Layer::new(LineFilter::default());
+        let _subscriber = tracing_subscriber::registry().with(filter);
+
+        let page = request(&handle, "GET / HTTP/1.1\r\n\r\n");
+        assert!(page.starts_with("HTTP/1.1 200 OK\r\n"));
+        let response = request(
+            &handle,
+            "GET /api/stats HTTP/1.1\r\nX-Api-Key: hunter2\r\n\r\n",
+        );
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        for auth in [
+            "",
+            "Authorization: Bearer hunter\r\n",
+            "Authorization: Basic hunter2\r\n",
+        ] {
+            let response = request(&handle, &format!("GET /api/stats HTTP/1.1\r\n{}\r\n", auth));
+            assert!(
+                response.starts_with("HTTP/1.1 401 Unauthorized\r\n"),
+                "{}",
+                response
+            );
+            assert!(response.contains("\r\nWWW-Authenticate: Bearer\r\n"));
+        }
+
+        // A server without a token doesn't serve its endpoints at all.
+        let mut server = Server::new(handle.clone());
+        server.token = None;
+        let response = serve(
+            &mut server,
+            "GET /api/stats HTTP/1.1\r\nAuthorization: Bearer \r\n\r\n",
+        );
+        assert!(response.starts_with("HTTP/1.1 401 Unauthorized\r\n"));
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let error = server.serve(&listener).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn requests_must_be_addressed_to_the_server() {
+        let (filter, handle) = reload::Layer::new(LineFilter::default());
+        let _subscriber = tracing_subscriber::registry().with(filter);
+        let get = |server: &mut Server<_>, headers: &str| {
+            let request = format!(
+                "GET /api/stats HTTP/1.1\r\nAuthorization: Bearer hunter2\r\n{}\r\n",
+                headers
+            );
+            let response = serve(server, &request);
+            response.split("\r\n").next().unwrap().to_owned()
+        };
+        let mut server = server(&handle);
+
+        for host in ["127.0.0.1:7071", "localhost:7071", "LOCALHOST:7071"] {
+            let response = get(&mut server, &format!("Host: {}\r\n", host));
+            assert_eq!(response, "HTTP/1.1 200 OK", "{}", host);
+        }
+        for host in [
+            "evil.example:7071",
+            "localhost:8080",
+            "127.0.0.2:7071",
+            "localhost",
+        ] {
+            let response = get(&mut server, &format!("Host: {}\r\n", host));
+            assert_eq!(response, "HTTP/1.1 403 Forbidden", "{}", host);
+        }
+
+        let same = "Host: localhost:7071\r\nOrigin: http://localhost:7071\r\n";
+        assert_eq!(get(&mut server, same), "HTTP/1.1 200 OK");
+        for origin in ["http://evil.example", "http://localhost:8080", "null"] {
+            let headers = format!("Host: localhost:7071\r\nOrigin: {}\r\n", origin);
+            let response = get(&mut server, &headers);
+            assert_eq!(response, "HTTP/1.1 403 Forbidden", "{}", origin);
+        }
+        let response = get(&mut server, "Origin: http://localhost:7071\r\n");
+        assert_eq!(response, "HTTP/1.1 403 Forbidden");
+
+        server.allow_host("logs.example.com");
+        let headers = "Host: logs.example.com\r\nOrigin: https://logs.example.com\r\n";
+        assert_eq!(get(&mut server, headers), "HTTP/1.1 200 OK");
+    }
+
+    #[test]
+    fn serves_the_streams_that_connections_are_wrapped_in() {
+        /// A stream, such as a TLS session, that wraps a connection.
+        struct Wrapped(TcpStream);
+        impl Read for Wrapped {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.0.read(buf)
+            }
+        }
+        impl Write for Wrapped {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.write(buf)
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                self.0.flush()
+            }
+        }
+
+        let (filter, handle) = reload::Layer::new(LineFilter::default());
+        let _subscriber = tracing_subscriber::registry().with(filter);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut server = Server::new(handle.clone());
+        server.token(TOKEN);
+        let mut handshakes = 0;
+        std::thread::spawn(move || {
+            server.serve_with(&listener, |stream| {
+                handshakes += 1;
+                if handshakes == 1 {
+                    return Err(io::Error::other("handshake failed"));
+                }
+                Ok(Wrapped(stream))
+            })
+        });
+
+        let send = || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(
+                stream,
+                "GET /api/stats HTTP/1.1\r\nHost: {}\r\nAuthorization: Bearer {}\r\n\r\n",
+                addr, TOKEN
+            )
+            .unwrap();
+            let mut response = String::new();
+            // A connection that's closed with the request unread may be
+            // reset, rather than reaching the end of its input.
+            let _ = stream.read_to_string(&mut response);
+            response
+        };
+        // The first connection's handshake fails, so it's closed unanswered.
+        assert_eq!(send(), "");
+        assert!(send().starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+
+    #[test]
+    fn only_ui_commands_can_be_run() {
+        let (filter, handle) = reload::Layer::new("my_crate::db@142".parse().unwrap());
+        let _subscriber = tracing_subscriber::registry().with(filter);
+
+        let path = std::env::temp_dir().join(format!("line-filter-http-{}", std::process::id()));
+        let response = post(&handle, &format!("save {}", path.display()));
+        assert!(
+            response.starts_with("HTTP/1.1 403 Forbidden\r\n"),
+            "{}",
+            response
+        );
+        assert!(!path.exists());
+        let response = post(&handle, "disable-all");
+        assert!(response.starts_with("HTTP/1.1 403 Forbidden\r\n"));
+        let list = handle.with_current(|filter| filter.to_string()).unwrap();
+        assert_eq!(list, "my_crate::db@142");
+    }
+
+    #[test]
+    fn commands_change_the_filter() {
+        let (filter, handle) = reload::Layer::new(LineFilter::default());
+        let _subscriber = tracing_subscriber::registry().with(filter);
+
+        let response = post(&handle, "enable my_crate::db@142");
+        assert!(response.ends_with("\r\n\r\nenabled my_crate::db@142"));
+        let response = post(&handle, "expire 5m");
+        assert!(response.ends_with("\r\n\r\nline entries expire in 5m"));
+        let response = post(&handle, "frobnicate");
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+
+        handle
+            .modify(|filter| {
+                filter.read_only_commands(true);
+            })
+            .unwrap();
+        let response = post(&handle, "disable my_crate::db@142");
+        assert!(response.starts_with("HTTP/1.1 403 Forbidden\r\n"));
+        let list = handle.with_current(|filter| filter.to_string()).unwrap();
+        assert_eq!(list, "my_crate::db@142");
+    }
+
+    #[test]
+    fn large_requests_are_rejected() {
+        let (filter, handle) = reload::Layer::new(LineFilter::default());
+        let _subscriber = tracing_subscriber::registry().with(filter);
+
+        let header = "x".repeat(MAX_HEADER_LEN as usize);
+        let response = request(&handle, &format!("GET / HTTP/1.1\r\n{}\r\n\r\n", header));
+        assert!(response.starts_with("HTTP/1.1 413 Content Too Large\r\n"));
+
+        let head = format!(
+            "POST /api/command HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY_LEN + 1
+        );
+        let response = request(&handle, &head);
+        assert!(response.starts_with("HTTP/1.1 413 Content Too Large\r\n"));
+    }
+}
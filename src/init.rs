@@ -0,0 +1,265 @@
+use crate::{Directive, LineFilter, ParseError};
+use std::{error::Error, fmt};
+use tracing_core::dispatcher::DefaultGuard;
+use tracing_subscriber::{prelude::*, reload, util::TryInitError, Registry};
+
+/// Builds and installs a global default subscriber that logs the spans and
+/// events enabled by a [`LineFilter`] using [`tracing_subscriber::fmt`],
+/// returned by [`fmt()`].
+///
+/// Use [`with_lines`](Self::with_lines) to add line filter directives, and
+/// [`with_env`](Self::with_env) to add `EnvFilter` directives. Errors in
+/// the directives are reported by [`try_init`](Self::try_init).
+///
+/// [`tracing_subscriber::fmt`]: mod@tracing_subscriber::fmt
+#[derive(Debug, Default)]
+#[must_use = "a `FmtBuilder` does nothing until it is initialized"]
+pub struct FmtBuilder {
+    filter: LineFilter,
+    /// The first directive that couldn't be parsed.
+    error: Option<ParseError>,
+}
+
+/// Indicates that [`FmtBuilder::try_init`] couldn't install a subscriber.
+#[derive(Debug)]
+pub struct InitError {
+    kind: ErrorKind,
+}
+
+#[derive(Debug)]
+enum ErrorKind {
+    Parse(ParseError),
+    Init(TryInitError),
+}
+
+/// Returns a builder for a global default subscriber that logs the spans and
+/// events enabled by a [`LineFilter`] using [`tracing_subscriber::fmt`].
+///
+/// This is a shortcut for the common case of a program that logs to
+/// standard output and wants to pin lines using its command line or
+/// environment, like [`tracing_subscriber::fmt::init`] for `EnvFilter`. The
+/// installed subscriber is a [`Registry`] with the filter, behind a
+/// [`reload`] layer, and a `fmt` layer with its default settings. Programs
+/// that need anything else should build their subscriber themselves.
+///
+/// # Examples
+///
+/// ```
+/// let handle = tracing_line_filter::fmt()
+///     .with_lines("my_crate::db@142,my_crate::http@debug")
+///     .with_env("error")
+///     .try_init()
+///     .unwrap();
+///
+/// tracing::error!("enabled by the EnvFilter");
+///
+/// // The filter can be changed at runtime using the returned handle.
+/// handle
+///     .modify(|filter| {
+///         filter.enable_by_mod("my_crate::cache", 42);
+///     })
+///     .unwrap();
+/// ```
+///
+/// [`tracing_subscriber::fmt`]: mod@tracing_subscriber::fmt
+/// [`tracing_subscriber::fmt::init`]: tracing_subscriber::fmt::init
+/// [`Registry`]: tracing_subscriber::Registry
+/// [`reload`]: tracing_subscriber::reload
+pub fn fmt() -> FmtBuilder {
+    FmtBuilder::default()
+}
+
+impl LineFilter {
+    /// Sets a subscriber that logs the spans and events enabled by this
+    /// filter as the default for the current thread, until the returned
+    /// guard is dropped.
+    ///
+    /// This is meant for tests: each test can install its own filter for
+    /// the length of its body, without depending on which test sets the
+    /// global default subscriber first. The subscriber is a [`Registry`]
+    /// with this filter and a `fmt` layer that writes using the [test
+    /// writer], so that the output is only shown for failing tests. Since
+    /// the subscriber is only the default for the current thread, spans and
+    /// events recorded by other threads (such as a multi-threaded async
+    /// runtime's worker threads) aren't logged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{
+    ///     atomic::{AtomicUsize, Ordering},
+    ///     Arc,
+    /// };
+    /// use tracing_line_filter::LineFilter;
+    ///
+    /// fn query() {
+    ///     tracing::trace!("running query");
+    /// }
+    ///
+    /// // In a test:
+    /// let directive = format!("{}@{}", module_path!(), line!() - 4);
+    /// let mut filter: LineFilter = directive.parse().unwrap();
+    /// let hits = Arc::new(AtomicUsize::new(0));
+    /// let counter = hits.clone();
+    /// filter.on_match(directive.parse().unwrap(), move |_| {
+    ///     counter.fetch_add(1, Ordering::Relaxed);
+    /// });
+    ///
+    /// let guard = filter.set_default_scoped();
+    /// query();
+    /// drop(guard);
+    ///
+    /// // The filter is no longer in use.
+    /// query();
+    /// assert_eq!(hits.load(Ordering::Relaxed), 1);
+    /// ```
+    ///
+    /// [`Registry`]: tracing_subscriber::Registry
+    /// [test writer]: tracing_subscriber::fmt::Layer::with_test_writer
+    #[must_use = "the subscriber is only the default until the guard is dropped"]
+    pub fn set_default_scoped(self) -> DefaultGuard {
+        tracing_subscriber::registry()
+            .with(self)
+            .with(tracing_subscriber::fmt::layer().with_test_writer())
+            .set_default()
+    }
+}
+
+// === impl FmtBuilder ===
+
+impl FmtBuilder {
+    /// Adds the comma-separated line filter directives in `directives`, such
+    /// as `my_crate::db@142,my_crate::http@debug`, to the filter.
+    ///
+    /// `EnvFilter` directives are also accepted, when the `env-filter`
+    /// feature flag is enabled.
+    pub fn with_lines(&mut self, directives: &str) -> &mut Self {
+        self.add(directives, false)
+    }
+
+    /// Adds the comma-separated [`EnvFilter`] directives in `directives`,
+    /// such as `error,my_crate::db=debug`, to the filter, to decide about
+    /// the spans and events that no line entry matches.
+    ///
+    /// This method is only available when the `env-filter` feature flag is
+    /// enabled.
+    ///
+    /// [`EnvFilter`]: tracing_subscriber::EnvFilter
+    #[cfg(feature = "env-filter")]
+    pub fn with_env(&mut self, directives: &str) -> &mut Self {
+        self.add(directives, true)
+    }
+
+    /// Installs the subscriber as the global default, returning a handle for
+    /// changing its filter at runtime.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the directives couldn't be parsed, or if a
+    /// global default subscriber has already been set.
+    pub fn try_init(&mut self) -> Result<reload::Handle<LineFilter, Registry>, InitError> {
+        if let Some(error) = self.error.take() {
+            return Err(InitError {
+                kind: ErrorKind::Parse(error),
+            });
+        }
+        let (filter, handle) = reload::Layer::new(std::mem::take(&mut self.filter));
+        tracing_subscriber::registry()
+            .with(filter)
+            .with(tracing_subscriber::fmt::layer())
+            .try_init()
+            .map_err(|e| InitError {
+                kind: ErrorKind::Init(e),
+            })?;
+        Ok(handle)
+    }
+
+    /// Installs the subscriber as the global default, returning a handle for
+    /// changing its filter at runtime.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the directives couldn't be parsed, or if a global
+    /// default subscriber has already been set.
+    pub fn init(&mut self) -> reload::Handle<LineFilter, Registry> {
+        self.try_init()
+            .expect("failed to install the line filter subscriber")
+    }
+
+    /// Sets the subscriber as the default for the current thread, until the
+    /// returned guard is dropped, rather than as the global default.
+    ///
+    /// See [`LineFilter::set_default_scoped`], which does the same for tests.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the directives couldn't be parsed.
+    pub fn try_set_default(
+        &mut self,
+    ) -> Result<(reload::Handle<LineFilter, Registry>, DefaultGuard), InitError> {
+        if let Some(error) = self.error.take() {
+            return Err(InitError {
+                kind: ErrorKind::Parse(error),
+            });
+        }
+        let (filter, handle) = reload::Layer::new(std::mem::take(&mut self.filter));
+        let guard = tracing_subscriber::registry()
+            .with(filter)
+            .with(tracing_subscriber::fmt::layer())
+            .set_default();
+        Ok((handle, guard))
+    }
+
+    fn add(&mut self, directives: &str, env_only: bool) -> &mut Self {
+        let directives = directives
+            .split(',')
+            .map(str::trim)
+            .filter(|directive| !directive.is_empty());
+        for text in directives {
+            let directive = text.parse::<Directive>().and_then(|directive| {
+                #[cfg(feature = "env-filter")]
+                let is_env = matches!(directive, Directive::Env(_));
+                #[cfg(not(feature = "env-filter"))]
+                let is_env = false;
+                if env_only && !is_env {
+                    return Err(ParseError::syntax(
+                        text,
+                        "expected an `EnvFilter` directive",
+                    ));
+                }
+                Ok(directive)
+            });
+            match directive {
+                Ok(directive) => {
+                    self.filter
+                        .add_directive(directive)
+                        .expect("paths were validated");
+                }
+                Err(e) => {
+                    self.error.get_or_insert(e);
+                }
+            }
+        }
+        self
+    }
+}
+
+// === impl InitError ===
+
+impl fmt::Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ErrorKind::Parse(ref e) => write!(f, "invalid directive: {}", e),
+            ErrorKind::Init(ref e) => fmt::Display::fmt(e, f),
+        }
+    }
+}
+
+impl Error for InitError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self.kind {
+            ErrorKind::Parse(ref e) => Some(e),
+            ErrorKind::Init(ref e) => Some(e),
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use crate::LineFilter;
+use tracing_core::Metadata;
+
+impl LineFilter {
+    /// Enable the span that [`#[tracing::instrument]`][instrument] creates for
+    /// the function at `path` (such as `my_crate::db::query`), on whichever
+    /// line it is reported at.
+    ///
+    /// The span for an instrumented function is reported on the line of the
+    /// `#[instrument]` attribute, rather than the line with the function's
+    /// name or the first line of its body, and the attribute moves whenever
+    /// a doc comment or another attribute is added above it. Rather than a
+    /// line, this entry matches the span by the function's module and name,
+    /// which is how `#[instrument]` names its spans, and reports each match
+    /// as the `module@line` entry for the line that the span was actually
+    /// reported at. Functions in `impl` blocks are named by their module and
+    /// function name, without the type (`my_crate::db::query` for
+    /// `my_crate::db::Client::query`).
+    ///
+    /// Functions instrumented with a custom `name` aren't matched, and
+    /// neither are spans created by hand in the same module with the same
+    /// name as the function. Since these entries aren't directives, they
+    /// aren't included in the filter's [`Display`] output or [statistics].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::prelude::*;
+    ///
+    /// mod db {
+    ///     /// Runs a query, returning whether its span is enabled.
+    ///     #[tracing::instrument]
+    ///     pub fn query(sql: &str) -> bool {
+    ///         !tracing::Span::current().is_none()
+    ///     }
+    /// }
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter.enable_instrumented_fn(&format!("{}::db::query", module_path!()));
+    /// let subscriber = tracing_subscriber::registry().with(filter);
+    ///
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     assert!(db::query("SELECT 1"));
+    /// });
+    /// ```
+    ///
+    /// [instrument]: https://docs.rs/tracing/latest/tracing/attr.instrument.html
+    /// [`Display`]: std::fmt::Display
+    /// [statistics]: Self::stats
+    pub fn enable_instrumented_fn(&mut self, path: &str) -> &mut Self {
+        let (module, name) = split_fn_path(path);
+        self.instrumented
+            .entry(module.to_owned())
+            .or_default()
+            .insert(name.to_owned());
+        self
+    }
+
+    /// Removes an entry added by [`enable_instrumented_fn`], returning
+    /// `false` if the filter had no entry for the function at `path`.
+    ///
+    /// [`enable_instrumented_fn`]: Self::enable_instrumented_fn
+    pub fn disable_instrumented_fn(&mut self, path: &str) -> bool {
+        let (module, name) = split_fn_path(path);
+        if !self
+            .instrumented
+            .get(module)
+            .is_some_and(|names| names.contains(name))
+        {
+            return false;
+        }
+        let names = self
+            .instrumented
+            .get_mut(module)
+            .expect("the module has an entry");
+        names.remove(name);
+        if names.is_empty() {
+            self.instrumented.remove(module);
+        }
+        true
+    }
+
+    /// Returns `true` if `metadata` describes the span for a function that
+    /// was enabled by [`enable_instrumented_fn`].
+    ///
+    /// [`enable_instrumented_fn`]: Self::enable_instrumented_fn
+    pub(crate) fn is_instrumented_fn(&self, metadata: &Metadata<'_>) -> bool {
+        if !metadata.is_span() || self.instrumented.is_empty() {
+            return false;
+        }
+        crate::module_keys(metadata).any(|module| {
+            self.instrumented
+                .get(module)
+                .is_some_and(|names| names.contains(metadata.name()))
+        })
+    }
+}
+
+/// Splits the path to a function into its module and name.
+fn split_fn_path(path: &str) -> (&str, &str) {
+    path.rsplit_once("::").unwrap_or(("", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::prelude::*;
+
+    #[test]
+    fn instrumented_fns_match_by_target() {
+        let mut filter = LineFilter::default();
+        filter.enable_instrumented_fn("my_service::db::query");
+        let subscriber = tracing_subscriber::registry().with(filter);
+
+        tracing::subscriber::with_default(subscriber, || {
+            assert!(!tracing::info_span!(target: "my_service::db", "query").is_disabled());
+            assert!(tracing::info_span!(target: "my_service::db", "insert").is_disabled());
+            assert!(tracing::info_span!(target: "my_service::api", "query").is_disabled());
+        });
+    }
+}
@@ -0,0 +1,791 @@
+//! A [JSON-RPC 2.0][jsonrpc] server for changing a filter over stdio or a
+//! socket.
+//!
+//! This is intended for IDE extensions and wrapper tools that launch the
+//! program being traced as a child process, and want to change its filter
+//! while it runs, and for tools that connect to a running service, such as
+//! the terminal UI of `cargo line-filter tui`. Each request and response is
+//! a JSON object on a single line. The following methods are supported:
+//!
+//! | Method                      | Params                      | Result                          |
+//! |-----------------------------|-----------------------------|---------------------------------|
+//! | `filter/add`                | `{"directive": DIRECTIVE}`  | The command output, as a string |
+//! | `filter/remove`             | `{"directive": DIRECTIVE}`  | The command output, as a string |
+//! | `filter/activateProfile`    | `{"profile": NAME}`         | The command output, as a string |
+//! | `filter/deactivateProfile`  | `{"profile": NAME}`         | The command output, as a string |
+//! | `filter/list`               | none                        | An array of directive strings   |
+//! | `filter/stats`              | none                        | A [`StatsSnapshot`]             |
+//! | `filter/health`             | none                        | A [`HealthSnapshot`]            |
+//! | `filter/callsites`          | `{"prefix": PREFIX}`        | An array of callsite objects    |
+//! | `filter/subscribe`          | none                        | `true`                          |
+//! | `filter/authenticate`       | `{"token": TOKEN}`          | `true`                          |
+//!
+//! `filter/add` and `filter/remove` behave like the `enable` and `disable`
+//! [`Command`]s, and `filter/activateProfile` and `filter/deactivateProfile`
+//! behave like the `activate` and `deactivate` commands. After a client
+//! calls `filter/subscribe`, each change made through the server is followed
+//! by a `filter/changed` notification whose params are
+//! `{"directives": [...]}`, listing the filter's directives as `filter/list`
+//! does.
+//!
+//! `filter/callsites` lists the callsites recorded since
+//! [`LineFilter::record_callsites`] was called, in the modules whose paths
+//! start with `prefix` (or in every module, if the params are left out), in
+//! the same order as [`LineFilter::known_callsites`]. Each callsite is an
+//! object with the `directive` that would enable it (a `module@line` string,
+//! or `null` if the callsite has no line number), its `kind` (`"span"` or
+//! `"event"`), `name`, `level`, `file`, and `fields`, and whether the filter
+//! has it `enabled`.
+//!
+//! If the server has a [token], each connection must call
+//! `filter/authenticate` with it before calling any other method, and
+//! other calls fail with the `-32001` error code, recording each of them as
+//! a `WARN` audit event. The token is read from the `LINE_FILTER_TOKEN`
+//! environment variable, or set with [`Server::token`], and
+//! [`serve_listener`] won't accept connections without one, since any
+//! program that can reach a socket could otherwise change the filter,
+//! including web pages, which browsers let send requests to any port. The
+//! token is sent in plain text over the socket, so to serve clients over a
+//! network that others can watch, wrap each connection in a TLS session
+//! with [`serve_listener_with`].
+//!
+//! Errors use the standard JSON-RPC error codes, or `-32000` if a command
+//! fails, including when the filter's [command limit] has been reached, or
+//! when the filter is [read-only] and the method would change it. Lines
+//! longer than 64 KiB are answered with a parse error, without being
+//! buffered. Each request for a supported method is recorded as an audit
+//! event with the `jsonrpc` transport and, for a socket, the client's
+//! address; see [`Command::apply`] for details.
+//! Only the methods that run a command count against the command limit.
+//!
+//! Since this requires a JSON parser, it is only available when the
+//! `jsonrpc` feature flag is enabled.
+//!
+//! # Examples
+//!
+//! Serving requests on the program's standard input and output:
+//!
+//! ```no_run
+//! use tracing_line_filter::{jsonrpc, LineFilter};
+//! use tracing_subscriber::{prelude::*, reload};
+//!
+//! let (filter, handle) = reload::Layer::new(LineFilter::default());
+//! tracing_subscriber::registry()
+//!     // Standard output is used by the server.
+//!     .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+//!     .with(filter)
+//!     .init();
+//!
+//! std::thread::spawn(move || jsonrpc::Server::new(handle).serve_stdio());
+//! ```
+//!
+//! Serving requests on a TCP port, such as for `cargo line-filter tui`:
+//!
+//! ```no_run
+//! use std::net::TcpListener;
+//! use tracing_line_filter::{jsonrpc, LineFilter};
+//! use tracing_subscriber::{prelude::*, reload};
+//!
+//! let (filter, handle) = reload::Layer::new(LineFilter::default());
+//! tracing_subscriber::registry()
+//!     .with(tracing_subscriber::fmt::layer())
+//!     .with(filter)
+//!     .init();
+//!
+//! std::thread::spawn(move || {
+//!     let listener = TcpListener::bind("127.0.0.1:7070").unwrap();
+//!     // The token could also be read from `LINE_FILTER_TOKEN`.
+//!     let token = std::env::var("MY_APP_CONTROL_TOKEN").unwrap();
+//!     jsonrpc::Server::new(handle)
+//!         .token(token)
+//!         .serve_listener(&listener)
+//! });
+//! ```
+//!
+//! [jsonrpc]: https://www.jsonrpc.org/specification
+//! [token]: Server::token
+//! [`serve_listener`]: Server::serve_listener
+//! [`serve_listener_with`]: Server::serve_listener_with
+//! [`Command`]: crate::Command
+//! [`StatsSnapshot`]: crate::StatsSnapshot
+//! [`HealthSnapshot`]: crate::HealthSnapshot
+//! [`Command::apply`]: crate::Command::apply
+//! [command limit]: crate::LineFilter::limit_commands
+//! [read-only]: crate::LineFilter::read_only_commands
+use crate::{audit, auth::Token, Command, LineFilter};
+use serde_json::{json, Value};
+use std::{
+    fmt,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+};
+use tracing_core::Subscriber;
+use tracing_subscriber::reload;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const COMMAND_FAILED: i64 = -32000;
+const UNAUTHORIZED: i64 = -32001;
+
+/// The longest request line that is read, in bytes.
+const MAX_LINE_LEN: usize = 64 * 1024;
+
+/// A method's result, or an error code and message.
+type Response = Result<Value, (i64, String)>;
+
+/// Serves JSON-RPC requests that change a [`LineFilter`].
+///
+/// See the [module-level documentation](self) for details.
+#[derive(Debug)]
+pub struct Server<S> {
+    handle: reload::Handle<LineFilter, S>,
+    token: Option<Token>,
+    /// Whether the client being served has called `filter/authenticate`
+    /// with the token.
+    authenticated: bool,
+    subscribed: bool,
+    /// The address of the client being served, if it is known.
+    peer: Option<String>,
+}
+
+impl<S> Server<S>
+where
+    S: Subscriber,
+{
+    /// Returns a new `Server` that changes the [`LineFilter`] behind
+    /// `handle`.
+    ///
+    /// The server's [token] is read from the `LINE_FILTER_TOKEN` environment
+    /// variable, if it is set.
+    ///
+    /// [token]: Self::token
+    pub fn new(handle: reload::Handle<LineFilter, S>) -> Self {
+        Self {
+            handle,
+            token: Token::from_env(),
+            authenticated: false,
+            subscribed: false,
+            peer: None,
+        }
+    }
+
+    /// Sets the token that clients must send to `filter/authenticate` before
+    /// calling any other method, replacing the one read from the
+    /// `LINE_FILTER_TOKEN` environment variable.
+    ///
+    /// The token should be a long random string, such as one generated for
+    /// each deployment, since anyone who has it can change the filter.
+    pub fn token(&mut self, token: impl Into<String>) -> &mut Self {
+        self.token = Some(Token::new(token));
+        self
+    }
+
+    /// Serves requests on standard input, writing responses to standard
+    /// output, until standard input is closed.
+    pub fn serve_stdio(&mut self) -> io::Result<()> {
+        let stdin = io::stdin();
+        self.serve(stdin.lock(), io::stdout())
+    }
+
+    /// Accepts connections on `listener`, serving them one at a time.
+    ///
+    /// Each connection is served as if by [`serve`], and starts out
+    /// without a `filter/authenticate` or `filter/subscribe`.
+    ///
+    /// This only returns if accepting a connection fails, or if the
+    /// subscriber containing the filter has been dropped.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error right away if the server has no [token].
+    ///
+    /// [`serve`]: Self::serve
+    /// [token]: Self::token
+    pub fn serve_listener(&mut self, listener: &TcpListener) -> io::Result<()> {
+        self.serve_listener_with(listener, Ok)
+    }
+
+    /// Accepts connections on `listener`, passing each one to `accept` and
+    /// serving the stream it returns, one at a time.
+    ///
+    /// This is like [`serve_listener`], except that `accept` can wrap each
+    /// connection in another stream, such as a TLS session, so that the
+    /// server can be reached over a network that others can watch. If
+    /// `accept` fails, such as because a TLS handshake failed, the
+    /// connection is closed, and the server keeps accepting others.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error right away if the server has no [token].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use std::{io, net::TcpStream};
+    /// # fn handshake(stream: TcpStream) -> io::Result<TcpStream> { Ok(stream) }
+    /// use std::net::TcpListener;
+    /// use tracing_line_filter::{jsonrpc, LineFilter};
+    /// use tracing_subscriber::{prelude::*, reload};
+    ///
+    /// let (filter, handle) = reload::Layer::new(LineFilter::default());
+    /// tracing_subscriber::registry().with(filter).init();
+    ///
+    /// let listener = TcpListener::bind("0.0.0.0:7070").unwrap();
+    /// // `handshake` returns the connection wrapped in a TLS session, such
+    /// // as a `rustls::StreamOwned`.
+    /// jsonrpc::Server::new(handle)
+    ///     .serve_listener_with(&listener, handshake)
+    ///     .unwrap();
+    /// ```
+    ///
+    /// [`serve_listener`]: Self::serve_listener
+    /// [token]: Self::token
+    pub fn serve_listener_with<T>(
+        &mut self,
+        listener: &TcpListener,
+        mut accept: impl FnMut(TcpStream) -> io::Result<T>,
+    ) -> io::Result<()>
+    where
+        T: Read + Write,
+    {
+        if self.token.is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "JSON-RPC can't be served on a listener without a token",
+            ));
+        }
+        loop {
+            let (stream, addr) = listener.accept()?;
+            let stream = match accept(stream) {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            self.peer = Some(addr.to_string());
+            self.authenticated = false;
+            self.subscribed = false;
+            let result = self.serve_stream(stream);
+            self.peer = None;
+            if let Err(e) = result {
+                // A client that goes away shouldn't stop the server, but
+                // there's no point in serving without a filter to change.
+                if self.handle.with_current(|_| ()).is_err() {
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// Serves requests read from `reader`, one per line, writing responses
+    /// to `writer`, until `reader` reaches the end of its input.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if reading or writing fails, or if the
+    /// subscriber containing the filter has been dropped. Malformed requests,
+    /// including lines longer than 64 KiB, are answered with JSON-RPC errors
+    /// instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::{jsonrpc, LineFilter};
+    /// use tracing_subscriber::{prelude::*, reload};
+    ///
+    /// let (filter, handle) = reload::Layer::new(LineFilter::default());
+    /// let _subscriber = tracing_subscriber::registry().with(filter);
+    ///
+    /// let input = r#"
+    /// {"jsonrpc":"2.0","id":1,"method":"filter/add","params":{"directive":"my_crate::db@142"}}
+    /// {"jsonrpc":"2.0","id":2,"method":"filter/list"}
+    /// "#;
+    /// let mut output = Vec::new();
+    /// jsonrpc::Server::new(handle)
+    ///     .serve(input.as_bytes(), &mut output)
+    ///     .unwrap();
+    ///
+    /// let output = String::from_utf8(output).unwrap();
+    /// let mut responses = output.lines();
+    /// assert_eq!(
+    ///     responses.next().unwrap(),
+    ///     r#"{"id":1,"jsonrpc":"2.0","result":"enabled my_crate::db@142"}"#,
+    /// );
+    /// assert_eq!(
+    ///     responses.next().unwrap(),
+    ///     r#"{"id":2,"jsonrpc":"2.0","result":["my_crate::db@142"]}"#,
+    /// );
+    /// ```
+    pub fn serve(&mut self, mut reader: impl BufRead, mut writer: impl Write) -> io::Result<()> {
+        let mut line = Vec::new();
+        while let Some(messages) = self.read_request(&mut reader, &mut line)? {
+            for message in messages {
+                write_message(&mut writer, &message)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Serves requests read from `stream`, one per line, writing responses
+    /// back to it, until it reaches the end of its input.
+    ///
+    /// This is like [`serve`], for streams that can't be split into a
+    /// reader and a writer, such as TLS sessions.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error in the same cases as [`serve`].
+    ///
+    /// [`serve`]: Self::serve
+    pub fn serve_stream(&mut self, stream: impl Read + Write) -> io::Result<()> {
+        let mut stream = BufReader::new(stream);
+        let mut line = Vec::new();
+        while let Some(messages) = self.read_request(&mut stream, &mut line)? {
+            for message in messages {
+                write_message(stream.get_mut(), &message)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the next request from `reader` and calls its method, returning
+    /// the messages to write in response, or `None` once `reader` reaches the
+    /// end of its input.
+    fn read_request(
+        &mut self,
+        reader: &mut impl BufRead,
+        line: &mut Vec<u8>,
+    ) -> io::Result<Option<Vec<Value>>> {
+        match read_line(reader, line)? {
+            Line::Read => {}
+            Line::TooLong => {
+                let message = format!("request is longer than {} bytes", MAX_LINE_LEN);
+                return Ok(Some(vec![error(Value::Null, PARSE_ERROR, message)]));
+            }
+            Line::Eof => return Ok(None),
+        }
+        if line.iter().all(u8::is_ascii_whitespace) {
+            return Ok(Some(Vec::new()));
+        }
+
+        let request = match serde_json::from_slice::<Value>(line) {
+            Ok(request) => request,
+            Err(e) => return Ok(Some(vec![error(Value::Null, PARSE_ERROR, e.to_string())])),
+        };
+        let id = request.get("id").cloned();
+        let (result, changed) = self.call(&request)?;
+        let mut messages = Vec::new();
+        // Requests without an `id` are notifications, which aren't answered.
+        if let Some(id) = id {
+            messages.push(match result {
+                Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                Err((code, message)) => error(id, code, message),
+            });
+        }
+
+        if changed && self.subscribed {
+            messages.push(json!({
+                "jsonrpc": "2.0",
+                "method": "filter/changed",
+                "params": { "directives": self.list()? },
+            }));
+        }
+        Ok(Some(messages))
+    }
+
+    /// Calls the method named by `request`, returning its result and whether
+    /// the filter was changed.
+    fn call(&mut self, request: &Value) -> io::Result<(Response, bool)> {
+        let method = match request["method"].as_str() {
+            Some(method) if request["jsonrpc"] == "2.0" => method,
+            _ => {
+                let error = (INVALID_REQUEST, "invalid JSON-RPC 2.0 request".to_owned());
+                return Ok((Err(error), false));
+            }
+        };
+
+        if method == "filter/authenticate" {
+            return Ok((self.authenticate(&request["params"]), false));
+        }
+        if self.token.is_some() && !self.authenticated {
+            self.request(&method).record_unauthorized();
+            let message = "call `filter/authenticate` with the server's token first";
+            return Ok((Err((UNAUTHORIZED, message.to_owned())), false));
+        }
+
+        if let "filter/list" | "filter/stats" | "filter/health" | "filter/callsites"
+        | "filter/subscribe" = method
+        {
+            self.request(&method).record(None);
+        }
+        let command = match method {
+            "filter/list" => return Ok((Ok(self.list()?.into()), false)),
+            "filter/stats" => {
+                let snapshot = self
+                    .handle
+                    .with_current(LineFilter::stats_snapshot)
+                    .map_err(io::Error::other)?;
+                let snapshot = serde_json::to_value(snapshot).map_err(io::Error::other)?;
+                return Ok((Ok(snapshot), false));
+            }
+            "filter/health" => {
+                let snapshot = self
+                    .handle
+                    .with_current(LineFilter::health_snapshot)
+                    .map_err(io::Error::other)?;
+                let snapshot = serde_json::to_value(snapshot).map_err(io::Error::other)?;
+                return Ok((Ok(snapshot), false));
+            }
+            "filter/callsites" => {
+                let prefix = match request["params"]["prefix"] {
+                    Value::Null => "",
+                    Value::String(ref prefix) => prefix,
+                    _ => {
+                        let error = (INVALID_PARAMS, "expected a `prefix` string".to_owned());
+                        return Ok((Err(error), false));
+                    }
+                };
+                let callsites = self
+                    .handle
+                    .with_current(|filter| callsites(filter, prefix))
+                    .map_err(io::Error::other)?;
+                return Ok((Ok(callsites), false));
+            }
+            "filter/subscribe" => {
+                self.subscribed = true;
+                return Ok((Ok(true.into()), false));
+            }
+            "filter/add" | "filter/remove" => {
+                let directive = match request["params"]["directive"].as_str() {
+                    Some(directive) => directive,
+                    None => {
+                        let error = (INVALID_PARAMS, "expected a `directive` string".to_owned());
+                        return Ok((Err(error), false));
+                    }
+                };
+                let name = if method == "filter/add" {
+                    "enable"
+                } else {
+                    "disable"
+                };
+                format!("{} {}", name, directive).parse::<Command>()
+            }
+            "filter/activateProfile" | "filter/deactivateProfile" => {
+                let profile = match request["params"]["profile"].as_str() {
+                    Some(profile) => profile.to_owned(),
+                    None => {
+                        let error = (INVALID_PARAMS, "expected a `profile` string".to_owned());
+                        return Ok((Err(error), false));
+                    }
+                };
+                if method == "filter/activateProfile" {
+                    Ok(Command::Activate(profile))
+                } else {
+                    Ok(Command::Deactivate(profile))
+                }
+            }
+            _ => {
+                let error = (METHOD_NOT_FOUND, format!("unknown method '{}'", method));
+                return Ok((Err(error), false));
+            }
+        };
+
+        let command = match command {
+            Ok(command) => command,
+            Err(e) => return Ok((Err((INVALID_PARAMS, e.to_string())), false)),
+        };
+        Ok(
+            match command.apply(&self.handle, "jsonrpc", self.peer.as_deref()) {
+                Ok(output) => (Ok(output.into()), true),
+                // There's no filter left to change.
+                Err(e) if e.is_reload() => return Err(io::Error::other(e)),
+                Err(e) => (Err((COMMAND_FAILED, e.to_string())), false),
+            },
+        )
+    }
+
+    /// Answers `filter/authenticate`, which always succeeds if the server
+    /// has no token.
+    fn authenticate(&mut self, params: &Value) -> Response {
+        let token = match self.token {
+            Some(ref token) => token,
+            None => return Ok(true.into()),
+        };
+        if params["token"]
+            .as_str()
+            .is_some_and(|sent| token.matches(sent))
+        {
+            self.authenticated = true;
+            return Ok(true.into());
+        }
+        self.request(&"filter/authenticate").record_unauthorized();
+        Err((UNAUTHORIZED, "invalid token".to_owned()))
+    }
+
+    fn request<'a>(&'a self, method: &'a dyn fmt::Display) -> audit::Request<'a> {
+        audit::Request {
+            transport: "jsonrpc",
+            peer: self.peer.as_deref(),
+            command: method,
+        }
+    }
+
+    fn list(&self) -> io::Result<Vec<String>> {
+        self.handle
+            .with_current(|filter| format!("{:#}", filter).lines().map(str::to_owned).collect())
+            .map_err(io::Error::other)
+    }
+}
+
+/// Returns the `filter/callsites` result for the callsites in modules that
+/// start with `prefix`.
+pub(crate) fn callsites(filter: &LineFilter, prefix: &str) -> Value {
+    filter
+        .known_callsites()
+        .filter_map(|metadata| {
+            let module = crate::module_keys(metadata).find(|key| key.starts_with(prefix))?;
+            let fields = metadata
+                .fields()
+                .iter()
+                .map(|field| field.name())
+                .collect::<Vec<_>>();
+            Some(json!({
+                "directive": metadata.line().map(|line| format!("{}@{}", module, line)),
+                "kind": if metadata.is_span() { "span" } else { "event" },
+                "name": metadata.name(),
+                "level": metadata.level().as_str(),
+                "file": metadata.file(),
+                "fields": fields,
+                "enabled": filter.explain(metadata).is_enabled(),
+            }))
+        })
+        .collect()
+}
+
+/// What [`read_line`] read.
+enum Line {
+    Read,
+    TooLong,
+    Eof,
+}
+
+/// Reads a line of at most [`MAX_LINE_LEN`] bytes into `line`. If the line
+/// is longer, the rest of it is skipped without being buffered.
+fn read_line(reader: &mut impl BufRead, line: &mut Vec<u8>) -> io::Result<Line> {
+    line.clear();
+    let read = reader
+        .take(MAX_LINE_LEN as u64 + 1)
+        .read_until(b'\n', line)?;
+    if read == 0 {
+        return Ok(Line::Eof);
+    }
+    if line.ends_with(b"\n") || line.len() <= MAX_LINE_LEN {
+        return Ok(Line::Read);
+    }
+
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            break;
+        }
+        match buf.iter().position(|&b| b == b'\n') {
+            Some(i) => {
+                reader.consume(i + 1);
+                break;
+            }
+            None => {
+                let len = buf.len();
+                reader.consume(len);
+            }
+        }
+    }
+    Ok(Line::TooLong)
+}
+
+fn error(id: Value, code: i64, message: String) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    writeln!(writer, "{}", message)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::prelude::*;
+
+    fn serve(input: &[u8]) -> Vec<Value> {
+        let (filter, handle) = reload::Layer::new(LineFilter::default());
+        let _subscriber = tracing_subscriber::registry().with(filter);
+        let mut output = Vec::new();
+        Server::new(handle).serve(input, &mut output).unwrap();
+        output
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn an_oversized_line_is_a_parse_error() {
+        let mut input = vec![b' '; MAX_LINE_LEN * 2];
+        input.extend_from_slice(b"\n{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"filter/list\"}\n");
+        let responses = serve(&input);
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0]["error"]["code"], PARSE_ERROR);
+        assert_eq!(responses[1]["id"], 1);
+        assert_eq!(responses[1]["result"], json!([]));
+    }
+
+    #[test]
+    fn an_oversized_last_line_is_a_parse_error() {
+        let responses = serve(&vec![b'x'; MAX_LINE_LEN + 1]);
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0]["error"]["code"], PARSE_ERROR);
+    }
+
+    #[test]
+    fn a_line_at_the_limit_is_read() {
+        let request = br#"{"jsonrpc":"2.0","id":1,"method":"filter/list"}"#;
+        let mut input = request.to_vec();
+        input.resize(MAX_LINE_LEN, b' ');
+        let responses = serve(&input);
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0]["result"], json!([]));
+    }
+
+    #[test]
+    fn invalid_utf8_is_a_parse_error() {
+        let responses = serve(b"\xff\n");
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0]["error"]["code"], PARSE_ERROR);
+    }
+
+    #[test]
+    fn serves_connections_one_at_a_time() {
+        let (filter, handle) = reload::Layer::new(LineFilter::default());
+        let _subscriber = tracing_subscriber::registry().with(filter);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut server = Server::new(handle.clone());
+        server.token("hunter2");
+        std::thread::spawn(move || server.serve_listener(&listener));
+
+        // Each connection has to authenticate again.
+        let request = |request: &str| {
+            let mut stream = std::net::TcpStream::connect(addr).unwrap();
+            let authenticate = json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "method": "filter/authenticate",
+                "params": { "token": "hunter2" },
+            });
+            writeln!(stream, "{}\n{}", authenticate, request).unwrap();
+            let mut responses = BufReader::new(stream).lines();
+            let response = responses.next().unwrap().unwrap();
+            assert_eq!(
+                serde_json::from_str::<Value>(&response).unwrap()["result"],
+                true
+            );
+            let response = responses.next().unwrap().unwrap();
+            serde_json::from_str::<Value>(&response).unwrap()
+        };
+        let response = request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"filter/add","params":{"directive":"my_crate::db@142"}}"#,
+        );
+        assert_eq!(response["result"], "enabled my_crate::db@142");
+        let response = request(r#"{"jsonrpc":"2.0","id":2,"method":"filter/list"}"#);
+        assert_eq!(response["result"], json!(["my_crate::db@142"]));
+    }
+
+    #[test]
+    fn serves_the_streams_that_connections_are_wrapped_in() {
+        /// A stream, such as a TLS session, that wraps a connection.
+        struct Wrapped(std::net::TcpStream);
+        impl Read for Wrapped {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.0.read(buf)
+            }
+        }
+        impl Write for Wrapped {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.write(buf)
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                self.0.flush()
+            }
+        }
+
+        let (filter, handle) = reload::Layer::new(LineFilter::default());
+        let _subscriber = tracing_subscriber::registry().with(filter);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut server = Server::new(handle.clone());
+        server.token("hunter2");
+        let mut handshakes = 0;
+        std::thread::spawn(move || {
+            server.serve_listener_with(&listener, |stream| {
+                handshakes += 1;
+                if handshakes == 1 {
+                    return Err(io::Error::other("handshake failed"));
+                }
+                Ok(Wrapped(stream))
+            })
+        });
+
+        let send = |request: &str| {
+            let mut stream = std::net::TcpStream::connect(addr).unwrap();
+            writeln!(stream, "{}", request).unwrap();
+            let mut response = String::new();
+            // A connection that's closed with the request unread may be
+            // reset, rather than reaching the end of its input.
+            let _ = BufReader::new(stream).read_line(&mut response);
+            response
+        };
+        let authenticate = r#"{"jsonrpc":"2.0","id":1,"method":"filter/authenticate","params":{"token":"hunter2"}}"#;
+        // The first connection's handshake fails, so it's closed unanswered.
+        assert_eq!(send(authenticate), "");
+        let response = serde_json::from_str::<Value>(&send(authenticate)).unwrap();
+        assert_eq!(response["result"], true);
+    }
+
+    #[test]
+    fn requests_need_the_token() {
+        let (filter, handle) = reload::Layer::new(LineFilter::default());
+        let _subscriber = tracing_subscriber::registry().with(filter);
+        let mut server = Server::new(handle.clone());
+        server.token("hunter2");
+
+        let input = br#"
+{"jsonrpc":"2.0","id":1,"method":"filter/add","params":{"directive":"my_crate::db@142"}}
+{"jsonrpc":"2.0","id":2,"method":"filter/authenticate","params":{"token":"hunter"}}
+{"jsonrpc":"2.0","id":3,"method":"filter/list"}
+{"jsonrpc":"2.0","id":4,"method":"filter/authenticate","params":{"token":"hunter2"}}
+{"jsonrpc":"2.0","id":5,"method":"filter/list"}
+"#;
+        let mut output = Vec::new();
+        server.serve(&input[..], &mut output).unwrap();
+        let responses = output
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice::<Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(responses[0]["error"]["code"], UNAUTHORIZED);
+        assert_eq!(responses[1]["error"]["code"], UNAUTHORIZED);
+        assert_eq!(responses[2]["error"]["code"], UNAUTHORIZED);
+        assert_eq!(responses[3]["result"], true);
+        assert_eq!(responses[4]["result"], json!([]));
+
+        // A listener isn't served at all without a token.
+        server.token = None;
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let error = server.serve_listener(&listener).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,223 @@
+use crate::LineFilter;
+use std::{borrow::Cow, fmt, iter::FromIterator};
+use tracing_core::Level;
+
+/// A set of [`Level`]s, which limits the spans and events a `module@line` or
+/// `file@line` entry enables to those at one of the levels in the set.
+///
+/// When several macros sit on the same line (or a `#[instrument]` attribute
+/// and the events in the function it creates), a line entry enables all of
+/// them. An entry with a level set, such as `my_crate::db@142=trace+debug`,
+/// only enables the `TRACE` and `DEBUG` spans and events on its line, and
+/// leaves an `INFO` event sharing the line to the rest of the filter. See
+/// [`Directive::Module`] and [the `FromStr` implementation for
+/// `LineFilter`][syntax] for the directive syntax.
+///
+/// The `Display` implementation formats the levels from most to least
+/// verbose, separated by `+`, such as `trace+debug`. When the `serde`
+/// feature flag is enabled, this implements `serde::Serialize`, as that
+/// string.
+///
+/// # Examples
+///
+/// ```
+/// use tracing::Level;
+/// use tracing_line_filter::LevelSet;
+///
+/// let levels: LevelSet = vec![Level::DEBUG, Level::TRACE].into_iter().collect();
+/// assert!(levels.contains(Level::TRACE));
+/// assert!(!levels.contains(Level::INFO));
+/// assert_eq!(levels.to_string(), "trace+debug");
+/// ```
+///
+/// [`Directive::Module`]: crate::Directive::Module
+/// [syntax]: crate::LineFilter#impl-FromStr-for-LineFilter
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct LevelSet {
+    bits: u8,
+}
+
+/// Every level, from most to least verbose.
+const ALL: [Level; 5] = [
+    Level::TRACE,
+    Level::DEBUG,
+    Level::INFO,
+    Level::WARN,
+    Level::ERROR,
+];
+
+impl LineFilter {
+    /// Returns `true` if the level set of the entry for `location` on `line`
+    /// (if it has one) includes `level`.
+    pub(crate) fn entry_allows(&self, location: &str, line: u32, level: &Level) -> bool {
+        self.levels
+            .get(&(Cow::Borrowed(location), line))
+            .is_none_or(|levels| levels.contains(*level))
+    }
+
+    /// Sets the level set of `entry`, or removes it if `levels` is `None`.
+    pub(crate) fn set_levels(&mut self, entry: (Cow<'static, str>, u32), levels: Option<LevelSet>) {
+        match levels {
+            Some(levels) => {
+                self.levels.insert(entry, levels);
+            }
+            None => {
+                self.levels.remove(&entry);
+            }
+        }
+    }
+}
+
+// === impl LevelSet ===
+
+impl LevelSet {
+    /// Returns an empty set.
+    pub const fn new() -> Self {
+        Self { bits: 0 }
+    }
+
+    /// Adds `level` to this set.
+    pub fn insert(&mut self, level: Level) -> &mut Self {
+        self.bits |= bit(level);
+        self
+    }
+
+    /// Returns `true` if this set includes `level`.
+    pub fn contains(&self, level: Level) -> bool {
+        self.bits & bit(level) != 0
+    }
+
+    /// Returns `true` if this set has no levels.
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    /// Returns the levels in this set, from most to least verbose.
+    pub fn iter(&self) -> impl Iterator<Item = Level> + '_ {
+        ALL.iter()
+            .copied()
+            .filter(move |&level| self.contains(level))
+    }
+}
+
+impl FromIterator<Level> for LevelSet {
+    fn from_iter<I: IntoIterator<Item = Level>>(iter: I) -> Self {
+        let mut levels = Self::new();
+        for level in iter {
+            levels.insert(level);
+        }
+        levels
+    }
+}
+
+impl fmt::Display for LevelSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for level in self.iter() {
+            if !first {
+                f.write_str("+")?;
+            }
+            f.write_str(&level.as_str().to_ascii_lowercase())?;
+            first = false;
+        }
+        Ok(())
+    }
+}
+
+/// Serializes a `LevelSet` as a string, such as `trace+debug`.
+///
+/// This is only available when the `serde` feature flag is enabled.
+#[cfg(feature = "serde")]
+impl serde::Serialize for LevelSet {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+fn bit(level: Level) -> u8 {
+    let index = ALL
+        .iter()
+        .position(|&l| l == level)
+        .expect("every level is in `ALL`");
+    1 << index
+}
+
+/// Parses a level set, such as `trace+debug`.
+pub(crate) fn parse(s: &str) -> Option<LevelSet> {
+    s.split('+')
+        .map(|name| {
+            ALL.iter()
+                .copied()
+                .find(|level| level.as_str().eq_ignore_ascii_case(name))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Directive, LineFilter};
+
+    fn directive(directive: &str) -> Directive {
+        directive.parse().unwrap()
+    }
+
+    #[test]
+    fn deactivating_a_profile_restores_the_level_set_it_replaced() {
+        let mut filter: LineFilter = "m@2".parse().unwrap();
+        filter
+            .define_profile("debug", vec![directive("m@2=debug")])
+            .unwrap();
+
+        filter.activate_profile("debug").unwrap();
+        assert_eq!(filter.to_string(), "m@2=debug");
+        filter.deactivate_profile("debug").unwrap();
+        assert_eq!(filter.to_string(), "m@2");
+    }
+
+    #[test]
+    fn deactivating_a_profile_restores_a_base_level_set() {
+        let mut filter: LineFilter = "m@2=info+warn".parse().unwrap();
+        filter
+            .define_profile("all", vec![directive("m@2")])
+            .unwrap()
+            .define_profile("trace", vec![directive("m@2=trace")])
+            .unwrap();
+
+        filter.activate_profile("all").unwrap();
+        filter.activate_profile("trace").unwrap();
+        assert_eq!(filter.to_string(), "m@2=trace");
+        filter.deactivate_profile("trace").unwrap();
+        assert_eq!(filter.to_string(), "m@2");
+        filter.deactivate_profile("all").unwrap();
+        assert_eq!(filter.to_string(), "m@2=info+warn");
+    }
+
+    #[test]
+    fn startup_level_sets_leave_the_base_entry_in_place() {
+        let mut filter: LineFilter = "m@2".parse().unwrap();
+        filter
+            .enable_during_startup(vec![directive("m@2=debug")], None)
+            .unwrap();
+        assert!(filter.directives().any(|d| d == directive("m@2=debug")));
+        // The entry is still saved as the filter's own, without the level set.
+        assert_eq!(filter.to_string(), "m@2");
+
+        filter.enable_during_startup(Vec::new(), None).unwrap();
+        assert_eq!(filter.directives().collect::<Vec<_>>(), [directive("m@2")]);
+    }
+
+    #[test]
+    fn ending_a_session_restores_the_level_set_it_replaced() {
+        use tracing_subscriber::{prelude::*, reload};
+
+        let (filter, handle) = reload::Layer::new("m@2=warn".parse::<LineFilter>().unwrap());
+        let _subscriber = tracing_subscriber::registry().with(filter);
+        let session =
+            LineFilter::start_session(&handle, "debug", vec![directive("m@2=debug")], None)
+                .unwrap();
+        let current = || handle.with_current(|filter| filter.to_string()).unwrap();
+        assert_eq!(current(), "m@2=debug");
+        session.end().unwrap();
+        assert_eq!(current(), "m@2=warn");
+    }
+}
@@ -99,38 +99,399 @@
 //! }
 //! ```
 //!
+//! The `EnvFilter` integration requires the `env-filter` feature flag, which
+//! is enabled by default. Builds that disable it (to avoid `EnvFilter`'s
+//! regex dependencies) can still decide about the spans and events that no
+//! entry matches with [`LineFilter::with_fallback`].
+//!
+//! ## Finding Line Numbers
+//!
+//! The [`cargo-line-filter`] subcommand lists every `tracing` span and event
+//! callsite in a Cargo workspace, along with the directive that enables it:
+//!
+//! ```sh
+//! cargo install cargo-line-filter
+//! cargo line-filter list
+//! ```
+//!
+//! When the `source` feature flag is enabled, the [`source`] module exposes
+//! the same callsite scanner as a library.
+//!
+//! ## Enabling Lines From an Editor
+//!
+//! When the `dap` feature flag is enabled, the [`dap`] module accepts
+//! logpoints from editors that speak the Debug Adapter Protocol, such as VS
+//! Code, and enables the spans and events on those lines in a running
+//! program. Tools that launch the program as a child process can instead
+//! enable the `jsonrpc` feature flag, and use the [`jsonrpc`] module to
+//! change its filter over standard input and output, or over a TCP socket,
+//! which `cargo line-filter tui` connects to for toggling entries from a
+//! terminal. The `http` feature flag's [`http`] module serves a web UI for
+//! browsing a running program's callsites and toggling their entries. On
+//! Unix, the `signal` feature flag enables `LineFilter::on_sigusr2`, which
+//! switches between a filter's named profiles when the process receives
+//! `SIGUSR2`.
+//!
+//! When spans are exported to OpenTelemetry, the `otel` feature flag's
+//! [`otel`] module provides a sampler that makes sure pinned spans are
+//! sampled.
+//!
+//! ## Metrics
+//!
+//! When the `metrics` feature flag is enabled, a `LineFilter` records the
+//! following metrics using the [`metrics`] crate, so that any `metrics`
+//! exporter (such as a Prometheus exporter) can show whether pinned lines
+//! are actually firing:
+//!
+//! | Metric                                              | Type    | Description |
+//! |-----------------------------------------------------|---------|-------------|
+//! | `tracing_line_filter_matches_total`                 | counter | Spans and events recorded because of an entry, labeled by `entry` (such as `my_crate::db@142`). |
+//! | `tracing_line_filter_suppressed_total`              | counter | Spans and events that the filter disabled. |
+//! | `tracing_line_filter_entries`                       | gauge   | The number of `(module, line)` and `(file, line)` entries. |
+//! | `tracing_line_filter_last_reload_timestamp_seconds` | gauge   | When the filter was last changed by a command, a config reload, or another control endpoint, in seconds since the Unix epoch. |
+//!
+//! `metrics` handles are created when callsites are registered, so the
+//! `metrics` recorder should be installed before the subscriber. Callsites
+//! that are never enabled are only checked once, rather than each time they
+//! are hit, so `tracing_line_filter_suppressed_total` only counts spans and
+//! events that had to be checked individually, such as those with
+//! [`EnvFilter`] directives that match on field values.
+//!
+//! ```
+//! # #[cfg(feature = "metrics")] {
+//! use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+//! use tracing_line_filter::LineFilter;
+//! use tracing_subscriber::prelude::*;
+//!
+//! let recorder = DebuggingRecorder::new();
+//! let snapshotter = recorder.snapshotter();
+//! recorder.install().unwrap();
+//!
+//! let mut filter = LineFilter::default();
+//! filter.enable_by_mod(module_path!(), line!() + 4);
+//! tracing_subscriber::registry().with(filter).init();
+//!
+//! for _ in 0..3 {
+//!     tracing::info!("pinned");
+//! }
+//!
+//! let matches = snapshotter
+//!     .snapshot()
+//!     .into_vec()
+//!     .into_iter()
+//!     .find(|(key, ..)| key.key().name() == "tracing_line_filter_matches_total")
+//!     .map(|(.., value)| value);
+//! assert_eq!(matches, Some(DebugValue::Counter(3)));
+//! # }
+//! ```
+//!
+//! ## WebAssembly
+//!
+//! The filter works on `wasm32-unknown-unknown` (in the browser) and on
+//! WASI. In the browser, times such as expiry deadlines and hit timestamps
+//! come from the browser's clock, using [`web-time`]. Since WebAssembly
+//! targets have no native absolute paths, `file@line` entries accept the
+//! relative paths that `file!()` reports there. Control endpoints that
+//! need a socket, a file system, or threads, such as [`dap`] and
+//! `LineFilter::on_sigusr2`, compile, but return errors (or, for
+//! `on_sigusr2`, aren't available) when the target doesn't support them.
+//!
 //! [`tracing`]: https://docs.rs/tracing
+//! [`cargo-line-filter`]: https://crates.io/crates/cargo-line-filter
+//! [`source`]: https://docs.rs/tracing-line-filter/latest/tracing_line_filter/source/index.html
+//! [`dap`]: https://docs.rs/tracing-line-filter/latest/tracing_line_filter/dap/index.html
+//! [`jsonrpc`]: https://docs.rs/tracing-line-filter/latest/tracing_line_filter/jsonrpc/index.html
+//! [`http`]: https://docs.rs/tracing-line-filter/latest/tracing_line_filter/http/index.html
+//! [`otel`]: https://docs.rs/tracing-line-filter/latest/tracing_line_filter/otel/index.html
+//! [`metrics`]: https://docs.rs/metrics
+//! [`web-time`]: https://docs.rs/web-time
 //! [spans]: https://docs.rs/tracing/latest/tracing/#spans
 //! [events]: https://docs.rs/tracing/latest/tracing/#events
 //! [`tracing-subscriber`]: https://docs.rs/tracing-subscriber
 //! [`EnvFilter`]: tracing_subscriber::EnvFilter
 //! [`Layer`]: tracing_subscriber::Layer
 
+use self::shared::Shared;
+use self::time::Instant;
 use std::borrow::Cow;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt;
+use std::panic::Location;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
 use tracing_core::{subscriber::Interest, Metadata, Subscriber};
+#[cfg(feature = "env-filter")]
+use tracing_subscriber::EnvFilter;
 use tracing_subscriber::{
-    filter::EnvFilter,
+    filter::LevelFilter,
     layer::{self, Layer},
 };
 
+mod alert;
+mod announce;
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+#[cfg(feature = "clap")]
+mod args;
+mod audit;
+#[cfg(feature = "jsonrpc")]
+mod auth;
+mod callsites;
+#[cfg(feature = "futures")]
+mod changes;
+mod command;
+mod condition;
+mod config;
+#[cfg(feature = "consul")]
+mod consul;
+mod crate_file;
+mod decisions;
+mod deny;
+mod diff;
+mod directive;
+mod entered;
+mod env;
+mod error;
+mod explain;
+mod fallback;
+mod fields;
+mod flight;
+mod flush;
+mod health;
+mod init;
+mod instrument;
+mod levels;
+mod lint;
+mod lru;
+mod mark;
+#[cfg(feature = "futures")]
+mod matches;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod module_map;
+mod note;
+mod overhead;
+mod overlay;
+mod per_layer;
+mod prefix;
+mod profile;
+mod provenance;
+mod publish;
+mod record;
+mod relevel;
+mod retarget;
+mod rewrite;
+mod session;
+mod set;
+mod shared;
+#[cfg(all(unix, feature = "signal"))]
+mod signal;
+mod simulate;
+mod stack;
+mod startup;
+mod static_filter;
+mod stats;
+mod subtree;
+mod time;
+mod validation;
+mod version;
+mod warning;
+mod writer;
+
+#[cfg(feature = "dap")]
+pub mod dap;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "jsonrpc")]
+pub mod jsonrpc;
+#[cfg(feature = "otel")]
+pub mod otel;
+#[cfg(feature = "source")]
+pub mod source;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+#[cfg(any(feature = "consul", feature = "tokio"))]
+mod watch;
+
+#[cfg(feature = "clap")]
+pub use self::args::{ArgsError, LineFilterArgs};
+#[cfg(feature = "futures")]
+pub use self::changes::{Changes, FilterChange};
+pub use self::command::{Command, CommandError};
+pub use self::config::FilterConfig;
+pub use self::deny::Precedence;
+pub use self::diff::{DirectiveChange, FilterDiff};
+#[doc(hidden)]
+pub use self::directive::__validate_directives;
+pub use self::directive::{Directive, ParseError};
+pub use self::error::Error;
+pub use self::explain::{EnvDecision, Explanation};
+pub use self::fields::SpanFields;
+pub use self::flight::FlightRecorder;
+pub use self::flush::FlushGuard;
+pub use self::health::{HealthSnapshot, ReloadStatus};
+pub use self::init::{fmt, FmtBuilder, InitError};
+pub use self::levels::LevelSet;
+pub use self::lint::{Lint, LintKind};
+pub use self::mark::LineMatch;
+#[cfg(feature = "futures")]
+pub use self::matches::{MatchEvents, MatchRecord};
+pub use self::module_map::ModuleMap;
+pub use self::overhead::{OverheadStats, Timings};
+pub use self::per_layer::LineFilterFilter;
+pub use self::profile::ProfileError;
+pub use self::provenance::{Provenance, RuleKind};
+pub use self::relevel::Relevel;
+pub use self::retarget::Retarget;
+pub use self::session::{DebugSession, SessionError, SessionStats};
+pub use self::set::LineFilterSet;
+#[cfg(all(unix, feature = "signal"))]
+pub use self::signal::Sigusr2;
+pub use self::simulate::{CallsiteDesc, SimulationReport};
+pub use self::stack::{ConfigSource, ConfigStack, ConfigStackError};
+pub use self::static_filter::StaticLineFilter;
+pub use self::stats::{EntryStats, LocationStats, Rollup, StatsSnapshot};
+pub use self::validation::Validation;
+pub use self::version::Migration;
+pub use self::warning::{Warning, WarningKind};
+pub use self::writer::MatchedWriter;
+
 /// A filter for enabling spans and events by file/module path and line number.
+///
+/// Cloning a `LineFilter` is cheap: the clone shares the filter's entries,
+/// deny entries, and profiles, and copies them the first time either filter
+/// changes them. This lets the same filter be attached to several layers or
+/// subscribers, or kept by a test harness to compare against, without
+/// copying its entries. Clones don't see the changes made to each other
+/// afterwards; change a filter that is in use through a [`reload`] handle
+/// instead. A clone starts without the
+/// original's [`on_match`] callbacks, hit counts, recorded callsites,
+/// overhead measurements, and streams, which belong to the layer that the
+/// original is attached to.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_line_filter::LineFilter;
+/// use tracing_subscriber::prelude::*;
+///
+/// let filter: LineFilter = "my_crate::db@142,my_crate::db@200".parse().unwrap();
+/// let stdout = tracing_subscriber::registry()
+///     .with(filter.clone())
+///     .with(tracing_subscriber::fmt::layer());
+/// let stderr = tracing_subscriber::registry()
+///     .with(filter.clone())
+///     .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr));
+///
+/// // Changing a clone doesn't change the original.
+/// let mut narrowed = filter.clone();
+/// narrowed.remove_directive(&"my_crate::db@200".parse().unwrap());
+/// assert_eq!(narrowed.to_string(), "my_crate::db@142");
+/// assert_eq!(filter.to_string(), "my_crate::db@142,my_crate::db@200");
+/// # drop((stdout, stderr));
+/// ```
+///
+/// [`reload`]: tracing_subscriber::reload
+/// [`on_match`]: Self::on_match
 #[derive(Debug, Default)]
 pub struct LineFilter {
-    by_module: HashSet<(Cow<'static, str>, u32)>,
-    by_file: HashSet<(Cow<'static, str>, u32)>,
-    env: Option<EnvFilter>,
+    // The entries are shared between clones of the filter until one of them
+    // changes.
+    by_module: Shared<HashSet<(Cow<'static, str>, u32)>>,
+    by_file: Shared<HashSet<(Cow<'static, str>, u32)>>,
+    /// The levels that modules are enabled at, regardless of line.
+    mod_levels: Shared<HashMap<Cow<'static, str>, LevelFilter>>,
+    /// Modules whose spans and events are enabled on any line, along with
+    /// their submodules.
+    mod_prefixes: Shared<prefix::PrefixTrie>,
+    /// Entries for files given relative to their crate's root, by line.
+    crate_files: Shared<crate_file::CrateFiles>,
+    /// The names of `#[instrument]`ed functions whose spans are enabled on
+    /// any line, by module.
+    instrumented: Shared<HashMap<String, HashSet<String>>>,
+    deny: Shared<deny::Deny>,
+    precedence: Precedence,
+    profiles: Shared<BTreeMap<String, profile::Profile>>,
+    sessions: BTreeMap<String, session::Session>,
+    startup: startup::Startup,
+    overlays: overlay::Overlays,
+    /// Hashes of the source lines that entries point at, for entries whose
+    /// directives included one.
+    hashes: Shared<HashMap<(Cow<'static, str>, u32), u32>>,
+    /// The level sets of entries whose directives included one.
+    levels: Shared<HashMap<(Cow<'static, str>, u32), LevelSet>>,
+    fallback: Option<Arc<fallback::Fallback>>,
+    expires: Option<Instant>,
+    /// Whether `disable_all` has been called.
+    disabled: bool,
+    /// Whether the filter is in marking mode, enabling everything.
+    marking: bool,
+    /// Whether `allow_lossy_paths` has been called.
+    lossy_paths: bool,
+    validation: Validation,
+    /// Fields attached to the spans that entries match.
+    span_fields: Vec<(String, String)>,
+    /// Notes attached to directives, keyed by their locations.
+    notes: BTreeMap<String, String>,
+    module_map: Shared<ModuleMap>,
+    span_conditions: entered::SpanConditions,
+    subtrees: subtree::Subtrees,
+    lru: lru::Lru,
+    alerts: alert::Alerts,
+    recorder: Option<record::Recorder>,
+    allowed: Option<record::Allowed>,
+    command_limit: Option<audit::RateLimit>,
+    /// Whether `read_only_commands` has been called.
+    read_only: bool,
+    /// When `set_config` last replaced the filter's config, and whether it
+    /// succeeded.
+    last_reload: Option<health::ReloadStatus>,
+    /// Where `publish_to` writes the filter's config.
+    published: Option<std::path::PathBuf>,
+    hits: stats::Hits,
+    trace_decisions: bool,
+    announce: announce::Announce,
+    overhead: overhead::Overhead,
+    #[cfg(feature = "log")]
+    normalize_log: bool,
+    #[cfg(feature = "metrics")]
+    metrics: metrics::Metrics,
+    #[cfg(feature = "futures")]
+    changes: changes::Subscribers,
+    #[cfg(feature = "futures")]
+    matches: matches::Subscribers,
+    /// Whether `FilterChange::Expired` has been sent for the current
+    /// deadline.
+    #[cfg(feature = "futures")]
+    expiry_notified: std::sync::atomic::AtomicBool,
+    /// Whether the filter's entries enabled its spans, for `PinnedSampler`s.
+    #[cfg(feature = "otel")]
+    pins: Arc<otel::Pins>,
+    /// Whether the filter's entries enabled its event callsites, for
+    /// `Relevel`s and `Retarget`s.
+    event_matches: Arc<rewrite::EventMatches>,
 }
 
 /// Indicates a file path was invalid for use in a `LineFilter`.
+///
+/// This is returned as [`Error::Path`].
 #[derive(Debug)]
 pub struct BadPath {
     path: PathBuf,
     message: &'static str,
 }
 
+/// Indicates a module path passed to [`LineFilter::try_enable_by_mod`] was
+/// not a valid Rust module path.
+///
+/// This is returned as [`Error::Module`].
+#[derive(Debug)]
+pub struct BadModule {
+    module: String,
+    message: &'static str,
+}
+
 impl LineFilter {
     /// Returns a new `LineFilter`.
     ///
@@ -157,24 +518,211 @@ impl LineFilter {
     ///     // all ERROR spans or events, regardless of location.
     ///     .with_env_filter(EnvFilter::new("error,my_crate::some_other_module=debug"));
     /// ```
+    ///
+    /// This method is only available when the `env-filter` feature flag is
+    /// enabled, which it is by default. Otherwise, use [`with_fallback`].
+    ///
+    /// [`with_fallback`]: Self::with_fallback
+    #[cfg(feature = "env-filter")]
     pub fn with_env_filter(&mut self, env: EnvFilter) -> &mut Self {
-        self.env = Some(env);
+        self.fallback = Some(Arc::new(fallback::Fallback::Env(Box::new(env))));
         self
     }
 
+    /// Adds a [`Directive`] to this filter.
+    ///
+    /// `Module`, `File`, `ModuleLevel`, and `ModulePrefix` directives are
+    /// equivalent to calling [`enable_by_mod`], [`enable_by_file`],
+    /// [`enable_by_mod_level`], and [`enable_by_mod_prefix`], respectively,
+    /// and `Deny` directives are equivalent to calling the corresponding
+    /// `deny_by_*` method, such as [`deny_by_mod`] or [`deny_by_file`].
+    /// `Env` directives are added to this filter's [`EnvFilter`], creating
+    /// one if the filter does not have an `EnvFilter` yet.
+    ///
+    /// A `Module` or `File` directive also replaces the [level set] of the
+    /// entry it adds, so adding a directive without one enables the spans
+    /// and events on its line at every level.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if a `File` directive's path is not valid for use
+    /// in a `LineFilter`. Directives parsed from strings have already had
+    /// their paths validated, so this never fails for those directives.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::{Directive, LineFilter};
+    ///
+    /// let mut filter = LineFilter::default();
+    /// for directive in ["my_crate::db@142", "my_crate=debug"] {
+    ///     let directive: Directive = directive.parse().unwrap();
+    ///     filter.add_directive(directive).unwrap();
+    /// }
+    /// ```
+    ///
+    /// [`enable_by_mod`]: Self::enable_by_mod
+    /// [`enable_by_file`]: Self::enable_by_file
+    /// [`enable_by_mod_level`]: Self::enable_by_mod_level
+    /// [`enable_by_mod_prefix`]: Self::enable_by_mod_prefix
+    /// [`deny_by_mod`]: Self::deny_by_mod
+    /// [`deny_by_file`]: Self::deny_by_file
+    /// [level set]: LevelSet
+    pub fn add_directive(&mut self, directive: Directive) -> Result<&mut Self, Error> {
+        match directive {
+            Directive::Module {
+                module,
+                line,
+                hash,
+                levels,
+            } => {
+                let entry = (Cow::Owned(module.clone()), line);
+                if let Some(hash) = hash {
+                    self.hashes.insert(entry.clone(), hash);
+                }
+                self.set_levels(entry, levels);
+                Ok(self.enable_by_mod(module, line))
+            }
+            Directive::File {
+                file,
+                line,
+                hash,
+                levels,
+            } => {
+                self.file_key(Path::new(&file))?;
+                let entry = (Cow::Owned(file.clone()), line);
+                if let Some(hash) = hash {
+                    self.hashes.insert(entry.clone(), hash);
+                }
+                self.set_levels(entry, levels);
+                self.enable_by_file(&file, line)
+            }
+            Directive::ModuleLevel { module, level } => Ok(self.enable_by_mod_level(module, level)),
+            Directive::ModulePrefix { prefix } => Ok(self.enable_by_mod_prefix(&prefix)),
+            Directive::Deny(directive) => match *directive {
+                Directive::Module { module, line, .. } => Ok(self.deny_by_mod(module, line)),
+                Directive::File { file, line, .. } => self.deny_by_file(&file, line),
+                Directive::ModulePrefix { prefix } => Ok(self.deny_by_mod_prefix(&prefix)),
+                _ => Ok(self),
+            },
+            #[cfg(feature = "env-filter")]
+            Directive::Env(directive) => {
+                #[cfg(feature = "futures")]
+                self.changes
+                    .notify(|| FilterChange::Added(Directive::Env(directive.clone())));
+                let env = match self.fallback.take().map(Arc::try_unwrap) {
+                    Some(Ok(fallback::Fallback::Env(env))) => *env,
+                    // A clone of this filter shares the `EnvFilter`, which
+                    // can't be cloned, so parse a copy of its directives.
+                    Some(Err(env)) if matches!(*env, fallback::Fallback::Env(_)) => {
+                        EnvFilter::new(env.to_string())
+                    }
+                    _ => EnvFilter::default(),
+                };
+                Ok(self.with_env_filter(env.add_directive(directive)))
+            }
+        }
+    }
+
+    /// Parses a list of directives, in the [same syntax] as a `LineFilter`'s
+    /// [`FromStr`] implementation, and adds them to this filter.
+    ///
+    /// Unlike parsing a new filter, this keeps the filter's existing entries
+    /// and settings, and checks `File` directives' paths using the filter's
+    /// [validation] mode. Named profiles in the list are defined, replacing
+    /// profiles with the same names, and span fields are added.
+    ///
+    /// # Panics
+    ///
+    /// If `directives` can't be parsed. Use [`try_add_directives`] to handle
+    /// invalid directives instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter
+    ///     .enable_by_mod("my_crate::db", 142)
+    ///     .add_directives("my_crate::db@200, my_crate::auth@12");
+    ///
+    /// assert_eq!(
+    ///     filter.to_string(),
+    ///     "my_crate::auth@12,my_crate::db@142,my_crate::db@200",
+    /// );
+    /// ```
+    ///
+    /// [same syntax]: LineFilter#impl-FromStr-for-LineFilter
+    /// [`FromStr`]: std::str::FromStr
+    /// [validation]: Self::with_validation
+    /// [`try_add_directives`]: Self::try_add_directives
+    #[track_caller]
+    pub fn add_directives(&mut self, directives: &str) -> &mut Self {
+        self.try_add_directives(directives)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Parses a list of directives and adds them to this filter, returning
+    /// an error if they can't be parsed.
+    ///
+    /// See [`add_directives`] for details. If any directive is invalid, none
+    /// of them are added.
+    ///
+    /// # Errors
+    ///
+    /// This returns [`Error::Parse`] if `directives` can't be parsed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::{Error, LineFilter};
+    ///
+    /// let mut filter = LineFilter::default();
+    /// let error = filter
+    ///     .try_add_directives("my_crate::db@142, my_crate::db@x")
+    ///     .unwrap_err();
+    ///
+    /// assert!(matches!(error, Error::Parse(_)));
+    /// assert_eq!(filter.to_string(), "");
+    /// ```
+    ///
+    /// [`add_directives`]: Self::add_directives
+    pub fn try_add_directives(&mut self, directives: &str) -> Result<&mut Self, Error> {
+        let sections = directive::parse_sections_with(directives, self.validation)?;
+        sections.record_warnings();
+        for directive in sections.directives {
+            self.add_directive(directive)?;
+        }
+        for (name, directives) in sections.profiles {
+            self.define_profile(name, directives)
+                .expect("profiles are validated by `parse_sections`");
+        }
+        for (name, value) in sections.fields {
+            self.add_span_field(name, value);
+        }
+        Ok(self)
+    }
+
     /// Enable a span or event in the Rust module `module` on line `line`.
     ///
     /// # Notes
     ///
     /// * Module paths should include the name of the crate. For
     ///   example, the module `my_module` in `my_crate` would have path
-    ///  `my_crate::my_module`.
+    ///   `my_crate::my_module`.
     /// * If no span or event exists at the specified location, or if the module
-    ///   path does not exist, this will silently do nothing.
+    ///   path does not exist, this will silently do nothing. Use
+    ///   [`try_enable_by_mod`] to catch module paths that can't exist.
     /// * Line numbers are relative to the start of the *file*, not to the start
     ///   of the module. If a module does not have its own file (i.e., it's
     ///   defined like `mod my_module { ... }`), the line number is relative to
     ///   the containing file.
+    /// * `module` is compared with both the module path and the target of each
+    ///   span and event, so a span or event with a custom `target:` can be
+    ///   enabled by either one. The module path is checked first: if both the
+    ///   module path and the target have `module@level` entries (see
+    ///   [`enable_by_mod_level`]), the module path's level applies.
     ///
     /// # Examples
     ///
@@ -220,8 +768,186 @@ impl LineFilter {
     ///
     ///  // ...
     /// ```
+    ///
+    /// Enabling an event with a custom target by its target:
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::prelude::*;
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter.enable_by_mod("audit", line!() + 4);
+    /// let subscriber = tracing_subscriber::registry().with(filter);
+    ///
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let span = tracing::info_span!(target: "audit", "login");
+    ///     assert!(!span.is_disabled());
+    /// });
+    /// ```
+    ///
+    /// [`enable_by_mod_level`]: Self::enable_by_mod_level
+    /// [`try_enable_by_mod`]: Self::try_enable_by_mod
     pub fn enable_by_mod(&mut self, module: impl Into<Cow<'static, str>>, line: u32) -> &mut Self {
-        self.by_module.insert((module.into(), line));
+        let entry = (module.into(), line);
+        #[cfg(feature = "futures")]
+        if !self.by_module.contains(&entry) {
+            self.changes
+                .notify(|| FilterChange::Added(self.module_directive(&entry)));
+        }
+        let added = stats::Entry::Module(entry.0.clone(), entry.1);
+        self.by_module.insert(entry);
+        self.entry_added(added);
+        self
+    }
+
+    /// Enable a span or event in the Rust module `module` on line `line`,
+    /// like [`enable_by_mod`], after checking that `module` is a valid
+    /// module path.
+    ///
+    /// A valid module path is one or more Rust identifiers separated by
+    /// `::`, with no whitespace. This catches the common mistakes of passing
+    /// a file path (such as `src/db.rs`), a directive (such as
+    /// `my_crate::db@42`), or a function call where a module path belongs.
+    /// It doesn't check that the module exists.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error describing the mistake if `module` isn't a
+    /// valid module path.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter.try_enable_by_mod("my_crate::db", 42).unwrap();
+    ///
+    /// let error = filter.try_enable_by_mod("src/db.rs", 42).unwrap_err();
+    /// assert_eq!(
+    ///     error.to_string(),
+    ///     "invalid module path 'src/db.rs': module paths are separated by `::`; \
+    ///      use `enable_by_file` for file paths",
+    /// );
+    /// ```
+    ///
+    /// [`enable_by_mod`]: Self::enable_by_mod
+    pub fn try_enable_by_mod(
+        &mut self,
+        module: impl Into<Cow<'static, str>>,
+        line: u32,
+    ) -> Result<&mut Self, Error> {
+        let module = module.into();
+        validate_module(&module)?;
+        Ok(self.enable_by_mod(module, line))
+    }
+
+    /// Enable spans and events in the Rust module `module` at `level` and
+    /// all levels above it, on any line.
+    ///
+    /// This sets a default for the whole module, which can be combined with
+    /// entries for individual lines in the same module: the spans and events
+    /// on those lines are enabled at any level. This expresses policies like
+    /// "`my_crate::db` at `INFO`, plus lines 142 and 200 at `TRACE`" in a
+    /// single filter, without an [`EnvFilter`].
+    ///
+    /// Only spans and events in `module` itself are enabled, not those in
+    /// its submodules. Setting a new level for a module replaces the old one.
+    /// Like the filter's line entries, module levels are disabled once the
+    /// filter's entries [expire].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing::Level;
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::prelude::*;
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter
+    ///     .enable_by_mod_level(module_path!(), Level::INFO)
+    ///     .enable_by_mod(module_path!(), line!() + 6);
+    /// let subscriber = tracing_subscriber::registry().with(filter);
+    ///
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let info = tracing::info_span!("info");
+    ///     let debug = tracing::debug_span!("debug");
+    ///     let pinned = tracing::trace_span!("pinned");
+    ///
+    ///     assert!(!info.is_disabled());
+    ///     assert!(debug.is_disabled());
+    ///     assert!(!pinned.is_disabled());
+    /// });
+    /// ```
+    ///
+    /// [`EnvFilter`]: tracing_subscriber::EnvFilter
+    /// [expire]: Self::expire_at
+    pub fn enable_by_mod_level(
+        &mut self,
+        module: impl Into<Cow<'static, str>>,
+        level: impl Into<LevelFilter>,
+    ) -> &mut Self {
+        let module = module.into();
+        let level = level.into();
+        #[cfg(feature = "futures")]
+        if self.mod_levels.get(&module) != Some(&level) {
+            self.changes.notify(|| {
+                FilterChange::Added(Directive::ModuleLevel {
+                    module: module.to_string(),
+                    level,
+                })
+            });
+        }
+        self.mod_levels.insert(module, level);
+        self
+    }
+
+    /// Enable every span and event in the Rust module `module` and in all of
+    /// its submodules, on any line and at any level.
+    ///
+    /// Unlike a glob pattern, this matches whole path segments: enabling
+    /// `my_crate::db` enables `my_crate::db` and `my_crate::db::pool`, but not
+    /// `my_crate::dbx`. Prefixes are stored in a trie, so checking a callsite
+    /// takes time proportional to the length of its module path, no matter
+    /// how many prefixes are enabled.
+    ///
+    /// The directive syntax for a prefix is `my_crate::db::**`. Like the
+    /// filter's line entries, prefixes are disabled once the filter's
+    /// entries [expire].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::prelude::*;
+    ///
+    /// mod db {
+    ///     pub mod pool {
+    ///         pub fn connect() -> bool {
+    ///             !tracing::trace_span!("connect").is_disabled()
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter.enable_by_mod_prefix(&format!("{}::db", module_path!()));
+    /// let subscriber = tracing_subscriber::registry().with(filter);
+    ///
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     assert!(db::pool::connect());
+    ///     assert!(tracing::info_span!("not in db").is_disabled());
+    /// });
+    /// ```
+    ///
+    /// [expire]: Self::expire_at
+    pub fn enable_by_mod_prefix(&mut self, module: &str) -> &mut Self {
+        if self.mod_prefixes.insert(module) {
+            #[cfg(feature = "futures")]
+            self.changes.notify(|| {
+                FilterChange::Added(Directive::ModulePrefix {
+                    prefix: module.to_owned(),
+                })
+            });
+        }
         self
     }
 
@@ -232,12 +958,15 @@ impl LineFilter {
     /// These file paths must match the file paths emitted by the
     /// [`std::file!()`] macro. In particular:
     ///
-    /// * Paths must be absolute.
+    /// * Paths must be absolute, except on WebAssembly targets, which don't
+    ///   have native absolute paths.
     /// * Paths must be Rust source code files.
-    /// * Paths must be valid UTF-8.
+    /// * Paths must be valid UTF-8, unless [lossy paths] are allowed.
     ///
-    /// This method validates paths and returns an error if the path is not
-    /// valid for use in a `LineFilter`.
+    /// These rules are loosened by [relaxed validation]. This method
+    /// validates paths and returns an error if the path is not valid for use
+    /// in a `LineFilter`; [`enable_by_file_or_panic`] panics instead, for
+    /// quick scripts and tests whose paths are known to be valid.
     ///
     /// Since these paths are absolute, files in Cargo dependencies will include
     /// their full path in the local Cargo registry. For example:
@@ -250,27 +979,166 @@ impl LineFilter {
     /// it's likely best to prefer Rust module paths rather than file paths when
     /// accepting input from users directly. Enabling events and spans by file
     /// paths is primarily intended for use by automated tools.
+    ///
+    /// # Errors
+    ///
+    /// This returns [`Error::Path`] if `file` is not valid for use in a
+    /// `LineFilter`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter
+    ///     .enable_by_file("/home/me/my_crate/src/db.rs", 42)
+    ///     .expect("absolute paths to Rust files are valid");
+    ///
+    /// assert!(filter.enable_by_file("/home/me/my_crate/README.md", 1).is_err());
+    /// ```
+    ///
+    /// [lossy paths]: Self::allow_lossy_paths
+    /// [relaxed validation]: Validation::Relaxed
+    /// [`enable_by_file_or_panic`]: Self::enable_by_file_or_panic
     pub fn enable_by_file(
         &mut self,
         file: impl AsRef<Path>,
         line: u32,
-    ) -> Result<&mut Self, BadPath> {
-        let file = file.as_ref();
-        if !file.is_absolute() {
-            return Err(BadPath::new(file, "file paths must be absolute"));
-        }
+    ) -> Result<&mut Self, Error> {
+        let file = self.file_key(file.as_ref())?.into_owned();
+        self.insert_file((Cow::Owned(file), line));
+        Ok(self)
+    }
+
+    /// Enable a span or event in the file `file` on line `line`, panicking if
+    /// `file` is not valid for use in a `LineFilter`.
+    ///
+    /// This is like [`enable_by_file`], for quick scripts and tests whose
+    /// paths are known to be valid, so that it can be chained with the
+    /// filter's other builder methods.
+    ///
+    /// # Panics
+    ///
+    /// If `file` is not valid for use in a `LineFilter`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter
+    ///     .enable_by_file_or_panic("/home/me/my_crate/src/db.rs", 42)
+    ///     .enable_by_mod("my_crate::auth", 12);
+    /// ```
+    ///
+    /// [`enable_by_file`]: Self::enable_by_file
+    #[track_caller]
+    pub fn enable_by_file_or_panic(&mut self, file: impl AsRef<Path>, line: u32) -> &mut Self {
+        self.enable_by_file(file, line)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Accept file paths that aren't valid UTF-8 in [`enable_by_file`] and
+    /// [`deny_by_file`], replacing their invalid sequences with
+    /// `U+FFFD REPLACEMENT CHARACTER`.
+    ///
+    /// The compiler reports the path of a source file whose name isn't valid
+    /// UTF-8 in this lossy form, since [`std::file!()`] is a string, so an
+    /// entry with the lossy form of the file's path matches its spans and
+    /// events. Since several paths can have the same lossy form, an entry
+    /// may also match files whose names differ only in their invalid
+    /// sequences. Without this, such paths are rejected, which makes files
+    /// on filesystems that allow them impossible to pin by path.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[cfg(unix)] {
+    /// use std::{ffi::OsStr, os::unix::ffi::OsStrExt, path::Path};
+    /// use tracing_line_filter::LineFilter;
+    ///
+    /// let path = Path::new(OsStr::from_bytes(b"/src/caf\xe9.rs"));
+    ///
+    /// let mut filter = LineFilter::default();
+    /// assert!(filter.enable_by_file(path, 42).is_err());
+    ///
+    /// filter.allow_lossy_paths().enable_by_file(path, 42).unwrap();
+    /// assert_eq!(filter.to_string(), "/src/caf\u{fffd}.rs@42");
+    /// # }
+    /// ```
+    ///
+    /// [`enable_by_file`]: Self::enable_by_file
+    /// [`deny_by_file`]: Self::deny_by_file
+    pub fn allow_lossy_paths(&mut self) -> &mut Self {
+        self.lossy_paths = true;
+        self
+    }
 
-        if file.extension().and_then(std::ffi::OsStr::to_str) != Some("rs") {
-            return Err(BadPath::new(file, "files must be Rust source code files"));
+    /// Validates `file`, returning the form that this filter stores it in.
+    pub(crate) fn file_key<'a>(&self, file: &'a Path) -> Result<Cow<'a, str>, BadPath> {
+        if self.lossy_paths && file.to_str().is_none() {
+            let lossy = file.to_string_lossy().into_owned();
+            self.validation.check_path(Path::new(&lossy))?;
+            return Ok(Cow::Owned(lossy));
         }
+        self.validation.check_path(file).map(Cow::Borrowed)
+    }
 
-        let file = file
-            .to_str()
-            .ok_or_else(|| BadPath::new(file, "file paths must be valid utf-8"))?
-            .to_owned();
+    /// Enable a span or event at the source location described by a
+    /// [`std::panic::Location`].
+    ///
+    /// This is useful for bridging error-reporting machinery with the line
+    /// filter: `Location`s captured via [`Location::caller`] (such as in error
+    /// types using `#[track_caller]`, or in panic hooks) can be used directly,
+    /// without having to convert them into a file path first.
+    ///
+    /// Unlike [`enable_by_file`], this method performs no path validation: a
+    /// `Location`'s file path is always the same path emitted by the
+    /// [`std::file!()`] macro, so it will always match the file path in a
+    /// span or event's metadata.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::panic::Location;
+    /// use tracing_line_filter::LineFilter;
+    ///
+    /// struct MyError {
+    ///     location: &'static Location<'static>,
+    /// }
+    ///
+    /// impl MyError {
+    ///     #[track_caller]
+    ///     fn new() -> Self {
+    ///         Self { location: Location::caller() }
+    ///     }
+    /// }
+    ///
+    /// let error = MyError::new();
+    ///
+    /// // Enable any span or event on the line where the error was constructed.
+    /// let mut filter = LineFilter::default();
+    /// filter.enable_from_location(error.location);
+    /// ```
+    ///
+    /// [`Location::caller`]: std::panic::Location::caller
+    /// [`enable_by_file`]: Self::enable_by_file
+    pub fn enable_from_location(&mut self, location: &'static Location<'static>) -> &mut Self {
+        self.insert_file((Cow::Borrowed(location.file()), location.line()));
+        self
+    }
 
-        self.by_file.insert((Cow::Owned(file), line));
-        Ok(self)
+    fn insert_file(&mut self, entry: (Cow<'static, str>, u32)) {
+        #[cfg(feature = "futures")]
+        if !self.by_file.contains(&entry) {
+            self.changes
+                .notify(|| FilterChange::Added(self.file_directive(&entry)));
+        }
+        let added = stats::Entry::File(entry.0.clone(), entry.1);
+        self.by_file.insert(entry);
+        self.entry_added(added);
     }
 
     /// Enable a set of spans or events by module path.
@@ -320,10 +1188,9 @@ impl LineFilter {
     where
         I: Into<Cow<'static, str>>,
     {
-        let modules = modules
-            .into_iter()
-            .map(|(module, line)| (module.into(), line));
-        self.by_module.extend(modules);
+        for (module, line) in modules {
+            self.enable_by_mod(module, line);
+        }
         self
     }
 
@@ -331,11 +1198,19 @@ impl LineFilter {
     ///
     /// This is equivalent to repeatedly calling [`enable_by_file`], and follows
     /// the same path validation rules as that method. See the documentation for
-    /// [`enable_by_file`] for details.
+    /// [`enable_by_file`] for details. The files before an invalid one are
+    /// still enabled.
+    ///
+    /// # Errors
+    ///
+    /// This returns [`Error::Path`] for the first file path that is not
+    /// valid for use in a `LineFilter`.
+    ///
+    /// [`enable_by_file`]: Self::enable_by_file
     pub fn with_files<I>(
         &mut self,
         files: impl IntoIterator<Item = (I, u32)>,
-    ) -> Result<&mut Self, BadPath>
+    ) -> Result<&mut Self, Error>
     where
         I: AsRef<Path>,
     {
@@ -345,51 +1220,730 @@ impl LineFilter {
         Ok(self)
     }
 
-    fn contains(&self, metadata: &Metadata<'_>) -> bool {
-        if let Some(line) = metadata.line() {
-            let module = metadata.module_path().unwrap_or_else(|| metadata.target());
-            let location = (Cow::Borrowed(module), line);
-            if self.by_module.contains(&location) {
-                return true;
-            }
+    /// Enable a set of spans or events by file path, panicking if one of the
+    /// file paths is not valid for use in a `LineFilter`.
+    ///
+    /// This is like [`with_files`], for quick scripts and tests whose paths
+    /// are known to be valid.
+    ///
+    /// # Panics
+    ///
+    /// If one of the file paths is not valid for use in a `LineFilter`.
+    ///
+    /// [`with_files`]: Self::with_files
+    #[track_caller]
+    pub fn with_files_or_panic<I>(&mut self, files: impl IntoIterator<Item = (I, u32)>) -> &mut Self
+    where
+        I: AsRef<Path>,
+    {
+        self.with_files(files).unwrap_or_else(|e| panic!("{}", e))
+    }
 
-            if let Some(file) = metadata.file() {
-                let location = (Cow::Borrowed(file), line);
-                if self.by_file.contains(&location) {
-                    return true;
+    /// Removes a `Module`, `File`, `ModuleLevel`, `ModulePrefix`, or `Deny`
+    /// [`Directive`] from this filter, returning `true` if it was present.
+    ///
+    /// A `ModuleLevel` directive is only removed if its level is the
+    /// module's current level.
+    ///
+    /// Since [`EnvFilter`]s do not support removing directives, this always
+    /// returns `false` for `Env` directives.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::{Directive, LineFilter};
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter.enable_by_mod("my_crate::db", 142);
+    ///
+    /// let directive: Directive = "my_crate::db@142".parse().unwrap();
+    /// assert!(filter.remove_directive(&directive));
+    /// assert!(!filter.remove_directive(&directive));
+    /// ```
+    pub fn remove_directive(&mut self, directive: &Directive) -> bool {
+        // `Cow`s compare equal regardless of whether they're borrowed or
+        // owned, but `HashSet::remove` requires a `'static` key here.
+        match directive {
+            Directive::Module { module, line, .. } => {
+                let entry = (Cow::Owned(module.clone()), *line);
+                #[cfg(feature = "futures")]
+                if self.by_module.contains(&entry) {
+                    self.changes
+                        .notify(|| FilterChange::Removed(self.module_directive(&entry)));
                 }
+                self.hashes.remove(&entry);
+                self.levels.remove(&entry);
+                let removed = stats::Entry::Module(entry.0.clone(), entry.1);
+                self.hits.remove(&removed);
+                self.entry_removed(&removed);
+                self.by_module.remove(&entry)
             }
+            Directive::File { file, line, .. } => {
+                let entry = (Cow::Owned(file.clone()), *line);
+                #[cfg(feature = "futures")]
+                if self.by_file.contains(&entry) {
+                    self.changes
+                        .notify(|| FilterChange::Removed(self.file_directive(&entry)));
+                }
+                self.hashes.remove(&entry);
+                self.levels.remove(&entry);
+                let removed = stats::Entry::File(entry.0.clone(), entry.1);
+                self.hits.remove(&removed);
+                self.entry_removed(&removed);
+                self.by_file.remove(&entry)
+            }
+            Directive::ModuleLevel { module, level } => {
+                if self.mod_levels.get(module.as_str()) != Some(level) {
+                    return false;
+                }
+                #[cfg(feature = "futures")]
+                self.changes
+                    .notify(|| FilterChange::Removed(directive.clone()));
+                self.mod_levels.remove(module.as_str());
+                true
+            }
+            Directive::ModulePrefix { prefix } => {
+                let removed = self.mod_prefixes.remove(prefix);
+                #[cfg(feature = "futures")]
+                if removed {
+                    self.changes
+                        .notify(|| FilterChange::Removed(directive.clone()));
+                }
+                removed
+            }
+            Directive::Deny(directive) => self.remove_deny(directive),
+            #[cfg(feature = "env-filter")]
+            Directive::Env(_) => false,
         }
+    }
+
+    /// Returns an iterator over this filter's `ModulePrefix`, `ModuleLevel`,
+    /// `Module`, `File`, and `Deny` [`Directive`]s.
+    ///
+    /// The directives are in the same order as the filter's [`Display`]
+    /// output: grouped by kind in the order above, and sorted by path and
+    /// line number within each group. The order only depends on which
+    /// directives the filter has, not on the order they were added in or on
+    /// the platform, so it can be compared across runs, such as in
+    /// golden-file tests.
+    ///
+    /// This does not include the directives of the filter's [`EnvFilter`],
+    /// if it has one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter
+    ///     .enable_by_mod("my_crate::db", 200)
+    ///     .enable_by_mod("my_crate::auth", 12)
+    ///     .enable_by_mod_prefix("my_crate::http")
+    ///     .enable_by_mod("my_crate::db", 142);
+    ///
+    /// let directives = filter.directives().map(|d| d.to_string()).collect::<Vec<_>>();
+    /// assert_eq!(
+    ///     directives,
+    ///     [
+    ///         "my_crate::http::**",
+    ///         "my_crate::auth@12",
+    ///         "my_crate::db@142",
+    ///         "my_crate::db@200",
+    ///     ],
+    /// );
+    /// ```
+    ///
+    /// [`Display`]: std::fmt::Display
+    pub fn directives(&self) -> impl Iterator<Item = Directive> + '_ {
+        let mut modules = self.by_module.iter().collect::<Vec<_>>();
+        modules.sort();
+        let mut files = self.by_file.iter().collect::<Vec<_>>();
+        files.sort();
+
+        let modules = modules
+            .into_iter()
+            .map(move |entry| self.module_directive(entry));
+        let files = files
+            .into_iter()
+            .map(move |entry| self.file_directive(entry));
+        self.mod_prefix_directives()
+            .into_iter()
+            .chain(self.mod_level_directives())
+            .chain(modules)
+            .chain(files)
+            .chain(self.deny_directives())
+    }
+
+    /// Returns this filter's `ModulePrefix` directives, sorted by module.
+    pub(crate) fn mod_prefix_directives(&self) -> Vec<Directive> {
+        self.mod_prefixes
+            .to_vec()
+            .into_iter()
+            .map(|prefix| Directive::ModulePrefix { prefix })
+            .collect()
+    }
+
+    /// Returns this filter's `ModuleLevel` directives, sorted by module.
+    pub(crate) fn mod_level_directives(&self) -> Vec<Directive> {
+        let mut levels = self.mod_levels.iter().collect::<Vec<_>>();
+        levels.sort();
+        levels
+            .into_iter()
+            .map(|(module, &level)| Directive::ModuleLevel {
+                module: module.to_string(),
+                level,
+            })
+            .collect()
+    }
+
+    fn module_directive(&self, entry: &(Cow<'static, str>, u32)) -> Directive {
+        Directive::Module {
+            module: entry.0.to_string(),
+            line: entry.1,
+            hash: self.hashes.get(entry).copied(),
+            levels: self.levels.get(entry).copied(),
+        }
+    }
+
+    fn file_directive(&self, entry: &(Cow<'static, str>, u32)) -> Directive {
+        Directive::File {
+            file: entry.0.to_string(),
+            line: entry.1,
+            hash: self.hashes.get(entry).copied(),
+            levels: self.levels.get(entry).copied(),
+        }
+    }
+
+    /// Disables all `(module, line)` and `(file, line)` entries once `ttl` has
+    /// elapsed.
+    ///
+    /// This is equivalent to `filter.expire_at(Instant::now() + ttl)`. See
+    /// [`expire_at`] for details. If `ttl` is so long that the deadline can't
+    /// be represented as an `Instant`, the entries never expire.
+    ///
+    /// [`expire_at`]: Self::expire_at
+    pub fn expire_after(&mut self, ttl: Duration) -> &mut Self {
+        self.expire_at(Instant::now().checked_add(ttl))
+    }
+
+    /// Disables all `(module, line)` and `(file, line)` entries at
+    /// `deadline`, or never, if `deadline` is `None`.
+    ///
+    /// This is intended for temporarily enabling verbose diagnostics in a
+    /// running program, without having to remember to turn them off again.
+    /// Once the deadline has passed, spans and events are only enabled by
+    /// this filter's [`EnvFilter`], if it has one. The entries themselves are
+    /// not removed, so setting a new deadline re-enables them.
+    ///
+    /// # Notes
+    ///
+    /// Callsites enabled by an entry are normally only checked once. While a
+    /// deadline is set, they are instead checked every time a span or event
+    /// is recorded, which is slower.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::{Duration, Instant};
+    /// use tracing_line_filter::LineFilter;
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter
+    ///     .enable_by_mod("my_crate::db", 142)
+    ///     .expire_at(Instant::now() + Duration::from_secs(5 * 60));
+    /// ```
+    pub fn expire_at(&mut self, deadline: impl Into<Option<Instant>>) -> &mut Self {
+        self.expires = deadline.into();
+        #[cfg(feature = "futures")]
+        self.expiry_notified
+            .store(false, std::sync::atomic::Ordering::Relaxed);
+        self
+    }
+
+    /// Returns the time at which this filter's `(module, line)` and
+    /// `(file, line)` entries are disabled, if one has been set using
+    /// [`expire_at`] or [`expire_after`].
+    ///
+    /// [`expire_at`]: Self::expire_at
+    /// [`expire_after`]: Self::expire_after
+    pub fn expires_at(&self) -> Option<Instant> {
+        self.expires
+    }
+
+    /// Disables all of this filter's entries at once, so that spans and
+    /// events are only enabled by its [`EnvFilter`], if it has one.
+    ///
+    /// This is a safety valve for when a bad config floods a production
+    /// program's logs: like an [expiry deadline][expire], it switches off
+    /// every `module@line`, `file@line`, `module@level`, `module::**`, and
+    /// deny entry, without removing them, until [`reenable_all`] is called.
+    /// When the filter is wrapped in a [`reload`] layer, call this inside
+    /// [`Handle::modify`], or use the `disable-all` [`Command`].
+    ///
+    /// Setting the `LINE_FILTER_DISABLE` environment variable to `1` (or
+    /// `true`) disables the entries of every `LineFilter` in the process in
+    /// the same way, so that a program can be started with its line entries
+    /// switched off. The variable is read once, the first time a filter
+    /// checks its entries, and `reenable_all` doesn't override it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::{prelude::*, reload};
+    ///
+    /// let filter: LineFilter = "my_crate::db@142,warn".parse().unwrap();
+    /// let (filter, handle) = reload::Layer::new(filter);
+    /// tracing_subscriber::registry()
+    ///     .with(tracing_subscriber::fmt::layer())
+    ///     .with(filter)
+    ///     .init();
+    ///
+    /// // Later, when the entries turn out to be too noisy...
+    /// handle.modify(|filter| { filter.disable_all(); }).unwrap();
+    /// assert!(handle.with_current(LineFilter::is_disabled).unwrap());
+    /// ```
+    ///
+    /// [`EnvFilter`]: tracing_subscriber::EnvFilter
+    /// [expire]: Self::expire_at
+    /// [`reenable_all`]: Self::reenable_all
+    /// [`reload`]: tracing_subscriber::reload
+    /// [`Handle::modify`]: tracing_subscriber::reload::Handle::modify
+    /// [`Command`]: crate::Command
+    pub fn disable_all(&mut self) -> &mut Self {
+        self.disabled = true;
+        self
+    }
+
+    /// Re-enables this filter's entries after [`disable_all`] was called.
+    ///
+    /// This has no effect while the `LINE_FILTER_DISABLE` environment
+    /// variable is set.
+    ///
+    /// [`disable_all`]: Self::disable_all
+    pub fn reenable_all(&mut self) -> &mut Self {
+        self.disabled = false;
+        self
+    }
+
+    /// Returns `true` if this filter's entries are disabled, either by
+    /// [`disable_all`] or by the `LINE_FILTER_DISABLE` environment variable.
+    ///
+    /// [`disable_all`]: Self::disable_all
+    pub fn is_disabled(&self) -> bool {
+        static KILL_SWITCH: OnceLock<bool> = OnceLock::new();
+        self.disabled
+            || *KILL_SWITCH.get_or_init(|| {
+                matches!(
+                    std::env::var("LINE_FILTER_DISABLE").as_deref(),
+                    Ok("1") | Ok("true")
+                )
+            })
+    }
+
+    /// Matches records from the [`log`] crate, bridged to `tracing` by
+    /// [`tracing-log`], by their original module path, file, and line.
+    ///
+    /// `tracing-log` emits every `log` record at a given level from the same
+    /// callsite, with the target `log` and no module path or line number, so
+    /// `(module, line)` and `(file, line)` entries never match them. With
+    /// this mode enabled, the filter instead checks each record against its
+    /// [normalized metadata], which has the module path, file, line, and
+    /// target of the `log` macro invocation. The [`EnvFilter`], if there is
+    /// one, also sees the normalized metadata, so its directives match `log`
+    /// records by their original targets.
+    ///
+    /// # Notes
+    ///
+    /// Since all `log` records share a callsite, each record is checked
+    /// individually while this mode is enabled, which is slower than
+    /// filtering `tracing` events. A `log` record's file path comes from the
+    /// [`std::file!()`] macro, just like a `tracing` event's, so `file@line`
+    /// entries match `log` records as well.
+    ///
+    /// This method is only available when the `log` feature flag is enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::prelude::*;
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter
+    ///     .enable_by_mod(module_path!(), line!() + 10)
+    ///     .normalize_log_metadata();
+    ///
+    /// // `init` also installs a `tracing_log::LogTracer`, which bridges `log`
+    /// // records to `tracing`.
+    /// tracing_subscriber::registry()
+    ///     .with(tracing_subscriber::fmt::layer())
+    ///     .with(filter)
+    ///     .init();
+    ///
+    /// log::debug!("i'm enabled!");
+    /// log::debug!("i'm not enabled");
+    /// ```
+    ///
+    /// [`log`]: https://docs.rs/log
+    /// [`tracing-log`]: https://docs.rs/tracing-log
+    /// [normalized metadata]: tracing_log::NormalizeEvent
+    #[cfg(feature = "log")]
+    pub fn normalize_log_metadata(&mut self) -> &mut Self {
+        self.normalize_log = true;
+        self
+    }
 
-        false
+    /// Returns `true` if `metadata` belongs to one of `tracing-log`'s
+    /// callsites, and this filter should check its records individually.
+    #[cfg(feature = "log")]
+    fn is_normalized_log(&self, metadata: &Metadata<'_>) -> bool {
+        // `tracing-log` checks whether records are enabled using metadata
+        // with the record's target, but its callsites' fields.
+        let fields = metadata.fields();
+        self.normalize_log
+            && fields.field("log.target").is_some()
+            && fields.field("log.line").is_some()
+    }
+
+    fn is_expired(&self) -> bool {
+        let expired = self
+            .expires
+            .is_some_and(|deadline| Instant::now() >= deadline);
+        #[cfg(feature = "futures")]
+        if expired {
+            use std::sync::atomic::Ordering;
+            if !self.expiry_notified.load(Ordering::Relaxed)
+                && !self.expiry_notified.swap(true, Ordering::Relaxed)
+            {
+                self.changes.notify(|| FilterChange::Expired);
+            }
+        }
+        expired
     }
 }
 
-impl<S: Subscriber> Layer<S> for LineFilter
-where
-    EnvFilter: Layer<S>,
-{
-    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
-        if self.contains(metadata) {
+// The decisions shared by `LineFilter`'s `Layer` implementation and
+// `LineFilterFilter`'s `Filter` implementation.
+impl LineFilter {
+    pub(crate) fn callsite_interest(&self, metadata: &'static Metadata<'static>) -> Interest {
+        let _timer = self.overhead.register_callsite();
+        if let Some(ref recorder) = self.recorder {
+            recorder.record(metadata);
+        }
+
+        self.hits.register(self, metadata);
+        self.record_event_match(metadata, || self.decide(metadata));
+
+        // Decision events are checked by `enabled`, which lets them through
+        // while they are being recorded.
+        if metadata.target() == decisions::TARGET {
+            return Interest::sometimes();
+        }
+        if metadata.target() == audit::TARGET || metadata.target() == flush::TARGET {
             return Interest::always();
         }
 
-        self.env
+        #[cfg(feature = "log")]
+        if self.is_normalized_log(metadata) {
+            return Interest::sometimes();
+        }
+
+        // Spans and events are marked in `on_new_span` and `event_enabled`,
+        // which are called whatever the callsite's interest.
+        if self.marking {
+            return Interest::always();
+        }
+
+        // Whether a conditional entry matches depends on the spans that are
+        // entered when `enabled` is called.
+        if self.has_conditional_match(metadata) {
+            return Interest::sometimes();
+        }
+
+        if let Some(enabled) = self.decide(metadata) {
+            // If the entries will expire, or startup will end, `enabled` must
+            // keep being called so that it can notice.
+            if self.expires.is_some() || self.sessions_expire() || self.is_starting_up() {
+                return Interest::sometimes();
+            }
+            return if enabled {
+                Interest::always()
+            } else {
+                Interest::never()
+            };
+        }
+
+        // Any span or event could descend from a subtree's root.
+        if self.has_subtrees() {
+            return Interest::sometimes();
+        }
+
+        self.fallback
             .as_ref()
-            .map(|env| env.register_callsite(metadata))
+            .map(|fallback| fallback.register_callsite(metadata))
             .unwrap_or_else(Interest::never)
     }
 
+    pub(crate) fn is_enabled<S>(&self, metadata: &Metadata<'_>, cx: layer::Context<'_, S>) -> bool
+    where
+        S: Subscriber,
+    {
+        let _timer = self.overhead.enabled();
+        if decisions::is_recording() {
+            return metadata.target() == decisions::TARGET;
+        }
+        if metadata.target() == audit::TARGET || metadata.target() == flush::TARGET {
+            return true;
+        }
+        if self.marking {
+            return true;
+        }
+
+        // `log` records are filtered in `event_enabled`, once their
+        // normalized metadata is available.
+        #[cfg(feature = "log")]
+        if self.is_normalized_log(metadata) {
+            return true;
+        }
+
+        let decision = self.decide(metadata);
+        self.record_event_match(metadata, || decision);
+        let enabled = match decision {
+            Some(true) => {
+                self.trace_decision(metadata, true);
+                return true;
+            }
+            Some(false) => false,
+            None if self.is_in_subtree(&cx) => {
+                self.trace_decision(metadata, true);
+                return true;
+            }
+            None => self
+                .fallback
+                .as_ref()
+                .is_some_and(|fallback| fallback.enabled(metadata, cx)),
+        };
+        self.trace_decision(metadata, enabled);
+        if !enabled {
+            self.hits.record_suppressed();
+            #[cfg(feature = "metrics")]
+            self.metrics.record_suppressed();
+        }
+        enabled
+    }
+
+    pub(crate) fn is_event_enabled<S>(
+        &self,
+        event: &tracing_core::Event<'_>,
+        cx: layer::Context<'_, S>,
+    ) -> bool
+    where
+        S: Subscriber,
+    {
+        #[cfg(not(feature = "log"))]
+        let _ = cx;
+        #[cfg(feature = "log")]
+        if self.is_normalized_log(event.metadata()) {
+            let enabled = self.log_enabled(event, cx);
+            if enabled {
+                self.record_allowed_callsite(event.metadata());
+            }
+            return enabled;
+        }
+        if self.marking {
+            self.mark_event(event.metadata().callsite(), event.metadata());
+        }
+        self.record_allowed_callsite(event.metadata());
+        true
+    }
+
+    pub(crate) fn span_created(
+        &self,
+        attrs: &tracing_core::span::Attributes<'_>,
+        id: &tracing_core::span::Id,
+    ) {
+        if self.marking {
+            self.mark_span(attrs, id);
+        } else {
+            self.record_provenance(attrs, id);
+        }
+        self.attach_span_fields(attrs, id);
+        #[cfg(feature = "otel")]
+        self.record_pin(attrs.metadata());
+        self.span_condition_created(attrs.metadata(), id);
+        self.record_allowed_callsite(attrs.metadata());
+        self.matched(attrs.metadata());
+    }
+
+    /// Records that a span or event with `metadata` was created or recorded,
+    /// if one of this filter's entries enabled it.
+    pub(crate) fn matched(&self, metadata: &Metadata<'_>) {
+        if !self.is_disabled() && !self.is_expired() {
+            self.hits.record(metadata);
+            self.alert(metadata);
+            self.record_session_hit(metadata);
+            #[cfg(feature = "futures")]
+            self.send_match(metadata);
+        }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for LineFilter {
+    fn on_register_dispatch(&self, subscriber: &tracing_core::Dispatch) {
+        if self.announce.enabled {
+            self.announce.announce(self, subscriber);
+        }
+    }
+
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        self.callsite_interest(metadata)
+    }
+
     fn enabled(&self, metadata: &Metadata<'_>, cx: layer::Context<'_, S>) -> bool {
-        if self.contains(metadata) {
+        self.is_enabled(metadata, cx)
+    }
+
+    fn event_enabled(&self, event: &tracing_core::Event<'_>, cx: layer::Context<'_, S>) -> bool {
+        self.is_event_enabled(event, cx)
+    }
+
+    fn on_new_span(
+        &self,
+        attrs: &tracing_core::span::Attributes<'_>,
+        id: &tracing_core::span::Id,
+        cx: layer::Context<'_, S>,
+    ) {
+        self.subtree_span_created(attrs, id, &cx);
+        self.span_created(attrs, id);
+    }
+
+    fn on_event(&self, event: &tracing_core::Event<'_>, _: layer::Context<'_, S>) {
+        self.matched(event.metadata());
+    }
+
+    fn on_enter(&self, id: &tracing_core::span::Id, _: layer::Context<'_, S>) {
+        self.span_condition_entered(id);
+    }
+
+    fn on_exit(&self, id: &tracing_core::span::Id, _: layer::Context<'_, S>) {
+        self.span_condition_exited(id);
+    }
+
+    fn on_close(&self, id: tracing_core::span::Id, _: layer::Context<'_, S>) {
+        self.span_condition_closed(&id);
+        self.subtree_span_closed(&id);
+    }
+}
+
+impl LineFilter {
+    /// Decides whether a `log` record with normalized metadata is enabled.
+    #[cfg(feature = "log")]
+    fn log_enabled<S>(&self, event: &tracing_core::Event<'_>, cx: layer::Context<'_, S>) -> bool
+    where
+        S: Subscriber,
+    {
+        use tracing_log::NormalizeEvent;
+
+        let _timer = self.overhead.enabled();
+        let metadata = match event.normalized_metadata() {
+            Some(metadata) => metadata,
+            None => return true,
+        };
+        if self.marking {
+            self.mark_event(event.metadata().callsite(), &metadata);
             return true;
         }
+        let enabled = self.decide(&metadata).unwrap_or_else(|| {
+            self.fallback
+                .as_ref()
+                .is_some_and(|fallback| fallback.enabled(&metadata, cx))
+        });
+        self.trace_decision(&metadata, enabled);
+        if !enabled {
+            self.hits.record_suppressed();
+            #[cfg(feature = "metrics")]
+            self.metrics.record_suppressed();
+        }
+        enabled
+    }
+}
 
-        self.env
-            .as_ref()
-            .map(|env| env.enabled(metadata, cx))
-            .unwrap_or(false)
+/// Returns the keys that module entries are matched against for `metadata`:
+/// its module path, followed by its target if that is different. Spans and
+/// events without a module path are only matched by their target.
+pub(crate) fn module_keys<'a>(metadata: &Metadata<'a>) -> impl Iterator<Item = &'a str> {
+    let target = metadata.target();
+    let module = metadata.module_path().filter(|&module| module != target);
+    module.into_iter().chain(std::iter::once(target))
+}
+
+/// Returns the first of the [`module_keys`] for `metadata`, which spans and
+/// events are listed under.
+pub(crate) fn module_key<'a>(metadata: &Metadata<'a>) -> &'a str {
+    module_keys(metadata)
+        .next()
+        .expect("the target is always a module key")
+}
+
+/// Checks that `file` is valid for use in a `LineFilter`, returning it as a
+/// string if it is.
+fn validate_path(file: &Path) -> Result<&str, BadPath> {
+    // WebAssembly targets have no native absolute paths, so `file!()` paths
+    // are whatever the build machine's were.
+    if !file.is_absolute() && !cfg!(target_family = "wasm") {
+        return Err(BadPath::new(file, "file paths must be absolute"));
     }
+
+    if file.extension().and_then(std::ffi::OsStr::to_str) != Some("rs") {
+        return Err(BadPath::new(file, "files must be Rust source code files"));
+    }
+
+    file.to_str()
+        .ok_or_else(|| BadPath::new(file, "file paths must be valid utf-8"))
+}
+
+/// Checks that `module` is a valid Rust module path.
+fn validate_module(module: &str) -> Result<(), BadModule> {
+    let error = |message| {
+        Err(BadModule {
+            module: module.to_owned(),
+            message,
+        })
+    };
+    if module.is_empty() {
+        return error("module paths may not be empty");
+    }
+    if module.contains(char::is_whitespace) {
+        return error("module paths may not contain whitespace");
+    }
+    if module.contains(['/', '\\']) || module.ends_with(".rs") {
+        return error("module paths are separated by `::`; use `enable_by_file` for file paths");
+    }
+    if module.contains('@') {
+        return error("module paths may not contain `@`; pass the line number separately");
+    }
+    if module.contains(['(', ')']) {
+        return error(
+            "module paths may not contain function calls; use the path of the module \
+             containing the function",
+        );
+    }
+    for segment in module.split("::") {
+        if segment.is_empty() {
+            return error("module paths may not have empty segments");
+        }
+        if segment.contains(':') {
+            return error("module path segments are separated by `::`, not `:`");
+        }
+        let mut chars = segment.chars();
+        let starts_ident = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_');
+        if !starts_ident || !chars.all(|c| c.is_alphanumeric() || c == '_') {
+            return error("module path segments must be Rust identifiers");
+        }
+    }
+    Ok(())
 }
 
 // === impl BadPath ===
@@ -415,3 +1969,13 @@ impl BadPath {
         }
     }
 }
+
+// === impl BadModule ===
+
+impl fmt::Display for BadModule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid module path '{}': {}", self.module, self.message)
+    }
+}
+
+impl std::error::Error for BadModule {}
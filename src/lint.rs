@@ -0,0 +1,333 @@
+use crate::{diff, CallsiteDesc, Directive, EnvDecision, FilterConfig, LineFilter, Validation};
+use std::fmt;
+use tracing_core::Level;
+
+/// A problem with one of a [`FilterConfig`]'s directives, returned by
+/// [`FilterConfig::lint`].
+///
+/// None of these problems stop a config from being applied, but each of them
+/// means that a directive does nothing, so it was probably written by
+/// mistake, or has been left behind by a change to the rest of the config.
+///
+/// The `Display` implementation formats a short, human-readable description,
+/// such as ``duplicate directive `my_crate@debug` (same as `my_crate@info`)``.
+/// When the `serde` feature flag is enabled, this implements
+/// `serde::Serialize`, with each directive serialized as a string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Lint {
+    kind: LintKind,
+    directive: Directive,
+    profile: Option<String>,
+}
+
+/// What kind of problem a [`Lint`] is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub enum LintKind {
+    /// The directive applies to the same location as an earlier directive in
+    /// the same section, or in the top-level section, so only one of them
+    /// takes effect.
+    Duplicate {
+        /// The earlier directive.
+        first: Directive,
+    },
+    /// The directive is a `module@line` entry, a `module@level` default, or a
+    /// `module::**` prefix whose spans and events are already enabled by a
+    /// broader directive in the same section, or in the top-level section.
+    Shadowed {
+        /// The directive that already enables them, or `None` if it is the
+        /// config's `EnvFilter` directives.
+        by: Option<Directive>,
+    },
+    /// The deny directive never disables anything, because none of the
+    /// config's other directives, in any section, enable any of the spans and
+    /// events it denies.
+    UnusedDeny,
+}
+
+impl FilterConfig {
+    /// Checks this config for directives that do nothing.
+    ///
+    /// This finds:
+    ///
+    /// - Duplicate directives, which apply to the same location as an earlier
+    ///   one, such as `my_crate::db@142` and `my_crate::db@142:1a2b3c4d`, or
+    ///   `my_crate@info` and `my_crate@debug`.
+    /// - Shadowed directives, whose spans and events are already enabled by a
+    ///   broader one, such as `my_crate::db@142` when the config also has
+    ///   `my_crate::**`. Since a `file@line` entry's module isn't known, only
+    ///   the other kinds of directives are checked.
+    /// - Unused deny directives, which deny spans and events that nothing in
+    ///   the config would enable, such as `!my_crate::db@142` without any
+    ///   directive for `my_crate::db`.
+    ///
+    /// Profiles are checked along with the top-level directives, which
+    /// always apply when the profile is active. The problems are returned in
+    /// the order of the directives they were found in, starting with the
+    /// top-level directives and followed by each profile's.
+    ///
+    /// Only the directives are checked. Use `LineFilter::validate_sources`
+    /// (when the `source` feature flag is enabled) to check that entries
+    /// point at spans and events.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::{FilterConfig, LintKind};
+    ///
+    /// let config: FilterConfig = "
+    ///     my_crate::db::**
+    ///     my_crate::db::pool@142
+    ///     my_crate::auth@info, my_crate::auth@debug
+    ///     !my_crate::cache@17
+    /// "
+    /// .parse()
+    /// .unwrap();
+    ///
+    /// let lints = config.lint();
+    /// assert_eq!(lints.len(), 3);
+    /// assert_eq!(
+    ///     lints[0].to_string(),
+    ///     "shadowed directive `my_crate::db::pool@142`: already enabled by `my_crate::db::**`",
+    /// );
+    /// assert!(matches!(lints[1].kind(), LintKind::Duplicate { .. }));
+    /// assert_eq!(lints[2].kind(), &LintKind::UnusedDeny);
+    /// assert_eq!(lints[2].directive().to_string(), "!my_crate::cache@17");
+    /// ```
+    pub fn lint(&self) -> Vec<Lint> {
+        let mut lints = Vec::new();
+        // Profiles may enable spans and events that the top-level directives
+        // deny, so deny directives are checked against every section.
+        let allows = self
+            .directives()
+            .iter()
+            .chain(self.profiles().flat_map(|(_, directives)| directives))
+            .filter(|directive| !matches!(directive, Directive::Deny(_)))
+            .collect::<Vec<_>>();
+        lint_section(&mut lints, None, &[], self.directives(), &allows);
+        for (name, directives) in self.profiles() {
+            lint_section(
+                &mut lints,
+                Some(name),
+                self.directives(),
+                directives,
+                &allows,
+            );
+        }
+        lints
+    }
+}
+
+// === impl Lint ===
+
+impl Lint {
+    /// Returns what kind of problem this is.
+    pub fn kind(&self) -> &LintKind {
+        &self.kind
+    }
+
+    /// Returns the directive with the problem.
+    pub fn directive(&self) -> &Directive {
+        &self.directive
+    }
+
+    /// Returns the name of the profile the directive is in, or `None` if it
+    /// is a top-level directive.
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(ref profile) = self.profile {
+            write!(f, "in profile `{}`: ", profile)?;
+        }
+        match self.kind {
+            LintKind::Duplicate { ref first } => write!(
+                f,
+                "duplicate directive `{}` (same as `{}`)",
+                self.directive, first
+            ),
+            LintKind::Shadowed { by: Some(ref by) } => write!(
+                f,
+                "shadowed directive `{}`: already enabled by `{}`",
+                self.directive, by
+            ),
+            LintKind::Shadowed { by: None } => write!(
+                f,
+                "shadowed directive `{}`: already enabled by the EnvFilter directives",
+                self.directive
+            ),
+            LintKind::UnusedDeny => write!(
+                f,
+                "unused deny directive `{}`: nothing enables what it denies",
+                self.directive
+            ),
+        }
+    }
+}
+
+/// Checks the directives in one section of a config, whose `base`
+/// directives (the top-level ones, for a profile) also apply.
+fn lint_section(
+    lints: &mut Vec<Lint>,
+    profile: Option<&str>,
+    base: &[Directive],
+    directives: &[Directive],
+    allows: &[&Directive],
+) {
+    let lint = |kind, directive: &Directive| Lint {
+        kind,
+        directive: directive.clone(),
+        profile: profile.map(ToOwned::to_owned),
+    };
+    for (i, directive) in directives.iter().enumerate() {
+        let first = base
+            .iter()
+            .chain(&directives[..i])
+            .find(|earlier| key(earlier) == key(directive));
+        if let Some(first) = first {
+            let first = first.clone();
+            lints.push(lint(LintKind::Duplicate { first }, directive));
+            continue;
+        }
+
+        if let Directive::Deny(ref denied) = directive {
+            if !may_deny(denied, allows) {
+                lints.push(lint(LintKind::UnusedDeny, directive));
+            }
+            continue;
+        }
+
+        let broader = base
+            .iter()
+            .chain(directives)
+            .filter(|other| key(other) != key(directive) && is_broader(other, directive));
+        if let Some(by) = enabled_by(broader, directive) {
+            lints.push(lint(LintKind::Shadowed { by }, directive));
+        }
+    }
+}
+
+/// Returns the location `directive` applies to, which no other directive in
+/// the same section should have.
+fn key(directive: &Directive) -> String {
+    match directive {
+        // `EnvFilter` directives for the same target with different levels
+        // may still be useful, if they filter by span or field.
+        #[cfg(feature = "env-filter")]
+        Directive::Env(_) => directive.to_string(),
+        directive => diff::key(directive),
+    }
+}
+
+/// Returns `true` if `other` is a kind of directive that can shadow
+/// `directive`.
+fn is_broader(other: &Directive, directive: &Directive) -> bool {
+    match (other, directive) {
+        (Directive::ModulePrefix { .. }, Directive::Module { .. })
+        | (Directive::ModulePrefix { .. }, Directive::ModuleLevel { .. })
+        | (Directive::ModulePrefix { .. }, Directive::ModulePrefix { .. })
+        | (Directive::ModuleLevel { .. }, Directive::Module { .. })
+        | (Directive::ModuleLevel { .. }, Directive::ModuleLevel { .. }) => true,
+        #[cfg(feature = "env-filter")]
+        (Directive::Env(_), Directive::Module { .. })
+        | (Directive::Env(_), Directive::ModuleLevel { .. })
+        | (Directive::Env(_), Directive::ModulePrefix { .. }) => true,
+        _ => false,
+    }
+}
+
+/// Returns `Some` with the directive that already enables everything that
+/// `directive` enables, if there is one, or `Some(None)` if the `EnvFilter`
+/// directives among `broader` do.
+fn enabled_by<'a>(
+    broader: impl Iterator<Item = &'a Directive>,
+    directive: &Directive,
+) -> Option<Option<Directive>> {
+    let callsite = match directive {
+        Directive::Module { module, line, .. } => {
+            CallsiteDesc::event(module.as_str(), *line, Level::TRACE)
+        }
+        Directive::ModuleLevel { module, level } => {
+            CallsiteDesc::event(module.as_str(), 0, level.into_level()?)
+        }
+        // `EnvFilter` directives and other prefixes also cover submodules, so
+        // checking the prefix's own module is enough.
+        Directive::ModulePrefix { prefix } => CallsiteDesc::event(prefix.as_str(), 0, Level::TRACE),
+        _ => return None,
+    };
+    let filter = filter(broader);
+    let explanation = filter.explain(callsite.metadata());
+    if !explanation.is_enabled() {
+        return None;
+    }
+    Some(explanation.entry().cloned())
+}
+
+/// Returns `true` if any of `allows` may enable a span or event that
+/// `denied` matches.
+fn may_deny(denied: &Directive, allows: &[&Directive]) -> bool {
+    let callsite = match denied {
+        Directive::Module { module, line, .. } => {
+            // A `file@line` entry may be on the same line.
+            if allows
+                .iter()
+                .any(|allow| matches!(allow, Directive::File { .. }))
+            {
+                return true;
+            }
+            CallsiteDesc::event(module.as_str(), *line, Level::ERROR)
+        }
+        // The module of a file denied by `file@line` isn't known, so any
+        // directive other than a `file@line` entry for another location may
+        // enable it.
+        Directive::File { .. } => {
+            return allows.iter().any(|allow| match allow {
+                Directive::File { .. } => key(allow) == key(denied),
+                _ => true,
+            });
+        }
+        Directive::ModulePrefix { prefix } => {
+            return allows.iter().any(|allow| match allow {
+                Directive::Module { module, .. } | Directive::ModuleLevel { module, .. } => {
+                    within_prefix(module, prefix)
+                }
+                Directive::ModulePrefix { prefix: other } => {
+                    within_prefix(other, prefix) || within_prefix(prefix, other)
+                }
+                // `EnvFilter` directives match targets by prefix, so assume
+                // that they may enable the denied modules.
+                _ => true,
+            });
+        }
+        _ => return true,
+    };
+    let filter = filter(allows.iter().copied());
+    let explanation = filter.explain(callsite.metadata());
+    explanation.is_enabled() || explanation.env() == Some(EnvDecision::Dynamic)
+}
+
+/// Returns `true` if `module` is in the module at `prefix`, or one of its
+/// submodules.
+fn within_prefix(module: &str, prefix: &str) -> bool {
+    module == prefix
+        || module
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with("::"))
+}
+
+/// Returns a filter with `directives`.
+fn filter<'a>(directives: impl Iterator<Item = &'a Directive>) -> LineFilter {
+    let mut filter = LineFilter::new();
+    filter.with_validation(Validation::Relaxed);
+    for directive in directives {
+        // Parsed configs only have valid paths, and configs built from
+        // directives with invalid paths can't be applied anyway.
+        let _ = filter.add_directive(directive.clone());
+    }
+    filter
+}
@@ -0,0 +1,189 @@
+use crate::{stats::Entry, Directive, LineFilter};
+use std::collections::HashMap;
+
+/// How many evicted directives are kept until a control endpoint records
+/// audit events for them.
+const MAX_PENDING: usize = 64;
+
+/// The limit on a filter's `(module, line)` and `(file, line)` entries,
+/// and what is needed to evict the least recently used ones.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Lru {
+    max: Option<usize>,
+    /// The use of the filter's entries that added each entry, counted by
+    /// the filter's hit counters.
+    added: HashMap<Entry, u64>,
+    /// Directives that were evicted since the last time they were taken.
+    evicted: Vec<Directive>,
+}
+
+impl LineFilter {
+    /// Limits this filter to `max` `(module, line)` and `(file, line)`
+    /// entries, evicting the least recently used entry whenever adding
+    /// another one would exceed the limit, or removes the limit if `max` is
+    /// `None`.
+    ///
+    /// Entries are added at runtime by control endpoints, editors, and
+    /// automation, and a leaky script can keep adding them over months of
+    /// uptime. An entry was last used when it last enabled a span or event,
+    /// or when it was added if that was more recent, so entries that never
+    /// fire are evicted first. Lowering the limit evicts entries right away,
+    /// and each eviction is reported as a [`FilterChange::Removed`] by the
+    /// filter's [`changes`] stream (when the `futures` feature flag is
+    /// enabled). When an eviction is caused by a change made through a
+    /// control endpoint, such as [`Command::apply`], the endpoint also
+    /// records an event with the `tracing_line_filter::audit` target and
+    /// the evicted `directive`.
+    ///
+    /// Entries that are applied with a config, such as by [`set_config`],
+    /// count toward the limit and are evicted in the same way as any others:
+    /// the limit is meant to bound the filter's memory use, wherever its
+    /// entries come from. A config's entries are added in order, so if it
+    /// has more than `max` of them, only its last `max` are kept.
+    ///
+    /// The other kinds of entries aren't limited, since a filter only has one
+    /// per module.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter
+    ///     .max_entries(2)
+    ///     .enable_by_mod("my_crate::db", 142)
+    ///     .enable_by_mod("my_crate::db", 200)
+    ///     .enable_by_mod("my_crate::db", 250);
+    ///
+    /// // Nothing was hit, so the oldest entry was evicted.
+    /// assert_eq!(filter.to_string(), "my_crate::db@200,my_crate::db@250");
+    /// ```
+    ///
+    /// [`FilterChange::Removed`]: https://docs.rs/tracing-line-filter/latest/tracing_line_filter/enum.FilterChange.html#variant.Removed
+    /// [`changes`]: https://docs.rs/tracing-line-filter/latest/tracing_line_filter/struct.LineFilter.html#method.changes
+    /// [`Command::apply`]: crate::Command::apply
+    /// [`set_config`]: Self::set_config
+    pub fn max_entries(&mut self, max: impl Into<Option<usize>>) -> &mut Self {
+        self.lru.max = max.into();
+        self.evict(None);
+        self
+    }
+
+    /// Remembers that `entry` was just added, and evicts other entries if
+    /// there are now too many.
+    pub(crate) fn entry_added(&mut self, entry: Entry) {
+        // Adding an entry that is already there counts as using it.
+        let added = self.hits.next_use();
+        self.lru.added.insert(entry.clone(), added);
+        self.evict(Some(&entry));
+    }
+
+    pub(crate) fn entry_removed(&mut self, entry: &Entry) {
+        self.lru.added.remove(entry);
+    }
+
+    /// Returns the directives evicted since this was last called.
+    pub(crate) fn take_evicted(&mut self) -> Vec<Directive> {
+        std::mem::take(&mut self.lru.evicted)
+    }
+
+    /// Evicts the least recently used entries other than `keep` until there
+    /// are no more than the maximum.
+    fn evict(&mut self, keep: Option<&Entry>) {
+        let max = match self.lru.max {
+            Some(max) => max,
+            None => return,
+        };
+        while self.entry_count() > max {
+            let modules = self
+                .by_module
+                .iter()
+                .map(|(module, line)| Entry::Module(module.clone(), *line));
+            let files = self
+                .by_file
+                .iter()
+                .map(|(file, line)| Entry::File(file.clone(), *line));
+            let lru = modules
+                .chain(files)
+                .filter(|entry| Some(entry) != keep)
+                .min_by_key(|entry| {
+                    let added = self.lru.added.get(entry).copied().unwrap_or(0);
+                    added.max(self.hits.last_used(entry))
+                });
+            let directive = match lru {
+                Some(Entry::Module(module, line)) => self.module_directive(&(module, line)),
+                Some(Entry::File(file, line)) => self.file_directive(&(file, line)),
+                None => return,
+            };
+            self.remove_directive(&directive);
+            if self.lru.evicted.len() == MAX_PENDING {
+                self.lru.evicted.remove(0);
+            }
+            self.lru.evicted.push(directive);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::{prelude::*, reload};
+
+    #[test]
+    fn evicts_the_least_recently_added_entry() {
+        let mut filter = LineFilter::default();
+        filter
+            .max_entries(3)
+            .enable_by_mod("m", 1)
+            .enable_by_mod("m", 2)
+            .enable_by_mod("m", 3)
+            // Adding an entry again counts as using it.
+            .enable_by_mod("m", 1)
+            .enable_by_mod("m", 4);
+        assert_eq!(filter.to_string(), "m@1,m@3,m@4");
+        assert_eq!(filter.take_evicted(), vec!["m@2".parse().unwrap()]);
+
+        // Lowering the limit evicts entries in the same order.
+        filter.max_entries(1);
+        assert_eq!(filter.to_string(), "m@4");
+    }
+
+    #[test]
+    fn hits_count_as_uses() {
+        let line = line!() + 11;
+        let mut filter = LineFilter::default();
+        filter
+            .max_entries(2)
+            .enable_by_mod(module_path!(), line)
+            .enable_by_mod(module_path!(), 1);
+        let (filter, handle) = reload::Layer::new(filter);
+        let subscriber = tracing_subscriber::registry().with(filter);
+
+        tracing::subscriber::with_default(subscriber, || {
+            // The entry for this line was added first, but hit last.
+            tracing::info!(target: "lru_hits_count_as_uses", "hit");
+            handle
+                .modify(|filter| {
+                    filter.enable_by_mod(module_path!(), 2);
+                })
+                .unwrap();
+            let current = handle.with_current(|filter| filter.to_string()).unwrap();
+            assert_eq!(current, format!("{0}@2,{0}@{1}", module_path!(), line));
+        });
+    }
+
+    #[test]
+    fn the_added_entry_is_kept() {
+        let mut filter = LineFilter::default();
+        filter.enable_by_mod("m", 1).max_entries(0);
+        assert_eq!(filter.to_string(), "");
+
+        // An entry that was just added isn't evicted to make room for
+        // itself, even if the limit is zero.
+        filter.enable_by_mod("m", 2);
+        assert_eq!(filter.to_string(), "m@2");
+        filter.enable_by_file("/src/m.rs", 3).unwrap();
+        assert_eq!(filter.to_string(), "/src/m.rs@3");
+    }
+}
@@ -0,0 +1,153 @@
+use crate::LineFilter;
+use std::cell::RefCell;
+use tracing_core::{callsite::Identifier, span, Event, Metadata};
+use tracing_subscriber::{registry::LookupSpan, Registry};
+
+thread_local! {
+    /// The callsite of the event most recently checked by a marking filter on
+    /// this thread, and its marker, if one of the filter's entries matched.
+    static MARKED_EVENT: RefCell<Option<(Identifier, LineMatch)>> = const { RefCell::new(None) };
+}
+
+/// Marks a span or event that one of a [`LineFilter`]'s entries matched,
+/// when the filter is in [marking mode].
+///
+/// For spans, a `LineMatch` is added to the span's [extensions], where
+/// other layers can find it using [`SpanRef::extensions`]. For events, which
+/// don't have extensions, use [`LineMatch::of_event`].
+///
+/// [marking mode]: LineFilter::mark_only
+/// [extensions]: tracing_subscriber::registry::Extensions
+/// [`SpanRef::extensions`]: tracing_subscriber::registry::SpanRef::extensions
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineMatch {
+    directive: String,
+}
+
+impl LineFilter {
+    /// Switches this filter to *marking mode*, where it enables every span
+    /// and event and marks the ones its entries match, instead of disabling
+    /// the rest.
+    ///
+    /// In marking mode, downstream layers decide what to do with the spans
+    /// and events that the filter's entries match, such as highlighting them
+    /// in a log, routing them to a separate file, or always exporting them.
+    /// Spans that an entry matches have a [`LineMatch`] added to their
+    /// extensions, and an event's `LineMatch` can be found by the layers
+    /// that record it using [`LineMatch::of_event`]. The filter's
+    /// [`EnvFilter`], if it has one, is ignored.
+    ///
+    /// `tracing` has no way to add fields to a span or event after it is
+    /// created, so matches are recorded alongside them instead. Span markers
+    /// are only added when the filter is part of a subscriber built on a
+    /// [`Registry`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing::{Event, Subscriber};
+    /// use tracing_line_filter::{LineFilter, LineMatch};
+    /// use tracing_subscriber::{layer, prelude::*};
+    ///
+    /// // A layer that flags the events on pinned lines.
+    /// struct Highlight;
+    ///
+    /// impl<S: Subscriber> tracing_subscriber::Layer<S> for Highlight {
+    ///     fn on_event(&self, event: &Event<'_>, _: layer::Context<'_, S>) {
+    ///         if let Some(line_match) = LineMatch::of_event(event) {
+    ///             eprintln!("matched by `{}`: {:?}", line_match.directive(), event);
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let mut filter: LineFilter = "my_crate::db@142".parse().unwrap();
+    /// filter.mark_only();
+    ///
+    /// tracing_subscriber::registry()
+    ///     .with(filter)
+    ///     // Every span and event is logged...
+    ///     .with(tracing_subscriber::fmt::layer())
+    ///     // ...and those on pinned lines are also flagged.
+    ///     .with(Highlight)
+    ///     .init();
+    /// ```
+    ///
+    /// [`EnvFilter`]: tracing_subscriber::EnvFilter
+    /// [`Registry`]: tracing_subscriber::Registry
+    pub fn mark_only(&mut self) -> &mut Self {
+        self.marking = true;
+        self
+    }
+
+    /// Returns `true` if this filter is in [marking mode](Self::mark_only).
+    pub fn is_marking(&self) -> bool {
+        self.marking
+    }
+
+    /// Returns the marker for `metadata`, if one of the filter's entries
+    /// matches it.
+    fn line_match(&self, metadata: &Metadata<'_>) -> Option<LineMatch> {
+        if self.decide(metadata) != Some(true) {
+            return None;
+        }
+        let (_, entry) = self.allow_match(metadata)?;
+        Some(LineMatch {
+            directive: entry.to_directive().to_string(),
+        })
+    }
+
+    /// Marks the event from `callsite` with `metadata`, so that layers
+    /// recording it can find its marker.
+    ///
+    /// The metadata of `log` records is normalized, so it doesn't
+    /// necessarily belong to the event's callsite.
+    pub(crate) fn mark_event(&self, callsite: Identifier, metadata: &Metadata<'_>) {
+        let marked = self
+            .line_match(metadata)
+            .map(|line_match| (callsite, line_match));
+        MARKED_EVENT.with(|event| *event.borrow_mut() = marked);
+    }
+
+    /// Adds a marker to the span with `attrs`, if one of the filter's
+    /// entries matches it.
+    pub(crate) fn mark_span(&self, attrs: &span::Attributes<'_>, id: &span::Id) {
+        let line_match = match self.line_match(attrs.metadata()) {
+            Some(line_match) => line_match,
+            None => return,
+        };
+        tracing::dispatcher::get_default(|dispatch| {
+            let span = dispatch
+                .downcast_ref::<Registry>()
+                .and_then(|registry| registry.span(id));
+            if let Some(span) = span {
+                span.extensions_mut().replace(line_match.clone());
+            }
+        });
+    }
+}
+
+// === impl LineMatch ===
+
+impl LineMatch {
+    /// Returns the marker for `event`, if it was matched by one of the
+    /// entries of a filter in [marking mode].
+    ///
+    /// This only returns the marker while the event is being recorded, such
+    /// as in a layer's [`on_event`].
+    ///
+    /// [marking mode]: LineFilter::mark_only
+    /// [`on_event`]: tracing_subscriber::Layer::on_event
+    pub fn of_event(event: &Event<'_>) -> Option<Self> {
+        MARKED_EVENT.with(|marked| match &*marked.borrow() {
+            Some((callsite, line_match)) if *callsite == event.metadata().callsite() => {
+                Some(line_match.clone())
+            }
+            _ => None,
+        })
+    }
+
+    /// Returns the entry that matched, such as `my_crate::db@142`.
+    pub fn directive(&self) -> &str {
+        &self.directive
+    }
+}
@@ -0,0 +1,156 @@
+use crate::{time::SystemTime, Directive, LineFilter};
+use futures_channel::mpsc;
+use futures_core::Stream;
+use std::{
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll},
+    thread::{self, ThreadId},
+};
+use tracing_core::Metadata;
+
+/// How many match records a [`MatchEvents`] stream buffers before new
+/// records are dropped.
+const CAPACITY: usize = 1024;
+
+/// A record of one of a [`LineFilter`]'s entries matching a span or event,
+/// as reported by [`LineFilter::match_events`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchRecord {
+    directive: Directive,
+    time: SystemTime,
+    thread: ThreadId,
+    thread_name: Option<String>,
+}
+
+/// A [`Stream`] of the spans and events matched by a [`LineFilter`]'s
+/// entries, returned by [`LineFilter::match_events`].
+#[derive(Debug)]
+#[must_use = "streams do nothing unless polled"]
+pub struct MatchEvents {
+    rx: mpsc::Receiver<MatchRecord>,
+}
+
+/// The senders for a filter's [`MatchEvents`] streams.
+#[derive(Debug, Default)]
+pub(crate) struct Subscribers {
+    txs: Mutex<Vec<mpsc::Sender<MatchRecord>>>,
+}
+
+impl LineFilter {
+    /// Returns a [`Stream`] of records of the spans and events that this
+    /// filter's entries match from now on.
+    ///
+    /// Each call returns a new stream. A [`MatchRecord`] is sent to every
+    /// stream each time a span that an entry matches is created, or an event
+    /// that an entry matches is recorded, naming the entry, the time, and
+    /// the thread. This lets async tasks react to activity on pinned lines,
+    /// such as by updating a dashboard, without writing a custom layer; use
+    /// [`on_match`] to react synchronously instead.
+    ///
+    /// Each stream buffers up to 1024 records. If a stream isn't read fast
+    /// enough, new records are dropped until there is room for them. The
+    /// stream ends when the filter is dropped, as with [`changes`].
+    ///
+    /// This method is only available when the `futures` feature flag is
+    /// enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use futures::StreamExt;
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::{prelude::*, reload};
+    ///
+    /// let directive = format!("{}@{}", module_path!(), line!() + 7);
+    /// let (filter, handle) = reload::Layer::new(directive.parse::<LineFilter>().unwrap());
+    /// let subscriber = tracing_subscriber::registry().with(filter);
+    ///
+    /// let mut matches = handle.with_current(LineFilter::match_events).unwrap();
+    ///
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info!("pinned");
+    /// });
+    ///
+    /// let record = futures::executor::block_on(matches.next()).unwrap();
+    /// assert_eq!(record.directive().to_string(), directive);
+    /// assert_eq!(record.thread(), std::thread::current().id());
+    /// ```
+    ///
+    /// [`on_match`]: Self::on_match
+    /// [`changes`]: Self::changes
+    pub fn match_events(&self) -> MatchEvents {
+        let (tx, rx) = mpsc::channel(CAPACITY);
+        self.matches
+            .txs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(tx);
+        MatchEvents { rx }
+    }
+
+    /// Sends a record of the entry that enables `metadata` to every
+    /// [`MatchEvents`] stream, if there are any.
+    pub(crate) fn send_match(&self, metadata: &Metadata<'_>) {
+        let mut txs = self.matches.txs.lock().unwrap_or_else(|e| e.into_inner());
+        if txs.is_empty() || self.decide(metadata) != Some(true) {
+            return;
+        }
+        let directive = match self.allow_match(metadata) {
+            Some((_, entry)) => entry.to_directive(),
+            None => return,
+        };
+        let thread = thread::current();
+        let record = MatchRecord {
+            directive,
+            time: SystemTime::now(),
+            thread: thread.id(),
+            thread_name: thread.name().map(str::to_owned),
+        };
+        // Streams that have been dropped are removed, and full streams miss
+        // the record.
+        txs.retain_mut(|tx| match tx.try_send(record.clone()) {
+            Ok(()) => true,
+            Err(error) => error.is_full(),
+        });
+    }
+}
+
+// === impl MatchRecord ===
+
+impl MatchRecord {
+    /// Returns the directive for the entry that matched.
+    pub fn directive(&self) -> &Directive {
+        &self.directive
+    }
+
+    /// Returns when the entry matched.
+    pub fn time(&self) -> SystemTime {
+        self.time
+    }
+
+    /// Returns the ID of the thread that the span or event was recorded on.
+    pub fn thread(&self) -> ThreadId {
+        self.thread
+    }
+
+    /// Returns the name of the thread that the span or event was recorded
+    /// on, if it has one.
+    pub fn thread_name(&self) -> Option<&str> {
+        self.thread_name.as_deref()
+    }
+}
+
+// === impl MatchEvents ===
+
+impl Stream for MatchEvents {
+    type Item = MatchRecord;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.rx).poll_next(cx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.rx.size_hint()
+    }
+}
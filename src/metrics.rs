@@ -0,0 +1,96 @@
+use crate::{
+    stats::Entry,
+    time::{SystemTime, UNIX_EPOCH},
+    LineFilter,
+};
+
+pub(crate) const MATCHES: &str = "tracing_line_filter_matches_total";
+pub(crate) const SUPPRESSED: &str = "tracing_line_filter_suppressed_total";
+pub(crate) const ENTRIES: &str = "tracing_line_filter_entries";
+pub(crate) const LAST_RELOAD: &str = "tracing_line_filter_last_reload_timestamp_seconds";
+
+/// Records a [`LineFilter`]'s metrics.
+#[derive(Debug, Default)]
+pub(crate) struct Metrics;
+
+/// Returns the counter for the spans and events matched by `entry`.
+pub(crate) fn counter(entry: &Entry) -> ::metrics::Counter {
+    let entry = match entry {
+        Entry::Module(module, line) => format!("{}@{}", module, line),
+        Entry::File(file, line) => format!("{}@{}", file, line),
+    };
+    ::metrics::counter!(MATCHES, "entry" => entry)
+}
+
+impl Metrics {
+    /// Counts a span or event that the filter disabled.
+    pub(crate) fn record_suppressed(&self) {
+        ::metrics::counter!(SUPPRESSED).increment(1);
+    }
+
+    /// Records the filter's entry count and the current time, after the
+    /// filter's config has been changed.
+    pub(crate) fn record_reload(&self, filter: &LineFilter) {
+        let entries = filter.by_module.len() + filter.by_file.len();
+        ::metrics::gauge!(ENTRIES).set(entries as f64);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        ::metrics::gauge!(LAST_RELOAD).set(now.as_secs_f64());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Command, FilterConfig};
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
+    use std::collections::HashMap;
+    use tracing_subscriber::{prelude::*, reload};
+
+    /// Returns the gauges recorded so far, by name.
+    fn gauges(snapshotter: &Snapshotter) -> HashMap<String, f64> {
+        snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .filter_map(|(key, .., value)| match value {
+                DebugValue::Gauge(value) => Some((key.key().name().to_owned(), value.into_inner())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reloads_are_recorded_when_the_config_changes() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, || {
+            let (filter, handle) = reload::Layer::new(LineFilter::default());
+            let _subscriber = tracing_subscriber::registry().with(filter);
+            // Rebuilding `tracing`'s callsite cache isn't a reload.
+            tracing_core::callsite::rebuild_interest_cache();
+            assert!(gauges(&snapshotter).is_empty());
+
+            // Neither is a command that only inspects the filter.
+            Command::List.apply(&handle, "test", None).unwrap();
+            assert!(gauges(&snapshotter).is_empty());
+
+            let enable = "enable my_crate::db@142".parse::<Command>().unwrap();
+            enable.apply(&handle, "test", None).unwrap();
+            let recorded = gauges(&snapshotter);
+            assert_eq!(recorded.get(ENTRIES), Some(&1.0));
+            assert!(recorded.contains_key(LAST_RELOAD));
+
+            let config = "my_crate::db@142\nmy_crate::db@200"
+                .parse::<FilterConfig>()
+                .unwrap();
+            handle
+                .modify(|filter| {
+                    filter.set_config(&config).unwrap();
+                })
+                .unwrap();
+            assert_eq!(gauges(&snapshotter).get(ENTRIES), Some(&2.0));
+        });
+    }
+}
@@ -0,0 +1,180 @@
+use crate::{LineFilter, ParseError};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashSet},
+    fmt,
+    str::FromStr,
+};
+
+/// A map between Rust module paths and the source files that define them,
+/// which lets a [`LineFilter`]'s `module@line` entries match callsites that
+/// only report a file, and its `file@line` entries match callsites that only
+/// report a module path.
+///
+/// Spans and events recorded with `tracing`'s macros report both their
+/// module path and their file, but those from other sources may only have
+/// one of them, such as `log` records from crates that don't report their
+/// module path, or callsites whose metadata is built by other macros. Once
+/// a filter has a map (set with [`LineFilter::with_module_map`]), an entry
+/// written in either form matches callsites that only report the other.
+///
+/// A map can be built by hand with [`insert`](Self::insert), parsed from a
+/// manifest with a `module::path = path/to/file.rs` mapping on each line
+/// (blank lines and lines starting with `#` are ignored), or, when the
+/// `source` feature flag is enabled, built by scanning a workspace's source
+/// code with `source::scan_module_map`. Its [`Display`] output is a
+/// manifest that can be parsed again, so a map can be generated at build time
+/// and loaded by programs that can't scan their own source code.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_line_filter::{LineFilter, ModuleMap};
+///
+/// let map: ModuleMap = "
+/// ## Generated by the build script.
+/// my_crate::db = src/db.rs
+/// my_crate::db::pool = src/db.rs
+/// "
+/// .parse()
+/// .unwrap();
+/// assert_eq!(map.file_for("my_crate::db"), Some("src/db.rs"));
+/// assert_eq!(
+///     map.modules_in("src/db.rs").collect::<Vec<_>>(),
+///     ["my_crate::db", "my_crate::db::pool"],
+/// );
+///
+/// let mut filter = LineFilter::default();
+/// filter.enable_by_mod("my_crate::db", 42).with_module_map(map);
+/// ```
+///
+/// [`Display`]: std::fmt::Display
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ModuleMap {
+    /// The file that defines each module.
+    modules: BTreeMap<String, String>,
+    /// The modules defined by each file, in order.
+    files: BTreeMap<String, Vec<String>>,
+}
+
+impl LineFilter {
+    /// Sets the [`ModuleMap`] used to match `module@line` entries against
+    /// callsites that only report a file, and `file@line` entries against
+    /// callsites that only report a module path.
+    ///
+    /// Setting a new map replaces the old one.
+    pub fn with_module_map(&mut self, map: ModuleMap) -> &mut Self {
+        *self.module_map = map;
+        self
+    }
+}
+
+// === impl ModuleMap ===
+
+impl ModuleMap {
+    /// Returns a new, empty `ModuleMap`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that the module at `module` is defined in `file`, replacing
+    /// the file it was previously mapped to.
+    ///
+    /// Several modules can be defined in the same file, such as a module and
+    /// the inline modules inside it.
+    pub fn insert(&mut self, module: impl Into<String>, file: impl Into<String>) -> &mut Self {
+        let (module, file) = (module.into(), file.into());
+        if let Some(old) = self.modules.insert(module.clone(), file.clone()) {
+            if let Some(modules) = self.files.get_mut(&old) {
+                modules.retain(|m| *m != module);
+                if modules.is_empty() {
+                    self.files.remove(&old);
+                }
+            }
+        }
+        let modules = self.files.entry(file).or_default();
+        let i = modules.binary_search(&module).unwrap_or_else(|i| i);
+        modules.insert(i, module);
+        self
+    }
+
+    /// Returns the file that defines the module at `module`, if it is in the
+    /// map.
+    pub fn file_for(&self, module: &str) -> Option<&str> {
+        self.modules.get(module).map(String::as_str)
+    }
+
+    /// Returns the modules defined in `file`, sorted by module path.
+    pub fn modules_in(&self, file: &str) -> impl Iterator<Item = &str> + '_ {
+        self.files
+            .get(file)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+    }
+
+    /// Returns the number of modules in the map.
+    pub fn len(&self) -> usize {
+        self.modules.len()
+    }
+
+    /// Returns `true` if the map has no modules.
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty()
+    }
+
+    /// Returns a module defined in `file` that has an entry in `by_module`
+    /// on `line`.
+    pub(crate) fn mapped_module(
+        &self,
+        by_module: &HashSet<(Cow<'static, str>, u32)>,
+        file: &str,
+        line: u32,
+    ) -> Option<&str> {
+        self.modules_in(file)
+            .find(|module| by_module.contains(&(Cow::Borrowed(*module), line)))
+    }
+
+    /// Returns the file that defines `module`, if it has an entry in
+    /// `by_file` on `line`.
+    pub(crate) fn mapped_file(
+        &self,
+        by_file: &HashSet<(Cow<'static, str>, u32)>,
+        module: &str,
+        line: u32,
+    ) -> Option<&str> {
+        self.file_for(module)
+            .filter(|file| by_file.contains(&(Cow::Borrowed(*file), line)))
+    }
+}
+
+impl FromStr for ModuleMap {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut map = Self::new();
+        for line in s.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (module, file) = line
+                .split_once('=')
+                .map(|(module, file)| (module.trim(), file.trim()))
+                .filter(|(module, file)| !module.is_empty() && !file.is_empty())
+                .ok_or_else(|| {
+                    ParseError::syntax(line, "expected `module::path = path/to/file.rs`")
+                })?;
+            map.insert(module, file);
+        }
+        Ok(map)
+    }
+}
+
+impl fmt::Display for ModuleMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (module, file) in &self.modules {
+            writeln!(f, "{} = {}", module, file)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+use crate::{Directive, LineFilter};
+use std::{collections::BTreeMap, fmt};
+
+impl LineFilter {
+    /// Attaches a free-form note to one of this filter's directives, such as
+    /// `"added for INCIDENT-4521, remove after fix"`.
+    ///
+    /// Notes keep long-lived configs auditable: they are written after their
+    /// directives as a `note = "..."` clause whenever the filter is
+    /// formatted or saved (see [the `FromStr` implementation for
+    /// `LineFilter`][syntax]), so they survive being parsed back, and are
+    /// shown by the `list` and `stats` [`Command`]s and in each entry's
+    /// [`EntryStats::note`]. Setting a note on a directive that already has
+    /// one replaces it.
+    ///
+    /// A directive's note is matched by the directive's location, ignoring
+    /// its line hash. The note is only shown while the filter has the
+    /// directive, but it is kept if the directive is removed (such as when a
+    /// profile is deactivated), so it comes back with the directive;
+    /// [`set_config`] replaces all of a filter's notes with the config's.
+    /// Notes on [`EnvFilter`] directives are ignored, since those directives
+    /// are formatted as a whole.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::{Directive, LineFilter};
+    ///
+    /// let mut filter: LineFilter = "my_crate::db@142,my_crate::db@200".parse().unwrap();
+    /// let directive: Directive = "my_crate::db@142".parse().unwrap();
+    /// filter.set_note(&directive, "added for INCIDENT-4521, remove after fix");
+    ///
+    /// assert_eq!(filter.note(&directive), Some("added for INCIDENT-4521, remove after fix"));
+    /// assert_eq!(
+    ///     format!("{:#}", filter),
+    ///     "my_crate::db@142 note = \"added for INCIDENT-4521, remove after fix\"\n\
+    ///      my_crate::db@200",
+    /// );
+    ///
+    /// // The note is parsed back along with the directive.
+    /// let parsed: LineFilter = format!("{:#}", filter).parse().unwrap();
+    /// assert_eq!(parsed.note(&directive), filter.note(&directive));
+    /// ```
+    ///
+    /// [syntax]: crate::LineFilter#impl-FromStr-for-LineFilter
+    /// [`Command`]: crate::Command
+    /// [`EntryStats::note`]: crate::EntryStats::note
+    /// [`set_config`]: Self::set_config
+    /// [`EnvFilter`]: tracing_subscriber::EnvFilter
+    pub fn set_note(&mut self, directive: &Directive, note: impl Into<String>) -> &mut Self {
+        insert(&mut self.notes, directive, note.into());
+        self
+    }
+
+    /// Removes the note attached to `directive` by [`set_note`], returning
+    /// it, if there was one.
+    ///
+    /// [`set_note`]: Self::set_note
+    pub fn remove_note(&mut self, directive: &Directive) -> Option<String> {
+        self.notes.remove(&key(directive)?)
+    }
+
+    /// Returns the note attached to `directive` by [`set_note`], or by a
+    /// `note = "..."` clause in a config, if it has one.
+    ///
+    /// [`set_note`]: Self::set_note
+    pub fn note(&self, directive: &Directive) -> Option<&str> {
+        get(&self.notes, directive)
+    }
+}
+
+/// Returns the key that `directive`'s note is stored under, or `None` if it
+/// can't have one.
+fn key(directive: &Directive) -> Option<String> {
+    match directive {
+        Directive::Module { module, line, .. } => Some(format!("{}@{}", module, line)),
+        Directive::File { file, line, .. } => Some(format!("{}@{}", file, line)),
+        Directive::Deny(denied) => key(denied).map(|denied| format!("!{}", denied)),
+        #[cfg(feature = "env-filter")]
+        Directive::Env(_) => None,
+        directive => Some(directive.to_string()),
+    }
+}
+
+/// Returns the note for `directive` in `notes`.
+pub(crate) fn get<'a>(
+    notes: &'a BTreeMap<String, String>,
+    directive: &Directive,
+) -> Option<&'a str> {
+    notes.get(&key(directive)?).map(String::as_str)
+}
+
+/// Adds `note` for `directive` to `notes`, unless it can't have one.
+pub(crate) fn insert(notes: &mut BTreeMap<String, String>, directive: &Directive, note: String) {
+    if let Some(key) = key(directive) {
+        notes.insert(key, note);
+    }
+}
+
+/// Writes `directives` to `f`, separated by `sep`, each followed by its note
+/// in `notes`, if it has one.
+///
+/// A directive with a note is always written on a line of its own, since a
+/// `note` clause applies to every directive on its line. This returns
+/// whether the last directive had a note, or `None` if there were none.
+pub(crate) fn fmt_directives<'a>(
+    f: &mut fmt::Formatter<'_>,
+    directives: impl IntoIterator<Item = &'a Directive>,
+    notes: &BTreeMap<String, String>,
+    sep: &str,
+) -> Result<Option<bool>, fmt::Error> {
+    let mut last_noted = None;
+    for directive in directives {
+        let note = get(notes, directive);
+        if let Some(prev_noted) = last_noted {
+            f.write_str(if prev_noted || note.is_some() {
+                "\n"
+            } else {
+                sep
+            })?;
+        }
+        fmt::Display::fmt(directive, f)?;
+        if let Some(note) = note {
+            write!(f, " {}", fmt_clause(note))?;
+        }
+        last_noted = Some(note.is_some());
+    }
+    Ok(last_noted)
+}
+
+/// Formats `note` as a `note = "..."` clause.
+pub(crate) fn fmt_clause(note: &str) -> String {
+    let mut clause = String::from("note = \"");
+    for c in note.chars() {
+        match c {
+            '"' => clause.push_str("\\\""),
+            '\\' => clause.push_str("\\\\"),
+            '\n' => clause.push_str("\\n"),
+            c => clause.push(c),
+        }
+    }
+    clause.push('"');
+    clause
+}
+
+/// Splits a `note = "..."` clause off the end of a line of directives,
+/// returning the rest of the line and the note, if there is one.
+pub(crate) fn split(line: &str) -> Result<(&str, Option<String>), &'static str> {
+    let mut search = 0;
+    while let Some(i) = line[search..].find("note") {
+        let start = search + i;
+        search = start + "note".len();
+        let preceded = line[..start]
+            .chars()
+            .next_back()
+            .is_none_or(|c| c.is_ascii_whitespace() || c == ',');
+        let rest = line[search..].trim_start();
+        let quoted = rest
+            .strip_prefix('=')
+            .map(str::trim_start)
+            .and_then(|rest| rest.strip_prefix('"'));
+        if let (true, Some(quoted)) = (preceded, quoted) {
+            return Ok((&line[..start], Some(unquote(quoted)?)));
+        }
+    }
+    Ok((line, None))
+}
+
+/// Parses the rest of a quoted note, after its opening quote.
+fn unquote(quoted: &str) -> Result<String, &'static str> {
+    let mut note = String::new();
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if chars.as_str().trim().is_empty() => return Ok(note),
+            '"' => return Err("a `note` clause must end its line"),
+            '\\' => match chars.next() {
+                Some('"') => note.push('"'),
+                Some('\\') => note.push('\\'),
+                Some('n') => note.push('\n'),
+                _ => return Err("notes may only contain the escapes `\\\"`, `\\\\`, and `\\n`"),
+            },
+            c => note.push(c),
+        }
+    }
+    Err("unterminated `note` clause")
+}
@@ -0,0 +1,469 @@
+//! Sampling pinned spans with [OpenTelemetry].
+//!
+//! When spans are exported to OpenTelemetry using [`tracing-opentelemetry`],
+//! a span enabled by a `(module, line)` or `(file, line)` entry may still be
+//! dropped by the OpenTelemetry SDK's [sampler], so enabling a line doesn't
+//! guarantee that its spans are exported. This module provides a
+//! [`PinnedSampler`], which wraps another sampler and always samples spans
+//! that a [`LineFilter`]'s entries enable, and marks them with a
+//! `tracing_line_filter.pinned = true` attribute, so that they can be told
+//! apart from spans that were sampled normally. The filter's [span fields]
+//! are added to pinned spans as attributes, too.
+//!
+//! Spans are matched against the filter's entries the same way as the filter
+//! decides whether to enable them, including module prefixes, level sets,
+//! and deny entries. The filter remembers the decision for each span
+//! callsite when it creates a span there, and the sampler finds it using the
+//! `code.module.name` and `code.line.number` attributes that
+//! `tracing-opentelemetry` records for each span, along with the span's
+//! name. The sampler doesn't lock the filter's [`reload`] handle to do so,
+//! so the OpenTelemetry layer may be layered inside the reload layer, as it
+//! is when it is combined with the filter using [`Layer::and_then`]. The
+//! sampler keeps sampling for the filter behind the handle when it was
+//! created: if the filter is replaced with [`Handle::reload`], rather than
+//! changed with [`Handle::modify`], create a new sampler.
+//!
+//! Sampling decisions are made when an OpenTelemetry span starts. When a
+//! pinned span is the root of a trace, the whole trace is sampled (as long
+//! as the wrapped sampler respects its parent's decision, like
+//! [`Sampler::ParentBased`] does). When a pinned span's parent was not
+//! sampled, only the pinned span and its descendants are exported.
+//!
+//! When pinning a busy line would export too many traces, the sampler can be
+//! told to only sample a fraction of the traces containing pinned spans (see
+//! [`PinnedSampler::sample_pinned`]). The decision is made from each span's
+//! trace ID, rather than at random, so a request's pinned spans are either
+//! all sampled or all left to the wrapped sampler, and its trace isn't
+//! broken into fragments.
+//!
+//! This module is only available when the `otel` feature flag is enabled.
+//!
+//! # Examples
+//!
+//! ```
+//! use opentelemetry::trace::TracerProvider;
+//! use opentelemetry_sdk::trace::{Sampler, SdkTracerProvider};
+//! use tracing_line_filter::{otel::PinnedSampler, LineFilter};
+//! use tracing_subscriber::{prelude::*, reload};
+//!
+//! let (filter, handle) = reload::Layer::new(LineFilter::default());
+//!
+//! // Sample 1% of traces, and every trace rooted at a pinned span.
+//! let sampler = Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(0.01)));
+//! let provider = SdkTracerProvider::builder()
+//!     .with_sampler(PinnedSampler::new(sampler, handle))
+//!     .build();
+//!
+//! tracing_subscriber::registry()
+//!     .with(filter)
+//!     .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("my_app")))
+//!     .init();
+//! ```
+//!
+//! [OpenTelemetry]: https://opentelemetry.io
+//! [span fields]: LineFilter::add_span_field
+//! [`tracing-opentelemetry`]: https://docs.rs/tracing-opentelemetry
+//! [sampler]: opentelemetry_sdk::trace::ShouldSample
+//! [`Sampler::ParentBased`]: opentelemetry_sdk::trace::Sampler::ParentBased
+//! [`reload`]: tracing_subscriber::reload
+//! [`Layer::and_then`]: tracing_subscriber::Layer::and_then
+//! [`Handle::reload`]: tracing_subscriber::reload::Handle::reload
+//! [`Handle::modify`]: tracing_subscriber::reload::Handle::modify
+use crate::LineFilter;
+use opentelemetry::{
+    trace::{Link, SpanKind, TraceContextExt, TraceId},
+    Context, KeyValue, Value,
+};
+use opentelemetry_sdk::trace::{SamplingDecision, SamplingResult, ShouldSample};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+};
+use tracing_core::Metadata;
+use tracing_subscriber::reload;
+
+/// The attribute added to spans sampled because they are pinned.
+const PINNED: &str = "tracing_line_filter.pinned";
+
+/// An OpenTelemetry sampler that always samples spans matched by a
+/// [`LineFilter`]'s entries.
+///
+/// See the [module-level documentation](self) for details.
+pub struct PinnedSampler<T> {
+    inner: T,
+    pins: Arc<Pins>,
+    /// The fraction of traces whose pinned spans are always sampled, if not
+    /// all of them.
+    ratio: Option<f64>,
+}
+
+/// Whether a [`LineFilter`]'s entries enabled the spans it created, which
+/// [`PinnedSampler`]s look up without locking the filter.
+#[derive(Debug, Default)]
+pub(crate) struct Pins {
+    /// Whether a `PinnedSampler` samples the filter's spans. Nothing is
+    /// recorded until one does.
+    sampled: AtomicBool,
+    state: RwLock<PinState>,
+}
+
+#[derive(Debug, Default)]
+struct PinState {
+    /// Whether the filter's entries enabled the last span created at each
+    /// callsite, by module path, line, and span name.
+    callsites: HashMap<(&'static str, u32, &'static str), bool>,
+    /// The filter's span fields, as of the last pinned span it created.
+    fields: Vec<(String, String)>,
+}
+
+impl<T> PinnedSampler<T> {
+    /// Returns a new `PinnedSampler` that samples spans matched by the
+    /// entries of the [`LineFilter`] behind `handle`, and defers to `inner`
+    /// for all other spans.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use opentelemetry::trace::TracerProvider;
+    /// use opentelemetry_sdk::trace::{InMemorySpanExporter, Sampler, SdkTracerProvider};
+    /// use tracing_line_filter::{otel::PinnedSampler, LineFilter};
+    /// use tracing_subscriber::{prelude::*, reload};
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter
+    ///     .enable_by_mod(module_path!(), line!() + 14)
+    ///     .with_env_filter(tracing_subscriber::EnvFilter::new("info"));
+    /// let (filter, handle) = reload::Layer::new(filter);
+    ///
+    /// let exporter = InMemorySpanExporter::default();
+    /// let provider = SdkTracerProvider::builder()
+    ///     // Don't sample anything, unless it's pinned.
+    ///     .with_sampler(PinnedSampler::new(Sampler::AlwaysOff, handle))
+    ///     .with_simple_exporter(exporter.clone())
+    ///     .build();
+    /// let subscriber = tracing_subscriber::registry()
+    ///     .with(filter.and_then(tracing_opentelemetry::layer().with_tracer(provider.tracer("test"))));
+    ///
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info_span!("pinned").in_scope(|| {});
+    ///     tracing::info_span!("enabled, but not pinned").in_scope(|| {});
+    /// });
+    ///
+    /// let spans = exporter.get_finished_spans().unwrap();
+    /// assert_eq!(spans.len(), 1);
+    /// assert_eq!(spans[0].name, "pinned");
+    /// ```
+    pub fn new<S>(inner: T, handle: reload::Handle<LineFilter, S>) -> Self {
+        let pins = handle
+            .with_current(|filter| {
+                filter.pins.sampled.store(true, Ordering::Relaxed);
+                filter.pins.clone()
+            })
+            // If the subscriber is gone, nothing is pinned.
+            .unwrap_or_default();
+        Self {
+            inner,
+            pins,
+            ratio: None,
+        }
+    }
+
+    /// Only always samples the pinned spans of `ratio` of traces, such as
+    /// `0.1` for one trace in ten, and defers to the wrapped sampler for the
+    /// pinned spans of other traces, as it does for spans that aren't pinned.
+    ///
+    /// Whether a trace is in the sampled fraction is decided from its trace
+    /// ID, the same way as OpenTelemetry's [`Sampler::TraceIdRatioBased`]
+    /// does, so every pinned span in a trace gets the same decision, and
+    /// each request's pinned spans are either all sampled or none are. This
+    /// also means that when the wrapped sampler samples a smaller ratio of
+    /// traces by trace ID, the traces it samples are among the ones sampled
+    /// for their pinned spans. A `ratio` of `1.0` or more samples every trace's
+    /// pinned spans, which is the default, and a `ratio` of `0.0` or less
+    /// treats pinned spans like any other span.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use opentelemetry::trace::TracerProvider;
+    /// use opentelemetry_sdk::trace::{InMemorySpanExporter, Sampler, SdkTracerProvider};
+    /// use std::collections::HashMap;
+    /// use tracing_line_filter::{otel::PinnedSampler, LineFilter};
+    /// use tracing_subscriber::{prelude::*, reload};
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter.enable_by_mod(module_path!(), line!() + 15);
+    /// let (filter, handle) = reload::Layer::new(filter);
+    ///
+    /// let mut sampler = PinnedSampler::new(Sampler::AlwaysOff, handle);
+    /// sampler.sample_pinned(0.5);
+    /// let exporter = InMemorySpanExporter::default();
+    /// let provider = SdkTracerProvider::builder()
+    ///     .with_sampler(sampler)
+    ///     .with_simple_exporter(exporter.clone())
+    ///     .build();
+    /// let subscriber = tracing_subscriber::registry()
+    ///     .with(filter.and_then(tracing_opentelemetry::layer().with_tracer(provider.tracer("test"))));
+    ///
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     for _ in 0..100 {
+    ///         tracing::info_span!("request").in_scope(|| tracing::info_span!("query").in_scope(|| {}));
+    ///     }
+    /// });
+    ///
+    /// // About half of the requests were sampled, each with both of its spans.
+    /// let mut traces = HashMap::new();
+    /// for span in exporter.get_finished_spans().unwrap() {
+    ///     *traces.entry(span.span_context.trace_id()).or_insert(0) += 1;
+    /// }
+    /// assert!(traces.len() > 10 && traces.len() < 90);
+    /// assert!(traces.values().all(|&spans| spans == 2));
+    /// ```
+    ///
+    /// [`Sampler::TraceIdRatioBased`]: opentelemetry_sdk::trace::Sampler::TraceIdRatioBased
+    pub fn sample_pinned(&mut self, ratio: f64) -> &mut Self {
+        self.ratio = if ratio >= 1.0 { None } else { Some(ratio) };
+        self
+    }
+
+    /// Returns `true` if the pinned spans in the trace with `trace_id` should
+    /// always be sampled.
+    fn samples_trace(&self, trace_id: TraceId) -> bool {
+        let ratio = match self.ratio {
+            Some(ratio) => ratio,
+            None => return true,
+        };
+        let bound = (ratio.max(0.0) * (1u64 << 63) as f64) as u64;
+        let bytes = trace_id.to_bytes();
+        let mut low = [0; 8];
+        low.copy_from_slice(&bytes[8..]);
+        (u64::from_be_bytes(low) >> 1) < bound
+    }
+
+    /// Returns the attributes to add to the span named `name` with
+    /// `attributes` if it is pinned, or `None` if it isn't.
+    fn pinned_attributes(&self, name: &str, attributes: &[KeyValue]) -> Option<Vec<KeyValue>> {
+        let mut module = None;
+        let mut line = None;
+        for attribute in attributes {
+            match (attribute.key.as_str(), &attribute.value) {
+                ("code.module.name", Value::String(value)) => module = Some(value.as_str()),
+                ("code.line.number", Value::I64(value)) => line = u32::try_from(*value).ok(),
+                _ => {}
+            }
+        }
+
+        let key = (module?, line?, name);
+        let state = self.pins.state.read().unwrap_or_else(|e| e.into_inner());
+        // The map's keys are `'static`, but they can be looked up by
+        // shorter-lived strings.
+        let callsites: &HashMap<(&str, u32, &str), bool> = &state.callsites;
+        if callsites.get(&key) != Some(&true) {
+            return None;
+        }
+        let fields = state
+            .fields
+            .iter()
+            .map(|(name, value)| KeyValue::new(name.clone(), value.clone()));
+        Some(
+            std::iter::once(KeyValue::new(PINNED, true))
+                .chain(fields)
+                .collect(),
+        )
+    }
+}
+
+impl LineFilter {
+    /// Records whether this filter's entries enabled the span with
+    /// `metadata` that it is creating, if a [`PinnedSampler`] samples its
+    /// spans.
+    pub(crate) fn record_pin(&self, metadata: &'static Metadata<'static>) {
+        if !self.pins.sampled.load(Ordering::Relaxed) {
+            return;
+        }
+        let key = match (metadata.module_path(), metadata.line()) {
+            (Some(module), Some(line)) => (module, line, metadata.name()),
+            _ => return,
+        };
+        let pinned = self.decide(metadata) == Some(true);
+
+        let state = self.pins.state.read().unwrap_or_else(|e| e.into_inner());
+        let changed = state.callsites.get(&key) != Some(&pinned)
+            || (pinned && state.fields != self.span_fields);
+        drop(state);
+        if changed {
+            let mut state = self.pins.state.write().unwrap_or_else(|e| e.into_inner());
+            state.callsites.insert(key, pinned);
+            if pinned {
+                state.fields.clone_from(&self.span_fields);
+            }
+        }
+    }
+}
+
+impl<T> ShouldSample for PinnedSampler<T>
+where
+    T: ShouldSample + Clone + 'static,
+{
+    fn should_sample(
+        &self,
+        parent_context: Option<&Context>,
+        trace_id: TraceId,
+        name: &str,
+        span_kind: &SpanKind,
+        attributes: &[KeyValue],
+        links: &[Link],
+    ) -> SamplingResult {
+        let pinned = match self.pinned_attributes(name, attributes) {
+            Some(pinned) if self.samples_trace(trace_id) => pinned,
+            _ => {
+                return self.inner.should_sample(
+                    parent_context,
+                    trace_id,
+                    name,
+                    span_kind,
+                    attributes,
+                    links,
+                )
+            }
+        };
+
+        SamplingResult {
+            decision: SamplingDecision::RecordAndSample,
+            attributes: pinned,
+            trace_state: parent_context
+                .map(|cx| cx.span().span_context().trace_state().clone())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl<T: Clone> Clone for PinnedSampler<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            pins: self.pins.clone(),
+            ratio: self.ratio,
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for PinnedSampler<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PinnedSampler")
+            .field("inner", &self.inner)
+            .field("ratio", &self.ratio)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Directive;
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, Sampler, SdkTracerProvider};
+    use tracing_subscriber::prelude::*;
+
+    /// Creates spans with `f` in a subscriber with `filter`, sampling only
+    /// the pinned ones, and returns the names of the spans exported.
+    fn exported(filter: LineFilter, f: impl FnOnce()) -> Vec<String> {
+        let (filter, handle) = reload::Layer::new(filter);
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_sampler(PinnedSampler::new(Sampler::AlwaysOff, handle))
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let otel = tracing_opentelemetry::layer().with_tracer(provider.tracer("test"));
+        let subscriber = tracing_subscriber::registry().with(filter.and_then(otel));
+        tracing::subscriber::with_default(subscriber, f);
+
+        let mut names = exporter
+            .get_finished_spans()
+            .unwrap()
+            .into_iter()
+            .map(|span| span.name.into_owned())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn module_prefixes_pin_spans() {
+        let filter = format!("{}::**", module_path!()).parse().unwrap();
+        let names = exported(filter, || {
+            tracing::trace_span!("prefixed").in_scope(|| {});
+        });
+        assert_eq!(names, vec!["prefixed"]);
+    }
+
+    #[test]
+    fn level_sets_pin_spans_at_their_levels() {
+        let line = line!() + 11;
+        let mut filter = format!(
+            "{0}@{1}=debug,{0}@{2}=debug",
+            module_path!(),
+            line,
+            line + 1
+        )
+        .parse::<LineFilter>()
+        .unwrap();
+        filter.with_env_filter(tracing_subscriber::EnvFilter::new("info"));
+        let names = exported(filter, || {
+            tracing::info_span!("enabled, but not pinned").in_scope(|| {});
+            tracing::debug_span!("pinned").in_scope(|| {});
+        });
+        assert_eq!(names, vec!["pinned"]);
+    }
+
+    #[test]
+    fn denied_spans_are_not_pinned() {
+        let line = line!() + 7;
+        let mut filter = format!("{0}::**,!{0}@{1}", module_path!(), line)
+            .parse::<LineFilter>()
+            .unwrap();
+        filter.with_env_filter(tracing_subscriber::EnvFilter::new("info"));
+        let names = exported(filter, || {
+            tracing::info_span!("pinned").in_scope(|| {});
+            tracing::info_span!("denied").in_scope(|| {});
+        });
+        assert_eq!(names, vec!["pinned"]);
+    }
+
+    #[test]
+    fn removed_entries_stop_pinning_spans() {
+        let line = line!() + 16;
+        let directive = format!("{}@{}", module_path!(), line)
+            .parse::<Directive>()
+            .unwrap();
+        let mut filter = LineFilter::default();
+        filter.add_directive(directive.clone()).unwrap();
+        filter.with_env_filter(tracing_subscriber::EnvFilter::new("info"));
+        let (filter, handle) = reload::Layer::new(filter);
+        let sampler = PinnedSampler::new(Sampler::AlwaysOff, handle.clone());
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_sampler(sampler)
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let otel = tracing_opentelemetry::layer().with_tracer(provider.tracer("test"));
+        let subscriber = tracing_subscriber::registry().with(filter.and_then(otel));
+        let span = || tracing::info_span!("span").in_scope(|| {});
+
+        tracing::subscriber::with_default(subscriber, || {
+            span();
+            handle
+                .modify(|filter| {
+                    filter.remove_directive(&directive);
+                })
+                .unwrap();
+            span();
+        });
+        assert_eq!(exporter.get_finished_spans().unwrap().len(), 1);
+    }
+}
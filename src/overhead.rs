@@ -0,0 +1,273 @@
+use crate::{time::Instant, LineFilter};
+use std::{
+    convert::TryFrom,
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// The number of histogram buckets. Bucket `i` counts calls that took less
+/// than 2<sup>`i`</sup> nanoseconds (and at least half as long), except for
+/// the last bucket, which counts everything slower.
+const BUCKETS: usize = 32;
+
+/// How long a [`LineFilter`] has spent deciding whether to enable spans and
+/// events, returned by [`LineFilter::overhead`].
+///
+/// This is only recorded after [`LineFilter::measure_overhead`] has been
+/// called.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct OverheadStats {
+    register_callsite: Timings,
+    enabled: Timings,
+}
+
+/// A histogram of how long calls to one of a [`LineFilter`]'s methods took,
+/// returned by [`OverheadStats::register_callsite`] and
+/// [`OverheadStats::enabled`].
+///
+/// Call durations are counted in buckets whose sizes are powers of two
+/// nanoseconds, so quantiles are only accurate to within a factor of two.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Timings {
+    count: u64,
+    total: Duration,
+    buckets: Vec<u64>,
+}
+
+/// Measures the filter's overhead, if [`LineFilter::measure_overhead`] has
+/// been called.
+#[derive(Debug, Default)]
+pub(crate) struct Overhead {
+    enabled: bool,
+    register_callsite: Histogram,
+    filter: Histogram,
+}
+
+/// Records the time until it is dropped to a histogram.
+pub(crate) struct Timer<'a> {
+    histogram: &'a Histogram,
+    start: Instant,
+}
+
+#[derive(Debug)]
+struct Histogram {
+    count: AtomicU64,
+    /// The total time spent, in nanoseconds.
+    total: AtomicU64,
+    buckets: [AtomicU64; BUCKETS],
+}
+
+impl LineFilter {
+    /// Measures how long this filter spends deciding whether to enable spans
+    /// and events, so that its cost can be quantified in a real workload.
+    ///
+    /// Once this is called, the filter times each call to its
+    /// `register_callsite` method, which is called once for each callsite
+    /// (and again whenever the filter is reloaded), and each call to its
+    /// `enabled` method, which is called each time a span or event whose
+    /// interest isn't cached is reached. The timings are returned by
+    /// [`overhead`] and included in the [`stats_snapshot`].
+    ///
+    /// Timing a call reads the clock twice, which is cheap but not free, so
+    /// this is disabled by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::{prelude::*, reload, EnvFilter};
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter
+    ///     // Filter events by their field values, so that each event is
+    ///     // checked individually.
+    ///     .with_env_filter(EnvFilter::new("[{pinned}]=info"))
+    ///     .measure_overhead();
+    /// let (filter, handle) = reload::Layer::new(filter);
+    /// let subscriber = tracing_subscriber::registry().with(filter);
+    ///
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     for _ in 0..10 {
+    ///         tracing::info!("not pinned");
+    ///     }
+    ///
+    ///     let overhead = handle.with_current(LineFilter::overhead).unwrap().unwrap();
+    ///     assert_eq!(overhead.enabled().count(), 10);
+    ///     assert!(overhead.enabled().quantile(0.99).is_some());
+    /// });
+    /// ```
+    ///
+    /// [`overhead`]: Self::overhead
+    /// [`stats_snapshot`]: Self::stats_snapshot
+    pub fn measure_overhead(&mut self) -> &mut Self {
+        self.overhead.enabled = true;
+        self
+    }
+
+    /// Returns how long this filter has spent deciding whether to enable
+    /// spans and events, or `None` if [`measure_overhead`] hasn't been
+    /// called.
+    ///
+    /// [`measure_overhead`]: Self::measure_overhead
+    pub fn overhead(&self) -> Option<OverheadStats> {
+        if !self.overhead.enabled {
+            return None;
+        }
+        Some(OverheadStats {
+            register_callsite: self.overhead.register_callsite.timings(),
+            enabled: self.overhead.filter.timings(),
+        })
+    }
+}
+
+// === impl OverheadStats ===
+
+impl OverheadStats {
+    /// Returns the timings for calls to the filter's `register_callsite`
+    /// method.
+    pub fn register_callsite(&self) -> &Timings {
+        &self.register_callsite
+    }
+
+    /// Returns the timings for calls to the filter's `enabled` method
+    /// (including `event_enabled`, for `log` records).
+    pub fn enabled(&self) -> &Timings {
+        &self.enabled
+    }
+}
+
+// === impl Timings ===
+
+impl Timings {
+    /// Returns the number of calls.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the total time spent in the calls.
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    /// Returns the mean time spent in a call, or `None` if there haven't
+    /// been any.
+    pub fn mean(&self) -> Option<Duration> {
+        let count = u32::try_from(self.count).unwrap_or(u32::MAX);
+        self.total.checked_div(count)
+    }
+
+    /// Returns an upper bound on the time taken by the fraction `q` of
+    /// calls, such as `0.99` for the 99th percentile, or `None` if there
+    /// haven't been any calls.
+    pub fn quantile(&self, q: f64) -> Option<Duration> {
+        let rank = (self.count as f64 * q.clamp(0.0, 1.0)).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bound, count) in self.buckets() {
+            seen += count;
+            if seen >= rank {
+                return Some(bound);
+            }
+        }
+        None
+    }
+
+    /// Returns the histogram's buckets, as the upper bound of each bucket
+    /// and the number of calls in it.
+    ///
+    /// The last bucket also counts any calls slower than its bound.
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.buckets
+            .iter()
+            .enumerate()
+            .map(|(i, &count)| (Duration::from_nanos(1 << i), count))
+    }
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plural = if self.count == 1 { "" } else { "s" };
+        write!(f, "{} call{}", self.count, plural)?;
+        if let (Some(mean), Some(p99)) = (self.mean(), self.quantile(0.99)) {
+            write!(f, ", mean {:?}, p99 < {:?}", mean, p99)?;
+        }
+        Ok(())
+    }
+}
+
+// === impl Overhead ===
+
+impl Overhead {
+    /// Returns an `Overhead` that measures the same things as this one, but
+    /// hasn't measured anything yet.
+    pub(crate) fn unmeasured(&self) -> Self {
+        Self {
+            enabled: self.enabled,
+            ..Self::default()
+        }
+    }
+
+    /// Starts timing a call to `register_callsite`.
+    pub(crate) fn register_callsite(&self) -> Option<Timer<'_>> {
+        self.start(&self.register_callsite)
+    }
+
+    /// Starts timing a call to `enabled` or `event_enabled`.
+    pub(crate) fn enabled(&self) -> Option<Timer<'_>> {
+        self.start(&self.filter)
+    }
+
+    fn start<'a>(&self, histogram: &'a Histogram) -> Option<Timer<'a>> {
+        if !self.enabled {
+            return None;
+        }
+        Some(Timer {
+            histogram,
+            start: Instant::now(),
+        })
+    }
+}
+
+// === impl Timer ===
+
+impl Drop for Timer<'_> {
+    fn drop(&mut self) {
+        let nanos = u64::try_from(self.start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+        self.histogram.record(nanos);
+    }
+}
+
+// === impl Histogram ===
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            count: AtomicU64::new(0),
+            total: AtomicU64::new(0),
+            buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+}
+
+impl Histogram {
+    fn record(&self, nanos: u64) {
+        let bucket = (u64::BITS - nanos.leading_zeros()) as usize;
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.total.fetch_add(nanos, Ordering::Relaxed);
+        self.buckets[bucket.min(BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn timings(&self) -> Timings {
+        Timings {
+            count: self.count.load(Ordering::Relaxed),
+            total: Duration::from_nanos(self.total.load(Ordering::Relaxed)),
+            buckets: self
+                .buckets
+                .iter()
+                .map(|bucket| bucket.load(Ordering::Relaxed))
+                .collect(),
+        }
+    }
+}
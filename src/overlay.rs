@@ -0,0 +1,137 @@
+use crate::{Directive, LineFilter};
+use std::collections::HashMap;
+
+/// Something that adds directives to a filter for a while, and takes them
+/// away again.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Owner {
+    Profile(String),
+    Startup,
+    Session(u64),
+}
+
+/// The directives that profiles, startup entries, and debug sessions have
+/// laid over a filter's own directives.
+///
+/// Overlays may overlap each other and the filter's own directives, such as
+/// two profiles that both enable `my_crate::db@142`, or a profile that adds a
+/// level set to a line the filter already enables. Adding a directive
+/// replaces whatever the filter had at its location, so each location an
+/// overlay changes remembers what was there before any overlay was applied,
+/// and removing an overlay rebuilds the location from the overlays that are
+/// still applied, falling back to what was there before.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Overlays {
+    /// The directives each overlay applied, in the order they were applied.
+    layers: Vec<(Owner, Vec<Directive>)>,
+    /// What each location that an overlay changed held before any overlay
+    /// changed it.
+    base: HashMap<String, Option<Directive>>,
+}
+
+impl LineFilter {
+    /// Lays `directives` over this filter on behalf of `owner`, returning
+    /// the directives that weren't already in the filter.
+    pub(crate) fn apply_overlay(
+        &mut self,
+        owner: Owner,
+        directives: &[Directive],
+    ) -> Vec<Directive> {
+        let mut added = Vec::new();
+        for directive in directives {
+            let key = location(directive);
+            let current = self.directive_at(&key);
+            let unchanged = current.as_ref() == Some(directive);
+            self.overlays.base.entry(key).or_insert(current);
+            if unchanged {
+                continue;
+            }
+            self.add_directive(directive.clone())
+                .expect("overlaid directives are validated by their owners");
+            added.push(directive.clone());
+        }
+        self.overlays.layers.push((owner, directives.to_vec()));
+        added
+    }
+
+    /// Removes the directives that `owner` laid over this filter, restoring
+    /// each location it changed to what the remaining overlays, or the
+    /// filter before them, had there.
+    pub(crate) fn remove_overlay(&mut self, owner: &Owner) {
+        let i = match self.overlays.layers.iter().position(|(o, _)| o == owner) {
+            Some(i) => i,
+            None => return,
+        };
+        let (_, directives) = self.overlays.layers.remove(i);
+        for directive in directives {
+            let key = location(&directive);
+            let wanted = match self.overlaid_at(&key, None) {
+                Some(wanted) => Some(wanted),
+                None => match self.overlays.base.remove(&key) {
+                    Some(base) => base,
+                    None => continue,
+                },
+            };
+            let current = self.directive_at(&key);
+            if current == wanted {
+                continue;
+            }
+            if let Some(ref current) = current {
+                self.remove_directive(current);
+            }
+            if let Some(wanted) = wanted {
+                self.add_directive(wanted)
+                    .expect("overlaid directives are validated by their owners");
+            }
+        }
+    }
+
+    /// Returns what the location of `directive` would hold without the
+    /// overlay applied by `owner`.
+    pub(crate) fn without_overlay(
+        &self,
+        owner: &Owner,
+        directive: &Directive,
+    ) -> Option<Directive> {
+        let key = location(directive);
+        self.overlaid_at(&key, Some(owner))
+            .or_else(|| self.overlays.base.get(&key).cloned().flatten())
+    }
+
+    /// Forgets every overlay, leaving their directives in the filter.
+    pub(crate) fn clear_overlays(&mut self) {
+        self.overlays = Overlays::default();
+    }
+
+    /// Returns the directive that the most recently applied overlay (other
+    /// than `except`) has at `key`, or `None` if no overlay has one.
+    fn overlaid_at(&self, key: &str, except: Option<&Owner>) -> Option<Directive> {
+        self.overlays
+            .layers
+            .iter()
+            .rev()
+            .filter(|(owner, _)| Some(owner) != except)
+            .flat_map(|(_, directives)| directives)
+            .find(|directive| location(directive) == key)
+            .cloned()
+    }
+
+    /// Returns this filter's directive at `key`, if it has one.
+    fn directive_at(&self, key: &str) -> Option<Directive> {
+        self.directives()
+            .find(|directive| location(directive) == key)
+    }
+}
+
+/// Returns the location that `directive` replaces the filter's entry at,
+/// when it is added: adding a `module@line` directive with a level set
+/// replaces the level set of the entry for that line, for example.
+fn location(directive: &Directive) -> String {
+    match directive {
+        Directive::Module { module, line, .. } => format!("{}@{}", module, line),
+        Directive::File { file, line, .. } => format!("{}@{}", file, line),
+        Directive::ModuleLevel { module, .. } => format!("{}@level", module),
+        Directive::Deny(denied) => format!("!{}", location(denied)),
+        directive => directive.to_string(),
+    }
+}
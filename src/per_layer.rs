@@ -0,0 +1,156 @@
+use crate::LineFilter;
+use tracing_core::{span, subscriber::Interest, Event, Metadata, Subscriber};
+use tracing_subscriber::layer::{self, Filter};
+
+/// A [`LineFilter`] used as a [per-layer filter][plf], which decides which
+/// spans and events a single layer records, without affecting the other
+/// layers in the subscriber.
+///
+/// A `LineFilter` used as a [`Layer`] filters spans and events for the whole
+/// subscriber, which is right when every output should see the pinned lines.
+/// Wrapping it in a `LineFilterFilter` and passing it to
+/// [`Layer::with_filter`] lets one layer (such as a verbose debug log) see
+/// only the pinned lines, while other layers keep their own filters. A
+/// `LineFilterFilter` makes the same decisions as its `LineFilter`,
+/// including its [`EnvFilter`] or fallback, and records the same hit
+/// counts, callbacks, and match records for the spans and events it enables.
+/// Since a per-layer filter isn't told which subscriber it is part of, a
+/// `LineFilterFilter` can't [announce] its configuration.
+///
+/// Use [`LineFilter::into_filter`] or `From` to convert a `LineFilter` into a
+/// `LineFilterFilter`, and [`LineFilterFilter::into_inner`] to convert it
+/// back. A `LineFilterFilter` can be wrapped in a [`reload::Layer`] to
+/// change it at runtime, using [`get_mut`](Self::get_mut) from
+/// [`reload::Handle::modify`], but `reload::Layer` doesn't pass events to
+/// the filter's `event_enabled` method, where a `LineFilterFilter` counts
+/// the events it enables and filters [`log` records][log].
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::{
+///     atomic::{AtomicUsize, Ordering},
+///     Arc,
+/// };
+/// use tracing_line_filter::LineFilter;
+/// use tracing_subscriber::{filter::LevelFilter, prelude::*};
+///
+/// let directive = format!("{}@{}", module_path!(), line!() + 17);
+/// let mut filter: LineFilter = directive.parse().unwrap();
+///
+/// let pinned = Arc::new(AtomicUsize::new(0));
+/// let counter = pinned.clone();
+/// filter.on_match(directive.parse().unwrap(), move |_| {
+///     counter.fetch_add(1, Ordering::Relaxed);
+/// });
+///
+/// let subscriber = tracing_subscriber::registry()
+///     // This layer only logs the pinned lines...
+///     .with(tracing_subscriber::fmt::layer().with_filter(filter.into_filter()))
+///     // ...while this one logs warnings and errors, as usual.
+///     .with(tracing_subscriber::fmt::layer().with_filter(LevelFilter::WARN));
+///
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::warn!("logged by the second layer");
+///     tracing::debug!("logged by the first layer");
+/// });
+///
+/// assert_eq!(pinned.load(Ordering::Relaxed), 1);
+/// ```
+///
+/// [plf]: tracing_subscriber::layer#per-layer-filtering
+/// [`Layer`]: tracing_subscriber::Layer
+/// [`Layer::with_filter`]: tracing_subscriber::Layer::with_filter
+/// [`EnvFilter`]: https://docs.rs/tracing-subscriber/latest/tracing_subscriber/filter/struct.EnvFilter.html
+/// [announce]: LineFilter::announce_config
+/// [`reload::Layer`]: tracing_subscriber::reload::Layer
+/// [`reload::Handle::modify`]: tracing_subscriber::reload::Handle::modify
+/// [log]: https://docs.rs/tracing-line-filter/latest/tracing_line_filter/struct.LineFilter.html#method.normalize_log_metadata
+#[derive(Clone, Debug, Default)]
+pub struct LineFilterFilter {
+    inner: LineFilter,
+}
+
+impl LineFilter {
+    /// Converts this filter into a [`LineFilterFilter`], for use as a
+    /// per-layer filter.
+    pub fn into_filter(self) -> LineFilterFilter {
+        LineFilterFilter::new(self)
+    }
+}
+
+// === impl LineFilterFilter ===
+
+impl LineFilterFilter {
+    /// Returns a per-layer filter that makes the same decisions as `filter`.
+    pub fn new(filter: LineFilter) -> Self {
+        Self { inner: filter }
+    }
+
+    /// Returns a reference to the wrapped `LineFilter`.
+    pub fn get_ref(&self) -> &LineFilter {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the wrapped `LineFilter`.
+    pub fn get_mut(&mut self) -> &mut LineFilter {
+        &mut self.inner
+    }
+
+    /// Returns the wrapped `LineFilter`, for use as a [`Layer`] again.
+    ///
+    /// [`Layer`]: tracing_subscriber::Layer
+    pub fn into_inner(self) -> LineFilter {
+        self.inner
+    }
+}
+
+impl From<LineFilter> for LineFilterFilter {
+    fn from(filter: LineFilter) -> Self {
+        Self::new(filter)
+    }
+}
+
+impl From<LineFilterFilter> for LineFilter {
+    fn from(filter: LineFilterFilter) -> Self {
+        filter.into_inner()
+    }
+}
+
+impl<S: Subscriber> Filter<S> for LineFilterFilter {
+    fn enabled(&self, metadata: &Metadata<'_>, cx: &layer::Context<'_, S>) -> bool {
+        self.inner.is_enabled(metadata, cx.clone())
+    }
+
+    fn callsite_enabled(&self, metadata: &'static Metadata<'static>) -> Interest {
+        self.inner.callsite_interest(metadata)
+    }
+
+    fn event_enabled(&self, event: &Event<'_>, cx: &layer::Context<'_, S>) -> bool {
+        // Per-layer filters aren't told about events once they are recorded,
+        // and this is only called for events that `enabled` let through.
+        let enabled = self.inner.is_event_enabled(event, cx.clone());
+        if enabled {
+            self.inner.matched(event.metadata());
+        }
+        enabled
+    }
+
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, cx: layer::Context<'_, S>) {
+        self.inner.subtree_span_created(attrs, id, &cx);
+        self.inner.span_created(attrs, id);
+    }
+
+    fn on_enter(&self, id: &span::Id, _: layer::Context<'_, S>) {
+        self.inner.span_condition_entered(id);
+    }
+
+    fn on_exit(&self, id: &span::Id, _: layer::Context<'_, S>) {
+        self.inner.span_condition_exited(id);
+    }
+
+    fn on_close(&self, id: span::Id, _: layer::Context<'_, S>) {
+        self.inner.span_condition_closed(&id);
+        self.inner.subtree_span_closed(&id);
+    }
+}
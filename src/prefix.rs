@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+/// A set of module path prefixes, stored as a trie of path segments.
+///
+/// A module matches a prefix if the prefix's segments are the first segments
+/// of the module's path, so `my_crate::db` matches `my_crate::db` and
+/// `my_crate::db::pool`, but not `my_crate::dbx`. Checking a module walks the
+/// trie once, regardless of how many prefixes there are.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct PrefixTrie {
+    root: Node,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Node {
+    /// Whether a prefix ends at this node.
+    terminal: bool,
+    children: HashMap<Box<str>, Node>,
+}
+
+impl PrefixTrie {
+    /// Adds `prefix`, returning `true` if it wasn't already present.
+    pub(crate) fn insert(&mut self, prefix: &str) -> bool {
+        let mut node = &mut self.root;
+        for segment in prefix.split("::") {
+            node = node.children.entry(segment.into()).or_default();
+        }
+        !std::mem::replace(&mut node.terminal, true)
+    }
+
+    /// Removes `prefix`, returning `true` if it was present.
+    ///
+    /// This doesn't prune the nodes that were only needed by `prefix`, since
+    /// prefixes are rarely removed.
+    pub(crate) fn remove(&mut self, prefix: &str) -> bool {
+        let mut node = &mut self.root;
+        for segment in prefix.split("::") {
+            node = match node.children.get_mut(segment) {
+                Some(node) => node,
+                None => return false,
+            };
+        }
+        std::mem::replace(&mut node.terminal, false)
+    }
+
+    /// Returns the longest prefix that matches `module`, if any do.
+    pub(crate) fn longest_match<'a>(&self, module: &'a str) -> Option<&'a str> {
+        let mut node = &self.root;
+        let mut matched = None;
+        let mut end = 0;
+        for segment in module.split("::") {
+            node = match node.children.get(segment) {
+                Some(node) => node,
+                None => break,
+            };
+            end += segment.len();
+            if node.terminal {
+                matched = Some(&module[..end]);
+            }
+            end += "::".len();
+        }
+        matched
+    }
+
+    /// Returns every prefix, sorted.
+    pub(crate) fn to_vec(&self) -> Vec<String> {
+        fn walk(node: &Node, path: &mut String, prefixes: &mut Vec<String>) {
+            for (segment, child) in &node.children {
+                let len = path.len();
+                if len > 0 {
+                    path.push_str("::");
+                }
+                path.push_str(segment);
+                if child.terminal {
+                    prefixes.push(path.clone());
+                }
+                walk(child, path, prefixes);
+                path.truncate(len);
+            }
+        }
+
+        let mut prefixes = Vec::new();
+        walk(&self.root, &mut String::new(), &mut prefixes);
+        prefixes.sort();
+        prefixes
+    }
+}
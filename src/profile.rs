@@ -0,0 +1,348 @@
+use crate::{note, overlay::Owner, BadPath, Directive, LineFilter, Validation};
+use std::{error::Error, fmt, path::Path};
+
+/// Indicates a profile could not be defined, activated, or deactivated.
+#[derive(Debug)]
+pub struct ProfileError {
+    name: String,
+    kind: ErrorKind,
+}
+
+#[derive(Debug)]
+enum ErrorKind {
+    Unknown,
+    #[cfg(feature = "env-filter")]
+    Env,
+    Path(BadPath),
+}
+
+/// A named set of directives that can be switched on and off as a unit.
+#[derive(Clone, Debug)]
+pub(crate) struct Profile {
+    directives: Vec<Directive>,
+    /// The directives that activating the profile added to the filter, or
+    /// `None` if the profile isn't active.
+    added: Option<Vec<Directive>>,
+}
+
+impl LineFilter {
+    /// Defines a named profile: a curated bundle of directives, such as the
+    /// lines needed to debug one subsystem, that can be switched on and off
+    /// as a unit using [`activate_profile`] and [`deactivate_profile`].
+    ///
+    /// Defining a profile doesn't activate it. If a profile with the same
+    /// name is already defined, it is replaced; if that profile was active,
+    /// it is deactivated first. Profiles can also be defined in a filter's
+    /// config, in a section starting with a `[profile.NAME]` line (see [the
+    /// `FromStr` implementation for `LineFilter`][syntax]).
+    ///
+    /// # Errors
+    ///
+    /// Profiles may contain any directives except [`EnvFilter`] directives,
+    /// which can't be removed from a filter when the profile is deactivated.
+    /// This returns an error if `directives` includes one, or if a `File`
+    /// directive's path is not valid for use in a `LineFilter`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter
+    ///     .define_profile(
+    ///         "db-debug",
+    ///         ["my_crate::db@142".parse().unwrap(), "my_crate::db@200".parse().unwrap()],
+    ///     )
+    ///     .unwrap();
+    ///
+    /// filter.activate_profile("db-debug").unwrap();
+    /// assert_eq!(filter.to_string(), "my_crate::db@142,my_crate::db@200");
+    ///
+    /// filter.deactivate_profile("db-debug").unwrap();
+    /// assert_eq!(filter.to_string(), "");
+    /// ```
+    ///
+    /// [`activate_profile`]: Self::activate_profile
+    /// [`deactivate_profile`]: Self::deactivate_profile
+    /// [syntax]: crate::LineFilter#impl-FromStr-for-LineFilter
+    /// [`EnvFilter`]: tracing_subscriber::EnvFilter
+    pub fn define_profile(
+        &mut self,
+        name: impl Into<String>,
+        directives: impl IntoIterator<Item = Directive>,
+    ) -> Result<&mut Self, ProfileError> {
+        let name = name.into();
+        let directives = directives.into_iter().collect::<Vec<_>>();
+        for directive in &directives {
+            check(&name, directive, self.validation)?;
+        }
+
+        if self.is_profile_active(&name) {
+            self.deactivate_profile(&name)?;
+        }
+        self.profiles.insert(
+            name,
+            Profile {
+                directives,
+                added: None,
+            },
+        );
+        Ok(self)
+    }
+
+    /// Activates the profile named `name`, adding its directives to this
+    /// filter.
+    ///
+    /// Activating a profile that is already active does nothing. When the
+    /// filter is wrapped in a [`reload`] layer, call this inside
+    /// [`Handle::modify`] to switch a profile on in a running program, or
+    /// use the `activate` [`Command`].
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if no profile named `name` is defined.
+    ///
+    /// [`reload`]: tracing_subscriber::reload
+    /// [`Handle::modify`]: tracing_subscriber::reload::Handle::modify
+    /// [`Command`]: crate::Command
+    pub fn activate_profile(&mut self, name: &str) -> Result<&mut Self, ProfileError> {
+        let profile = self
+            .profiles
+            .get(name)
+            .ok_or_else(|| ProfileError::new(name, ErrorKind::Unknown))?;
+        if profile.added.is_some() {
+            return Ok(self);
+        }
+
+        let directives = profile.directives.clone();
+        let added = self.apply_overlay(Owner::Profile(name.to_owned()), &directives);
+        if let Some(profile) = self.profiles.get_mut(name) {
+            profile.added = Some(added);
+        }
+        Ok(self)
+    }
+
+    /// Deactivates the profile named `name`, removing the directives that
+    /// activating it added to this filter.
+    ///
+    /// Directives that were already in the filter when the profile was
+    /// activated, or that another active profile also contains, are left in
+    /// place. Where the profile changed a directive that was already there,
+    /// such as by adding a level set to a line, the directive is changed
+    /// back. Deactivating a profile that isn't active does nothing.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if no profile named `name` is defined.
+    pub fn deactivate_profile(&mut self, name: &str) -> Result<&mut Self, ProfileError> {
+        let added = self
+            .profiles
+            .get_mut(name)
+            .ok_or_else(|| ProfileError::new(name, ErrorKind::Unknown))?
+            .added
+            .take();
+        if added.is_some() {
+            self.remove_overlay(&Owner::Profile(name.to_owned()));
+        }
+        Ok(self)
+    }
+
+    /// Returns `true` if the profile named `name` is defined and active.
+    pub fn is_profile_active(&self, name: &str) -> bool {
+        self.profiles
+            .get(name)
+            .is_some_and(|profile| profile.added.is_some())
+    }
+
+    /// Returns the names of this filter's profiles, sorted by name, and
+    /// whether each one is active.
+    pub fn profiles(&self) -> impl Iterator<Item = (&str, bool)> + '_ {
+        self.profiles
+            .iter()
+            .map(|(name, profile)| (name.as_str(), profile.added.is_some()))
+    }
+
+    /// Switches to the next profile, in name order, returning the name of
+    /// the profile that is now active.
+    ///
+    /// This deactivates every active profile, and activates the profile
+    /// after the first of them. If no profile was active, the first profile
+    /// is activated; if the last profile was active, no profile is activated
+    /// and this returns `None`. Calling this repeatedly steps through each
+    /// profile in turn, and then back to the filter's own directives.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    ///
+    /// let config = "[profile.1-debug]\nmy_crate@debug\n[profile.2-trace]\nmy_crate@trace";
+    /// let mut filter: LineFilter = config.parse().unwrap();
+    ///
+    /// assert_eq!(filter.cycle_profiles().as_deref(), Some("1-debug"));
+    /// assert_eq!(filter.cycle_profiles().as_deref(), Some("2-trace"));
+    /// assert_eq!(filter.cycle_profiles(), None);
+    /// assert_eq!(filter.to_string(), "");
+    /// ```
+    pub fn cycle_profiles(&mut self) -> Option<String> {
+        let names = self.profiles.keys().cloned().collect::<Vec<_>>();
+        let next = match names.iter().position(|name| self.is_profile_active(name)) {
+            Some(i) => names.get(i + 1),
+            None => names.first(),
+        };
+        for name in &names {
+            self.deactivate_profile(name).expect("profile is defined");
+        }
+        let next = next?;
+        self.activate_profile(next).expect("profile is defined");
+        Some(next.clone())
+    }
+
+    /// Formats this filter's profiles as `[profile.NAME]` sections, one
+    /// directive per line, to follow the filter's own directives in a config
+    /// file.
+    pub(crate) fn profile_sections(&self) -> String {
+        let mut sections = String::new();
+        for (name, profile) in &self.profiles {
+            sections.push_str(&format!("\n\n[profile.{}]", name));
+            for directive in &profile.directives {
+                sections.push_str(&format!("\n{}", directive));
+                if let Some(note) = self.note(directive) {
+                    sections.push_str(&format!(" {}", note::fmt_clause(note)));
+                }
+            }
+        }
+        sections
+    }
+}
+
+/// Checks that `directive` can be part of a profile.
+fn check(name: &str, directive: &Directive, validation: Validation) -> Result<(), ProfileError> {
+    match directive {
+        #[cfg(feature = "env-filter")]
+        Directive::Env(_) => Err(ProfileError::new(name, ErrorKind::Env)),
+        Directive::File { file, .. } => validation
+            .check_path(Path::new(file))
+            .map(|_| ())
+            .map_err(|e| ProfileError::new(name, ErrorKind::Path(e))),
+        Directive::Deny(denied) => check(name, denied, validation),
+        _ => Ok(()),
+    }
+}
+
+// === impl ProfileError ===
+
+impl ProfileError {
+    fn new(name: &str, kind: ErrorKind) -> Self {
+        Self {
+            name: name.to_owned(),
+            kind,
+        }
+    }
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ErrorKind::Unknown => write!(f, "unknown profile '{}'", self.name),
+            #[cfg(feature = "env-filter")]
+            ErrorKind::Env => write!(
+                f,
+                "profile '{}' may not contain `EnvFilter` directives",
+                self.name
+            ),
+            ErrorKind::Path(ref e) => write!(f, "profile '{}': {}", self.name, e),
+        }
+    }
+}
+
+impl Error for ProfileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self.kind {
+            ErrorKind::Path(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directives(directives: &str) -> Vec<Directive> {
+        directives
+            .split(',')
+            .map(|directive| directive.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn overlapping_profiles_are_removed_once_both_are_deactivated() {
+        let mut filter = LineFilter::default();
+        filter
+            .define_profile("a", directives("m@1,m@2"))
+            .unwrap()
+            .define_profile("b", directives("m@1,m@3"))
+            .unwrap();
+
+        filter.activate_profile("a").unwrap();
+        filter.activate_profile("b").unwrap();
+        assert_eq!(filter.to_string(), "m@1,m@2,m@3");
+
+        filter.deactivate_profile("a").unwrap();
+        assert_eq!(filter.to_string(), "m@1,m@3");
+        filter.deactivate_profile("b").unwrap();
+        assert_eq!(filter.to_string(), "");
+    }
+
+    #[test]
+    fn overlapping_profiles_in_either_order() {
+        let mut filter = LineFilter::default();
+        filter
+            .define_profile("a", directives("m@1"))
+            .unwrap()
+            .define_profile("b", directives("m@1"))
+            .unwrap();
+
+        filter.activate_profile("a").unwrap();
+        filter.activate_profile("b").unwrap();
+        filter.deactivate_profile("b").unwrap();
+        assert_eq!(filter.to_string(), "m@1");
+        filter.deactivate_profile("a").unwrap();
+        assert_eq!(filter.to_string(), "");
+    }
+
+    #[test]
+    fn deactivating_keeps_the_filters_own_directives() {
+        let mut filter: LineFilter = "m@1".parse().unwrap();
+        filter
+            .define_profile("a", directives("m@1,m@2"))
+            .unwrap()
+            .define_profile("b", directives("m@2"))
+            .unwrap();
+
+        filter.activate_profile("a").unwrap();
+        filter.activate_profile("b").unwrap();
+        filter.deactivate_profile("a").unwrap();
+        filter.deactivate_profile("b").unwrap();
+        assert_eq!(filter.to_string(), "m@1");
+    }
+
+    #[test]
+    fn cycling_overlapping_profiles_ends_with_the_filters_own_directives() {
+        let mut filter: LineFilter = "m@1".parse().unwrap();
+        filter
+            .define_profile("a", directives("m@1,m@2"))
+            .unwrap()
+            .define_profile("b", directives("m@2,m@3"))
+            .unwrap();
+
+        filter.activate_profile("a").unwrap();
+        filter.activate_profile("b").unwrap();
+        assert_eq!(filter.cycle_profiles().as_deref(), Some("b"));
+        assert_eq!(filter.to_string(), "m@1,m@2,m@3");
+        assert_eq!(filter.cycle_profiles(), None);
+        assert_eq!(filter.to_string(), "m@1");
+    }
+}
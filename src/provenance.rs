@@ -0,0 +1,167 @@
+use crate::{deny::Matched, Directive, LineFilter};
+use std::fmt;
+use tracing_core::span;
+use tracing_subscriber::{registry::LookupSpan, Registry};
+
+/// Records which of a [`LineFilter`]'s entries enabled a span.
+///
+/// Whenever one of a filter's entries enables a span, a `Provenance` is added
+/// to the span's [extensions], where other layers (such as exporters, custom
+/// `fmt` formatters, or an OpenTelemetry bridge) can find it using
+/// [`SpanRef::extensions`], to tell spans that only exist because of a
+/// pinned line apart from the rest. Spans that are only enabled by the
+/// filter's [`EnvFilter`] don't get one.
+///
+/// Provenance is only recorded when the filter is part of a subscriber built
+/// on a [`Registry`], and that subscriber is the global default: `tracing`
+/// doesn't give layers access to a scoped default subscriber (such as one set
+/// by [`with_default`]) while a span is being created. In [marking mode],
+/// which enables every span, spans that an entry matches get a [`LineMatch`]
+/// instead.
+///
+/// The `Display` implementation formats the entry along with its kind, such
+/// as `` `my_crate::db@142` (line entry) ``. When the `serde` feature flag is
+/// enabled, this implements `serde::Serialize`, with the entry serialized as
+/// a string.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::{Arc, Mutex};
+/// use tracing::{span, Subscriber};
+/// use tracing_line_filter::{LineFilter, Provenance, RuleKind};
+/// use tracing_subscriber::{layer, prelude::*, registry::LookupSpan, EnvFilter};
+///
+/// // A layer that collects the provenance of each new span.
+/// struct Collect(Arc<Mutex<Vec<Option<Provenance>>>>);
+///
+/// impl<S: Subscriber + for<'a> LookupSpan<'a>> tracing_subscriber::Layer<S> for Collect {
+///     fn on_new_span(&self, _: &span::Attributes<'_>, id: &span::Id, cx: layer::Context<'_, S>) {
+///         let span = cx.span(id).unwrap();
+///         let provenance = span.extensions().get::<Provenance>().cloned();
+///         self.0.lock().unwrap().push(provenance);
+///     }
+/// }
+///
+/// let directive = format!("{}@{}", module_path!(), line!() + 9);
+/// let mut filter: LineFilter = directive.parse().unwrap();
+/// filter.with_env_filter(EnvFilter::new("warn"));
+///
+/// let collected = Arc::new(Mutex::new(Vec::new()));
+/// tracing_subscriber::registry()
+///     .with(filter)
+///     .with(Collect(collected.clone()))
+///     .init();
+/// let _pinned = tracing::info_span!("pinned");
+/// let _warn = tracing::warn_span!("enabled by the EnvFilter");
+///
+/// let collected = collected.lock().unwrap();
+/// let provenance = collected[0].as_ref().unwrap();
+/// assert_eq!(provenance.entry().to_string(), directive);
+/// assert_eq!(provenance.kind(), RuleKind::Line);
+/// assert_eq!(collected[1], None);
+/// ```
+///
+/// [extensions]: tracing_subscriber::registry::Extensions
+/// [`SpanRef::extensions`]: tracing_subscriber::registry::SpanRef::extensions
+/// [`EnvFilter`]: tracing_subscriber::EnvFilter
+/// [`Registry`]: tracing_subscriber::Registry
+/// [`with_default`]: tracing::subscriber::with_default
+/// [marking mode]: LineFilter::mark_only
+/// [`LineMatch`]: crate::LineMatch
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Provenance {
+    entry: Directive,
+    kind: RuleKind,
+}
+
+/// What kind of entry enabled a span, as reported by [`Provenance::kind`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub enum RuleKind {
+    /// A `module@line` entry, or one of the entries added by
+    /// [`LineFilter::enable_instrumented_fn`].
+    Line,
+    /// A `file@line` entry, or one of the entries added by
+    /// [`LineFilter::enable_in_crate_file`].
+    File,
+    /// A `module@level` entry.
+    Level,
+    /// A `module::**` prefix.
+    Prefix,
+}
+
+impl LineFilter {
+    /// Adds a [`Provenance`] to the span with `attrs`, if one of the filter's
+    /// entries enabled it.
+    pub(crate) fn record_provenance(&self, attrs: &span::Attributes<'_>, id: &span::Id) {
+        let metadata = attrs.metadata();
+        if self.decide(metadata) != Some(true) {
+            return;
+        }
+        let provenance = match self.allow_match(metadata) {
+            Some((_, entry)) => Provenance::new(&entry),
+            None => return,
+        };
+        tracing::dispatcher::get_default(|dispatch| {
+            let span = dispatch
+                .downcast_ref::<Registry>()
+                .and_then(|registry| registry.span(id));
+            if let Some(span) = span {
+                span.extensions_mut().replace(provenance.clone());
+            }
+        });
+    }
+}
+
+// === impl Provenance ===
+
+impl Provenance {
+    fn new(entry: &Matched<'_>) -> Self {
+        let kind = match entry {
+            Matched::Module(..) => RuleKind::Line,
+            Matched::File(..) => RuleKind::File,
+            Matched::Level(..) => RuleKind::Level,
+            Matched::Prefix(_) => RuleKind::Prefix,
+        };
+        Self {
+            entry: entry.to_directive(),
+            kind,
+        }
+    }
+
+    /// Returns the `Module`, `File`, `ModuleLevel`, or `ModulePrefix`
+    /// directive for the entry that enabled the span.
+    ///
+    /// A line entry's hash isn't included, so its directive can be compared
+    /// with those in [`LineFilter::directives`] by location.
+    pub fn entry(&self) -> &Directive {
+        &self.entry
+    }
+
+    /// Returns what kind of entry enabled the span.
+    pub fn kind(&self) -> RuleKind {
+        self.kind
+    }
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` ({})", self.entry, self.kind)
+    }
+}
+
+// === impl RuleKind ===
+
+impl fmt::Display for RuleKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RuleKind::Line => "line entry",
+            RuleKind::File => "file entry",
+            RuleKind::Level => "level entry",
+            RuleKind::Prefix => "prefix entry",
+        })
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{audit, version, Error, LineFilter};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+};
+use tracing_core::Subscriber;
+use tracing_subscriber::reload;
+
+impl LineFilter {
+    /// Writes this filter's config to the file at `path`, and writes it again
+    /// each time a control endpoint changes the filter.
+    ///
+    /// This lets a pre-forking server, which runs several worker processes
+    /// (such as one per core), change the filters of all of its workers at
+    /// once, without a control connection to each of them. The primary
+    /// process serves a control endpoint (such as the [`jsonrpc`] server,
+    /// or [`Command::apply`] on a socket) for a filter that publishes its
+    /// config, and each worker follows the file with `LineFilter::from_file`
+    /// (when the `tokio` feature flag is enabled), or by re-reading it with
+    /// [`set_config`]. Since a `LineFilter` may not survive a `fork`, workers
+    /// should set up their filters after they are forked.
+    ///
+    /// The file is written in the same format as the `save` command writes,
+    /// so it includes the filter's directives (including those added by its
+    /// active profiles), span fields, and profile definitions, but not other
+    /// state such as whether the filter's entries are disabled or when they
+    /// expire. Each write replaces the file atomically, by writing a
+    /// temporary file in the same directory and renaming it, so workers never
+    /// read a partially-written config. The file is written after each change
+    /// made by [`Command::apply`], the [`dap`] bridge, or
+    /// `LineFilter::on_sigusr2`; if a write fails, the change still takes
+    /// effect in this process, and a `WARN` event is recorded with the
+    /// `tracing_line_filter::audit` target.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the filter's current config can't be
+    /// written to `path`, in which case it isn't published.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::{Command, FilterConfig, LineFilter};
+    /// use tracing_subscriber::{prelude::*, reload};
+    ///
+    /// let path = std::env::temp_dir().join("published-lines.conf");
+    /// let mut filter: LineFilter = "my_crate::db@142".parse().unwrap();
+    /// filter.publish_to(&path).unwrap();
+    ///
+    /// let (filter, handle) = reload::Layer::new(filter);
+    /// let _subscriber = tracing_subscriber::registry().with(filter);
+    /// let enable: Command = "enable my_crate::auth@info".parse().unwrap();
+    /// enable.apply(&handle, "socket", None).unwrap();
+    ///
+    /// // What a worker process reads:
+    /// let published = std::fs::read_to_string(&path).unwrap();
+    /// let config: FilterConfig = published.parse().unwrap();
+    /// assert_eq!(config.to_string(), "my_crate::auth@info,my_crate::db@142");
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    ///
+    /// [`jsonrpc`]: https://docs.rs/tracing-line-filter/latest/tracing_line_filter/jsonrpc/index.html
+    /// [`dap`]: https://docs.rs/tracing-line-filter/latest/tracing_line_filter/dap/index.html
+    /// [`Command::apply`]: crate::Command::apply
+    /// [`set_config`]: Self::set_config
+    pub fn publish_to(&mut self, path: impl Into<PathBuf>) -> Result<&mut Self, Error> {
+        let path = path.into();
+        write_atomically(&path, &self.saved_config()).map_err(Error::Io)?;
+        self.published = Some(path);
+        Ok(self)
+    }
+
+    /// Stops writing this filter's config to the file set by
+    /// [`publish_to`]. The file is left in place.
+    ///
+    /// [`publish_to`]: Self::publish_to
+    pub fn stop_publishing(&mut self) -> &mut Self {
+        self.published = None;
+        self
+    }
+
+    /// Returns this filter's config, as the `save` command writes it.
+    pub(crate) fn saved_config(&self) -> String {
+        format!("{}\n{}\n", version::stamp(), self.config_text())
+    }
+}
+
+/// Writes the config of the filter behind `handle` to the file set by
+/// [`LineFilter::publish_to`], if there is one, after a change made by
+/// `transport`.
+///
+/// This must not be called while the filter is being modified.
+pub(crate) fn publish<S>(handle: &reload::Handle<LineFilter, S>, transport: &str)
+where
+    S: Subscriber,
+{
+    let published = handle.with_current(|filter| {
+        let path = filter.published.clone()?;
+        Some((path, filter.saved_config()))
+    });
+    if let Ok(Some((path, config))) = published {
+        if let Err(error) = write_atomically(&path, &config) {
+            tracing::warn!(
+                target: audit::TARGET,
+                transport,
+                path = %path.display(),
+                %error,
+                "failed to publish the filter config",
+            );
+        }
+    }
+}
+
+/// Replaces the file at `path` with `contents`, so that readers see either
+/// the old contents or the new ones.
+fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "the path has no file name"))?;
+    let mut temp = name.to_owned();
+    temp.push(format!(".{}.tmp", process::id()));
+    let temp = path.with_file_name(temp);
+    fs::write(&temp, contents)?;
+    fs::rename(&temp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temp);
+    })
+}
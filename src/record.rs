@@ -0,0 +1,401 @@
+use crate::{version, LineFilter};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    sync::{Mutex, PoisonError},
+};
+use tracing_core::{callsite::Identifier, Metadata};
+
+/// Records the metadata of every callsite a [`LineFilter`] is asked about.
+#[derive(Debug, Default)]
+pub(crate) struct Recorder {
+    callsites: Mutex<HashMap<Identifier, &'static Metadata<'static>>>,
+}
+
+/// Records the metadata of every callsite whose spans or events a
+/// [`LineFilter`] allowed, in the order they were first allowed.
+#[derive(Debug, Default)]
+pub(crate) struct Allowed {
+    callsites: Mutex<(HashSet<Identifier>, Vec<&'static Metadata<'static>>)>,
+}
+
+impl LineFilter {
+    /// Records every span and event callsite that this filter is asked
+    /// about, so that they can be listed using [`template`] and
+    /// [`complete`].
+    ///
+    /// `tracing` asks a subscriber's filters about each callsite the first
+    /// time it is reached, so once a program has been running for a while,
+    /// the recorded callsites are the spans and events it actually emits
+    /// (whether or not they were enabled).
+    ///
+    /// [`template`]: Self::template
+    /// [`complete`]: Self::complete
+    pub fn record_callsites(&mut self) -> &mut Self {
+        self.recorder.get_or_insert_with(Recorder::default);
+        self
+    }
+
+    /// Returns the metadata of every span and event callsite recorded since
+    /// [`record_callsites`] was called, sorted by module and line number.
+    ///
+    /// This is for browsing what could be enabled in a running program,
+    /// rather than guessing at line numbers: each callsite's metadata
+    /// includes its module path, file, line number, level, name, and field
+    /// names. Callsites without a module path are sorted by their target.
+    /// If callsites are not being recorded, this returns an empty iterator.
+    ///
+    /// The `callsites` [`Command`] and the [`jsonrpc`] server's
+    /// `filter/callsites` method list the same callsites.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::{prelude::*, reload};
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter.record_callsites();
+    /// let (filter, handle) = reload::Layer::new(filter);
+    /// let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(filter));
+    ///
+    /// let line = line!() + 1;
+    /// tracing::info_span!("request", path = "/").in_scope(|| {
+    ///     tracing::debug!(status = 200, "responded");
+    /// });
+    ///
+    /// let callsites = handle
+    ///     .with_current(|filter| filter.known_callsites().collect::<Vec<_>>())
+    ///     .unwrap();
+    /// assert_eq!(callsites.len(), 2);
+    /// assert_eq!(callsites[0].name(), "request");
+    /// assert_eq!(callsites[0].line(), Some(line));
+    /// assert!(callsites[1].is_event());
+    /// assert!(callsites[1].fields().field("status").is_some());
+    /// ```
+    ///
+    /// [`record_callsites`]: Self::record_callsites
+    /// [`Command`]: crate::Command
+    /// [`jsonrpc`]: https://docs.rs/tracing-line-filter/latest/tracing_line_filter/jsonrpc/index.html
+    pub fn known_callsites(&self) -> impl Iterator<Item = &'static Metadata<'static>> {
+        let mut callsites = self
+            .recorder
+            .as_ref()
+            .map(Recorder::callsites)
+            .unwrap_or_default();
+        callsites.sort_by_key(|metadata| {
+            (
+                crate::module_key(metadata),
+                metadata.line(),
+                metadata.name(),
+            )
+        });
+        callsites.into_iter()
+    }
+
+    /// Returns a commented template of directives for every callsite
+    /// recorded since [`record_callsites`] was called.
+    ///
+    /// The template lists one `module@line` directive per recorded callsite,
+    /// grouped by module and sorted by line number. Directives for callsites
+    /// that this filter already enables are left as-is, and all other
+    /// directives are commented out, so that the template can be saved to a
+    /// file and used to bootstrap a configuration by uncommenting the lines
+    /// to enable. Each directive is followed by a comment describing the
+    /// span or event on that line.
+    ///
+    /// If callsites are not being recorded, the template contains only its
+    /// header.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::{prelude::*, reload};
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter.record_callsites();
+    /// let (filter, handle) = reload::Layer::new(filter);
+    /// let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(filter));
+    ///
+    /// let line = line!() + 1;
+    /// tracing::info!("hello world");
+    ///
+    /// let template = handle.with_current(|filter| filter.template()).unwrap();
+    /// let directive = format!("# {}@{}  # INFO event", module_path!(), line);
+    /// assert!(template.contains(&directive));
+    /// ```
+    ///
+    /// [`record_callsites`]: Self::record_callsites
+    pub fn template(&self) -> String {
+        let callsites = self
+            .known_callsites()
+            .filter_map(|metadata| Some((crate::module_key(metadata), metadata.line()?, metadata)));
+
+        let mut template = format!(
+            "{}\n\
+             # Spans and events recorded by `tracing-line-filter`.\n\
+             # Uncomment a directive to enable the span or event on that line.\n",
+            version::stamp(),
+        );
+        let mut current_module = None;
+        for (module, line, metadata) in callsites {
+            if current_module != Some(module) {
+                let _ = write!(template, "\n# == {} ==\n", module);
+                current_module = Some(module);
+            }
+
+            let enabled = self.by_module.contains(&(Cow::Borrowed(module), line))
+                || metadata
+                    .file()
+                    .is_some_and(|file| self.by_file.contains(&(Cow::Borrowed(file), line)));
+            if !enabled {
+                template.push_str("# ");
+            }
+            let _ = writeln!(template, "{}@{}  # {}", module, line, describe(metadata));
+        }
+        template
+    }
+
+    /// Completes a partially-typed `module@line` directive, using the
+    /// callsites recorded since [`record_callsites`] was called.
+    ///
+    /// If `prefix` does not contain an `@`, this returns the path of every
+    /// recorded module that starts with `prefix`. Otherwise, it returns every
+    /// `module@line` directive for a recorded callsite that starts with
+    /// `prefix`. Either way, the completions are sorted and contain no
+    /// duplicates.
+    ///
+    /// This is intended for implementing shell completion or
+    /// autocompletion for commands that enable directives in a running
+    /// program. If callsites are not being recorded, this always returns an
+    /// empty list.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::{prelude::*, reload};
+    ///
+    /// mod db {
+    ///     pub fn query() -> u32 {
+    ///         let line = line!() + 1;
+    ///         tracing::debug!("querying");
+    ///         line
+    ///     }
+    /// }
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter.record_callsites();
+    /// let (filter, handle) = reload::Layer::new(filter);
+    /// let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(filter));
+    /// let line = db::query();
+    ///
+    /// let module = format!("{}::db", module_path!());
+    /// let complete = |prefix: &str| handle.with_current(|filter| filter.complete(prefix)).unwrap();
+    ///
+    /// assert_eq!(complete(&module[..module.len() - 1]), vec![module.clone()]);
+    /// assert_eq!(
+    ///     complete(&format!("{}@", module)),
+    ///     vec![format!("{}@{}", module, line)],
+    /// );
+    /// ```
+    ///
+    /// [`record_callsites`]: Self::record_callsites
+    pub fn complete(&self, prefix: &str) -> Vec<String> {
+        let callsites = self
+            .recorder
+            .as_ref()
+            .map(Recorder::callsites)
+            .unwrap_or_default();
+        let mut locations = callsites
+            .iter()
+            .filter_map(|metadata| Some((crate::module_keys(metadata), metadata.line()?)))
+            .flat_map(|(modules, line)| modules.map(move |module| (module, line)))
+            .collect::<Vec<_>>();
+        locations.sort_unstable();
+
+        if prefix.contains('@') {
+            let mut completions = locations
+                .into_iter()
+                .map(|(module, line)| format!("{}@{}", module, line))
+                .filter(|directive| directive.starts_with(prefix))
+                .collect::<Vec<_>>();
+            completions.dedup();
+            completions
+        } else {
+            let mut completions = locations
+                .into_iter()
+                .map(|(module, _)| module)
+                .filter(|module| module.starts_with(prefix))
+                .map(str::to_owned)
+                .collect::<Vec<_>>();
+            completions.dedup();
+            completions
+        }
+    }
+
+    /// Records the metadata of every span and event that this filter
+    /// allows, so that they can be listed using [`allowed_callsites`].
+    ///
+    /// This is intended for integration tests that check exactly which
+    /// callsites a filter enabled while a scenario ran. Unlike
+    /// [`record_callsites`], which records every callsite the filter is
+    /// asked about, only spans that were created and events that were
+    /// recorded are listed, whether they were enabled by one of the filter's
+    /// entries or by its [`EnvFilter`]. Since a callsite is only listed
+    /// once, the list's size is bounded by the number of callsites in the
+    /// program.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::{prelude::*, reload};
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter
+    ///     .enable_by_mod(module_path!(), line!() + 7)
+    ///     .record_allowed();
+    /// let (filter, handle) = reload::Layer::new(filter);
+    /// let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(filter));
+    ///
+    /// for _ in 0..3 {
+    ///     tracing::debug!("not pinned");
+    ///     tracing::info!("pinned");
+    /// }
+    ///
+    /// let allowed = handle.with_current(|filter| filter.allowed_callsites()).unwrap();
+    /// assert_eq!(allowed.len(), 1);
+    /// assert_eq!(allowed[0].module_path(), Some(module_path!()));
+    /// assert_eq!(allowed[0].line(), Some(line!() - 6));
+    /// assert_eq!(*allowed[0].level(), tracing::Level::INFO);
+    /// ```
+    ///
+    /// [`allowed_callsites`]: Self::allowed_callsites
+    /// [`record_callsites`]: Self::record_callsites
+    /// [`EnvFilter`]: tracing_subscriber::EnvFilter
+    pub fn record_allowed(&mut self) -> &mut Self {
+        self.allowed.get_or_insert_with(Allowed::default);
+        self
+    }
+
+    /// Returns the metadata of every callsite whose spans or events this
+    /// filter allowed since [`record_allowed`] was called, in the order they
+    /// were first allowed.
+    ///
+    /// The metadata includes each callsite's module path, file, line number,
+    /// and level. If allowed callsites are not being recorded, this always
+    /// returns an empty list.
+    ///
+    /// [`record_allowed`]: Self::record_allowed
+    pub fn allowed_callsites(&self) -> Vec<&'static Metadata<'static>> {
+        self.allowed
+            .as_ref()
+            .map(|allowed| allowed.lock().1.clone())
+            .unwrap_or_default()
+    }
+
+    /// Forgets the callsites recorded by [`record_allowed`], so that a test
+    /// can check the callsites allowed by each of several scenarios.
+    ///
+    /// [`record_allowed`]: Self::record_allowed
+    pub fn clear_allowed(&self) {
+        if let Some(ref allowed) = self.allowed {
+            let mut callsites = allowed.lock();
+            callsites.0.clear();
+            callsites.1.clear();
+        }
+    }
+
+    /// Records that a span or event with `metadata` was allowed, if allowed
+    /// callsites are being recorded.
+    pub(crate) fn record_allowed_callsite(&self, metadata: &'static Metadata<'static>) {
+        if let Some(ref allowed) = self.allowed {
+            let mut callsites = allowed.lock();
+            if callsites.0.insert(metadata.callsite()) {
+                callsites.1.push(metadata);
+            }
+        }
+    }
+}
+
+// === impl Allowed ===
+
+impl Allowed {
+    #[allow(clippy::type_complexity)]
+    fn lock(
+        &self,
+    ) -> std::sync::MutexGuard<'_, (HashSet<Identifier>, Vec<&'static Metadata<'static>>)> {
+        self.callsites
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+// === impl Recorder ===
+
+impl Recorder {
+    pub(crate) fn record(&self, metadata: &'static Metadata<'static>) {
+        self.callsites
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(metadata.callsite(), metadata);
+    }
+
+    pub(crate) fn callsites(&self) -> Vec<&'static Metadata<'static>> {
+        self.callsites
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .values()
+            .copied()
+            .collect()
+    }
+}
+
+/// Describes the span or event at a callsite, such as
+/// ``INFO span `request` at src/lib.rs:42``.
+pub(crate) fn describe(metadata: &Metadata<'_>) -> String {
+    let mut description = metadata.level().to_string();
+    if metadata.is_span() {
+        let _ = write!(description, " span `{}`", metadata.name());
+    } else {
+        description.push_str(" event");
+    }
+    if let (Some(file), Some(line)) = (metadata.file(), metadata.line()) {
+        let _ = write!(description, " at {}:{}", file, line);
+    }
+    description
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::{prelude::*, reload};
+
+    #[test]
+    fn callsites_are_completed_by_target() {
+        let mut filter = LineFilter::default();
+        filter.record_callsites();
+        let (filter, handle) = reload::Layer::new(filter);
+        let _guard = tracing_subscriber::registry().with(filter).set_default();
+
+        let line = line!() + 1;
+        tracing::info!(target: "recorded_service::db", "hello");
+
+        let complete = |prefix: &str| {
+            handle
+                .with_current(|filter| filter.complete(prefix))
+                .unwrap()
+        };
+        assert_eq!(
+            complete("recorded_service::db@"),
+            vec![format!("recorded_service::db@{}", line)]
+        );
+        assert_eq!(
+            complete(&format!("{}@{}", module_path!(), line)),
+            vec![format!("{}@{}", module_path!(), line)]
+        );
+    }
+}
@@ -0,0 +1,295 @@
+use crate::rewrite::{self, EventMatches, Rewrite, Rewrites};
+use crate::LineFilter;
+use std::{any::TypeId, fmt, sync::Arc};
+use tracing_core::{span, subscriber::Interest, Event, Level, Metadata, Subscriber};
+use tracing_subscriber::{
+    layer::{self, Layer},
+    reload,
+};
+
+/// The name of the field added to matched events by a [`Relevel`] that only
+/// [tags](Relevel::tag_only) them.
+const EFFECTIVE_LEVEL: &str = "effective_level";
+
+/// A layer that raises the levels of the verbose events that a
+/// [`LineFilter`]'s entries match, before passing them to a wrapped layer.
+///
+/// Pinned lines are usually `DEBUG` or `TRACE` events, and logging
+/// infrastructure often drops everything below `INFO` long before the
+/// [`LineFilter`] has a say. A `Relevel` wraps the layer that sends a
+/// program's output to that infrastructure, and for each event that the
+/// filter behind a [`reload`] handle enables with its `module@line`,
+/// `file@line`, `module@level`, or `module::**` entries, and that is more
+/// verbose than the configured level (`INFO` by default), gives the wrapped
+/// layer a copy of the event at the configured level. Other events, and all
+/// spans, are passed through unchanged.
+///
+/// Alternatively, a `Relevel` can [leave the levels alone][tag_only] and
+/// add an `effective_level` field with the configured level to the matched
+/// events instead, for pipelines that can route events by their fields.
+///
+/// The copy has the same target, location, parent, and fields as the
+/// original, although field values other than numbers, booleans, and
+/// strings are formatted with `Debug` first. As with [`Retarget`], the
+/// wrapped layer is asked about every event individually, and the metadata
+/// of each rewritten callsite is allocated once and never freed.
+///
+/// A `Relevel` doesn't lock the filter's [`reload`] handle to check an
+/// event, so it may be layered inside the reload layer. Instead, the filter
+/// remembers whether it enabled each event callsite when the callsite is
+/// registered, and each time it is asked about an event there. If the filter
+/// is layered inside the `Relevel`, as it is below, it is asked about an event
+/// after the `Relevel` is, so an event that a span condition, an expiry, or
+/// the end of startup changes the decision for is matched as the previous
+/// event at its callsite was. The `Relevel` keeps following the filter behind
+/// the handle when it was created: if the filter is replaced with
+/// [`Handle::reload`], rather than changed with [`Handle::modify`], create a
+/// new `Relevel`.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_line_filter::{LineFilter, Relevel};
+/// use tracing_subscriber::{filter::LevelFilter, prelude::*, reload};
+///
+/// let filter: LineFilter = "my_crate::db@142,info".parse().unwrap();
+/// let (filter, handle) = reload::Layer::new(filter);
+///
+/// // The `TRACE` event on line 142 is logged as a `WARN` event, and gets
+/// // past the `fmt` layer's `INFO` filter.
+/// let fmt = tracing_subscriber::fmt::layer().with_filter(LevelFilter::INFO);
+/// let mut relevel = Relevel::new(handle, fmt);
+/// relevel.level(tracing::Level::WARN);
+///
+/// tracing_subscriber::registry()
+///     .with(filter)
+///     .with(relevel)
+///     .init();
+/// ```
+///
+/// [`reload`]: tracing_subscriber::reload
+/// [`Handle::reload`]: tracing_subscriber::reload::Handle::reload
+/// [`Handle::modify`]: tracing_subscriber::reload::Handle::modify
+/// [tag_only]: Relevel::tag_only
+/// [`Retarget`]: crate::Retarget
+pub struct Relevel<L> {
+    inner: L,
+    level: Level,
+    tag_only: bool,
+    /// The decisions of the filter behind the handle.
+    matches: Arc<EventMatches>,
+    /// The rewritten metadata for each callsite that has been matched.
+    rewritten: Rewrites,
+}
+
+impl<L> Relevel<L> {
+    /// Returns a `Relevel` that raises the levels of the verbose events
+    /// matched by the entries of the filter behind `handle`, and passes all
+    /// events and spans to `inner`.
+    ///
+    /// Matched events are raised to `INFO` by default.
+    pub fn new<S>(handle: reload::Handle<LineFilter, S>, inner: L) -> Self {
+        let matches = handle
+            .with_current(|filter| {
+                filter.event_matches.watch();
+                filter.event_matches.clone()
+            })
+            // If the subscriber is gone, nothing is matched.
+            .unwrap_or_default();
+        Self {
+            inner,
+            level: Level::INFO,
+            tag_only: false,
+            matches,
+            rewritten: Rewrites::new(),
+        }
+    }
+
+    /// Sets the level that matched events are raised to.
+    ///
+    /// Matched events at this level or a less verbose one are passed
+    /// through unchanged.
+    pub fn level(&mut self, level: Level) -> &mut Self {
+        self.level = level;
+        self.rewritten.clear();
+        self
+    }
+
+    /// Keeps the levels of matched events, and adds an `effective_level`
+    /// field with the [configured level](Self::level) to them instead.
+    pub fn tag_only(&mut self) -> &mut Self {
+        self.tag_only = true;
+        self.rewritten.clear();
+        self
+    }
+
+    /// Returns a reference to the wrapped layer.
+    pub fn get_ref(&self) -> &L {
+        &self.inner
+    }
+
+    fn is_matched(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.is_event() && *metadata.level() > self.level && self.matches.is_matched(metadata)
+    }
+
+    /// Returns the rewritten metadata for `metadata`'s callsite.
+    fn rewrite(&self, metadata: &Metadata<'_>) -> &'static Metadata<'static> {
+        self.rewritten.get(metadata, || Rewrite {
+            target: metadata.target().to_owned(),
+            level: if self.tag_only {
+                *metadata.level()
+            } else {
+                self.level
+            },
+            extra_field: if self.tag_only {
+                Some(EFFECTIVE_LEVEL)
+            } else {
+                None
+            },
+        })
+    }
+
+    /// Calls `f` with the rewritten copy of `event` if it is matched, or
+    /// with `event` itself if it isn't.
+    fn with_event<R>(&self, event: &Event<'_>, f: impl FnOnce(&Event<'_>) -> R) -> R {
+        if !self.is_matched(event.metadata()) {
+            return f(event);
+        }
+        let level = self.level.as_str();
+        rewrite::with_copy(event, self.rewrite(event.metadata()), Some(&level), f)
+    }
+}
+
+impl<S, L> Layer<S> for Relevel<L>
+where
+    S: Subscriber,
+    L: Layer<S>,
+{
+    fn on_register_dispatch(&self, subscriber: &tracing_core::Dispatch) {
+        self.inner.on_register_dispatch(subscriber);
+    }
+
+    fn on_layer(&mut self, subscriber: &mut S) {
+        self.inner.on_layer(subscriber);
+    }
+
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        let interest = self.inner.register_callsite(metadata);
+        if metadata.is_event() && !interest.is_always() {
+            return Interest::sometimes();
+        }
+        interest
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, cx: layer::Context<'_, S>) -> bool {
+        if self.is_matched(metadata) {
+            return self.inner.enabled(self.rewrite(metadata), cx);
+        }
+        self.inner.enabled(metadata, cx)
+    }
+
+    fn event_enabled(&self, event: &Event<'_>, cx: layer::Context<'_, S>) -> bool {
+        self.with_event(event, |event| self.inner.event_enabled(event, cx))
+    }
+
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, cx: layer::Context<'_, S>) {
+        self.inner.on_new_span(attrs, id, cx);
+    }
+
+    fn on_record(&self, span: &span::Id, values: &span::Record<'_>, cx: layer::Context<'_, S>) {
+        self.inner.on_record(span, values, cx);
+    }
+
+    fn on_follows_from(&self, span: &span::Id, follows: &span::Id, cx: layer::Context<'_, S>) {
+        self.inner.on_follows_from(span, follows, cx);
+    }
+
+    fn on_event(&self, event: &Event<'_>, cx: layer::Context<'_, S>) {
+        self.with_event(event, |event| self.inner.on_event(event, cx))
+    }
+
+    fn on_enter(&self, id: &span::Id, cx: layer::Context<'_, S>) {
+        self.inner.on_enter(id, cx);
+    }
+
+    fn on_exit(&self, id: &span::Id, cx: layer::Context<'_, S>) {
+        self.inner.on_exit(id, cx);
+    }
+
+    fn on_close(&self, id: span::Id, cx: layer::Context<'_, S>) {
+        self.inner.on_close(id, cx);
+    }
+
+    fn on_id_change(&self, old: &span::Id, new: &span::Id, cx: layer::Context<'_, S>) {
+        self.inner.on_id_change(old, new, cx);
+    }
+
+    fn max_level_hint(&self) -> Option<tracing_core::LevelFilter> {
+        self.inner.max_level_hint()
+    }
+
+    #[doc(hidden)]
+    unsafe fn downcast_raw(&self, id: TypeId) -> Option<*const ()> {
+        if id == TypeId::of::<Self>() {
+            return Some(self as *const Self as *const ());
+        }
+        self.inner.downcast_raw(id)
+    }
+}
+
+impl<L: fmt::Debug> fmt::Debug for Relevel<L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Relevel")
+            .field("inner", &self.inner)
+            .field("level", &self.level)
+            .field("tag_only", &self.tag_only)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Directive;
+    use std::sync::Mutex;
+    use tracing_subscriber::prelude::*;
+
+    /// Records the levels of the events it is given.
+    #[derive(Clone, Default)]
+    struct Levels(Arc<Mutex<Vec<Level>>>);
+
+    impl<S: Subscriber> Layer<S> for Levels {
+        fn on_event(&self, event: &Event<'_>, _: layer::Context<'_, S>) {
+            self.0.lock().unwrap().push(*event.metadata().level());
+        }
+    }
+
+    #[test]
+    fn follows_the_filter_it_is_layered_inside() {
+        let line = line!() + 15;
+        let directive = format!("{}@{}", module_path!(), line)
+            .parse::<Directive>()
+            .unwrap();
+        let mut filter = LineFilter::default();
+        filter.add_directive(directive.clone()).unwrap();
+        filter.with_fallback(|_| true);
+        let (filter, handle) = reload::Layer::new(filter);
+        let levels = Levels::default();
+        let relevel = Relevel::new(handle.clone(), levels.clone());
+        // The `Relevel` is asked about events while the reload layer's lock
+        // is held.
+        let subscriber = tracing_subscriber::registry().with(filter.and_then(relevel));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let event = || tracing::trace!(target: "relevel_follows_the_filter", "event");
+            event();
+            handle
+                .modify(|filter| {
+                    filter.remove_directive(&directive);
+                })
+                .unwrap();
+            event();
+        });
+        assert_eq!(*levels.0.lock().unwrap(), vec![Level::INFO, Level::TRACE]);
+    }
+}
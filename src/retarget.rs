@@ -0,0 +1,275 @@
+use crate::rewrite::{self, EventMatches, Rewrite, Rewrites};
+use crate::LineFilter;
+use std::{any::TypeId, fmt, sync::Arc};
+use tracing_core::{span, subscriber::Interest, Event, Metadata, Subscriber};
+use tracing_subscriber::{
+    layer::{self, Layer},
+    reload,
+};
+
+/// A layer that rewrites the targets of the events that a [`LineFilter`]'s
+/// entries match, before passing them to a wrapped layer.
+///
+/// Layers and filters that route output by target, such as an [`fmt`] layer
+/// with a [`Targets`] filter, can't tell whether an event was enabled by a
+/// pinned line. A `Retarget` wraps such a layer (usually the one that writes
+/// the program's output), and for each event that the filter behind a
+/// [`reload`] handle enables with its `module@line`, `file@line`,
+/// `module@level`, or `module::**` entries, gives the wrapped layer a copy of
+/// the event with a prefixed target: with the default prefix, an event
+/// from `my_crate::db` becomes an event from `pinned::my_crate::db`. Other
+/// events, and all spans, are passed through unchanged.
+///
+/// The copy has the same level, location, parent, and fields as the
+/// original, although field values other than numbers, booleans, and
+/// strings are formatted with `Debug` first.
+///
+/// Since whether an event matches can change whenever the filter is
+/// modified, the wrapped layer is asked about every event individually,
+/// rather than once per callsite. The metadata of each rewritten callsite is
+/// allocated once and never freed.
+///
+/// Like a [`Relevel`], a `Retarget` doesn't lock the filter's [`reload`]
+/// handle to check an event, so it may be layered inside the reload layer,
+/// and it matches events the way the filter last decided about their
+/// callsites. See the [`Relevel`] documentation for when that may lag behind
+/// the filter.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_line_filter::{LineFilter, Retarget};
+/// use tracing_subscriber::{filter::Targets, prelude::*, reload};
+///
+/// let filter: LineFilter = "my_crate::db@142,info".parse().unwrap();
+/// let (filter, handle) = reload::Layer::new(filter);
+///
+/// // Log pinned events at any level, but only warnings from `noisy_dep`.
+/// let targets = Targets::new()
+///     .with_default(tracing::Level::TRACE)
+///     .with_target("noisy_dep", tracing::Level::WARN);
+/// let fmt = tracing_subscriber::fmt::layer().with_filter(targets);
+///
+/// tracing_subscriber::registry()
+///     .with(filter)
+///     .with(Retarget::new(handle, fmt))
+///     .init();
+/// ```
+///
+/// [`fmt`]: tracing_subscriber::fmt
+/// [`Targets`]: tracing_subscriber::filter::Targets
+/// [`reload`]: tracing_subscriber::reload
+/// [`Relevel`]: crate::Relevel
+pub struct Retarget<L> {
+    inner: L,
+    prefix: String,
+    /// The decisions of the filter behind the handle.
+    matches: Arc<EventMatches>,
+    /// The rewritten metadata for each callsite that has been matched.
+    rewritten: Rewrites,
+}
+
+impl<L> Retarget<L> {
+    /// Returns a `Retarget` that rewrites the targets of the events matched
+    /// by the entries of the filter behind `handle`, and passes all events
+    /// and spans to `inner`.
+    ///
+    /// Targets are prefixed with `pinned::` by default.
+    pub fn new<S>(handle: reload::Handle<LineFilter, S>, inner: L) -> Self {
+        let matches = handle
+            .with_current(|filter| {
+                filter.event_matches.watch();
+                filter.event_matches.clone()
+            })
+            // If the subscriber is gone, nothing is matched.
+            .unwrap_or_default();
+        Self {
+            inner,
+            prefix: "pinned::".to_owned(),
+            matches,
+            rewritten: Rewrites::new(),
+        }
+    }
+
+    /// Sets the prefix added to the targets of matched events.
+    ///
+    /// The prefix is added as-is, so it should usually end with `::`.
+    pub fn prefix(&mut self, prefix: impl Into<String>) -> &mut Self {
+        self.prefix = prefix.into();
+        self.rewritten.clear();
+        self
+    }
+
+    /// Returns a reference to the wrapped layer.
+    pub fn get_ref(&self) -> &L {
+        &self.inner
+    }
+
+    fn is_matched(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.is_event() && self.matches.is_matched(metadata)
+    }
+
+    /// Returns the rewritten metadata for `metadata`'s callsite.
+    fn rewrite(&self, metadata: &Metadata<'_>) -> &'static Metadata<'static> {
+        self.rewritten.get(metadata, || Rewrite {
+            target: format!("{}{}", self.prefix, metadata.target()),
+            level: *metadata.level(),
+            extra_field: None,
+        })
+    }
+}
+
+impl<S, L> Layer<S> for Retarget<L>
+where
+    S: Subscriber,
+    L: Layer<S>,
+{
+    fn on_register_dispatch(&self, subscriber: &tracing_core::Dispatch) {
+        self.inner.on_register_dispatch(subscriber);
+    }
+
+    fn on_layer(&mut self, subscriber: &mut S) {
+        self.inner.on_layer(subscriber);
+    }
+
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        let interest = self.inner.register_callsite(metadata);
+        if metadata.is_event() && !interest.is_always() {
+            return Interest::sometimes();
+        }
+        interest
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, cx: layer::Context<'_, S>) -> bool {
+        if self.is_matched(metadata) {
+            return self.inner.enabled(self.rewrite(metadata), cx);
+        }
+        self.inner.enabled(metadata, cx)
+    }
+
+    fn event_enabled(&self, event: &Event<'_>, cx: layer::Context<'_, S>) -> bool {
+        self.with_event(event, |event| self.inner.event_enabled(event, cx))
+    }
+
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, cx: layer::Context<'_, S>) {
+        self.inner.on_new_span(attrs, id, cx);
+    }
+
+    fn on_record(&self, span: &span::Id, values: &span::Record<'_>, cx: layer::Context<'_, S>) {
+        self.inner.on_record(span, values, cx);
+    }
+
+    fn on_follows_from(&self, span: &span::Id, follows: &span::Id, cx: layer::Context<'_, S>) {
+        self.inner.on_follows_from(span, follows, cx);
+    }
+
+    fn on_event(&self, event: &Event<'_>, cx: layer::Context<'_, S>) {
+        self.with_event(event, |event| self.inner.on_event(event, cx))
+    }
+
+    fn on_enter(&self, id: &span::Id, cx: layer::Context<'_, S>) {
+        self.inner.on_enter(id, cx);
+    }
+
+    fn on_exit(&self, id: &span::Id, cx: layer::Context<'_, S>) {
+        self.inner.on_exit(id, cx);
+    }
+
+    fn on_close(&self, id: span::Id, cx: layer::Context<'_, S>) {
+        self.inner.on_close(id, cx);
+    }
+
+    fn on_id_change(&self, old: &span::Id, new: &span::Id, cx: layer::Context<'_, S>) {
+        self.inner.on_id_change(old, new, cx);
+    }
+
+    fn max_level_hint(&self) -> Option<tracing_core::LevelFilter> {
+        self.inner.max_level_hint()
+    }
+
+    #[doc(hidden)]
+    unsafe fn downcast_raw(&self, id: TypeId) -> Option<*const ()> {
+        if id == TypeId::of::<Self>() {
+            return Some(self as *const Self as *const ());
+        }
+        self.inner.downcast_raw(id)
+    }
+}
+
+impl<L> Retarget<L> {
+    /// Calls `f` with the rewritten copy of `event` if it is matched, or
+    /// with `event` itself if it isn't.
+    fn with_event<S, R>(&self, event: &Event<'_>, f: impl FnOnce(&Event<'_>) -> R) -> R
+    where
+        L: Layer<S>,
+        S: Subscriber,
+    {
+        if !self.is_matched(event.metadata()) {
+            return f(event);
+        }
+        rewrite::with_copy(event, self.rewrite(event.metadata()), None, f)
+    }
+}
+
+impl<L: fmt::Debug> fmt::Debug for Retarget<L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Retarget")
+            .field("inner", &self.inner)
+            .field("prefix", &self.prefix)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Directive;
+    use std::sync::Mutex;
+    use tracing_subscriber::prelude::*;
+
+    /// Records the targets of the events it is given.
+    #[derive(Clone, Default)]
+    struct Targets(Arc<Mutex<Vec<String>>>);
+
+    impl<S: Subscriber> Layer<S> for Targets {
+        fn on_event(&self, event: &Event<'_>, _: layer::Context<'_, S>) {
+            let target = event.metadata().target().to_owned();
+            self.0.lock().unwrap().push(target);
+        }
+    }
+
+    #[test]
+    fn follows_the_filter_it_is_layered_inside() {
+        let line = line!() + 15;
+        let directive = format!("{}@{}", module_path!(), line)
+            .parse::<Directive>()
+            .unwrap();
+        let mut filter = LineFilter::default();
+        filter.add_directive(directive.clone()).unwrap();
+        filter.with_fallback(|_| true);
+        let (filter, handle) = reload::Layer::new(filter);
+        let targets = Targets::default();
+        let retarget = Retarget::new(handle.clone(), targets.clone());
+        // The `Retarget` is asked about events while the reload layer's lock
+        // is held.
+        let subscriber = tracing_subscriber::registry().with(filter.and_then(retarget));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let event = || tracing::info!(target: "retarget_follows_the_filter", "event");
+            event();
+            handle
+                .modify(|filter| {
+                    filter.remove_directive(&directive);
+                })
+                .unwrap();
+            event();
+        });
+        assert_eq!(
+            *targets.0.lock().unwrap(),
+            vec![
+                "pinned::retarget_follows_the_filter",
+                "retarget_follows_the_filter"
+            ]
+        );
+    }
+}
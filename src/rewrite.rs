@@ -0,0 +1,306 @@
+use crate::LineFilter;
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock, PoisonError, RwLock,
+    },
+};
+use tracing_core::{
+    callsite::Identifier,
+    field::{self, Field, FieldSet, Value, Visit},
+    subscriber::Interest,
+    Event, Level, Metadata,
+};
+
+/// The rewritten metadata for each event callsite that a layer has
+/// rewritten.
+///
+/// Rewritten callsites are allocated once and never freed. They aren't
+/// registered with `tracing` or with the layers that are given rewritten
+/// events, since layers with per-layer filters expect callsites to be
+/// registered by the subscriber.
+pub(crate) struct Rewrites(RwLock<HashMap<Identifier, &'static Metadata<'static>>>);
+
+/// Whether a [`LineFilter`]'s entries enabled each event callsite, which
+/// the layers that rewrite matched events look up without locking the
+/// filter.
+///
+/// The filter records its decision when a callsite is registered (and
+/// registered again after the filter is modified), and each time it is
+/// asked whether an event is enabled.
+#[derive(Debug, Default)]
+pub(crate) struct EventMatches {
+    /// Whether a layer looks up the filter's decisions. Nothing is recorded
+    /// until one does.
+    watched: AtomicBool,
+    callsites: RwLock<HashMap<Identifier, bool>>,
+}
+
+/// How an event callsite's metadata is rewritten.
+pub(crate) struct Rewrite {
+    pub(crate) target: String,
+    pub(crate) level: Level,
+    /// The name of a field to add after the event's own fields.
+    pub(crate) extra_field: Option<&'static str>,
+}
+
+/// The callsite of a rewritten event, which is never registered with
+/// `tracing`.
+struct Callsite(OnceLock<&'static Metadata<'static>>);
+
+/// A field value copied out of an event.
+enum Copied {
+    I64(i64),
+    U64(u64),
+    I128(i128),
+    U128(u128),
+    F64(f64),
+    Bool(bool),
+    Str(String),
+    Debug(field::DebugValue<Formatted>),
+}
+
+/// A value that has already been formatted, which formats as itself.
+struct Formatted(String);
+
+/// Copies an event's field values, indexed by field.
+struct CopyVisitor(Vec<Option<Copied>>);
+
+// === impl EventMatches ===
+
+impl EventMatches {
+    /// Starts recording the filter's decisions.
+    pub(crate) fn watch(&self) {
+        self.watched.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if the filter's entries enabled the last event at
+    /// `metadata`'s callsite.
+    pub(crate) fn is_matched(&self, metadata: &Metadata<'_>) -> bool {
+        let callsites = self
+            .callsites
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        callsites.get(&metadata.callsite()) == Some(&true)
+    }
+}
+
+impl LineFilter {
+    /// Records whether this filter's entries enable the event with
+    /// `metadata`, if a layer looks up its decisions.
+    pub(crate) fn record_event_match(
+        &self,
+        metadata: &Metadata<'_>,
+        decide: impl FnOnce() -> Option<bool>,
+    ) {
+        let matches = &self.event_matches;
+        if !metadata.is_event() || !matches.watched.load(Ordering::Relaxed) {
+            return;
+        }
+        let matched = decide() == Some(true);
+        let callsite = metadata.callsite();
+        let callsites = matches
+            .callsites
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let changed = callsites.get(&callsite) != Some(&matched);
+        drop(callsites);
+        if changed {
+            matches
+                .callsites
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert(callsite, matched);
+        }
+    }
+}
+
+// === impl Rewrites ===
+
+impl Rewrites {
+    pub(crate) fn new() -> Self {
+        Self(RwLock::new(HashMap::new()))
+    }
+
+    /// Forgets every rewritten callsite, so that they are rewritten again.
+    pub(crate) fn clear(&mut self) {
+        self.0
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    /// Returns the rewritten metadata for `metadata`'s callsite, rewriting it
+    /// as described by `rewrite` if it hasn't been rewritten yet.
+    pub(crate) fn get(
+        &self,
+        metadata: &Metadata<'_>,
+        rewrite: impl FnOnce() -> Rewrite,
+    ) -> &'static Metadata<'static> {
+        let callsite = metadata.callsite();
+        let rewritten = self.0.read().unwrap_or_else(PoisonError::into_inner);
+        if let Some(&rewritten) = rewritten.get(&callsite) {
+            return rewritten;
+        }
+        drop(rewritten);
+
+        let mut rewritten = self.0.write().unwrap_or_else(PoisonError::into_inner);
+        if let Some(&rewritten) = rewritten.get(&callsite) {
+            return rewritten;
+        }
+        fn leak(s: &str) -> &'static str {
+            Box::leak(s.to_owned().into_boxed_str())
+        }
+        let rewrite = rewrite();
+        let mut names = metadata
+            .fields()
+            .iter()
+            .map(|field| field.name())
+            .collect::<Vec<_>>();
+        if let Some(extra) = rewrite.extra_field {
+            if !names.contains(&extra) {
+                names.push(extra);
+            }
+        }
+        let new_callsite: &'static Callsite = Box::leak(Box::new(Callsite(OnceLock::new())));
+        let fields = FieldSet::new(
+            Box::leak(names.into_boxed_slice()),
+            Identifier(new_callsite),
+        );
+        let new = Box::leak(Box::new(Metadata::new(
+            metadata.name(),
+            leak(&rewrite.target),
+            rewrite.level,
+            metadata.file().map(leak),
+            metadata.line(),
+            metadata.module_path().map(leak),
+            fields,
+            tracing_core::metadata::Kind::EVENT,
+        )));
+        let _ = new_callsite.0.set(new);
+        rewritten.insert(callsite, new);
+        new
+    }
+}
+
+/// Calls `f` with a copy of `event` that has the rewritten `metadata`.
+///
+/// The copy has the same parent and field values as `event`, although values
+/// other than numbers, booleans, and strings are formatted with `Debug`
+/// first. If `metadata` has an extra field, it is set to `extra`.
+pub(crate) fn with_copy<R>(
+    event: &Event<'_>,
+    metadata: &'static Metadata<'static>,
+    extra: Option<&dyn Value>,
+    f: impl FnOnce(&Event<'_>) -> R,
+) -> R {
+    let mut visitor = CopyVisitor((0..metadata.fields().len()).map(|_| None).collect());
+    event.record(&mut visitor);
+    let mut values = visitor
+        .0
+        .iter()
+        .map(|value| value.as_ref().map(Copied::as_value))
+        .collect::<Vec<_>>();
+    if metadata.fields().len() > event.metadata().fields().len() {
+        if let Some(slot) = values.last_mut() {
+            *slot = extra;
+        }
+    }
+    let values = metadata.fields().value_set_all(&values);
+    let copy = if event.is_root() {
+        Event::new_child_of(None, metadata, &values)
+    } else if let Some(parent) = event.parent() {
+        Event::new_child_of(parent.clone(), metadata, &values)
+    } else {
+        Event::new(metadata, &values)
+    };
+    f(&copy)
+}
+
+// === impl Callsite ===
+
+impl tracing_core::Callsite for Callsite {
+    fn set_interest(&self, _: Interest) {}
+
+    fn metadata(&self) -> &Metadata<'_> {
+        self.0
+            .get()
+            .expect("metadata is set when the callsite is created")
+    }
+}
+
+// === impl Copied ===
+
+impl Copied {
+    fn as_value(&self) -> &dyn Value {
+        match self {
+            Copied::I64(value) => value,
+            Copied::U64(value) => value,
+            Copied::I128(value) => value,
+            Copied::U128(value) => value,
+            Copied::F64(value) => value,
+            Copied::Bool(value) => value,
+            Copied::Str(value) => value,
+            Copied::Debug(value) => value,
+        }
+    }
+}
+
+impl fmt::Debug for Formatted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+// === impl CopyVisitor ===
+
+impl CopyVisitor {
+    fn set(&mut self, field: &Field, value: Copied) {
+        if let Some(slot) = self.0.get_mut(field.index()) {
+            *slot = Some(value);
+        }
+    }
+}
+
+impl Visit for CopyVisitor {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.set(field, Copied::I64(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.set(field, Copied::U64(value));
+    }
+
+    fn record_i128(&mut self, field: &Field, value: i128) {
+        self.set(field, Copied::I128(value));
+    }
+
+    fn record_u128(&mut self, field: &Field, value: u128) {
+        self.set(field, Copied::U128(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.set(field, Copied::F64(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.set(field, Copied::Bool(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.set(field, Copied::Str(value.to_owned()));
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn Error + 'static)) {
+        let formatted = Formatted(value.to_string());
+        self.set(field, Copied::Debug(field::debug(formatted)));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let formatted = Formatted(format!("{:?}", value));
+        self.set(field, Copied::Debug(field::debug(formatted)));
+    }
+}
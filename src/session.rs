@@ -0,0 +1,529 @@
+use crate::{
+    deny::Matched, overlay::Owner, time::Instant, validate_path, BadPath, Directive, LineFilter,
+};
+use std::{
+    error::Error,
+    fmt,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+use tracing_core::{Metadata, Subscriber};
+use tracing_subscriber::reload;
+
+/// A labeled set of entries added to a [`LineFilter`] to investigate
+/// something, such as an incident, returned by
+/// [`LineFilter::start_session`].
+///
+/// A session's entries are removed from the filter when the session is
+/// [ended](Self::end), and stop matching anything once its time to live (if
+/// it has one) passes. Dropping a `DebugSession` doesn't end it, so that a
+/// session with a time to live can be left to expire on its own.
+pub struct DebugSession<S> {
+    handle: reload::Handle<LineFilter, S>,
+    label: String,
+    id: u64,
+}
+
+/// Statistics about a [`DebugSession`], returned by [`DebugSession::stats`]
+/// and [`LineFilter::sessions`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SessionStats {
+    label: String,
+    directives: Vec<Directive>,
+    hits: u64,
+    elapsed: Duration,
+    expires_in: Option<Duration>,
+}
+
+/// Indicates a [`DebugSession`] could not be started.
+#[derive(Debug)]
+pub struct SessionError {
+    label: String,
+    kind: ErrorKind,
+}
+
+#[derive(Debug)]
+enum ErrorKind {
+    Started,
+    #[cfg(feature = "env-filter")]
+    Env,
+    Path(BadPath),
+    Dropped(reload::Error),
+}
+
+/// A debug session's state in the filter.
+#[derive(Debug)]
+pub(crate) struct Session {
+    /// Tells this session apart from later sessions with the same label.
+    id: u64,
+    directives: Vec<Directive>,
+    /// The directives that starting the session added to the filter.
+    added: Vec<Directive>,
+    started: Instant,
+    expires: Option<Instant>,
+    hits: AtomicU64,
+}
+
+impl LineFilter {
+    /// Starts a debug session labeled `label` in the filter behind a
+    /// [`reload`] handle, adding `directives` to the filter until the session
+    /// is ended or `ttl` passes.
+    ///
+    /// A debug session models the work of investigating one problem, such as
+    /// "incident 1234": the lines needed to investigate it are added
+    /// together, counted together, and removed together once the
+    /// investigation is over, without disturbing the filter's other
+    /// entries. Directives that were already in the filter when the session
+    /// started are left in place when it ends. Once `ttl` has passed, the
+    /// session's entries stop matching anything, like entries that have
+    /// [expired], and they are removed when the session is ended or another
+    /// session is started. A `ttl` too long to be represented as a deadline
+    /// never passes.
+    ///
+    /// The returned [`DebugSession`] ends the session and reports its
+    /// [`SessionStats`]. Each session counts the spans and events its entries
+    /// enabled.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if a session labeled `label` is already running
+    /// (sessions whose time to live has passed can be replaced), if
+    /// `directives` includes an [`EnvFilter`] directive, which can't be
+    /// removed when the session ends, if a `File` directive's path is not
+    /// valid for use in a `LineFilter`, or if the subscriber containing the
+    /// filter has been dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::{prelude::*, reload};
+    ///
+    /// let (filter, handle) = reload::Layer::new(LineFilter::default());
+    /// tracing_subscriber::registry().with(filter).init();
+    ///
+    /// let directive = format!("{}@{}", module_path!(), line!() + 11);
+    /// let session = LineFilter::start_session(
+    ///     &handle,
+    ///     "incident-1234",
+    ///     [directive.parse().unwrap()],
+    ///     Duration::from_secs(30 * 60),
+    /// )
+    /// .unwrap();
+    /// assert_eq!(session.label(), "incident-1234");
+    ///
+    /// for _ in 0..3 {
+    ///     tracing::debug!("investigating");
+    /// }
+    ///
+    /// let stats = session.end().unwrap();
+    /// assert_eq!(stats.hits(), 3);
+    /// assert_eq!(handle.with_current(|filter| filter.to_string()).unwrap(), "");
+    /// ```
+    ///
+    /// [`reload`]: tracing_subscriber::reload
+    /// [expired]: Self::expire_at
+    /// [`EnvFilter`]: tracing_subscriber::EnvFilter
+    pub fn start_session<S>(
+        handle: &reload::Handle<Self, S>,
+        label: impl Into<String>,
+        directives: impl IntoIterator<Item = Directive>,
+        ttl: impl Into<Option<Duration>>,
+    ) -> Result<DebugSession<S>, SessionError>
+    where
+        S: Subscriber,
+        Self: tracing_subscriber::Layer<S>,
+    {
+        let label = label.into();
+        let directives = directives.into_iter().collect::<Vec<_>>();
+        for directive in &directives {
+            check(&label, directive)?;
+        }
+        let ttl = ttl.into();
+
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let mut result = Ok(());
+        handle
+            .modify(|filter| {
+                let now = Instant::now();
+                let expired = filter
+                    .sessions
+                    .iter()
+                    .filter(|(_, session)| session.is_expired(now))
+                    .map(|(label, _)| label.clone())
+                    .collect::<Vec<_>>();
+                for label in expired {
+                    filter.end_session(&label);
+                }
+                if filter.sessions.contains_key(&label) {
+                    result = Err(SessionError::new(&label, ErrorKind::Started));
+                    return;
+                }
+
+                let added = filter.apply_overlay(Owner::Session(id), &directives);
+                let session = Session {
+                    id,
+                    directives: directives.clone(),
+                    added,
+                    started: now,
+                    expires: ttl.and_then(|ttl| now.checked_add(ttl)),
+                    hits: AtomicU64::new(0),
+                };
+                filter.sessions.insert(label.clone(), session);
+            })
+            .map_err(|e| SessionError::new(&label, ErrorKind::Dropped(e)))?;
+        result?;
+        Ok(DebugSession {
+            handle: handle.clone(),
+            label,
+            id,
+        })
+    }
+
+    /// Returns statistics about each of this filter's debug sessions, sorted
+    /// by label, including sessions whose time to live has passed but that
+    /// haven't been ended yet.
+    pub fn sessions(&self) -> Vec<SessionStats> {
+        self.sessions
+            .iter()
+            .map(|(label, session)| session.stats(label))
+            .collect()
+    }
+
+    /// Ends the session labeled `label`, removing the directives that
+    /// starting it added, unless an active profile or another session still
+    /// needs them.
+    fn end_session(&mut self, label: &str) -> Option<SessionStats> {
+        let session = self.sessions.remove(label)?;
+        self.remove_overlay(&Owner::Session(session.id));
+        Some(session.stats(label))
+    }
+
+    /// Returns `true` if any debug session has a time to live.
+    pub(crate) fn sessions_expire(&self) -> bool {
+        self.sessions
+            .values()
+            .any(|session| session.expires.is_some())
+    }
+
+    /// Returns `true` if `entry` was added by a debug session whose time to
+    /// live has passed, and isn't needed by another session.
+    pub(crate) fn is_session_expired(&self, entry: &Matched<'_>) -> bool {
+        if self.sessions.is_empty() {
+            return false;
+        }
+        let now = Instant::now();
+        let mut expired = false;
+        for session in self.sessions.values() {
+            if session.is_expired(now) {
+                expired |= session.added.iter().any(|directive| entry.is(directive));
+            } else if session
+                .directives
+                .iter()
+                .any(|directive| entry.is(directive))
+            {
+                return false;
+            }
+        }
+        expired
+    }
+
+    /// Counts a hit for each running debug session containing the entry that
+    /// enables `metadata`.
+    pub(crate) fn record_session_hit(&self, metadata: &Metadata<'_>) {
+        if self.sessions.is_empty() {
+            return;
+        }
+        // The entry that enabled `metadata` is found along with the
+        // decision, rather than matched again.
+        let entry = match self.decide_match(metadata) {
+            (_, Some(entry)) => entry,
+            (_, None) => return,
+        };
+        let now = Instant::now();
+        for session in self.sessions.values() {
+            if !session.is_expired(now)
+                && session
+                    .directives
+                    .iter()
+                    .any(|directive| entry.is(directive))
+            {
+                session.hits.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+// === impl DebugSession ===
+
+impl<S> DebugSession<S>
+where
+    S: Subscriber,
+    LineFilter: tracing_subscriber::Layer<S>,
+{
+    /// Returns the session's label.
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Returns statistics about the session, or `None` if it has been ended
+    /// (such as by starting another session with the same label after it
+    /// expired), or if the subscriber containing the filter has been
+    /// dropped.
+    pub fn stats(&self) -> Option<SessionStats> {
+        self.handle
+            .with_current(|filter| {
+                filter
+                    .sessions
+                    .get(&self.label)
+                    .filter(|session| session.id == self.id)
+                    .map(|session| session.stats(&self.label))
+            })
+            .ok()
+            .flatten()
+    }
+
+    /// Ends the session, removing the directives that starting it added to
+    /// the filter and returning its final statistics.
+    ///
+    /// Directives that an active profile or another running session also
+    /// contains are left in place. This returns `None` if the session has
+    /// already been ended, or if the subscriber containing the filter has
+    /// been dropped.
+    pub fn end(self) -> Option<SessionStats> {
+        let mut stats = None;
+        let _ = self.handle.modify(|filter| {
+            let current = filter.sessions.get(&self.label);
+            if current.is_some_and(|session| session.id == self.id) {
+                stats = filter.end_session(&self.label);
+            }
+        });
+        stats
+    }
+}
+
+impl<S> fmt::Debug for DebugSession<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DebugSession")
+            .field("label", &self.label)
+            .finish_non_exhaustive()
+    }
+}
+
+// === impl SessionStats ===
+
+impl SessionStats {
+    /// Returns the session's label.
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Returns the directives the session was started with.
+    pub fn directives(&self) -> &[Directive] {
+        &self.directives
+    }
+
+    /// Returns the number of spans and events that the session's entries
+    /// enabled.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Returns how long ago the session was started.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns how much of the session's time to live is left, or `None` if
+    /// it doesn't have one. This is zero once the session has expired.
+    pub fn expires_in(&self) -> Option<Duration> {
+        self.expires_in
+    }
+
+    /// Returns `true` if the session's time to live has passed.
+    pub fn is_expired(&self) -> bool {
+        self.expires_in == Some(Duration::ZERO)
+    }
+}
+
+// === impl Session ===
+
+impl Session {
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expires.is_some_and(|deadline| now >= deadline)
+    }
+
+    fn stats(&self, label: &str) -> SessionStats {
+        let now = Instant::now();
+        SessionStats {
+            label: label.to_owned(),
+            directives: self.directives.clone(),
+            hits: self.hits.load(Ordering::Relaxed),
+            elapsed: now.saturating_duration_since(self.started),
+            expires_in: self
+                .expires
+                .map(|deadline| deadline.saturating_duration_since(now)),
+        }
+    }
+}
+
+impl Clone for Session {
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id,
+            directives: self.directives.clone(),
+            added: self.added.clone(),
+            started: self.started,
+            expires: self.expires,
+            hits: AtomicU64::new(self.hits.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Checks that `directive` can be part of a debug session.
+fn check(label: &str, directive: &Directive) -> Result<(), SessionError> {
+    match directive {
+        #[cfg(feature = "env-filter")]
+        Directive::Env(_) => Err(SessionError::new(label, ErrorKind::Env)),
+        Directive::File { file, .. } => validate_path(Path::new(file))
+            .map(|_| ())
+            .map_err(|e| SessionError::new(label, ErrorKind::Path(e))),
+        Directive::Deny(denied) => check(label, denied),
+        _ => Ok(()),
+    }
+}
+
+// === impl SessionError ===
+
+impl SessionError {
+    fn new(label: &str, kind: ErrorKind) -> Self {
+        Self {
+            label: label.to_owned(),
+            kind,
+        }
+    }
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ErrorKind::Started => {
+                write!(f, "debug session '{}' is already running", self.label)
+            }
+            #[cfg(feature = "env-filter")]
+            ErrorKind::Env => write!(
+                f,
+                "debug session '{}' may not contain `EnvFilter` directives",
+                self.label
+            ),
+            ErrorKind::Path(ref e) => write!(f, "debug session '{}': {}", self.label, e),
+            ErrorKind::Dropped(_) => write!(
+                f,
+                "could not start debug session '{}': the subscriber was dropped",
+                self.label
+            ),
+        }
+    }
+}
+
+impl Error for SessionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self.kind {
+            ErrorKind::Path(ref e) => Some(e),
+            ErrorKind::Dropped(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::prelude::*;
+
+    fn directive(line: u32) -> Directive {
+        format!("{}@{}", module_path!(), line).parse().unwrap()
+    }
+
+    #[test]
+    fn expired_sessions_stop_matching() {
+        let event = || tracing::info!(target: "session_expired_sessions", "event");
+        let line = line!() - 1;
+        let (layer, handle) = reload::Layer::new(LineFilter::default());
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let session =
+                LineFilter::start_session(&handle, "expired", [directive(line)], Duration::ZERO)
+                    .unwrap();
+            event();
+            let stats = session.stats().unwrap();
+            assert!(stats.is_expired());
+            assert_eq!(stats.hits(), 0);
+
+            // An expired session can be replaced, which removes its entries.
+            let session =
+                LineFilter::start_session(&handle, "expired", [], Duration::from_secs(60)).unwrap();
+            assert_eq!(session.stats().unwrap().directives(), &[]);
+            let current = handle.with_current(|filter| filter.to_string()).unwrap();
+            assert_eq!(current, "");
+        });
+    }
+
+    #[test]
+    fn overlapping_sessions_count_their_own_entries() {
+        let first = || tracing::info!(target: "session_overlapping_sessions", "first");
+        let first_line = line!() - 1;
+        let second = || tracing::info!(target: "session_overlapping_sessions", "second");
+        let second_line = line!() - 1;
+        let (layer, handle) = reload::Layer::new(LineFilter::default());
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let both = [directive(first_line), directive(second_line)];
+            let wide = LineFilter::start_session(&handle, "wide", both, None).unwrap();
+            let narrow =
+                LineFilter::start_session(&handle, "narrow", [directive(second_line)], None)
+                    .unwrap();
+            first();
+            second();
+            second();
+            assert_eq!(wide.stats().unwrap().hits(), 3);
+            assert_eq!(narrow.stats().unwrap().hits(), 2);
+
+            // The entry that both sessions contain outlives the first to end.
+            assert_eq!(wide.end().unwrap().hits(), 3);
+            let current = handle.with_current(|filter| filter.to_string()).unwrap();
+            assert_eq!(current, directive(second_line).to_string());
+            first();
+            second();
+            assert_eq!(narrow.end().unwrap().hits(), 3);
+            let current = handle.with_current(|filter| filter.to_string()).unwrap();
+            assert_eq!(current, "");
+        });
+    }
+
+    #[test]
+    fn an_expired_session_keeps_entries_another_session_needs() {
+        let event = || tracing::info!(target: "session_expired_but_needed", "event");
+        let line = line!() - 1;
+        let (layer, handle) = reload::Layer::new(LineFilter::default());
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let running =
+                LineFilter::start_session(&handle, "running", [directive(line)], None).unwrap();
+            let expired =
+                LineFilter::start_session(&handle, "expired", [directive(line)], Duration::ZERO)
+                    .unwrap();
+            event();
+            // The entry is still enabled while a running session contains
+            // it, but it only counts for that session.
+            assert_eq!(running.stats().unwrap().hits(), 1);
+            assert_eq!(expired.stats().unwrap().hits(), 0);
+        });
+    }
+}
@@ -0,0 +1,444 @@
+use crate::{audit, decisions, flush, LineFilter};
+use tracing_core::{span, subscriber::Interest, Event, Metadata, Subscriber};
+use tracing_subscriber::layer::{self, Layer};
+
+/// A layer that combines several named [`LineFilter`]s, each with a
+/// priority, and lets whole members be switched on and off at runtime.
+///
+/// Larger dynamic logging systems often have several independent sources of
+/// entries: a "base" filter shipped with the program, an "incident" filter
+/// that an on-call engineer changes, and a filter for each tenant. A
+/// `LineFilterSet` keeps them apart, so that each member can be changed,
+/// replaced, or disabled without touching the others.
+///
+/// A `LineFilterSet` asks its enabled members about each span and event in
+/// order of priority, highest first (members with the same priority are
+/// asked in the order they were inserted). The first member with an entry
+/// or deny entry that matches decides whether the span or event is enabled,
+/// so a higher-priority member can deny what a lower-priority member
+/// enables. If no member's entries match, the first member with an
+/// [`EnvFilter`] or [fallback], or with a [subtree] that the span or event
+/// descends from, decides, and otherwise the span or event is disabled.
+/// Members that [normalize `log` records][log] decide about them in the same
+/// order, by their normalized metadata. Hit counts, [callbacks], and match
+/// records are recorded by the member that enabled each span or event.
+///
+/// Wrap a `LineFilterSet` in a [`reload::Layer`] to change it at runtime.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::{
+///     atomic::{AtomicUsize, Ordering},
+///     Arc,
+/// };
+/// use tracing_line_filter::{LineFilter, LineFilterSet};
+/// use tracing_subscriber::{prelude::*, reload};
+///
+/// let directive = format!("{}@{}", module_path!(), line!() + 1);
+/// let query = || tracing::debug!("running query");
+///
+/// // The base filter enables the query's event...
+/// let mut base: LineFilter = directive.parse().unwrap();
+/// let hits = Arc::new(AtomicUsize::new(0));
+/// let counter = hits.clone();
+/// base.on_match(directive.parse().unwrap(), move |_| {
+///     counter.fetch_add(1, Ordering::Relaxed);
+/// });
+///
+/// // ...but the incident filter, which has a higher priority, denies it.
+/// let incident: LineFilter = format!("!{}", directive).parse().unwrap();
+///
+/// let mut set = LineFilterSet::new();
+/// set.insert("base", 0, base).insert("incident", 10, incident);
+/// let (set, handle) = reload::Layer::new(set);
+/// tracing_subscriber::registry().with(set).init();
+///
+/// query();
+/// assert_eq!(hits.load(Ordering::Relaxed), 0);
+///
+/// // Once the incident is over, the base filter decides again.
+/// handle.modify(|set| {
+///     set.disable("incident");
+/// }).unwrap();
+/// query();
+/// assert_eq!(hits.load(Ordering::Relaxed), 1);
+/// ```
+///
+/// [`EnvFilter`]: https://docs.rs/tracing-subscriber/latest/tracing_subscriber/filter/struct.EnvFilter.html
+/// [fallback]: LineFilter::with_fallback
+/// [subtree]: LineFilter::enable_subtree
+/// [callbacks]: LineFilter::on_match
+/// [log]: https://docs.rs/tracing-line-filter/latest/tracing_line_filter/struct.LineFilter.html#method.normalize_log_metadata
+/// [`reload::Layer`]: tracing_subscriber::reload::Layer
+#[derive(Clone, Debug, Default)]
+pub struct LineFilterSet {
+    /// The members, in the order they are asked about spans and events.
+    members: Vec<Member>,
+}
+
+#[derive(Clone, Debug)]
+struct Member {
+    name: String,
+    priority: i32,
+    enabled: bool,
+    filter: LineFilter,
+}
+
+// === impl LineFilterSet ===
+
+impl LineFilterSet {
+    /// Returns a new `LineFilterSet` with no members.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `filter` to the set as an enabled member named `name`, which is
+    /// asked about spans and events before the members with lower
+    /// priorities.
+    ///
+    /// If the set already has a member named `name`, it is replaced.
+    pub fn insert(
+        &mut self,
+        name: impl Into<String>,
+        priority: i32,
+        filter: LineFilter,
+    ) -> &mut Self {
+        let name = name.into();
+        self.remove(&name);
+        // Members with the same priority are asked in the order they were
+        // inserted.
+        let i = self
+            .members
+            .iter()
+            .position(|member| member.priority < priority)
+            .unwrap_or(self.members.len());
+        self.members.insert(
+            i,
+            Member {
+                name,
+                priority,
+                enabled: true,
+                filter,
+            },
+        );
+        self
+    }
+
+    /// Removes the member named `name` from the set, returning its filter,
+    /// or `None` if the set has no such member.
+    pub fn remove(&mut self, name: &str) -> Option<LineFilter> {
+        let i = self.members.iter().position(|member| member.name == name)?;
+        Some(self.members.remove(i).filter)
+    }
+
+    /// Enables the member named `name`, returning `false` if the set has no
+    /// such member.
+    pub fn enable(&mut self, name: &str) -> bool {
+        self.set_enabled(name, true)
+    }
+
+    /// Disables the member named `name`, so that it isn't asked about spans
+    /// and events until it is enabled again, returning `false` if the set
+    /// has no such member.
+    pub fn disable(&mut self, name: &str) -> bool {
+        self.set_enabled(name, false)
+    }
+
+    /// Returns whether the member named `name` is enabled, or `None` if the
+    /// set has no such member.
+    pub fn is_enabled(&self, name: &str) -> Option<bool> {
+        self.member(name).map(|member| member.enabled)
+    }
+
+    /// Returns the filter of the member named `name`, if there is one.
+    pub fn get(&self, name: &str) -> Option<&LineFilter> {
+        self.member(name).map(|member| &member.filter)
+    }
+
+    /// Returns a mutable reference to the filter of the member named
+    /// `name`, if there is one.
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut LineFilter> {
+        self.members
+            .iter_mut()
+            .find(|member| member.name == name)
+            .map(|member| &mut member.filter)
+    }
+
+    /// Returns the names of the set's members, in the order they are asked
+    /// about spans and events.
+    pub fn names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.members.iter().map(|member| member.name.as_str())
+    }
+
+    fn member(&self, name: &str) -> Option<&Member> {
+        self.members.iter().find(|member| member.name == name)
+    }
+
+    fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        match self.members.iter_mut().find(|member| member.name == name) {
+            Some(member) => {
+                member.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn enabled_members(&self) -> impl Iterator<Item = &LineFilter> + '_ {
+        self.members
+            .iter()
+            .filter(|member| member.enabled)
+            .map(|member| &member.filter)
+    }
+
+    /// Returns the first enabled member whose entries match `metadata`,
+    /// along with whether they enable it.
+    fn decide(&self, metadata: &Metadata<'_>) -> Option<(&LineFilter, bool)> {
+        self.enabled_members()
+            .find_map(|filter| filter.decide(metadata).map(|enabled| (filter, enabled)))
+    }
+
+    /// Returns the member that enabled `metadata` with one of its entries,
+    /// if there is one.
+    fn matching(&self, metadata: &Metadata<'_>) -> Option<&LineFilter> {
+        match self.decide(metadata) {
+            Some((filter, true)) => Some(filter),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if an enabled member checks the `log` record with
+    /// `metadata` against its normalized metadata.
+    #[cfg(feature = "log")]
+    fn normalizes_log(&self, metadata: &Metadata<'_>) -> bool {
+        self.enabled_members()
+            .any(|filter| filter.is_normalized_log(metadata))
+    }
+
+    /// Returns the member that decides whether the `log` record `event` is
+    /// enabled, if it is a `log` record that an enabled member normalizes:
+    /// the first of those members whose entries match its normalized
+    /// metadata, or else the first with a fallback, or `Some(None)` if none
+    /// of them decide.
+    #[cfg(feature = "log")]
+    fn log_decider(&self, event: &Event<'_>) -> Option<Option<&LineFilter>> {
+        use tracing_log::NormalizeEvent;
+
+        if !self.normalizes_log(event.metadata()) {
+            return None;
+        }
+        let metadata = event.normalized_metadata()?;
+        let normalizing = || {
+            self.enabled_members()
+                .filter(|filter| filter.is_normalized_log(event.metadata()))
+        };
+        let decider = normalizing()
+            .find(|filter| filter.decide(&metadata).is_some())
+            .or_else(|| normalizing().find(|filter| filter.fallback.is_some()));
+        Some(decider)
+    }
+}
+
+impl<S: Subscriber> Layer<S> for LineFilterSet {
+    fn on_register_dispatch(&self, subscriber: &tracing_core::Dispatch) {
+        for member in &self.members {
+            Layer::<S>::on_register_dispatch(&member.filter, subscriber);
+        }
+    }
+
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        // Let every member record and count the callsite, even if it won't
+        // decide about it.
+        let mut sometimes = false;
+        for member in &self.members {
+            let interest = member.filter.callsite_interest(metadata);
+            sometimes |= member.enabled && interest.is_sometimes();
+        }
+        if metadata.target() == audit::TARGET || metadata.target() == flush::TARGET {
+            return Interest::always();
+        }
+        if sometimes {
+            return Interest::sometimes();
+        }
+
+        match self.decide(metadata) {
+            Some((_, true)) => Interest::always(),
+            Some((_, false)) => Interest::never(),
+            None => self
+                .enabled_members()
+                .find_map(|filter| filter.fallback.as_ref())
+                .map(|fallback| fallback.register_callsite(metadata))
+                .unwrap_or_else(Interest::never),
+        }
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, cx: layer::Context<'_, S>) -> bool {
+        if decisions::is_recording() {
+            return metadata.target() == decisions::TARGET;
+        }
+        if metadata.target() == audit::TARGET || metadata.target() == flush::TARGET {
+            return true;
+        }
+
+        // `log` records are filtered in `event_enabled`, once their
+        // normalized metadata is available.
+        #[cfg(feature = "log")]
+        if self.normalizes_log(metadata) {
+            return true;
+        }
+
+        match self.decide(metadata) {
+            Some((filter, enabled)) => {
+                filter.trace_decision(metadata, enabled);
+                enabled
+            }
+            None => self
+                .enabled_members()
+                .find(|filter| filter.fallback.is_some() || filter.is_in_subtree(&cx))
+                .is_some_and(|filter| filter.is_enabled(metadata, cx)),
+        }
+    }
+
+    fn event_enabled(&self, event: &Event<'_>, cx: layer::Context<'_, S>) -> bool {
+        #[cfg(feature = "log")]
+        if let Some(decider) = self.log_decider(event) {
+            return decider.is_some_and(|filter| filter.is_event_enabled(event, cx));
+        }
+        // Other events were decided by `enabled`, but every member still
+        // marks them and records their callsites, in order of priority.
+        for filter in self.enabled_members() {
+            filter.is_event_enabled(event, cx.clone());
+        }
+        true
+    }
+
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, cx: layer::Context<'_, S>) {
+        // Let every member remember the spans its conditions and subtrees
+        // point at, even if another member enabled them.
+        for member in &self.members {
+            member.filter.span_condition_created(attrs.metadata(), id);
+            member.filter.subtree_span_created(attrs, id, &cx);
+        }
+        if let Some(filter) = self.matching(attrs.metadata()) {
+            filter.span_created(attrs, id);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, _: layer::Context<'_, S>) {
+        if let Some(filter) = self.matching(event.metadata()) {
+            filter.matched(event.metadata());
+        }
+    }
+
+    fn on_enter(&self, id: &span::Id, _: layer::Context<'_, S>) {
+        for member in &self.members {
+            member.filter.span_condition_entered(id);
+        }
+    }
+
+    fn on_exit(&self, id: &span::Id, _: layer::Context<'_, S>) {
+        for member in &self.members {
+            member.filter.span_condition_exited(id);
+        }
+    }
+
+    fn on_close(&self, id: span::Id, _: layer::Context<'_, S>) {
+        for member in &self.members {
+            member.filter.span_condition_closed(&id);
+            member.filter.subtree_span_closed(&id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::{prelude::*, reload};
+
+    #[test]
+    fn members_see_enabled_events() {
+        let line = line!() + 1;
+        let query = || tracing::info!("running query");
+
+        let mut base: LineFilter = format!("{}@{}", module_path!(), line).parse().unwrap();
+        base.record_allowed();
+        let mut audit = LineFilter::default();
+        audit.record_allowed();
+        let mut set = LineFilterSet::new();
+        set.insert("base", 0, base).insert("audit", 10, audit);
+        let (set, handle) = reload::Layer::new(set);
+        let _guard = tracing_subscriber::registry().with(set).set_default();
+
+        query();
+        handle
+            .with_current(|set| {
+                for name in ["base", "audit"] {
+                    let allowed = set.get(name).unwrap().allowed_callsites();
+                    assert_eq!(allowed.len(), 1, "{}", name);
+                    assert_eq!(allowed[0].line(), Some(line));
+                }
+            })
+            .unwrap();
+    }
+
+    #[cfg(feature = "log")]
+    mod log {
+        use super::*;
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        const LINE: u32 = 42;
+
+        struct Count(Arc<AtomicUsize>);
+
+        impl<S: Subscriber> Layer<S> for Count {
+            fn on_event(&self, _: &Event<'_>, _: layer::Context<'_, S>) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        fn normalizing(config: &str) -> LineFilter {
+            let mut filter: LineFilter = config.parse().unwrap();
+            filter.normalize_log_metadata();
+            filter
+        }
+
+        fn records(set: LineFilterSet) -> usize {
+            let count = Arc::new(AtomicUsize::new(0));
+            let subscriber = tracing_subscriber::registry()
+                .with(set)
+                .with(Count(count.clone()));
+            tracing::subscriber::with_default(subscriber, || {
+                let record = ::log::Record::builder()
+                    .level(::log::Level::Debug)
+                    .target("legacy")
+                    .module_path_static(Some(module_path!()))
+                    .file_static(Some(file!()))
+                    .line(Some(LINE))
+                    .args(format_args!("running query"))
+                    .build();
+                tracing_log::format_trace(&record).unwrap();
+            });
+            count.load(Ordering::Relaxed)
+        }
+
+        #[test]
+        fn first_matching_member_decides() {
+            let directive = format!("{}@{}", module_path!(), LINE);
+
+            let mut set = LineFilterSet::new();
+            set.insert("base", 0, normalizing(&directive))
+                .insert("quiet", 10, normalizing(""));
+            assert_eq!(records(set.clone()), 1);
+
+            set.insert("incident", 20, normalizing(&format!("!{}", directive)));
+            assert_eq!(records(set.clone()), 0);
+
+            set.disable("incident");
+            assert_eq!(records(set), 1);
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use crate::{audit, LineFilter};
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
+
+/// A value shared between clones of a [`LineFilter`], which is copied the
+/// first time it is changed through a clone that shares it.
+///
+/// [`LineFilter`]: crate::LineFilter
+#[derive(Default)]
+pub(crate) struct Shared<T>(Arc<T>);
+
+// === impl Shared ===
+
+impl<T> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Deref for Shared<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Clone> DerefMut for Shared<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        Arc::make_mut(&mut self.0)
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Shared<T>
+where
+    &'a T: IntoIterator,
+{
+    type Item = <&'a T as IntoIterator>::Item;
+    type IntoIter = <&'a T as IntoIterator>::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.as_ref().into_iter()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Shared<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+// === impl LineFilter ===
+
+impl Clone for LineFilter {
+    fn clone(&self) -> Self {
+        Self {
+            by_module: self.by_module.clone(),
+            by_file: self.by_file.clone(),
+            mod_levels: self.mod_levels.clone(),
+            mod_prefixes: self.mod_prefixes.clone(),
+            crate_files: self.crate_files.clone(),
+            instrumented: self.instrumented.clone(),
+            deny: self.deny.clone(),
+            precedence: self.precedence,
+            profiles: self.profiles.clone(),
+            sessions: self.sessions.clone(),
+            startup: self.startup.clone(),
+            overlays: self.overlays.clone(),
+            hashes: self.hashes.clone(),
+            levels: self.levels.clone(),
+            fallback: self.fallback.clone(),
+            expires: self.expires,
+            disabled: self.disabled,
+            marking: self.marking,
+            lossy_paths: self.lossy_paths,
+            validation: self.validation,
+            span_fields: self.span_fields.clone(),
+            notes: self.notes.clone(),
+            module_map: self.module_map.clone(),
+            span_conditions: self.span_conditions.clone(),
+            subtrees: self.subtrees.clone(),
+            lru: self.lru.clone(),
+            // Callbacks can't be cloned, and the rest describes what the
+            // filter has seen since it was attached to a subscriber.
+            alerts: Default::default(),
+            recorder: self.recorder.as_ref().map(|_| Default::default()),
+            allowed: self.allowed.as_ref().map(|_| Default::default()),
+            command_limit: self.command_limit.as_ref().map(audit::RateLimit::unused),
+            read_only: self.read_only,
+            last_reload: self.last_reload.clone(),
+            published: self.published.clone(),
+            hits: self.hits.unrecorded(),
+            trace_decisions: self.trace_decisions,
+            announce: self.announce.clone(),
+            overhead: self.overhead.unmeasured(),
+            #[cfg(feature = "log")]
+            normalize_log: self.normalize_log,
+            #[cfg(feature = "metrics")]
+            metrics: Default::default(),
+            #[cfg(feature = "futures")]
+            changes: Default::default(),
+            #[cfg(feature = "futures")]
+            matches: Default::default(),
+            #[cfg(feature = "futures")]
+            expiry_notified: Default::default(),
+            #[cfg(feature = "otel")]
+            pins: Default::default(),
+            event_matches: Default::default(),
+        }
+    }
+}
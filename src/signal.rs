@@ -0,0 +1,192 @@
+use crate::{audit, publish, LineFilter};
+use std::{
+    io::{self, Read, Write},
+    os::unix::net::UnixStream,
+    thread,
+};
+use tracing_core::Subscriber;
+use tracing_subscriber::reload;
+
+/// What a [`LineFilter`] does with its profiles when the process receives
+/// `SIGUSR2`. See [`LineFilter::on_sigusr2`] for details.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Sigusr2 {
+    /// Switch to the next profile, using [`LineFilter::cycle_profiles`].
+    Cycle,
+    /// Activate the named profile if it isn't active, or deactivate it if it
+    /// is.
+    Toggle(String),
+}
+
+impl LineFilter {
+    /// Changes the profiles of the filter behind a [`reload`] handle each
+    /// time the process receives a `SIGUSR2` signal.
+    ///
+    /// This gives operators a way to escalate a program's logging on a
+    /// machine where they can do nothing but send it a signal (such as with
+    /// `kill -USR2 <PID>`). With [`Sigusr2::Cycle`], each signal switches to
+    /// the next of the filter's profiles (see [`define_profile`]), and then
+    /// back to no profile; with [`Sigusr2::Toggle`], each signal switches a
+    /// single profile on or off.
+    ///
+    /// The signal handler only wakes a background thread, which changes the
+    /// filter inside [`Handle::modify`] and records an audit event with the
+    /// `signal` transport (see [`Command::apply`]). If the subscriber
+    /// containing the filter has been dropped, the next signal stops the
+    /// thread and removes the handler. Signals received while a change is
+    /// being made may be combined into one change.
+    ///
+    /// This method is only available on Unix, when the `signal` feature flag
+    /// is enabled.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the signal handler or its thread can't be
+    /// set up.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use tracing_line_filter::{LineFilter, Sigusr2};
+    /// use tracing_subscriber::{prelude::*, reload};
+    ///
+    /// let config = r#"
+    /// warn
+    ///
+    /// [profile.db-debug]
+    /// my_crate::db@debug
+    ///
+    /// [profile.incident]
+    /// my_crate::**
+    /// "#;
+    /// let (filter, handle) = reload::Layer::new(config.parse::<LineFilter>().unwrap());
+    /// tracing_subscriber::registry()
+    ///     .with(tracing_subscriber::fmt::layer())
+    ///     .with(filter)
+    ///     .init();
+    ///
+    /// // `kill -USR2` now switches from `warn` to `db-debug`, then to
+    /// // `incident`, and then back.
+    /// LineFilter::on_sigusr2(handle, Sigusr2::Cycle).unwrap();
+    /// ```
+    ///
+    /// [`reload`]: tracing_subscriber::reload
+    /// [`define_profile`]: Self::define_profile
+    /// [`Handle::modify`]: tracing_subscriber::reload::Handle::modify
+    /// [`Command::apply`]: crate::Command::apply
+    pub fn on_sigusr2<S>(handle: reload::Handle<Self, S>, action: Sigusr2) -> io::Result<()>
+    where
+        S: Subscriber + 'static,
+    {
+        let (mut rx, tx) = UnixStream::pair()?;
+        // The handler must never block, so if the thread is behind on
+        // signals, extra signals are dropped.
+        tx.set_nonblocking(true)?;
+        // Safety: the handler only calls `write`, which is async-signal-safe,
+        // and doesn't allocate or take locks.
+        let id = unsafe {
+            signal_hook_registry::register(libc::SIGUSR2, move || {
+                let _ = (&tx).write(&[0]);
+            })?
+        };
+
+        let spawned = thread::Builder::new()
+            .name("tracing-line-filter-sigusr2".to_owned())
+            .spawn(move || {
+                let mut buf = [0; 16];
+                while matches!(rx.read(&mut buf), Ok(n) if n > 0) {
+                    if !apply(&handle, &action) {
+                        // The subscriber was dropped.
+                        break;
+                    }
+                }
+                signal_hook_registry::unregister(id);
+            });
+        if let Err(error) = spawned {
+            signal_hook_registry::unregister(id);
+            return Err(error);
+        }
+        Ok(())
+    }
+}
+
+/// Applies `action` to the filter behind `handle`, returning `false` if the
+/// subscriber containing it was dropped.
+fn apply<S>(handle: &reload::Handle<LineFilter, S>, action: &Sigusr2) -> bool
+where
+    S: Subscriber,
+{
+    let mut change = String::new();
+    let mut error = None;
+    let mut entries = (0, 0);
+    let mut evicted = Vec::new();
+    let modified = handle.modify(|filter| {
+        entries.0 = filter.entry_count();
+        let before = active(filter);
+        match action {
+            Sigusr2::Cycle => {
+                filter.cycle_profiles();
+            }
+            Sigusr2::Toggle(name) if filter.is_profile_active(name) => {
+                filter.deactivate_profile(name).expect("profile is defined");
+            }
+            Sigusr2::Toggle(name) => {
+                error = filter.activate_profile(name).err();
+            }
+        }
+        let after = active(filter);
+        change = before
+            .iter()
+            .filter(|name| !after.contains(name))
+            .map(|name| format!("deactivate {}", name))
+            .chain(
+                after
+                    .iter()
+                    .filter(|name| !before.contains(name))
+                    .map(|name| format!("activate {}", name)),
+            )
+            .collect::<Vec<_>>()
+            .join(", ");
+        #[cfg(feature = "metrics")]
+        if !change.is_empty() {
+            filter.metrics.record_reload(filter);
+        }
+        entries.1 = filter.entry_count();
+        evicted = filter.take_evicted();
+    });
+    if modified.is_err() {
+        return false;
+    }
+
+    // Record events outside of `modify`, since the filter can't filter them
+    // until the modification is done.
+    if let Some(error) = error {
+        tracing::warn!(
+            target: "tracing_line_filter",
+            %error,
+            "ignoring SIGUSR2",
+        );
+    }
+    if !change.is_empty() {
+        audit::Change {
+            transport: "signal",
+            peer: None,
+            change: &change,
+            entries_before: entries.0,
+            entries_after: entries.1,
+            evicted: &evicted,
+        }
+        .record();
+        publish::publish(handle, "signal");
+    }
+    true
+}
+
+/// Returns the names of the filter's active profiles.
+fn active(filter: &LineFilter) -> Vec<String> {
+    filter
+        .profiles()
+        .filter(|&(_, active)| active)
+        .map(|(name, _)| name.to_owned())
+        .collect()
+}
@@ -0,0 +1,289 @@
+use crate::{Explanation, LineFilter};
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Mutex, OnceLock},
+};
+use tracing_core::{
+    callsite::Identifier, field::FieldSet, metadata::Kind, subscriber::Interest, Level, Metadata,
+};
+
+/// Describes a span or event callsite that a [`LineFilter`] can be asked
+/// about without running the code that contains it, for use with
+/// [`LineFilter::simulate`].
+///
+/// When the `source` feature flag is enabled, the [callsites] found by
+/// scanning a crate's source code can be converted into `CallsiteDesc`s.
+/// Callsites whose level couldn't be determined are described as `TRACE`
+/// callsites, since a filter that enables a `TRACE` callsite enables it at
+/// every level.
+///
+/// [callsites]: https://docs.rs/tracing-line-filter/latest/tracing_line_filter/source/struct.Callsite.html
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CallsiteDesc {
+    module_path: String,
+    file: Option<String>,
+    line: u32,
+    level: Level,
+    is_span: bool,
+}
+
+/// Which of a list of callsites a [`LineFilter`] would enable, returned by
+/// [`LineFilter::simulate`].
+///
+/// The `Display` implementation lists each callsite on its own line, along
+/// with the [`Explanation`] of why it is enabled or disabled, such as
+/// ``my_crate::db@142 (INFO event): enabled by `my_crate::db@142` ``.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SimulationReport {
+    results: Vec<(CallsiteDesc, Explanation)>,
+}
+
+/// The callsite that simulated metadata points at.
+struct Simulated;
+
+static SIMULATED: Simulated = Simulated;
+
+static SIMULATED_META: Metadata<'static> = tracing_core::metadata! {
+    name: "simulated",
+    target: module_path!(),
+    level: Level::TRACE,
+    fields: &[],
+    callsite: &SIMULATED,
+    kind: Kind::EVENT,
+};
+
+impl LineFilter {
+    /// Reports which of `callsites` this filter would enable, without
+    /// running the program that contains them.
+    ///
+    /// Each callsite is checked as if by [`explain`](Self::explain), so the
+    /// filter's entries, deny entries, and [`EnvFilter`] are all considered.
+    /// Combined with the callsites found by scanning a crate's source code,
+    /// this lets CI check that a filter config enables the spans and events
+    /// it should, and no others.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing::Level;
+    /// use tracing_line_filter::{CallsiteDesc, LineFilter};
+    ///
+    /// let filter: LineFilter = "my_crate::db@142, my_crate::auth@warn".parse().unwrap();
+    /// let report = filter.simulate(vec![
+    ///     CallsiteDesc::event("my_crate::db", 142, Level::DEBUG),
+    ///     CallsiteDesc::event("my_crate::db", 150, Level::DEBUG),
+    ///     CallsiteDesc::span("my_crate::auth", 17, Level::INFO),
+    ///     CallsiteDesc::event("my_crate::auth", 30, Level::ERROR),
+    /// ]);
+    ///
+    /// let enabled = report.enabled().map(ToString::to_string).collect::<Vec<_>>();
+    /// assert_eq!(enabled, ["my_crate::db@142", "my_crate::auth@30"]);
+    /// assert_eq!(report.disabled().count(), 2);
+    /// ```
+    ///
+    /// [`EnvFilter`]: https://docs.rs/tracing-subscriber/latest/tracing_subscriber/filter/struct.EnvFilter.html
+    pub fn simulate(&self, callsites: impl IntoIterator<Item = CallsiteDesc>) -> SimulationReport {
+        let results = callsites
+            .into_iter()
+            .map(|callsite| {
+                let explanation = self.explain(callsite.metadata());
+                (callsite, explanation)
+            })
+            .collect();
+        SimulationReport { results }
+    }
+}
+
+// === impl CallsiteDesc ===
+
+impl CallsiteDesc {
+    /// Describes an event at `level` on line `line` of the module at
+    /// `module_path`.
+    pub fn event(module_path: impl Into<String>, line: u32, level: Level) -> Self {
+        Self {
+            module_path: module_path.into(),
+            file: None,
+            line,
+            level,
+            is_span: false,
+        }
+    }
+
+    /// Describes a span at `level` on line `line` of the module at
+    /// `module_path`.
+    pub fn span(module_path: impl Into<String>, line: u32, level: Level) -> Self {
+        Self {
+            is_span: true,
+            ..Self::event(module_path, line, level)
+        }
+    }
+
+    /// Sets the path of the file containing this callsite, as reported by
+    /// [`std::file!()`], so that `file@line` entries can match it.
+    pub fn with_file(&mut self, file: impl Into<String>) -> &mut Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    /// Returns the path of the module containing this callsite.
+    pub fn module_path(&self) -> &str {
+        &self.module_path
+    }
+
+    /// Returns the path of the file containing this callsite, if it is
+    /// known.
+    pub fn file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+
+    /// Returns the line number of this callsite.
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
+    /// Returns the callsite's level.
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    /// Returns `true` if this callsite is a span, or `false` if it is an
+    /// event.
+    pub fn is_span(&self) -> bool {
+        self.is_span
+    }
+
+    /// Returns metadata for this callsite.
+    ///
+    /// [`LineFilter::explain`] only accepts `'static` metadata, so each
+    /// callsite's metadata is leaked the first time it is needed, and reused
+    /// after that.
+    pub(crate) fn metadata(&self) -> &'static Metadata<'static> {
+        static METADATA: OnceLock<Mutex<HashMap<CallsiteDesc, &'static Metadata<'static>>>> =
+            OnceLock::new();
+        let mut metadata = METADATA
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        metadata.entry(self.clone()).or_insert_with(|| {
+            let module: &'static str = Box::leak(self.module_path.clone().into_boxed_str());
+            let file = self
+                .file
+                .as_ref()
+                .map(|file| &*Box::leak(file.clone().into_boxed_str()));
+            Box::leak(Box::new(Metadata::new(
+                "simulated",
+                module,
+                self.level,
+                file,
+                Some(self.line),
+                Some(module),
+                FieldSet::new(&[], Identifier(&SIMULATED)),
+                if self.is_span {
+                    Kind::SPAN
+                } else {
+                    Kind::EVENT
+                },
+            )))
+        })
+    }
+}
+
+impl fmt::Display for CallsiteDesc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.module_path, self.line)
+    }
+}
+
+#[cfg(feature = "source")]
+impl From<&crate::source::Callsite> for CallsiteDesc {
+    fn from(callsite: &crate::source::Callsite) -> Self {
+        let level = callsite.level().unwrap_or(Level::TRACE);
+        let mut desc = match callsite.kind() {
+            crate::source::CallsiteKind::Span => {
+                Self::span(callsite.module_path(), callsite.line(), level)
+            }
+            crate::source::CallsiteKind::Event => {
+                Self::event(callsite.module_path(), callsite.line(), level)
+            }
+        };
+        desc.with_file(callsite.file().to_string_lossy());
+        desc
+    }
+}
+
+#[cfg(feature = "source")]
+impl From<crate::source::Callsite> for CallsiteDesc {
+    fn from(callsite: crate::source::Callsite) -> Self {
+        Self::from(&callsite)
+    }
+}
+
+// === impl SimulationReport ===
+
+impl SimulationReport {
+    /// Returns each callsite, along with the explanation of why the filter
+    /// would enable or disable it, in the order they were provided.
+    pub fn iter(&self) -> impl Iterator<Item = (&CallsiteDesc, &Explanation)> + '_ {
+        self.results
+            .iter()
+            .map(|(callsite, explanation)| (callsite, explanation))
+    }
+
+    /// Returns the callsites that the filter would always enable.
+    pub fn enabled(&self) -> impl Iterator<Item = &CallsiteDesc> + '_ {
+        self.iter()
+            .filter(|(_, explanation)| explanation.is_enabled())
+            .map(|(callsite, _)| callsite)
+    }
+
+    /// Returns the callsites that the filter would not always enable.
+    ///
+    /// This includes callsites that the filter's `EnvFilter` only enables in
+    /// some spans or with some field values. Their [`Explanation::env`] is
+    /// [`EnvDecision::Dynamic`].
+    ///
+    /// [`EnvDecision::Dynamic`]: crate::EnvDecision::Dynamic
+    pub fn disabled(&self) -> impl Iterator<Item = &CallsiteDesc> + '_ {
+        self.iter()
+            .filter(|(_, explanation)| !explanation.is_enabled())
+            .map(|(callsite, _)| callsite)
+    }
+
+    /// Returns the number of callsites in the report.
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    /// Returns `true` if the report has no callsites.
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+}
+
+impl fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (callsite, explanation)) in self.results.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            write!(
+                f,
+                "{} ({} {}): {}",
+                callsite,
+                callsite.level,
+                if callsite.is_span { "span" } else { "event" },
+                explanation,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl tracing_core::Callsite for Simulated {
+    fn set_interest(&self, _: Interest) {}
+
+    fn metadata(&self) -> &Metadata<'_> {
+        &SIMULATED_META
+    }
+}
@@ -0,0 +1,623 @@
+//! Discovering `tracing` callsites by scanning Rust source code.
+//!
+//! When enabling spans and events by line number, the hardest part is often
+//! figuring out *which* line numbers to enable. This module provides a simple
+//! scanner that parses a crate's source code and finds every `tracing` span
+//! and event macro invocation (and `#[instrument]` attribute), along with the
+//! module path, file, and line that a [`LineFilter`] would match them by.
+//!
+//! The [`LineFilter::validate_sources`] method uses this scanner to check
+//! that a filter's entries actually point at spans and events,
+//! [`LineFilter::enable_fn_at`] uses it to enable every span and event in a
+//! function, and [`reanchor`] uses it to update a config file's line numbers
+//! after the source code changes. Scanned [`Callsite`]s can also be passed
+//! to [`LineFilter::simulate`], to check which of them a filter enables
+//! without running the program.
+//!
+//! Since this requires parsing Rust source code, it is only available when
+//! the `source` feature flag is enabled.
+//!
+//! [`LineFilter`]: crate::LineFilter
+use proc_macro2::{Delimiter, Spacing, TokenStream, TokenTree};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+use syn::visit::{self, Visit};
+use tracing_core::Level;
+
+mod extent;
+mod reanchor;
+mod validate;
+mod workspace;
+
+pub use self::reanchor::{reanchor, Reanchor, Reanchored};
+pub use self::validate::{warn_drifted, Finding, FindingKind};
+pub use self::workspace::{scan_module_map, scan_workspace};
+
+/// A span or event callsite discovered in Rust source code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Callsite {
+    kind: CallsiteKind,
+    level: Option<Level>,
+    message: Option<String>,
+    module_path: String,
+    file: PathBuf,
+    line: u32,
+}
+
+/// Whether a [`Callsite`] is a span or an event.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CallsiteKind {
+    /// The callsite is a span.
+    Span,
+    /// The callsite is an event.
+    Event,
+}
+
+/// An error encountered while scanning a crate's source code.
+#[derive(Debug)]
+pub struct ScanError {
+    path: PathBuf,
+    kind: ScanErrorKind,
+}
+
+#[derive(Debug)]
+enum ScanErrorKind {
+    Io(io::Error),
+    Parse(syn::Error),
+    Manifest(toml::de::Error),
+}
+
+/// Scans the crate named `crate_name` whose root source file is `root`, and
+/// returns every `tracing` callsite found in the crate.
+///
+/// Scanning starts at the crate root (such as `src/lib.rs` or `src/main.rs`)
+/// and follows `mod` declarations into other files, the same way the
+/// compiler does. This determines the module path of each callsite. Module
+/// declarations whose files cannot be found (such as modules for other
+/// platforms or generated code) are skipped.
+///
+/// File paths in the returned [`Callsite`]s are formed by joining `root`
+/// with module file names. To match the file paths emitted by
+/// [`std::file!()`], `root` should be relative to the workspace root when
+/// scanning workspace crates, or absolute for crates outside the workspace.
+///
+/// # Limitations
+///
+/// Callsites are found syntactically, so any invocation of a macro named like
+/// a `tracing` macro (`info!`, `span!`, and so on) is considered a callsite,
+/// even if it refers to a different macro. Callsites generated by other
+/// macros are only found if they are invoked directly in the macro's input.
+/// Items excluded by `#[cfg]` attributes are still scanned.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_line_filter::source::{self, CallsiteKind};
+///
+/// let callsites = source::scan_crate("tracing_line_filter", "src/lib.rs")
+///     .expect("scanning should succeed");
+///
+/// for callsite in &callsites {
+///     println!("{}@{}", callsite.module_path(), callsite.line());
+/// }
+/// ```
+pub fn scan_crate(crate_name: &str, root: impl AsRef<Path>) -> Result<Vec<Callsite>, ScanError> {
+    let mut callsites = Vec::new();
+    Scan::new(Path::new("")).crate_root(crate_name, root.as_ref(), &mut callsites)?;
+    Ok(callsites)
+}
+
+/// Returns a hash of a line of source code, for use in directives like
+/// `my_crate::db@142:1a2b3c4d`.
+///
+/// Leading and trailing whitespace is ignored, so re-indenting a line does
+/// not change its hash. The hash is 32-bit FNV-1a, so it is the same on
+/// every platform and in every version of this crate. It is written in
+/// directives as 8 hexadecimal digits.
+///
+/// See [`LineFilter::validate_sources`] for how line hashes are checked.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_line_filter::source;
+///
+/// let hash = source::line_hash(r#"tracing::info!("hello world");"#);
+/// assert_eq!(hash, source::line_hash(r#"    tracing::info!("hello world");"#));
+///
+/// let directive = format!("my_crate@25:{:08x}", hash);
+/// ```
+///
+/// [`LineFilter::validate_sources`]: crate::LineFilter::validate_sources
+pub fn line_hash(line: &str) -> u32 {
+    line.trim().bytes().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// Options for a scan.
+#[derive(Copy, Clone)]
+struct Scan<'a> {
+    /// The directory that file paths are relative to.
+    base: &'a Path,
+    /// Whether to follow `mod` declarations into other files.
+    follow_modules: bool,
+}
+
+/// A cache of the lines of the source files in a directory.
+struct Files {
+    root: PathBuf,
+    files: HashMap<PathBuf, Option<Vec<String>>>,
+}
+
+struct Visitor<'a> {
+    scan: Scan<'a>,
+    file: &'a Path,
+    module: Vec<String>,
+    /// The directory in which the current module's child modules live.
+    dir: PathBuf,
+    callsites: &'a mut Vec<Callsite>,
+    error: Option<ScanError>,
+}
+
+// === impl Callsite ===
+
+impl Callsite {
+    /// Returns whether this callsite is a span or an event.
+    pub fn kind(&self) -> CallsiteKind {
+        self.kind
+    }
+
+    /// Returns the callsite's level, if it could be determined.
+    ///
+    /// This is `None` if the level is not a constant (e.g. an `event!` whose
+    /// level is a variable).
+    pub fn level(&self) -> Option<Level> {
+        self.level
+    }
+
+    /// Returns the callsite's message, if it has one.
+    ///
+    /// For events, this is the event's format string. For spans, this is the
+    /// span's name.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    /// Returns the path of the module containing this callsite.
+    pub fn module_path(&self) -> &str {
+        &self.module_path
+    }
+
+    /// Returns the path of the file containing this callsite.
+    pub fn file(&self) -> &Path {
+        &self.file
+    }
+
+    /// Returns the line number of this callsite.
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+}
+
+// === impl CallsiteKind ===
+
+impl CallsiteKind {
+    /// Returns a string representation of this kind.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CallsiteKind::Span => "span",
+            CallsiteKind::Event => "event",
+        }
+    }
+}
+
+impl fmt::Display for CallsiteKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+// === impl ScanError ===
+
+impl ScanError {
+    fn new(path: &Path, kind: ScanErrorKind) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            kind,
+        }
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to scan '{}': ", self.path.display())?;
+        match self.kind {
+            ScanErrorKind::Io(ref e) => fmt::Display::fmt(e, f),
+            ScanErrorKind::Parse(ref e) => fmt::Display::fmt(e, f),
+            ScanErrorKind::Manifest(ref e) => fmt::Display::fmt(e, f),
+        }
+    }
+}
+
+impl Error for ScanError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self.kind {
+            ScanErrorKind::Io(ref e) => Some(e),
+            ScanErrorKind::Parse(ref e) => Some(e),
+            ScanErrorKind::Manifest(ref e) => Some(e),
+        }
+    }
+}
+
+// === impl Files ===
+
+impl Files {
+    fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            files: HashMap::new(),
+        }
+    }
+
+    fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the lines of `file`, or `None` if it does not exist.
+    fn lines(&mut self, file: &Path) -> Result<Option<&[String]>, ScanError> {
+        if !self.files.contains_key(file) {
+            let lines = match fs::read_to_string(self.root.join(file)) {
+                Ok(src) => Some(src.lines().map(str::to_owned).collect()),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+                Err(e) => return Err(ScanError::new(file, ScanErrorKind::Io(e))),
+            };
+            self.files.insert(file.to_path_buf(), lines);
+        }
+        Ok(self.files[file].as_deref())
+    }
+
+    /// Returns the trimmed text of line `line` of `file`, or `None` if there
+    /// is no such line, or if it is blank.
+    fn line(&mut self, file: &Path, line: u32) -> Result<Option<String>, ScanError> {
+        let text = self
+            .lines(file)?
+            .zip(line.checked_sub(1))
+            .and_then(|(lines, i)| lines.get(i as usize))
+            .map(|text| text.trim());
+        Ok(text.filter(|text| !text.is_empty()).map(str::to_owned))
+    }
+}
+
+// === impl Scan ===
+
+impl<'a> Scan<'a> {
+    fn new(base: &'a Path) -> Self {
+        Self {
+            base,
+            follow_modules: true,
+        }
+    }
+
+    /// Returns a scan that only scans individual files, without following
+    /// `mod` declarations.
+    fn single_file(base: &'a Path) -> Self {
+        Self {
+            base,
+            follow_modules: false,
+        }
+    }
+
+    fn crate_root(
+        self,
+        crate_name: &str,
+        root: &Path,
+        callsites: &mut Vec<Callsite>,
+    ) -> Result<(), ScanError> {
+        let dir = root.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+        self.file(root, vec![crate_name.to_owned()], dir, callsites)
+    }
+
+    fn file(
+        self,
+        file: &Path,
+        module: Vec<String>,
+        dir: PathBuf,
+        callsites: &mut Vec<Callsite>,
+    ) -> Result<(), ScanError> {
+        let src = fs::read_to_string(self.base.join(file))
+            .map_err(|e| ScanError::new(file, ScanErrorKind::Io(e)))?;
+        let syntax =
+            syn::parse_file(&src).map_err(|e| ScanError::new(file, ScanErrorKind::Parse(e)))?;
+        let mut visitor = Visitor {
+            scan: self,
+            file,
+            module,
+            dir,
+            callsites,
+            error: None,
+        };
+        visitor.visit_file(&syntax);
+        match visitor.error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.base.join(path).is_file()
+    }
+}
+
+// === impl Visitor ===
+
+impl Visitor<'_> {
+    fn record(
+        &mut self,
+        kind: CallsiteKind,
+        level: Option<Level>,
+        message: Option<String>,
+        line: u32,
+    ) {
+        self.callsites.push(Callsite {
+            kind,
+            level,
+            message,
+            module_path: self.module.join("::"),
+            file: self.file.to_path_buf(),
+            line,
+        });
+    }
+
+    /// Records the callsite for a macro invocation, if it is a `tracing` macro.
+    ///
+    /// Since `line!()` reports the line of the outermost macro invocation,
+    /// `line` is the line of the outermost macro, rather than of `name`.
+    fn record_macro(&mut self, name: &str, tokens: TokenStream, line: u32) {
+        let (kind, level) = match macro_kind(name) {
+            Some(kind) => kind,
+            None => return,
+        };
+        let args = split_args(tokens);
+        let level = level.or_else(|| args.iter().find_map(|arg| parse_level(arg)));
+        let message = args.iter().find_map(|arg| lit_str(arg));
+        self.record(kind, level, message, line);
+    }
+
+    /// Finds macro invocations nested inside another macro's input.
+    fn scan_tokens(&mut self, tokens: TokenStream, line: u32) {
+        let mut tokens = tokens.into_iter().peekable();
+        let mut ident = None;
+        while let Some(tt) = tokens.next() {
+            match tt {
+                TokenTree::Ident(i) => {
+                    ident = Some(i.to_string());
+                    continue;
+                }
+                TokenTree::Punct(ref p) if p.as_char() == '!' && p.spacing() == Spacing::Alone => {
+                    if let (Some(name), Some(TokenTree::Group(_))) = (ident.take(), tokens.peek()) {
+                        if let Some(TokenTree::Group(group)) = tokens.next() {
+                            self.record_macro(&name, group.stream(), line);
+                            self.scan_tokens(group.stream(), line);
+                        }
+                    }
+                }
+                TokenTree::Group(group) => self.scan_tokens(group.stream(), line),
+                _ => {}
+            }
+            ident = None;
+        }
+    }
+
+    fn visit_instrumented(&mut self, attrs: &[syn::Attribute], sig: &syn::Signature) {
+        for attr in attrs {
+            let path = attr.path();
+            if path
+                .segments
+                .last()
+                .map(|s| s.ident != "instrument")
+                .unwrap_or(true)
+            {
+                continue;
+            }
+
+            let mut level = Some(Level::INFO);
+            let mut name = sig.ident.to_string();
+            if let syn::Meta::List(ref list) = attr.meta {
+                for arg in split_args(list.tokens.clone()) {
+                    match arg.as_slice() {
+                        [TokenTree::Ident(key), TokenTree::Punct(eq), value @ ..]
+                            if eq.as_char() == '=' =>
+                        {
+                            if key == "level" {
+                                level = parse_level(value).or_else(|| {
+                                    lit_str(value).and_then(|lit| lit.parse::<Level>().ok())
+                                });
+                            } else if key == "name" {
+                                if let Some(lit) = lit_str(value) {
+                                    name = lit;
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+            let line = attr.pound_token.span.start().line as u32;
+            self.record(CallsiteKind::Span, level, Some(name), line);
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for Visitor<'_> {
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        let line = match mac.path.segments.first() {
+            Some(segment) => segment.ident.span().start().line as u32,
+            None => mac.bang_token.span.start().line as u32,
+        };
+        if let Some(name) = mac.path.segments.last() {
+            self.record_macro(&name.ident.to_string(), mac.tokens.clone(), line);
+        }
+        self.scan_tokens(mac.tokens.clone(), line);
+    }
+
+    fn visit_item_fn(&mut self, item: &'ast syn::ItemFn) {
+        self.visit_instrumented(&item.attrs, &item.sig);
+        visit::visit_item_fn(self, item);
+    }
+
+    fn visit_impl_item_fn(&mut self, item: &'ast syn::ImplItemFn) {
+        self.visit_instrumented(&item.attrs, &item.sig);
+        visit::visit_impl_item_fn(self, item);
+    }
+
+    fn visit_trait_item_fn(&mut self, item: &'ast syn::TraitItemFn) {
+        if item.default.is_some() {
+            self.visit_instrumented(&item.attrs, &item.sig);
+        }
+        visit::visit_trait_item_fn(self, item);
+    }
+
+    fn visit_item_mod(&mut self, item: &'ast syn::ItemMod) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let name = item.ident.to_string();
+        let path_attr = item.attrs.iter().find_map(|attr| match attr.meta {
+            syn::Meta::NameValue(ref nv) if nv.path.is_ident("path") => match nv.value {
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(ref lit),
+                    ..
+                }) => Some(lit.value()),
+                _ => None,
+            },
+            _ => None,
+        });
+
+        let mut module = self.module.clone();
+        module.push(name.clone());
+
+        if item.content.is_some() {
+            let dir = match path_attr {
+                Some(path) => self.dir.join(path),
+                None => self.dir.join(&name),
+            };
+            let parent_dir = std::mem::replace(&mut self.dir, dir);
+            let parent_module = std::mem::replace(&mut self.module, module);
+            visit::visit_item_mod(self, item);
+            self.dir = parent_dir;
+            self.module = parent_module;
+            return;
+        }
+
+        if !self.scan.follow_modules {
+            return;
+        }
+
+        let (file, dir) = match path_attr {
+            Some(path) => {
+                let file = self
+                    .file
+                    .parent()
+                    .unwrap_or_else(|| Path::new(""))
+                    .join(path);
+                let dir = file.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+                (file, dir)
+            }
+            None => {
+                let file = self.dir.join(format!("{}.rs", name));
+                let mod_rs = self.dir.join(&name).join("mod.rs");
+                if self.scan.is_file(&file) {
+                    (file, self.dir.join(&name))
+                } else if self.scan.is_file(&mod_rs) {
+                    (mod_rs, self.dir.join(&name))
+                } else {
+                    return;
+                }
+            }
+        };
+
+        if !self.scan.is_file(&file) {
+            return;
+        }
+
+        if let Err(error) = self.scan.file(&file, module, dir, self.callsites) {
+            self.error = Some(error);
+        }
+    }
+}
+
+fn macro_kind(name: &str) -> Option<(CallsiteKind, Option<Level>)> {
+    let kind = match name {
+        "trace" => (CallsiteKind::Event, Some(Level::TRACE)),
+        "debug" => (CallsiteKind::Event, Some(Level::DEBUG)),
+        "info" => (CallsiteKind::Event, Some(Level::INFO)),
+        "warn" => (CallsiteKind::Event, Some(Level::WARN)),
+        "error" => (CallsiteKind::Event, Some(Level::ERROR)),
+        "event" => (CallsiteKind::Event, None),
+        "trace_span" => (CallsiteKind::Span, Some(Level::TRACE)),
+        "debug_span" => (CallsiteKind::Span, Some(Level::DEBUG)),
+        "info_span" => (CallsiteKind::Span, Some(Level::INFO)),
+        "warn_span" => (CallsiteKind::Span, Some(Level::WARN)),
+        "error_span" => (CallsiteKind::Span, Some(Level::ERROR)),
+        "span" => (CallsiteKind::Span, None),
+        _ => return None,
+    };
+    Some(kind)
+}
+
+/// Splits a macro's input on top-level commas.
+fn split_args(tokens: TokenStream) -> Vec<Vec<TokenTree>> {
+    let mut args = vec![Vec::new()];
+    for tt in tokens {
+        match tt {
+            TokenTree::Punct(ref p) if p.as_char() == ',' => args.push(Vec::new()),
+            // `macro_rules!` macros may wrap their arguments in invisible
+            // groups; look through them.
+            TokenTree::Group(ref g) if g.delimiter() == Delimiter::None => {
+                args.last_mut().unwrap().extend(g.stream())
+            }
+            tt => args.last_mut().unwrap().push(tt),
+        }
+    }
+    args.retain(|arg| !arg.is_empty());
+    args
+}
+
+/// Parses a level expression like `Level::DEBUG` or `tracing::Level::DEBUG`.
+fn parse_level(tokens: &[TokenTree]) -> Option<Level> {
+    let level = match tokens.last()? {
+        TokenTree::Ident(ident) => ident.to_string(),
+        _ => return None,
+    };
+    let is_level_path = matches!(
+        tokens.iter().rev().nth(3),
+        Some(TokenTree::Ident(ident)) if ident == "Level"
+    );
+    if !is_level_path {
+        return None;
+    }
+    match level.as_str() {
+        "TRACE" => Some(Level::TRACE),
+        "DEBUG" => Some(Level::DEBUG),
+        "INFO" => Some(Level::INFO),
+        "WARN" => Some(Level::WARN),
+        "ERROR" => Some(Level::ERROR),
+        _ => None,
+    }
+}
+
+fn lit_str(tokens: &[TokenTree]) -> Option<String> {
+    match tokens {
+        [lit @ TokenTree::Literal(_)] => syn::parse2::<syn::LitStr>(lit.clone().into())
+            .ok()
+            .map(|lit| lit.value()),
+        _ => None,
+    }
+}
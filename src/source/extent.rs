@@ -0,0 +1,146 @@
+use super::{scan_workspace, ScanError, ScanErrorKind};
+use crate::{Directive, LineFilter};
+use std::{fs, path::Path};
+use syn::{
+    spanned::Spanned,
+    visit::{self, Visit},
+};
+
+/// Finds the innermost function whose source contains a line.
+struct Extent {
+    line: u32,
+    /// The first and last lines of the innermost function found so far.
+    found: Option<(u32, u32)>,
+}
+
+impl LineFilter {
+    /// Enable every span and event in the function that contains line `line`
+    /// of the Rust module `module`, in the Cargo workspace at
+    /// `workspace_root`, returning the directives for the entries that were
+    /// added.
+    ///
+    /// This turns "trace everything in `process_batch`" into a single call:
+    /// `line` can be any line of the function, such as the line with its
+    /// name, and the function's source is parsed to find its first and last
+    /// lines. Each callsite in that range gets a `module@line` entry of its
+    /// own, as if by [`enable_by_mod`], so the entries keep pointing at the
+    /// same lines if the function changes, until this is called again. The
+    /// function's attributes are part of its range, so an
+    /// [`#[instrument]`][instrument] span is enabled along with the
+    /// function's events. If the line is inside a closure or a nested
+    /// function, the innermost function containing it is used.
+    ///
+    /// If no function in `module` contains `line`, nothing is added, and the
+    /// returned list is empty.
+    ///
+    /// This method is only available when the `source` feature flag is
+    /// enabled.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the workspace's source code couldn't be
+    /// scanned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    ///
+    /// let mut filter = LineFilter::default();
+    /// // Line 4 of `examples/basic.rs` is `pub fn do_stuff() {`.
+    /// let added = filter
+    ///     .enable_fn_at(".", "basic::some_module", 4)
+    ///     .expect("scanning should succeed");
+    ///
+    /// assert_eq!(added.len(), 2);
+    /// assert_eq!(
+    ///     filter.to_string(),
+    ///     "basic::some_module@5,basic::some_module@6",
+    /// );
+    /// ```
+    ///
+    /// [`enable_by_mod`]: Self::enable_by_mod
+    /// [instrument]: https://docs.rs/tracing/latest/tracing/attr.instrument.html
+    pub fn enable_fn_at(
+        &mut self,
+        workspace_root: impl AsRef<Path>,
+        module: &str,
+        line: u32,
+    ) -> Result<Vec<Directive>, ScanError> {
+        let root = workspace_root.as_ref();
+        let callsites = scan_workspace(root)?;
+        // All of a module's callsites are in the file that defines it.
+        let file = match callsites.iter().find(|c| c.module_path() == module) {
+            Some(callsite) => callsite.file().to_path_buf(),
+            None => return Ok(Vec::new()),
+        };
+
+        let src = fs::read_to_string(root.join(&file))
+            .map_err(|e| ScanError::new(&file, ScanErrorKind::Io(e)))?;
+        let syntax =
+            syn::parse_file(&src).map_err(|e| ScanError::new(&file, ScanErrorKind::Parse(e)))?;
+        let mut extent = Extent { line, found: None };
+        extent.visit_file(&syntax);
+        let (first, last) = match extent.found {
+            Some(found) => found,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut added = Vec::new();
+        for callsite in &callsites {
+            if callsite.file() != file || callsite.line() < first || callsite.line() > last {
+                continue;
+            }
+            let directive = Directive::Module {
+                module: callsite.module_path().to_owned(),
+                line: callsite.line(),
+                hash: None,
+                levels: None,
+            };
+            if added.contains(&directive) {
+                continue;
+            }
+            self.enable_by_mod(callsite.module_path().to_owned(), callsite.line());
+            added.push(directive);
+        }
+        Ok(added)
+    }
+}
+
+// === impl Extent ===
+
+impl Extent {
+    fn function(&mut self, item: &impl Spanned) {
+        let span = item.span();
+        let (first, last) = (span.start().line as u32, span.end().line as u32);
+        if first > self.line || last < self.line {
+            return;
+        }
+        // Functions nested inside the one found so far are found later.
+        self.found = Some((first, last));
+    }
+}
+
+impl<'ast> Visit<'ast> for Extent {
+    fn visit_item_fn(&mut self, item: &'ast syn::ItemFn) {
+        self.function(item);
+        visit::visit_item_fn(self, item);
+    }
+
+    fn visit_impl_item_fn(&mut self, item: &'ast syn::ImplItemFn) {
+        self.function(item);
+        visit::visit_impl_item_fn(self, item);
+    }
+
+    fn visit_trait_item_fn(&mut self, item: &'ast syn::TraitItemFn) {
+        if item.default.is_some() {
+            self.function(item);
+        }
+        visit::visit_trait_item_fn(self, item);
+    }
+
+    fn visit_expr_closure(&mut self, closure: &'ast syn::ExprClosure) {
+        self.function(closure);
+        visit::visit_expr_closure(self, closure);
+    }
+}
@@ -0,0 +1,273 @@
+use super::{scan_workspace, Callsite, Files, ScanError};
+use crate::Directive;
+use std::{fmt, path::Path};
+
+/// A config file whose entries have been re-anchored by [`reanchor`].
+#[derive(Clone, Debug)]
+pub struct Reanchored {
+    config: String,
+    entries: Vec<Reanchor>,
+}
+
+/// An entry that was moved (or could not be moved) by [`reanchor`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reanchor {
+    config_line: usize,
+    old: Directive,
+    new: Option<Directive>,
+}
+
+/// Re-anchors the `module@line` and `file@line` entries in a config file
+/// after the source code they point at has changed.
+///
+/// `config` is a list of directives, in the format accepted by [the
+/// `FromStr` implementation for `LineFilter`][syntax], whose line numbers
+/// refer to the source code of the Cargo workspace at `old_root`. For each
+/// entry, this finds the text of the line it points at in `old_root`, and
+/// then finds the same line in `new_root`, the workspace after the change.
+/// If the line moved, the entry is updated to point at its new line number.
+/// When the line appears several times near the entry, the closest match to
+/// the old line number wins.
+///
+/// `module@line` entries are only moved to lines that contain a span or
+/// event callsite in the same module (see [`scan_workspace`]).
+/// `file@line` entries may move to any line in the same file, but keep their
+/// path; paths outside of both workspaces are left as-is.
+///
+/// The returned config is identical to `config`, except for the line numbers
+/// of moved entries, so comments and formatting are preserved. Entries whose
+/// line cannot be found in `new_root` are left unchanged, and are reported
+/// along with the moved entries in [`Reanchored::entries`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use tracing_line_filter::source;
+///
+/// // Check out the old version of the code to `/tmp/old`, then...
+/// let config = std::fs::read_to_string("lines.conf").unwrap();
+/// let reanchored = source::reanchor(&config, "/tmp/old", ".").unwrap();
+///
+/// for entry in reanchored.entries() {
+///     eprintln!("lines.conf:{}: {}", entry.config_line(), entry);
+/// }
+/// std::fs::write("lines.conf", reanchored.config()).unwrap();
+/// ```
+///
+/// [syntax]: crate::LineFilter#impl-FromStr-for-LineFilter
+pub fn reanchor(
+    config: &str,
+    old_root: impl AsRef<Path>,
+    new_root: impl AsRef<Path>,
+) -> Result<Reanchored, ScanError> {
+    let mut old = Tree::scan(old_root.as_ref())?;
+    let mut new = Tree::scan(new_root.as_ref())?;
+
+    let mut reanchored = Reanchored {
+        config: String::with_capacity(config.len()),
+        entries: Vec::new(),
+    };
+    for (i, line) in config.split_inclusive('\n').enumerate() {
+        let code = &line[..line.find('#').unwrap_or(line.len())];
+        let mut copied = 0;
+        let mut start = 0;
+        for segment in code.split(',') {
+            let segment_start = start;
+            start += segment.len() + 1;
+
+            let is_space = |c: char| c.is_ascii_whitespace();
+            let directive = segment.trim_matches(is_space);
+            let directive_start =
+                segment_start + segment.len() - segment.trim_start_matches(is_space).len();
+            let old_directive = match directive.parse::<Directive>() {
+                Ok(directive @ Directive::Module { .. })
+                | Ok(directive @ Directive::File { .. }) => directive,
+                _ => continue,
+            };
+
+            let new_directive = find(&mut old, &mut new, &old_directive)?;
+            if new_directive.as_ref() == Some(&old_directive) {
+                continue;
+            }
+            if let Some(ref new_directive) = new_directive {
+                reanchored.config.push_str(&line[copied..directive_start]);
+                reanchored.config.push_str(&new_directive.to_string());
+                copied = directive_start + directive.len();
+            }
+            reanchored.entries.push(Reanchor {
+                config_line: i + 1,
+                old: old_directive,
+                new: new_directive,
+            });
+        }
+        reanchored.config.push_str(&line[copied..]);
+    }
+
+    Ok(reanchored)
+}
+
+/// Finds the directive in `new` that points at the same source line as
+/// `directive` does in `old`.
+fn find(
+    old: &mut Tree,
+    new: &mut Tree,
+    directive: &Directive,
+) -> Result<Option<Directive>, ScanError> {
+    match *directive {
+        Directive::Module {
+            ref module,
+            line: old_line,
+            hash,
+            levels,
+        } => {
+            let old_callsites = old.in_module(module);
+            // Prefer the file of the callsite on the entry's line; if the
+            // entry is stale, guess the file of the nearest callsite.
+            let old_file = match old_callsites
+                .iter()
+                .min_by_key(|callsite| callsite.line().abs_diff(old_line))
+            {
+                Some(callsite) => callsite.file().to_path_buf(),
+                None => return Ok(None),
+            };
+            let text = match old.files.line(&old_file, old_line)? {
+                Some(text) => text,
+                None => return Ok(None),
+            };
+
+            let mut best: Option<(bool, u32, u32)> = None;
+            for callsite in new.in_module(module) {
+                let file = callsite.file().to_path_buf();
+                let line = callsite.line();
+                if new.files.line(&file, line)?.as_deref() != Some(text.as_str()) {
+                    continue;
+                }
+                // Prefer matches in the same file, and then the closest.
+                let key = (file != old_file, line.abs_diff(old_line), line);
+                if best.is_none_or(|best| key < best) {
+                    best = Some(key);
+                }
+            }
+            Ok(best.map(|(_, _, line)| Directive::Module {
+                module: module.clone(),
+                line,
+                hash,
+                levels,
+            }))
+        }
+        Directive::File {
+            ref file,
+            line: old_line,
+            hash,
+            levels,
+        } => {
+            let path = Path::new(file);
+            let relative = if let Ok(relative) = path.strip_prefix(new.files.root()) {
+                relative
+            } else if let Ok(relative) = path.strip_prefix(old.files.root()) {
+                relative
+            } else {
+                return Ok(Some(directive.clone()));
+            };
+            let text = match old.files.line(relative, old_line)? {
+                Some(text) => text,
+                None => return Ok(None),
+            };
+            let lines = match new.files.lines(relative)? {
+                Some(lines) => lines,
+                None => return Ok(None),
+            };
+            let line = (1..)
+                .zip(lines)
+                .filter(|(_, line)| line.trim() == text)
+                .map(|(line, _): (u32, _)| line)
+                .min_by_key(|line| (line.abs_diff(old_line), *line));
+            Ok(line.map(|line| Directive::File {
+                file: file.clone(),
+                line,
+                hash,
+                levels,
+            }))
+        }
+        Directive::Deny(ref denied) => {
+            let denied = find(old, new, denied)?;
+            Ok(denied.map(|denied| Directive::Deny(Box::new(denied))))
+        }
+        Directive::ModuleLevel { .. } | Directive::ModulePrefix { .. } => {
+            Ok(Some(directive.clone()))
+        }
+        #[cfg(feature = "env-filter")]
+        Directive::Env(_) => Ok(Some(directive.clone())),
+    }
+}
+
+/// A scanned source tree.
+struct Tree {
+    callsites: Vec<Callsite>,
+    files: Files,
+}
+
+impl Tree {
+    fn scan(root: &Path) -> Result<Self, ScanError> {
+        Ok(Self {
+            callsites: scan_workspace(root)?,
+            files: Files::new(root),
+        })
+    }
+
+    fn in_module(&self, module: &str) -> Vec<Callsite> {
+        self.callsites
+            .iter()
+            .filter(|callsite| callsite.module_path() == module)
+            .cloned()
+            .collect()
+    }
+}
+
+// === impl Reanchored ===
+
+impl Reanchored {
+    /// Returns the re-anchored config.
+    pub fn config(&self) -> &str {
+        &self.config
+    }
+
+    /// Returns the entries that were moved, or that could not be found in
+    /// the new source tree, in the order they appear in the config.
+    pub fn entries(&self) -> &[Reanchor] {
+        &self.entries
+    }
+}
+
+// === impl Reanchor ===
+
+impl Reanchor {
+    /// Returns the (1-based) line of the config on which this entry appears.
+    pub fn config_line(&self) -> usize {
+        self.config_line
+    }
+
+    /// Returns the entry as it was before re-anchoring.
+    pub fn before(&self) -> &Directive {
+        &self.old
+    }
+
+    /// Returns the re-anchored entry, or `None` if the line it pointed at
+    /// could not be found in the new source tree.
+    pub fn after(&self) -> Option<&Directive> {
+        self.new.as_ref()
+    }
+}
+
+impl fmt::Display for Reanchor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.new {
+            Some(ref new) => write!(f, "moved `{}` to `{}`", self.old, new),
+            None => write!(
+                f,
+                "could not find the line `{}` points at in the new source",
+                self.old
+            ),
+        }
+    }
+}
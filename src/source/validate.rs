@@ -0,0 +1,362 @@
+use super::{line_hash, scan_workspace, Files, Scan, ScanError};
+use crate::LineFilter;
+use std::{
+    borrow::Cow,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// How many lines away from an entry's line a callsite may be for it to be
+/// suggested as the line the entry should have enabled.
+const SLACK: u32 = 3;
+
+/// A problem with a [`LineFilter`] entry, found by
+/// [`LineFilter::validate_sources`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    location: String,
+    line: u32,
+    kind: FindingKind,
+}
+
+/// The kind of problem described by a [`Finding`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FindingKind {
+    /// There is no callsite on the entry's line, but there is a callsite on
+    /// a nearby line.
+    ///
+    /// This is usually a sign that the entry's line number is off by one, or
+    /// that the source code has changed since the entry was written. Since
+    /// callsites spanning multiple lines are recorded on the line where the
+    /// macro invocation *starts*, this also occurs when an entry points into
+    /// the middle of a multi-line macro invocation.
+    Nearby {
+        /// The line of the nearest callsite.
+        line: u32,
+    },
+    /// There is no callsite on or near the entry's line.
+    NoCallsite,
+    /// No callsites were found in the entry's module.
+    ///
+    /// The module path may be misspelled, or the module may not be part of
+    /// the workspace.
+    UnknownModule,
+    /// The entry's file does not exist.
+    MissingFile,
+    /// The entry's [line hash] does not match the text of the line it points
+    /// at.
+    ///
+    /// This means that the source code has changed since the entry was
+    /// written, so the entry may no longer enable the span or event it was
+    /// intended to.
+    ///
+    /// [line hash]: super::line_hash
+    Drifted {
+        /// The line of the nearest callsite whose text matches the entry's
+        /// hash, if there is one. This is probably the line that the entry
+        /// should point at now.
+        line: Option<u32>,
+    },
+}
+
+impl LineFilter {
+    /// Checks this filter's `(module, line)` and `(file, line)` entries
+    /// against the source code of the Cargo workspace at `workspace_root`.
+    ///
+    /// This parses the workspace's source code (see
+    /// [`scan_workspace`](super::scan_workspace)) and confirms that each
+    /// enabled line actually contains a span or event callsite. A
+    /// [`Finding`] is returned for every entry that does not. Entries that
+    /// point to files outside of the workspace (such as dependencies in the
+    /// local Cargo registry) are checked by parsing just that file.
+    ///
+    /// Entries parsed from directives with a [line hash] (such as
+    /// `my_crate::db@142:1a2b3c4d`) are also checked against the text of the
+    /// line they point at. If the text no longer matches, a
+    /// [`FindingKind::Drifted`] finding is returned, pointing at the callsite
+    /// that now has the matching text, if there is one.
+    ///
+    /// Findings are sorted by module or file path, and then by line number.
+    /// This method is only available when the `source` feature flag is
+    /// enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::{LineFilter, source::FindingKind};
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter
+    ///     // This is an event in `examples/basic.rs`...
+    ///     .enable_by_mod("basic", 25)
+    ///     // ...but there's nothing on this line...
+    ///     .enable_by_mod("basic", 28)
+    ///     // ...and this module path is misspelled.
+    ///     .enable_by_mod("bsaic", 25);
+    ///
+    /// let findings = filter.validate_sources(".").expect("scanning should succeed");
+    ///
+    /// assert_eq!(findings.len(), 2);
+    /// assert_eq!(findings[0].location(), "basic");
+    /// assert_eq!(findings[0].kind(), &FindingKind::Nearby { line: 27 });
+    /// assert_eq!(findings[1].location(), "bsaic");
+    /// assert_eq!(findings[1].kind(), &FindingKind::UnknownModule);
+    ///
+    /// for finding in findings {
+    ///     eprintln!("warning: {}", finding);
+    /// }
+    /// ```
+    ///
+    /// Checking line hashes:
+    ///
+    /// ```
+    /// use tracing_line_filter::{LineFilter, source::{self, FindingKind}};
+    ///
+    /// // The text of the event on line 25 of `examples/basic.rs`.
+    /// let hash = source::line_hash(r#"tracing::debug!("i'm enabled!");"#);
+    ///
+    /// // A directive for that event, written when it was on line 23.
+    /// let filter: LineFilter = format!("basic@23:{:08x}", hash).parse().unwrap();
+    ///
+    /// let findings = filter.validate_sources(".").expect("scanning should succeed");
+    /// assert_eq!(findings[0].kind(), &FindingKind::Drifted { line: Some(25) });
+    /// ```
+    ///
+    /// [line hash]: super::line_hash
+    pub fn validate_sources(
+        &self,
+        workspace_root: impl AsRef<Path>,
+    ) -> Result<Vec<Finding>, ScanError> {
+        let root = workspace_root.as_ref();
+        let callsites = scan_workspace(root)?;
+        let mut files = Files::new(root);
+        let mut findings = Vec::new();
+
+        for entry in &self.by_module {
+            let (ref module, line) = *entry;
+            let in_module = callsites
+                .iter()
+                .filter(|c| c.module_path() == module)
+                .map(|c| (c.file().to_path_buf(), c.line()))
+                .collect::<Vec<_>>();
+            let kind = if in_module.is_empty() {
+                Some(FindingKind::UnknownModule)
+            } else {
+                self.check_entry(&mut files, entry, &in_module, None)?
+            };
+            findings.extend(kind.map(|kind| Finding::new(module, line, kind)));
+        }
+
+        for entry in &self.by_file {
+            let (ref file, line) = *entry;
+            let path = Path::new(file.as_ref());
+            let relative = path.strip_prefix(root).unwrap_or(path);
+            let mut in_file = callsites
+                .iter()
+                .filter(|c| c.file() == relative)
+                .map(|c| (c.file().to_path_buf(), c.line()))
+                .collect::<Vec<_>>();
+
+            let kind = if in_file.is_empty() && !root.join(relative).is_file() {
+                Some(FindingKind::MissingFile)
+            } else {
+                if in_file.is_empty() {
+                    // The file isn't part of any crate in the workspace, so
+                    // scan it on its own.
+                    let mut file_callsites = Vec::new();
+                    Scan::single_file(root).file(
+                        relative,
+                        Vec::new(),
+                        Default::default(),
+                        &mut file_callsites,
+                    )?;
+                    in_file.extend(
+                        file_callsites
+                            .iter()
+                            .map(|c| (c.file().to_path_buf(), c.line())),
+                    );
+                }
+                self.check_entry(&mut files, entry, &in_file, Some(relative))?
+            };
+            findings.extend(kind.map(|kind| Finding::new(file, line, kind)));
+        }
+
+        findings.sort_by(|a, b| (&a.location, a.line).cmp(&(&b.location, b.line)));
+        Ok(findings)
+    }
+
+    /// Checks a single entry against the `(file, line)` locations of the
+    /// callsites it could match.
+    fn check_entry(
+        &self,
+        files: &mut Files,
+        entry: &(Cow<'static, str>, u32),
+        callsites: &[(PathBuf, u32)],
+        file: Option<&Path>,
+    ) -> Result<Option<FindingKind>, ScanError> {
+        let line = entry.1;
+        let lines = callsites.iter().map(|&(_, line)| line).collect::<Vec<_>>();
+        let hash = match self.hashes.get(entry) {
+            Some(&hash) => hash,
+            None => return Ok(check_line(&lines, line)),
+        };
+
+        // Module entries don't say which file they're in, so guess the file
+        // of the nearest callsite.
+        let file = match file {
+            Some(file) => file.to_path_buf(),
+            None => match callsites.iter().min_by_key(|(_, l)| l.abs_diff(line)) {
+                Some((file, _)) => file.clone(),
+                None => return Ok(check_line(&lines, line)),
+            },
+        };
+        if files.line(&file, line)?.map(|text| line_hash(&text)) == Some(hash) {
+            return Ok(check_line(&lines, line));
+        }
+
+        let mut moved_to = None;
+        for (file, l) in callsites {
+            let matches = files.line(file, *l)?.map(|text| line_hash(&text)) == Some(hash);
+            if matches && moved_to.is_none_or(|m: u32| l.abs_diff(line) < m.abs_diff(line)) {
+                moved_to = Some(*l);
+            }
+        }
+        Ok(Some(FindingKind::Drifted { line: moved_to }))
+    }
+}
+
+/// Emits a warning event listing the [`FindingKind::Drifted`] entries in
+/// `findings`, if there are any.
+///
+/// This is intended to be used with the findings returned by
+/// [`LineFilter::validate_sources`] when a config is loaded, so that entries
+/// whose [line hashes] show that they no longer point at the lines they were
+/// written for are noticed. Since the filter is usually moved into the
+/// subscriber, validate the filter first, then set up the subscriber, and
+/// then call this function.
+///
+/// The event is emitted at the `WARN` level with the target
+/// `tracing_line_filter`, and has the fields `count` (the number of drifted
+/// entries) and `entries` (a list of the drifted entries, with the lines they
+/// may have moved to). Note that the subscriber's filters must enable this
+/// event for it to be recorded; with a `LineFilter`, this can be done using
+/// an `EnvFilter` directive like `tracing_line_filter=warn`.
+///
+/// Returns the number of drifted entries.
+///
+/// # Examples
+///
+/// ```no_run
+/// use tracing_line_filter::{source, LineFilter};
+/// use tracing_subscriber::prelude::*;
+///
+/// let config = std::fs::read_to_string("lines.conf").unwrap();
+/// let mut filter: LineFilter = config.parse().unwrap();
+/// filter.add_directive("tracing_line_filter=warn".parse().unwrap()).unwrap();
+/// let findings = filter.validate_sources(".").expect("scanning should succeed");
+///
+/// tracing_subscriber::registry()
+///     .with(tracing_subscriber::fmt::layer())
+///     .with(filter)
+///     .init();
+///
+/// source::warn_drifted(&findings);
+/// ```
+///
+/// [line hashes]: super::line_hash
+pub fn warn_drifted(findings: &[Finding]) -> usize {
+    let drifted = findings
+        .iter()
+        .filter_map(|finding| match finding.kind {
+            FindingKind::Drifted { line: Some(line) } => Some(format!(
+                "{}@{} (moved to line {}?)",
+                finding.location, finding.line, line
+            )),
+            FindingKind::Drifted { line: None } => {
+                Some(format!("{}@{}", finding.location, finding.line))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if !drifted.is_empty() {
+        tracing::warn!(
+            target: "tracing_line_filter",
+            count = drifted.len(),
+            entries = %drifted.join(", "),
+            "the source code has changed since these line filter entries were written",
+        );
+    }
+    drifted.len()
+}
+
+fn check_line(lines: &[u32], line: u32) -> Option<FindingKind> {
+    if lines.contains(&line) {
+        return None;
+    }
+
+    let nearest = lines
+        .iter()
+        .copied()
+        .filter(|l| l.abs_diff(line) <= SLACK)
+        .min_by_key(|l| l.abs_diff(line));
+    match nearest {
+        Some(line) => Some(FindingKind::Nearby { line }),
+        None => Some(FindingKind::NoCallsite),
+    }
+}
+
+// === impl Finding ===
+
+impl Finding {
+    fn new(location: &str, line: u32, kind: FindingKind) -> Self {
+        Self {
+            location: location.to_owned(),
+            line,
+            kind,
+        }
+    }
+
+    /// Returns the module path or file path of the entry this finding
+    /// describes.
+    pub fn location(&self) -> &str {
+        &self.location
+    }
+
+    /// Returns the line number of the entry this finding describes.
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
+    /// Returns the kind of problem this finding describes.
+    pub fn kind(&self) -> &FindingKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}: ", self.location, self.line)?;
+        match self.kind {
+            FindingKind::Nearby { line } => write!(
+                f,
+                "no span or event on line {}; the nearest is on line {}",
+                self.line, line
+            ),
+            FindingKind::NoCallsite => write!(f, "no span or event on line {}", self.line),
+            FindingKind::UnknownModule => {
+                write!(f, "no spans or events found in module '{}'", self.location)
+            }
+            FindingKind::MissingFile => write!(f, "file '{}' does not exist", self.location),
+            FindingKind::Drifted { line: Some(line) } => write!(
+                f,
+                "line {} has changed since this entry was written; it may have moved to line {}",
+                self.line, line
+            ),
+            FindingKind::Drifted { line: None } => write!(
+                f,
+                "line {} has changed since this entry was written",
+                self.line
+            ),
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use super::{Callsite, Scan, ScanError, ScanErrorKind};
+use crate::ModuleMap;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use toml::Table;
+
+/// Scans every crate in the Cargo workspace (or package) at `root`, and
+/// returns every `tracing` callsite found in those crates.
+///
+/// Packages are found by searching `root` and its subdirectories for
+/// `Cargo.toml` files (skipping `target` directories and hidden
+/// directories). Each package's library, binaries, examples, tests, and
+/// benchmarks are scanned, using both the targets listed in its manifest and
+/// the targets Cargo discovers automatically.
+///
+/// File paths in the returned [`Callsite`]s are relative to `root`, matching
+/// the file paths that [`std::file!()`] emits for workspace crates.
+///
+/// See [`scan_crate`](super::scan_crate) for details on how crates are
+/// scanned.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_line_filter::source;
+///
+/// let callsites = source::scan_workspace(".").expect("scanning should succeed");
+///
+/// // `examples/basic.rs` contains an event on line 25.
+/// assert!(callsites
+///     .iter()
+///     .any(|callsite| callsite.module_path() == "basic" && callsite.line() == 25));
+/// ```
+pub fn scan_workspace(root: impl AsRef<Path>) -> Result<Vec<Callsite>, ScanError> {
+    let root = root.as_ref();
+    let mut manifests = Vec::new();
+    find_manifests(root, Path::new(""), &mut manifests)?;
+
+    let scan = Scan::new(root);
+    let mut callsites = Vec::new();
+    for manifest in manifests {
+        for (crate_name, path) in targets(root, &manifest)? {
+            scan.crate_root(&crate_name, &path, &mut callsites)?;
+        }
+    }
+    Ok(callsites)
+}
+
+/// Scans every crate in the Cargo workspace (or package) at `root`, and
+/// returns a [`ModuleMap`] between the module paths and files of every
+/// module with a `tracing` callsite in it.
+///
+/// File paths are relative to `root`, as with [`scan_workspace`], and
+/// modules without any callsites are left out, since there is nothing in
+/// them for an entry to match. The map's [`Display`] output can be saved as
+/// a manifest, for programs that can't scan their own source code at
+/// runtime.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_line_filter::source;
+///
+/// let map = source::scan_module_map(".").expect("scanning should succeed");
+/// assert_eq!(map.file_for("basic::some_module"), Some("examples/basic.rs"));
+/// ```
+///
+/// [`ModuleMap`]: crate::ModuleMap
+/// [`Display`]: std::fmt::Display
+pub fn scan_module_map(root: impl AsRef<Path>) -> Result<ModuleMap, ScanError> {
+    let mut map = ModuleMap::new();
+    for callsite in scan_workspace(root)? {
+        if map.file_for(callsite.module_path()).is_none() {
+            map.insert(
+                callsite.module_path(),
+                callsite.file().to_string_lossy().into_owned(),
+            );
+        }
+    }
+    Ok(map)
+}
+
+/// Recursively finds `Cargo.toml` files in `root.join(dir)`, returning their
+/// paths relative to `root`.
+fn find_manifests(root: &Path, dir: &Path, manifests: &mut Vec<PathBuf>) -> Result<(), ScanError> {
+    let manifest = dir.join("Cargo.toml");
+    if root.join(&manifest).is_file() {
+        manifests.push(manifest);
+    }
+
+    let entries =
+        fs::read_dir(root.join(dir)).map_err(|e| ScanError::new(dir, ScanErrorKind::Io(e)))?;
+    let mut subdirs = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| ScanError::new(dir, ScanErrorKind::Io(e)))?;
+        let name = entry.file_name();
+        let skip = name == "target" || name.to_string_lossy().starts_with('.');
+        if !skip && entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            subdirs.push(dir.join(name));
+        }
+    }
+
+    // Sort subdirectories so that the order of results is deterministic.
+    subdirs.sort();
+    for subdir in subdirs {
+        find_manifests(root, &subdir, manifests)?;
+    }
+    Ok(())
+}
+
+/// Returns the crate name and root file (relative to `root`) of each target
+/// in the package with the given manifest.
+fn targets(root: &Path, manifest: &Path) -> Result<Vec<(String, PathBuf)>, ScanError> {
+    let src = fs::read_to_string(root.join(manifest))
+        .map_err(|e| ScanError::new(manifest, ScanErrorKind::Io(e)))?;
+    let toml = src
+        .parse::<Table>()
+        .map_err(|e| ScanError::new(manifest, ScanErrorKind::Manifest(e)))?;
+
+    // Virtual manifests have no targets of their own.
+    let package = match toml
+        .get("package")
+        .and_then(|p| p.get("name"))
+        .and_then(|n| n.as_str())
+    {
+        Some(package) => package,
+        None => return Ok(Vec::new()),
+    };
+    let dir = manifest.parent().unwrap_or_else(|| Path::new(""));
+    let mut targets = Vec::new();
+    let mut add = |name: &str, path: PathBuf| {
+        if !targets.iter().any(|(_, p)| p == &path) {
+            targets.push((name.replace('-', "_"), path));
+        }
+    };
+
+    // Targets listed explicitly in the manifest.
+    if let Some(lib) = toml.get("lib") {
+        let name = lib.get("name").and_then(|n| n.as_str()).unwrap_or(package);
+        let path = lib
+            .get("path")
+            .and_then(|p| p.as_str())
+            .unwrap_or("src/lib.rs");
+        add(name, dir.join(path));
+    }
+    for kind in &["bin", "example", "test", "bench"] {
+        let explicit = toml
+            .get(*kind)
+            .and_then(|t| t.as_array())
+            .into_iter()
+            .flatten();
+        for target in explicit {
+            if let (Some(name), Some(path)) = (
+                target.get("name").and_then(|n| n.as_str()),
+                target.get("path").and_then(|p| p.as_str()),
+            ) {
+                add(name, dir.join(path));
+            }
+        }
+    }
+
+    // Targets Cargo discovers automatically.
+    for (name, path) in &[(package, "src/lib.rs"), (package, "src/main.rs")] {
+        if root.join(dir).join(path).is_file() {
+            add(name, dir.join(path));
+        }
+    }
+    for target_dir in &["src/bin", "examples", "tests", "benches"] {
+        let target_dir = dir.join(target_dir);
+        let entries = match fs::read_dir(root.join(&target_dir)) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        let mut discovered = Vec::new();
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            let name = match path.file_stem().and_then(|n| n.to_str()) {
+                Some(name) => name.to_owned(),
+                None => continue,
+            };
+            if path.extension().is_some_and(|ext| ext == "rs") && path.is_file() {
+                discovered.push((name.clone(), target_dir.join(format!("{}.rs", name))));
+            } else if path.join("main.rs").is_file() {
+                discovered.push((name.clone(), target_dir.join(&name).join("main.rs")));
+            }
+        }
+        discovered.sort();
+        for (name, path) in discovered {
+            add(&name, path);
+        }
+    }
+
+    Ok(targets)
+}
@@ -0,0 +1,368 @@
+use crate::{directive, fields, Directive, FilterConfig, LineFilter, ParseError};
+use std::{
+    collections::BTreeMap,
+    env,
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Merges directives from several configuration sources into one
+/// [`FilterConfig`], remembering where each directive came from.
+///
+/// Programs often read their configuration from several places: a
+/// system-wide config file, a per-user config file, an environment
+/// variable, and the program's own defaults or command-line flags. A
+/// `ConfigStack` layers these sources, from lowest to highest precedence:
+///
+/// 1. [System config files](Self::system_file)
+/// 2. [User config files](Self::user_file)
+/// 3. [Environment variables](Self::env_var)
+/// 4. [Directives added by the program](Self::add)
+///
+/// Sources of the same kind take precedence in the order they were added,
+/// regardless of the order in which sources of different kinds are added.
+///
+/// Most directives are combined from every source. Where two sources
+/// disagree, the higher-precedence source wins:
+///
+/// * A module's `module@level` directive replaces the same module's level
+///   from lower-precedence sources.
+/// * The [`EnvFilter`] directives all come from the highest-precedence
+///   source that has any, in the same way that setting `RUST_LOG` replaces
+///   a program's default `EnvFilter`.
+/// * A profile replaces the profile with the same name from
+///   lower-precedence sources.
+/// * A span field (set by a `fields = { ... }` line) replaces the field with
+///   the same name from lower-precedence sources.
+///
+/// [`ConfigStack::origins`] lists every directive in the merged config,
+/// along with the source it came from, so that it's possible to find out
+/// why an entry is enabled.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_line_filter::{ConfigSource, ConfigStack};
+///
+/// std::env::set_var("MY_APP_LINES", "my_crate::db@142,warn");
+///
+/// let mut stack = ConfigStack::new();
+/// stack
+///     // Missing config files are skipped.
+///     .system_file("/etc/my-app/lines.conf")
+///     .unwrap()
+///     .env_var("MY_APP_LINES")
+///     .unwrap()
+///     .add("my_crate::auth@200".parse().unwrap());
+///
+/// let filter = stack.to_filter().unwrap();
+/// assert_eq!(filter.to_string(), "my_crate::auth@200,my_crate::db@142,warn");
+///
+/// let (directive, source) = &stack.origins()[0];
+/// assert_eq!(directive.to_string(), "my_crate::db@142");
+/// assert_eq!(source, &ConfigSource::EnvVar("MY_APP_LINES".to_owned()));
+/// ```
+///
+/// [`EnvFilter`]: tracing_subscriber::EnvFilter
+#[derive(Clone, Debug, Default)]
+pub struct ConfigStack {
+    layers: Vec<Layer>,
+}
+
+/// Where a directive in a [`ConfigStack`] came from.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum ConfigSource {
+    /// A system-wide config file.
+    System(PathBuf),
+    /// A per-user config file.
+    User(PathBuf),
+    /// The environment variable with the given name.
+    EnvVar(String),
+    /// A directive added by the program using [`ConfigStack::add`].
+    Program,
+}
+
+/// Indicates a [`ConfigStack`] source could not be read.
+#[derive(Debug)]
+pub struct ConfigStackError {
+    source: ConfigSource,
+    kind: ErrorKind,
+}
+
+#[derive(Debug)]
+enum ErrorKind {
+    Io(io::Error),
+    Parse(ParseError),
+}
+
+#[derive(Clone, Debug)]
+struct Layer {
+    source: ConfigSource,
+    directives: Vec<Directive>,
+    profiles: BTreeMap<String, Vec<Directive>>,
+    fields: Vec<(String, String)>,
+}
+
+/// The directives, profiles, and span fields of the merged layers, each
+/// with the index of the layer it came from.
+struct Merged {
+    directives: Vec<(Directive, usize)>,
+    profiles: BTreeMap<String, (Vec<Directive>, usize)>,
+    fields: Vec<(String, String)>,
+}
+
+impl ConfigStack {
+    /// Returns a new, empty `ConfigStack`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a system-wide config file, such as `/etc/my-app/lines.conf`,
+    /// which has the lowest precedence.
+    ///
+    /// The file contains directives in the format accepted by [the `FromStr`
+    /// implementation for `LineFilter`][syntax]. If the file doesn't exist,
+    /// it is skipped.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the file exists but cannot be read, or
+    /// contains an invalid directive.
+    ///
+    /// [syntax]: crate::LineFilter#impl-FromStr-for-LineFilter
+    pub fn system_file(&mut self, path: impl AsRef<Path>) -> Result<&mut Self, ConfigStackError> {
+        let path = path.as_ref().to_path_buf();
+        self.file(ConfigSource::System(path.clone()), &path)
+    }
+
+    /// Adds a per-user config file, such as
+    /// `~/.config/my-app/lines.conf`, which takes precedence over system
+    /// config files.
+    ///
+    /// As with [`system_file`](Self::system_file), a missing file is
+    /// skipped.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the file exists but cannot be read, or
+    /// contains an invalid directive.
+    pub fn user_file(&mut self, path: impl AsRef<Path>) -> Result<&mut Self, ConfigStackError> {
+        let path = path.as_ref().to_path_buf();
+        self.file(ConfigSource::User(path.clone()), &path)
+    }
+
+    /// Adds the directives in the environment variable `name`, which take
+    /// precedence over config files.
+    ///
+    /// If the variable isn't set, it is skipped.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the variable contains an invalid directive.
+    pub fn env_var(&mut self, name: impl Into<String>) -> Result<&mut Self, ConfigStackError> {
+        let name = name.into();
+        let source = ConfigSource::EnvVar(name.clone());
+        match env::var(&name) {
+            Ok(value) => self.parse(source, &value),
+            Err(_) => Ok(self),
+        }
+    }
+
+    /// Adds a directive from the program itself, such as from a
+    /// command-line flag, which takes precedence over every other source.
+    pub fn add(&mut self, directive: Directive) -> &mut Self {
+        match self.layers.last_mut() {
+            Some(layer) if layer.source == ConfigSource::Program => {
+                layer.directives.push(directive)
+            }
+            _ => self.layers.push(Layer {
+                source: ConfigSource::Program,
+                directives: vec![directive],
+                profiles: BTreeMap::new(),
+                fields: Vec::new(),
+            }),
+        }
+        self
+    }
+
+    /// Returns the merged config.
+    pub fn to_config(&self) -> FilterConfig {
+        let merged = self.merge();
+        let directives = merged
+            .directives
+            .into_iter()
+            .map(|(directive, _)| directive);
+        let profiles = merged
+            .profiles
+            .into_iter()
+            .map(|(name, (directives, _))| (name, directives))
+            .collect();
+        FilterConfig::from_parts(directives.collect(), profiles, merged.fields)
+    }
+
+    /// Returns a new [`LineFilter`] with the merged config.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if a `File` directive added using
+    /// [`add`](Self::add) has a path that is not valid for use in a
+    /// `LineFilter`.
+    pub fn to_filter(&self) -> Result<LineFilter, crate::Error> {
+        let mut filter = LineFilter::new();
+        filter.set_config(&self.to_config())?;
+        Ok(filter)
+    }
+
+    /// Returns every directive in the merged config, other than those in
+    /// profiles, along with the source it came from.
+    ///
+    /// Directives appear in the order they would be applied: sources from
+    /// lowest to highest precedence, and each source's directives in order.
+    /// A directive that appears in several sources is attributed to the
+    /// highest-precedence one.
+    pub fn origins(&self) -> Vec<(Directive, ConfigSource)> {
+        self.merge()
+            .directives
+            .into_iter()
+            .map(|(directive, i)| (directive, self.layers[i].source.clone()))
+            .collect()
+    }
+
+    /// Returns the source that the profile named `name` in the merged config
+    /// came from, if it is defined.
+    pub fn profile_origin(&self, name: &str) -> Option<&ConfigSource> {
+        let merged = self.merge();
+        merged
+            .profiles
+            .get(name)
+            .map(|&(_, i)| &self.layers[i].source)
+    }
+
+    fn file(&mut self, source: ConfigSource, path: &Path) -> Result<&mut Self, ConfigStackError> {
+        match fs::read_to_string(path) {
+            Ok(config) => self.parse(source, &config),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(self),
+            Err(e) => Err(ConfigStackError {
+                source,
+                kind: ErrorKind::Io(e),
+            }),
+        }
+    }
+
+    fn parse(&mut self, source: ConfigSource, config: &str) -> Result<&mut Self, ConfigStackError> {
+        let sections = directive::parse_sections(config).map_err(|e| ConfigStackError {
+            source: source.clone(),
+            kind: ErrorKind::Parse(e),
+        })?;
+        sections.record_warnings();
+        self.layers.push(Layer {
+            source,
+            directives: sections.directives,
+            profiles: sections.profiles,
+            fields: sections.fields,
+        });
+        Ok(self)
+    }
+
+    /// Merges the layers, returning the effective directives, profiles, and
+    /// span fields.
+    fn merge(&self) -> Merged {
+        // Sorting is stable, so layers of the same kind stay in the order
+        // they were added.
+        let mut order = (0..self.layers.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| self.layers[i].source.rank());
+
+        #[cfg(feature = "env-filter")]
+        let env_layer = {
+            let has_env = |i: &usize| {
+                self.layers[*i]
+                    .directives
+                    .iter()
+                    .any(|directive| matches!(directive, Directive::Env(_)))
+            };
+            order.iter().copied().rfind(has_env)
+        };
+
+        let mut directives = Vec::<(Directive, usize)>::new();
+        let mut profiles = BTreeMap::new();
+        let mut span_fields = Vec::new();
+        for i in order {
+            let layer = &self.layers[i];
+            for directive in &layer.directives {
+                match directive {
+                    #[cfg(feature = "env-filter")]
+                    Directive::Env(_) if Some(i) != env_layer => continue,
+                    Directive::ModuleLevel { module, .. } => directives.retain(|(d, _)| {
+                        !matches!(d, Directive::ModuleLevel { module: m, .. } if m == module)
+                    }),
+                    _ => directives.retain(|(d, _)| d != directive),
+                }
+                directives.push((directive.clone(), i));
+            }
+            for (name, profile) in &layer.profiles {
+                profiles.insert(name.clone(), (profile.clone(), i));
+            }
+            for (name, value) in &layer.fields {
+                fields::set(&mut span_fields, name.clone(), value.clone());
+            }
+        }
+        Merged {
+            directives,
+            profiles,
+            fields: span_fields,
+        }
+    }
+}
+
+// === impl ConfigSource ===
+
+impl ConfigSource {
+    fn rank(&self) -> u8 {
+        match self {
+            ConfigSource::System(_) => 0,
+            ConfigSource::User(_) => 1,
+            ConfigSource::EnvVar(_) => 2,
+            ConfigSource::Program => 3,
+        }
+    }
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::System(path) => write!(f, "system config '{}'", path.display()),
+            ConfigSource::User(path) => write!(f, "user config '{}'", path.display()),
+            ConfigSource::EnvVar(name) => write!(f, "environment variable `{}`", name),
+            ConfigSource::Program => f.write_str("the program"),
+        }
+    }
+}
+
+// === impl ConfigStackError ===
+
+impl ConfigStackError {
+    /// Returns the source that could not be read.
+    pub fn config_source(&self) -> &ConfigSource {
+        &self.source
+    }
+}
+
+impl fmt::Display for ConfigStackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ErrorKind::Io(ref e) => write!(f, "failed to read {}: {}", self.source, e),
+            ErrorKind::Parse(ref e) => write!(f, "{}: {}", self.source, e),
+        }
+    }
+}
+
+impl Error for ConfigStackError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self.kind {
+            ErrorKind::Io(ref e) => Some(e),
+            ErrorKind::Parse(ref e) => Some(e),
+        }
+    }
+}
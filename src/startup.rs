@@ -0,0 +1,232 @@
+use crate::{
+    command::{parse_duration, FmtDuration},
+    deny::Matched,
+    note,
+    overlay::Owner,
+    time::Instant,
+    Directive, Error, LineFilter, ParseError,
+};
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+    time::Duration,
+};
+
+/// Whether [`LineFilter::mark_ready`] has been called.
+static READY: AtomicBool = AtomicBool::new(false);
+
+/// When startup entries were first added in this process, which startup
+/// windows are measured from.
+static STARTED: OnceLock<Instant> = OnceLock::new();
+
+/// A filter's startup entries, which are only enabled while the program is
+/// starting up.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Startup {
+    directives: Vec<Directive>,
+    /// The directives that adding the startup entries added to the filter.
+    added: Vec<Directive>,
+    window: Option<Duration>,
+}
+
+impl LineFilter {
+    /// Adds entries that are only enabled while the program is starting up,
+    /// for the common "verbose during boot, quiet afterwards" pattern.
+    ///
+    /// The entries stop matching anything once `window` has passed, or once
+    /// [`mark_ready`] is called, whichever comes first; with no `window`,
+    /// they match until `mark_ready` is called. The window is measured from
+    /// the first time startup entries are added in the process, so reloading
+    /// a config (such as with [`set_config`]) doesn't restart it. After
+    /// startup, the entries are left in place, matching nothing, so that the
+    /// filter's config can still be saved with them.
+    ///
+    /// The entries replace the filter's previous startup entries, if it had
+    /// any. Startup entries can also be listed in a config's `[startup]`
+    /// section (see [the `FromStr` implementation for `LineFilter`][syntax]),
+    /// and they are written to that section, rather than with the filter's
+    /// other directives, when the filter is formatted or saved. Directives
+    /// that were already in the filter are left enabled after startup.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if `directives` includes an [`EnvFilter`] or
+    /// `Deny` directive, which can't be limited to startup, or if a `File`
+    /// directive's path is not valid for use in a `LineFilter`, in which case
+    /// the filter is not changed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tracing::Level;
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::prelude::*;
+    ///
+    /// let mut filter = LineFilter::default();
+    /// let boot = format!("{}@debug", module_path!()).parse().unwrap();
+    /// filter
+    ///     .enable_during_startup(vec![boot], Duration::from_secs(600))
+    ///     .unwrap();
+    /// assert!(filter.is_starting_up());
+    /// let _guard = tracing_subscriber::registry().with(filter).set_default();
+    ///
+    /// let loading_config = || tracing::event_enabled!(Level::DEBUG);
+    /// assert!(loading_config());
+    ///
+    /// // Once the program is serving, its config loading goes quiet.
+    /// LineFilter::mark_ready();
+    /// assert!(!loading_config());
+    /// ```
+    ///
+    /// [`mark_ready`]: Self::mark_ready
+    /// [`set_config`]: Self::set_config
+    /// [syntax]: crate::LineFilter#impl-FromStr-for-LineFilter
+    /// [`EnvFilter`]: tracing_subscriber::EnvFilter
+    pub fn enable_during_startup(
+        &mut self,
+        directives: impl IntoIterator<Item = Directive>,
+        window: impl Into<Option<Duration>>,
+    ) -> Result<&mut Self, Error> {
+        let directives = directives.into_iter().collect::<Vec<_>>();
+        for directive in &directives {
+            if let Some(message) = unsupported(directive) {
+                return Err(Error::Parse(ParseError::syntax(
+                    &directive.to_string(),
+                    message,
+                )));
+            }
+            if let Directive::File { file, .. } = directive {
+                self.file_key(Path::new(file))?;
+            }
+        }
+        if !directives.is_empty() {
+            STARTED.get_or_init(Instant::now);
+        }
+
+        self.remove_overlay(&Owner::Startup);
+        let added = self.apply_overlay(Owner::Startup, &directives);
+        self.startup = Startup {
+            directives,
+            added,
+            window: window.into(),
+        };
+        Ok(self)
+    }
+
+    /// Marks the program as ready, ending the startup window of every
+    /// `LineFilter` in the process, so that their [startup entries] stop
+    /// matching anything.
+    ///
+    /// This is typically called once a server has started listening for
+    /// requests. It can't be undone.
+    ///
+    /// [startup entries]: Self::enable_during_startup
+    pub fn mark_ready() {
+        READY.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if [`mark_ready`] has been called in this process.
+    ///
+    /// [`mark_ready`]: Self::mark_ready
+    pub fn is_ready() -> bool {
+        READY.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if this filter has [startup entries], and they are
+    /// still enabled.
+    ///
+    /// [startup entries]: Self::enable_during_startup
+    pub fn is_starting_up(&self) -> bool {
+        !self.startup.directives.is_empty() && !self.startup.is_over()
+    }
+
+    /// Returns `true` if `entry` was added as a startup entry, and startup is
+    /// over.
+    pub(crate) fn is_startup_over(&self, entry: &Matched<'_>) -> bool {
+        !self.startup.added.is_empty()
+            && self.startup.is_over()
+            && self
+                .startup
+                .added
+                .iter()
+                .any(|directive| entry.is(directive))
+    }
+
+    /// Returns `directive` as it should be listed with the filter's other
+    /// directives: if it was added as a startup entry, which is listed in
+    /// the `[startup]` section instead, this is the directive it replaced,
+    /// if any.
+    pub(crate) fn without_startup(&self, directive: Directive) -> Option<Directive> {
+        if self.startup.added.contains(&directive) {
+            self.without_overlay(&Owner::Startup, &directive)
+        } else {
+            Some(directive)
+        }
+    }
+
+    /// Formats this filter's startup entries as a `[startup]` section, to
+    /// follow the filter's own directives in a config file.
+    pub(crate) fn startup_section(&self) -> String {
+        if self.startup.directives.is_empty() {
+            return String::new();
+        }
+        let mut section = format!("\n\n{}", fmt_header(self.startup.window));
+        for directive in &self.startup.directives {
+            section.push_str(&format!("\n{}", directive));
+            if let Some(note) = self.note(directive) {
+                section.push_str(&format!(" {}", note::fmt_clause(note)));
+            }
+        }
+        section
+    }
+}
+
+/// Returns why `directive` can't be a startup entry, if it can't.
+pub(crate) fn unsupported(directive: &Directive) -> Option<&'static str> {
+    match directive {
+        #[cfg(feature = "env-filter")]
+        Directive::Env(_) => Some("startup entries may not be `EnvFilter` directives"),
+        Directive::Deny(_) => Some("startup entries may not be deny directives"),
+        _ => None,
+    }
+}
+
+/// Formats the `[startup]` header, followed by a `window = "..."` line if
+/// the startup window has a length.
+pub(crate) fn fmt_header(window: Option<Duration>) -> String {
+    match window {
+        Some(window) => format!("[startup]\nwindow = \"{}\"", FmtDuration(window)),
+        None => "[startup]".to_owned(),
+    }
+}
+
+/// Parses a `window = "DURATION"` line, returning `None` if `line` isn't
+/// one.
+pub(crate) fn parse_window(line: &str) -> Option<Result<Duration, &'static str>> {
+    let rest = line.trim().strip_prefix("window")?.trim_start();
+    let rest = rest.strip_prefix('=')?.trim_start();
+    let window = rest.strip_prefix('"')?;
+    Some(
+        window
+            .strip_suffix('"')
+            .and_then(parse_duration)
+            .ok_or("expected `window = \"DURATION\"`, such as `window = \"30s\"`"),
+    )
+}
+
+// === impl Startup ===
+
+impl Startup {
+    fn is_over(&self) -> bool {
+        let elapsed = |window: Duration| {
+            STARTED
+                .get()
+                .is_some_and(|started| started.elapsed() >= window)
+        };
+        READY.load(Ordering::Relaxed) || self.window.is_some_and(elapsed)
+    }
+}
@@ -0,0 +1,117 @@
+use tracing_core::{subscriber::Interest, Metadata, Subscriber};
+use tracing_subscriber::layer::{self, Layer};
+
+/// A line filter that can be constructed in `const` and `static` contexts.
+///
+/// Unlike [`LineFilter`], a `StaticLineFilter` is built from `&'static`
+/// slices of `(path, line)` pairs and performs no allocation, either when it
+/// is constructed or when it is used. This makes it suitable for embedded
+/// applications and applications where startup latency matters, where the
+/// set of enabled lines is known at compile time.
+///
+/// Like [`LineFilter::enable_by_mod`], module entries are compared with both
+/// the module path and the target of each span and event.
+///
+/// Since a `StaticLineFilter` cannot be composed with an [`EnvFilter`], spans
+/// and events that are not in one of its lists are always disabled. Lookups
+/// are a linear scan over the provided slices, so a `StaticLineFilter` is
+/// best suited to small sets of lines; for larger sets, prefer
+/// [`LineFilter`].
+///
+/// # Examples
+///
+/// ```
+/// use tracing_line_filter::StaticLineFilter;
+///
+/// mod my_module {
+///     pub fn do_stuff() {
+///         tracing::info!("doing stuff!")
+///         // ...
+///     }
+/// }
+///
+/// static FILTER: StaticLineFilter = StaticLineFilter::new()
+///     .with_modules(&[("my_crate::my_module", 5)]);
+///
+/// use tracing_subscriber::prelude::*;
+///
+/// tracing_subscriber::registry()
+///     .with(tracing_subscriber::fmt::layer())
+///     .with(FILTER)
+///     .init();
+///
+/// // Now, the event is enabled!
+/// my_module::do_stuff();
+/// ```
+///
+/// [`LineFilter`]: crate::LineFilter
+/// [`EnvFilter`]: tracing_subscriber::EnvFilter
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StaticLineFilter {
+    modules: &'static [(&'static str, u32)],
+    files: &'static [(&'static str, u32)],
+}
+
+impl StaticLineFilter {
+    /// Returns a new `StaticLineFilter`.
+    ///
+    /// By default, no spans and events are enabled.
+    pub const fn new() -> Self {
+        Self {
+            modules: &[],
+            files: &[],
+        }
+    }
+
+    /// Sets the list of `(module, line)` pairs enabled by this filter.
+    ///
+    /// Module paths follow the same rules as
+    /// [`LineFilter::enable_by_mod`](crate::LineFilter::enable_by_mod).
+    pub const fn with_modules(self, modules: &'static [(&'static str, u32)]) -> Self {
+        Self { modules, ..self }
+    }
+
+    /// Sets the list of `(file, line)` pairs enabled by this filter.
+    ///
+    /// These file paths must match the file paths emitted by the
+    /// [`std::file!()`] macro. Because this method is a `const fn`, paths are
+    /// *not* validated; see
+    /// [`LineFilter::enable_by_file`](crate::LineFilter::enable_by_file) for
+    /// the rules a valid path must follow.
+    pub const fn with_files(self, files: &'static [(&'static str, u32)]) -> Self {
+        Self { files, ..self }
+    }
+
+    fn contains(&self, metadata: &Metadata<'_>) -> bool {
+        let line = match metadata.line() {
+            Some(line) => line,
+            None => return false,
+        };
+
+        if crate::module_keys(metadata)
+            .any(|module| self.modules.iter().any(|&(m, l)| l == line && m == module))
+        {
+            return true;
+        }
+
+        if let Some(file) = metadata.file() {
+            return self.files.iter().any(|&(f, l)| l == line && f == file);
+        }
+
+        false
+    }
+}
+
+impl<S: Subscriber> Layer<S> for StaticLineFilter {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if self.contains(metadata) {
+            Interest::always()
+        } else {
+            Interest::never()
+        }
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, _: layer::Context<'_, S>) -> bool {
+        self.contains(metadata)
+    }
+}
@@ -0,0 +1,662 @@
+use crate::{
+    time::{Instant, SystemTime, UNIX_EPOCH},
+    Directive, LineFilter, OverheadStats,
+};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    convert::TryFrom,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, OnceLock, RwLock,
+    },
+    time::Duration,
+};
+use tracing_core::{callsite::Identifier, Metadata};
+use tracing_subscriber::reload;
+
+/// Statistics about one of a [`LineFilter`]'s `(module, line)` or
+/// `(file, line)` entries, returned by [`LineFilter::stats`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct EntryStats {
+    directive: Directive,
+    hits: u64,
+    last_hit: Option<SystemTime>,
+    note: Option<String>,
+}
+
+/// Statistics about a [`LineFilter`]'s entries in one module or file,
+/// returned by [`LineFilter::rollup`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LocationStats {
+    location: String,
+    entries: usize,
+    hits: u64,
+    last_hit: Option<SystemTime>,
+}
+
+/// How [`LineFilter::rollup`] groups a filter's entries.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub enum Rollup {
+    /// By the module path of the spans and events they match.
+    Module,
+    /// By the source file of the spans and events they match.
+    File,
+}
+
+/// A snapshot of a [`LineFilter`]'s statistics, returned by
+/// [`LineFilter::stats_snapshot`].
+///
+/// When the `serde` feature flag is enabled, this implements
+/// `serde::Serialize`, so that it can be exported as JSON (such as by the
+/// `filter/stats` method of the `jsonrpc` server).
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct StatsSnapshot {
+    entries: Vec<EntryStats>,
+    modules: Vec<LocationStats>,
+    files: Vec<LocationStats>,
+    expires_at: Option<SystemTime>,
+    suppressed: u64,
+    overhead: Option<OverheadStats>,
+}
+
+/// The hit counters for a filter's entries.
+#[derive(Debug, Default)]
+pub(crate) struct Hits {
+    /// The counters for each registered callsite that an entry matches.
+    callsites: RwLock<HashMap<Identifier, Arc<EntryHits>>>,
+    entries: Mutex<HashMap<Entry, Arc<EntryHits>>>,
+    suppressed: AtomicU64,
+    /// Counts the uses of the filter's entries, so that they can be ordered
+    /// by when they were last used without relying on the system clock.
+    uses: AtomicU64,
+}
+
+#[derive(Debug)]
+struct EntryHits {
+    count: AtomicU64,
+    /// When the entry last matched, in microseconds since the Unix epoch, or
+    /// 0 if it never has.
+    last_hit: AtomicU64,
+    /// The use of the filter's entries that was the entry's last match, or 0
+    /// if it never has matched.
+    last_used: AtomicU64,
+    /// The module path and file of the first callsite the entry matched.
+    location: OnceLock<(Option<&'static str>, Option<&'static str>)>,
+    #[cfg(feature = "metrics")]
+    counter: ::metrics::Counter,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum Entry {
+    Module(Cow<'static, str>, u32),
+    File(Cow<'static, str>, u32),
+}
+
+impl LineFilter {
+    /// Returns statistics about each of this filter's `(module, line)` and
+    /// `(file, line)` entries.
+    ///
+    /// Entries are listed in the same order as the filter's [`Display`]
+    /// output: `module@line` entries first, followed by `file@line` entries,
+    /// each sorted by path and line number.
+    ///
+    /// An entry with no hits doesn't match any span or event that has been
+    /// recorded. Either the code it points at hasn't run yet, or its path or
+    /// line number is wrong. An entry whose last hit was long ago may point
+    /// at code that has moved, or that no longer runs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::{prelude::*, reload};
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter
+    ///     .enable_by_mod(module_path!(), line!() + 7)
+    ///     .enable_by_mod(module_path!(), 1000);
+    /// let (filter, handle) = reload::Layer::new(filter);
+    /// let subscriber = tracing_subscriber::registry().with(filter);
+    ///
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     for _ in 0..3 {
+    ///         tracing::info!("pinned");
+    ///     }
+    ///
+    ///     let stats = handle.with_current(LineFilter::stats).unwrap();
+    ///     assert_eq!(stats[0].hits(), 3);
+    ///     assert!(stats[0].last_hit().is_some());
+    ///     // Nothing is on line 1000.
+    ///     assert_eq!(stats[1].hits(), 0);
+    ///     assert_eq!(stats[1].last_hit(), None);
+    /// });
+    /// ```
+    ///
+    /// [`Display`]: std::fmt::Display
+    pub fn stats(&self) -> Vec<EntryStats> {
+        let mut modules = self.by_module.iter().collect::<Vec<_>>();
+        modules.sort();
+        let mut files = self.by_file.iter().collect::<Vec<_>>();
+        files.sort();
+
+        let hits = self.hits.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let stats = |directive: Directive, entry: Entry| {
+            let mut stats = EntryStats {
+                note: self.note(&directive).map(str::to_owned),
+                directive,
+                hits: 0,
+                last_hit: None,
+            };
+            if let Some(hits) = hits.get(&entry) {
+                stats.hits = hits.count.load(Ordering::Relaxed);
+                stats.last_hit = hits.last_hit();
+            }
+            stats
+        };
+        let modules = modules.into_iter().map(|entry| {
+            let directive = self.module_directive(entry);
+            stats(directive, Entry::Module(entry.0.clone(), entry.1))
+        });
+        let files = files.into_iter().map(|entry| {
+            let directive = self.file_directive(entry);
+            stats(directive, Entry::File(entry.0.clone(), entry.1))
+        });
+        modules.chain(files).collect()
+    }
+
+    /// Returns statistics about this filter's entries, added up for each
+    /// module or file, with the noisiest first.
+    ///
+    /// This helps with deciding which entries to remove, or to replace with
+    /// narrower ones, when pinned lines produce more output than expected.
+    /// Each location's hits are the total for all of its entries, and its
+    /// last hit is the latest of theirs. Locations with the same number of
+    /// hits are sorted by path.
+    ///
+    /// A `module@line` entry's file, and a `file@line` entry's module, are
+    /// those of the first callsite that the entry matched, so an entry that
+    /// hasn't matched a registered callsite is only counted for the kind of
+    /// location it names. Use [`noisiest`] for just the first few locations.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::{LineFilter, Rollup};
+    /// use tracing_subscriber::{prelude::*, reload};
+    ///
+    /// mod db {
+    ///     pub fn query() {
+    ///         tracing::info!("querying");
+    ///         tracing::info!("queried");
+    ///     }
+    /// }
+    ///
+    /// let db = format!("{}::db", module_path!());
+    /// let mut filter = LineFilter::default();
+    /// filter
+    ///     .enable_by_mod(db.clone(), line!() - 8)
+    ///     .enable_by_mod(db.clone(), line!() - 8)
+    ///     .enable_by_mod(module_path!(), line!() + 7);
+    /// let (filter, handle) = reload::Layer::new(filter);
+    /// let _guard = tracing_subscriber::registry().with(filter).set_default();
+    ///
+    /// for _ in 0..3 {
+    ///     db::query();
+    /// }
+    /// tracing::info!("done");
+    ///
+    /// let modules = handle.with_current(|filter| filter.rollup(Rollup::Module)).unwrap();
+    /// assert_eq!(modules[0].location(), db);
+    /// assert_eq!(modules[0].entries(), 2);
+    /// assert_eq!(modules[0].hits(), 6);
+    /// assert_eq!(modules[1].hits(), 1);
+    ///
+    /// // Both modules are in the same file.
+    /// let files = handle.with_current(|filter| filter.rollup(Rollup::File)).unwrap();
+    /// assert_eq!(files.len(), 1);
+    /// assert_eq!(files[0].hits(), 7);
+    /// ```
+    ///
+    /// [`noisiest`]: Self::noisiest
+    pub fn rollup(&self, by: Rollup) -> Vec<LocationStats> {
+        let hits = self.hits.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let entries = self
+            .by_module
+            .iter()
+            .map(|(module, line)| Entry::Module(module.clone(), *line))
+            .chain(
+                self.by_file
+                    .iter()
+                    .map(|(file, line)| Entry::File(file.clone(), *line)),
+            );
+
+        let mut locations = BTreeMap::<String, LocationStats>::new();
+        for entry in entries {
+            let counter = hits.get(&entry);
+            let (module, file) = counter
+                .and_then(|counter| counter.location.get().copied())
+                .unwrap_or_default();
+            let location = match (by, &entry) {
+                (Rollup::Module, Entry::Module(module, _)) => Some(&**module),
+                (Rollup::Module, Entry::File(..)) => module,
+                (Rollup::File, Entry::File(file, _)) => Some(&**file),
+                (Rollup::File, Entry::Module(..)) => file,
+            };
+            let location = match location {
+                Some(location) => location,
+                None => continue,
+            };
+            let stats = locations
+                .entry(location.to_owned())
+                .or_insert_with(|| LocationStats {
+                    location: location.to_owned(),
+                    entries: 0,
+                    hits: 0,
+                    last_hit: None,
+                });
+            stats.entries += 1;
+            if let Some(counter) = counter {
+                stats.hits += counter.count.load(Ordering::Relaxed);
+                stats.last_hit = stats.last_hit.max(counter.last_hit());
+            }
+        }
+
+        let mut locations = locations.into_values().collect::<Vec<_>>();
+        // The sort is stable, so ties stay sorted by path.
+        locations.sort_by_key(|location| std::cmp::Reverse(location.hits));
+        locations
+    }
+
+    /// Returns the `n` modules or files with the most hits, as listed by
+    /// [`rollup`].
+    ///
+    /// [`rollup`]: Self::rollup
+    pub fn noisiest(&self, by: Rollup, n: usize) -> Vec<LocationStats> {
+        let mut locations = self.rollup(by);
+        locations.truncate(n);
+        locations
+    }
+
+    /// Returns a snapshot of this filter's statistics: each entry's
+    /// [`stats`], the same statistics added up for each module and file (see
+    /// [`rollup`]), when the entries expire, how many spans and events the
+    /// filter has disabled, and its [`overhead`] (if it is being measured).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::{prelude::*, reload, EnvFilter};
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter
+    ///     .enable_by_mod(module_path!(), line!() + 8)
+    ///     // Filter events by their field values, so that each event is
+    ///     // checked individually.
+    ///     .with_env_filter(EnvFilter::new("[{pinned}]=info"));
+    /// let (filter, handle) = reload::Layer::new(filter);
+    /// let subscriber = tracing_subscriber::registry().with(filter);
+    ///
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info!("pinned");
+    ///     tracing::info!("not pinned");
+    ///
+    ///     let snapshot = handle.with_current(LineFilter::stats_snapshot).unwrap();
+    ///     assert_eq!(snapshot.entries()[0].hits(), 1);
+    ///     assert_eq!(snapshot.suppressed(), 1);
+    ///     assert_eq!(snapshot.expires_at(), None);
+    /// });
+    /// ```
+    ///
+    /// [`stats`]: Self::stats
+    /// [`rollup`]: Self::rollup
+    /// [`overhead`]: Self::overhead
+    pub fn stats_snapshot(&self) -> StatsSnapshot {
+        let expires_at = self.expires.map(|deadline| {
+            let now = Instant::now();
+            match deadline.checked_duration_since(now) {
+                Some(ttl) => SystemTime::now() + ttl,
+                None => SystemTime::now() - now.duration_since(deadline),
+            }
+        });
+        StatsSnapshot {
+            entries: self.stats(),
+            modules: self.rollup(Rollup::Module),
+            files: self.rollup(Rollup::File),
+            expires_at,
+            suppressed: self.hits.suppressed.load(Ordering::Relaxed),
+            overhead: self.overhead(),
+        }
+    }
+
+    /// Returns the `Module` and `File` directives whose entries don't match
+    /// any callsite that has been registered with this filter.
+    ///
+    /// `tracing` registers each span and event callsite the first time it is
+    /// reached, so once a program has been running for a while (such as
+    /// after startup, or after a request has been served), a stale entry
+    /// usually means that its module path has a typo or its line number has
+    /// drifted as the code around it changed. Use
+    /// [`warn_stale_entries`] to report them as warning events.
+    ///
+    /// Directives are listed in the same order as [`stats`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::{Directive, LineFilter};
+    /// use tracing_subscriber::{prelude::*, reload};
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter
+    ///     .enable_by_mod(module_path!(), line!() + 6)
+    ///     .enable_by_mod("my_crte::db", 142);
+    /// let (filter, handle) = reload::Layer::new(filter);
+    /// let subscriber = tracing_subscriber::registry().with(filter);
+    ///
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info!("pinned");
+    ///
+    ///     let stale = handle.with_current(LineFilter::stale_entries).unwrap();
+    ///     let typo: Directive = "my_crte::db@142".parse().unwrap();
+    ///     assert_eq!(stale, vec![typo]);
+    /// });
+    /// ```
+    ///
+    /// [`warn_stale_entries`]: Self::warn_stale_entries
+    /// [`stats`]: Self::stats
+    pub fn stale_entries(&self) -> Vec<Directive> {
+        let mut modules = self.by_module.iter().collect::<Vec<_>>();
+        modules.sort();
+        let mut files = self.by_file.iter().collect::<Vec<_>>();
+        files.sort();
+
+        let hits = self.hits.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let modules = modules
+            .into_iter()
+            .filter(|entry| !hits.contains_key(&Entry::Module(entry.0.clone(), entry.1)))
+            .map(|entry| self.module_directive(entry));
+        let files = files
+            .into_iter()
+            .filter(|entry| !hits.contains_key(&Entry::File(entry.0.clone(), entry.1)))
+            .map(|entry| self.file_directive(entry));
+        modules.chain(files).collect()
+    }
+
+    /// Records a `WARN` event for each of the [stale entries] of the filter
+    /// behind a [`reload`] handle, returning their directives.
+    ///
+    /// Each event has the `tracing_line_filter` target and a `directive`
+    /// field containing the stale directive. The events are recorded after
+    /// the filter has been read, since recording an event while the filter
+    /// is in use by [`Handle::with_current`] could deadlock.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the subscriber containing the filter has
+    /// been dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::{prelude::*, reload};
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter.enable_by_mod("my_crte::db", 142);
+    /// let (filter, handle) = reload::Layer::new(filter);
+    /// tracing_subscriber::registry()
+    ///     .with(filter)
+    ///     .with(tracing_subscriber::fmt::layer())
+    ///     .init();
+    ///
+    /// // Once startup has finished...
+    /// let stale = LineFilter::warn_stale_entries(&handle).unwrap();
+    /// assert_eq!(stale.len(), 1);
+    /// ```
+    ///
+    /// [stale entries]: Self::stale_entries
+    /// [`reload`]: tracing_subscriber::reload
+    /// [`Handle::with_current`]: tracing_subscriber::reload::Handle::with_current
+    pub fn warn_stale_entries<S>(
+        handle: &reload::Handle<Self, S>,
+    ) -> Result<Vec<Directive>, reload::Error> {
+        let stale = handle.with_current(Self::stale_entries)?;
+        for directive in &stale {
+            tracing::warn!(
+                target: "tracing_line_filter",
+                %directive,
+                "filter entry does not match any registered callsite; check its path and line number",
+            );
+        }
+        Ok(stale)
+    }
+
+    /// Returns the entry that matches `metadata`, if there is one.
+    pub(crate) fn entry_for(&self, metadata: &'static Metadata<'static>) -> Option<Entry> {
+        let line = metadata.line()?;
+        let allows = |location: &str| self.entry_allows(location, line, metadata.level());
+        for key in crate::module_keys(metadata) {
+            if self.by_module.contains(&(Cow::Borrowed(key), line)) && allows(key) {
+                return Some(Entry::Module(Cow::Borrowed(key), line));
+            }
+        }
+        if let Some(file) = metadata.file() {
+            if self.by_file.contains(&(Cow::Borrowed(file), line)) && allows(file) {
+                return Some(Entry::File(Cow::Borrowed(file), line));
+            }
+            let module = self.module_map.mapped_module(&self.by_module, file, line);
+            if let Some(module) = module.filter(|module| allows(module)) {
+                return Some(Entry::Module(Cow::Owned(module.to_owned()), line));
+            }
+        }
+        let module = metadata.module_path()?;
+        let file = self.module_map.mapped_file(&self.by_file, module, line)?;
+        allows(file).then(|| Entry::File(Cow::Owned(file.to_owned()), line))
+    }
+}
+
+// === impl EntryStats ===
+
+impl EntryStats {
+    /// Returns the directive for this entry.
+    pub fn directive(&self) -> &Directive {
+        &self.directive
+    }
+
+    /// Returns the number of spans and events this entry has enabled.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Returns when this entry last enabled a span or event, or `None` if it
+    /// never has.
+    pub fn last_hit(&self) -> Option<SystemTime> {
+        self.last_hit
+    }
+
+    /// Returns the note attached to this entry's directive, if it has one.
+    ///
+    /// See [`LineFilter::set_note`] for details on notes.
+    pub fn note(&self) -> Option<&str> {
+        self.note.as_deref()
+    }
+}
+
+// === impl LocationStats ===
+
+impl LocationStats {
+    /// Returns the module path or file.
+    pub fn location(&self) -> &str {
+        &self.location
+    }
+
+    /// Returns the number of the filter's entries in this location.
+    pub fn entries(&self) -> usize {
+        self.entries
+    }
+
+    /// Returns the number of spans and events the entries in this location
+    /// have enabled.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Returns when an entry in this location last enabled a span or event,
+    /// or `None` if none of them ever has.
+    pub fn last_hit(&self) -> Option<SystemTime> {
+        self.last_hit
+    }
+}
+
+// === impl StatsSnapshot ===
+
+impl StatsSnapshot {
+    /// Returns the [`EntryStats`] for each of the filter's entries, in the
+    /// same order as [`LineFilter::stats`].
+    pub fn entries(&self) -> &[EntryStats] {
+        &self.entries
+    }
+
+    /// Returns the statistics for each module with entries, as listed by
+    /// [`LineFilter::rollup`].
+    pub fn modules(&self) -> &[LocationStats] {
+        &self.modules
+    }
+
+    /// Returns the statistics for each file with entries, as listed by
+    /// [`LineFilter::rollup`].
+    pub fn files(&self) -> &[LocationStats] {
+        &self.files
+    }
+
+    /// Returns when the filter's entries expire, or `None` if they don't.
+    ///
+    /// See [`LineFilter::expire_at`] for details.
+    pub fn expires_at(&self) -> Option<SystemTime> {
+        self.expires_at
+    }
+
+    /// Returns the number of spans and events that the filter has disabled.
+    ///
+    /// `tracing` only asks the filter about most callsites once, so this
+    /// only counts spans and events that had to be checked individually,
+    /// like the `tracing_line_filter_suppressed_total` metric.
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed
+    }
+
+    /// Returns how long the filter has spent deciding whether to enable
+    /// spans and events, or `None` if [`LineFilter::measure_overhead`]
+    /// hasn't been called.
+    pub fn overhead(&self) -> Option<&OverheadStats> {
+        self.overhead.as_ref()
+    }
+}
+
+// === impl Hits ===
+
+impl Hits {
+    /// Updates the counter for a callsite after it has been (re-)registered.
+    pub(crate) fn register(&self, filter: &LineFilter, metadata: &'static Metadata<'static>) {
+        let entry = filter.entry_for(metadata);
+        let mut callsites = self.callsites.write().unwrap_or_else(|e| e.into_inner());
+        match entry {
+            Some(entry) => {
+                let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+                let hits = entries
+                    .entry(entry)
+                    .or_insert_with_key(|entry| Arc::new(EntryHits::new(entry)));
+                hits.location
+                    .get_or_init(|| (metadata.module_path(), metadata.file()));
+                callsites.insert(metadata.callsite(), hits.clone());
+            }
+            None => {
+                callsites.remove(&metadata.callsite());
+            }
+        }
+    }
+
+    /// Counts a span or event recorded from a callsite.
+    pub(crate) fn record(&self, metadata: &Metadata<'_>) {
+        let callsites = self.callsites.read().unwrap_or_else(|e| e.into_inner());
+        if let Some(hits) = callsites.get(&metadata.callsite()) {
+            hits.count.fetch_add(1, Ordering::Relaxed);
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            // Never store 0, which means the entry hasn't been hit.
+            let micros = u64::try_from(now.as_micros()).unwrap_or(u64::MAX).max(1);
+            hits.last_hit.store(micros, Ordering::Relaxed);
+            hits.last_used.store(self.next_use(), Ordering::Relaxed);
+            #[cfg(feature = "metrics")]
+            hits.counter.increment(1);
+        }
+    }
+
+    /// Returns the use that was `entry`'s last match, or 0 if it never has
+    /// matched.
+    pub(crate) fn last_used(&self, entry: &Entry) -> u64 {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .get(entry)
+            .map_or(0, |hits| hits.last_used.load(Ordering::Relaxed))
+    }
+
+    /// Counts a use of one of the filter's entries, returning a number
+    /// greater than that of every use before it.
+    pub(crate) fn next_use(&self) -> u64 {
+        self.uses.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Returns `Hits` that haven't counted anything yet, but whose uses
+    /// come after this one's, so that entries can still be ordered by the
+    /// uses recorded before.
+    pub(crate) fn unrecorded(&self) -> Self {
+        Self {
+            uses: AtomicU64::new(self.uses.load(Ordering::Relaxed)),
+            ..Self::default()
+        }
+    }
+
+    /// Counts a span or event that the filter disabled.
+    pub(crate) fn record_suppressed(&self) {
+        self.suppressed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Resets the counter for an entry that was removed.
+    pub(crate) fn remove(&self, entry: &Entry) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.remove(entry);
+    }
+}
+
+// === impl EntryHits ===
+
+impl EntryHits {
+    fn new(entry: &Entry) -> Self {
+        #[cfg(not(feature = "metrics"))]
+        let _ = entry;
+        Self {
+            count: AtomicU64::new(0),
+            last_hit: AtomicU64::new(0),
+            last_used: AtomicU64::new(0),
+            location: OnceLock::new(),
+            #[cfg(feature = "metrics")]
+            counter: crate::metrics::counter(entry),
+        }
+    }
+
+    fn last_hit(&self) -> Option<SystemTime> {
+        match self.last_hit.load(Ordering::Relaxed) {
+            0 => None,
+            micros => Some(UNIX_EPOCH + Duration::from_micros(micros)),
+        }
+    }
+}
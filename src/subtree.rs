@@ -0,0 +1,184 @@
+use crate::{entered::points_at, Directive, Error, LineFilter};
+use std::{collections::HashSet, fmt, sync::RwLock};
+use tracing_core::{span, Subscriber};
+use tracing_subscriber::layer;
+
+/// The roots added by [`LineFilter::enable_subtree`], and the open spans
+/// that descend from them.
+#[derive(Default)]
+pub(crate) struct Subtrees {
+    roots: Vec<Directive>,
+    /// The open spans that are roots, or whose parent is in a subtree.
+    spans: RwLock<HashSet<span::Id>>,
+}
+
+impl LineFilter {
+    /// Enable the span matched by `root`, along with every span and event
+    /// that descends from it, wherever they are recorded.
+    ///
+    /// This enables a whole call tree with a single entry, such as all of
+    /// the work done on behalf of one kind of request. Descendants are found
+    /// by following `tracing`'s parent relationships, rather than by which
+    /// thread the root span is entered on: a span is in the subtree if its
+    /// parent is, and an event is in the subtree if the span that is entered
+    /// when it is recorded is. The root is added as if by [`add_directive`],
+    /// and the rest of the subtree is enabled as though by an entry that is
+    /// less specific than any other, so deny entries still disable parts of
+    /// it.
+    ///
+    /// Since a span's descendants are linked to it by the spans' IDs, a
+    /// subtree follows work that hops between threads and executors as long
+    /// as that work is wrapped in a span from the subtree, such as by
+    /// [`Instrument::instrument`] or [`#[instrument]`][attr]. Spans and events
+    /// that name their parent explicitly (with `parent:`) are judged by the
+    /// span that is entered when they are created instead. While a filter
+    /// has subtrees, it asks about every span and event that no other entry
+    /// matches each time, since any of them could descend from a root. Use
+    /// [`clear_subtrees`] to remove the roots' subtrees, leaving the roots'
+    /// entries in place.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if `root` is a `File` directive whose path is
+    /// not valid for use in a `LineFilter`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::prelude::*;
+    ///
+    /// let root = format!("{}@{}", module_path!(), line!() + 1);
+    /// let request = || tracing::info_span!("request");
+    ///
+    /// let mut filter = LineFilter::default();
+    /// filter.enable_subtree(root.parse().unwrap()).unwrap();
+    /// let subscriber = tracing_subscriber::registry().with(filter);
+    ///
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let request = request();
+    ///     let query = request.in_scope(|| tracing::debug_span!("query"));
+    ///     assert!(!query.is_disabled());
+    ///
+    ///     // The query's span is in the subtree on other threads too.
+    ///     let dispatch = tracing::dispatcher::get_default(|dispatch| dispatch.clone());
+    ///     std::thread::spawn(move || {
+    ///         tracing::dispatcher::with_default(&dispatch, || {
+    ///             let rows = query.in_scope(|| tracing::trace_span!("rows"));
+    ///             assert!(!rows.is_disabled());
+    ///         });
+    ///     })
+    ///     .join()
+    ///     .unwrap();
+    ///
+    ///     assert!(tracing::debug_span!("unrelated").is_disabled());
+    /// });
+    /// ```
+    ///
+    /// [`add_directive`]: Self::add_directive
+    /// [`Instrument::instrument`]: https://docs.rs/tracing/latest/tracing/trait.Instrument.html#method.instrument
+    /// [attr]: https://docs.rs/tracing/latest/tracing/attr.instrument.html
+    /// [`clear_subtrees`]: Self::clear_subtrees
+    pub fn enable_subtree(&mut self, root: Directive) -> Result<&mut Self, Error> {
+        self.add_directive(root.clone())?;
+        self.subtrees.roots.push(root);
+        Ok(self)
+    }
+
+    /// Removes the subtrees added by [`enable_subtree`], leaving the entries
+    /// for their roots in place.
+    ///
+    /// [`enable_subtree`]: Self::enable_subtree
+    pub fn clear_subtrees(&mut self) -> &mut Self {
+        self.subtrees.roots.clear();
+        self
+    }
+
+    /// Returns `true` if this filter has subtrees.
+    pub(crate) fn has_subtrees(&self) -> bool {
+        !self.subtrees.roots.is_empty()
+    }
+
+    /// Returns `true` if the span that is entered in `cx` is in one of this
+    /// filter's subtrees.
+    pub(crate) fn is_in_subtree<S>(&self, cx: &layer::Context<'_, S>) -> bool
+    where
+        S: Subscriber,
+    {
+        if !self.has_subtrees() || self.is_disabled() || self.is_expired() {
+            return false;
+        }
+        let current = cx.current_span();
+        let id = match current.id() {
+            Some(id) => id,
+            None => return false,
+        };
+        let spans = self
+            .subtrees
+            .spans
+            .read()
+            .unwrap_or_else(|e| e.into_inner());
+        spans.contains(id)
+    }
+
+    /// Adds a newly created span to the subtree of its parent, or starts a
+    /// subtree if it is a root.
+    pub(crate) fn subtree_span_created<S>(
+        &self,
+        attrs: &span::Attributes<'_>,
+        id: &span::Id,
+        cx: &layer::Context<'_, S>,
+    ) where
+        S: Subscriber,
+    {
+        if !self.has_subtrees() {
+            return;
+        }
+        let is_root = self
+            .subtrees
+            .roots
+            .iter()
+            .any(|root| points_at(root, attrs.metadata()));
+        if !is_root && !self.is_in_subtree(cx) {
+            return;
+        }
+        let mut spans = self
+            .subtrees
+            .spans
+            .write()
+            .unwrap_or_else(|e| e.into_inner());
+        spans.insert(id.clone());
+    }
+
+    /// Forgets a span that was closed, so that its ID can be reused.
+    pub(crate) fn subtree_span_closed(&self, id: &span::Id) {
+        if !self.has_subtrees() {
+            return;
+        }
+        let mut spans = self
+            .subtrees
+            .spans
+            .write()
+            .unwrap_or_else(|e| e.into_inner());
+        spans.remove(id);
+    }
+}
+
+// === impl Subtrees ===
+
+impl Clone for Subtrees {
+    fn clone(&self) -> Self {
+        // The spans are the ones the filter has seen since it was attached to
+        // a subscriber.
+        Self {
+            roots: self.roots.clone(),
+            spans: Default::default(),
+        }
+    }
+}
+
+impl fmt::Debug for Subtrees {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(&self.roots).finish()
+    }
+}
@@ -0,0 +1,303 @@
+//! Utilities for testing filter configurations.
+//!
+//! Applications that ship a filter config, or build one from their own
+//! settings, can check which spans and events it enables without writing a
+//! mock subscriber. The [`assert_enabled!`] and [`assert_disabled!`] macros
+//! ask a [`LineFilter`] about a `module@line` location directly, and a
+//! [`Capture`] layer records the spans and events that a filter allows
+//! through to a subscriber, for tests that run the code being filtered.
+//!
+//! When the `tracing-mock` feature flag is also enabled, [`mock_subscriber`]
+//! and [`run_with_mock`] combine a filter with a [`tracing-mock`] layer, for
+//! tests that assert on each span and event the filter enables, in order.
+//!
+//! This module is only available when the `test-util` feature flag is
+//! enabled, which is usually only needed in `[dev-dependencies]`.
+//!
+//! # Examples
+//!
+//! ```
+//! use tracing_line_filter::{
+//!     assert_disabled, assert_enabled, test_util::Capture, LineFilter,
+//! };
+//!
+//! let filter: LineFilter = "my_crate::db@142, my_crate::auth=info".parse().unwrap();
+//! assert_enabled!(filter, "my_crate::db", 142);
+//! assert_disabled!(filter, "my_crate::db", 143);
+//! assert_enabled!(filter, "my_crate::auth", 17, tracing::Level::INFO);
+//! assert_disabled!(filter, "my_crate::auth", 17, tracing::Level::DEBUG);
+//!
+//! let mut filter = LineFilter::default();
+//! filter.enable_by_mod(module_path!(), line!() + 4);
+//! let capture = Capture::new();
+//! tracing::subscriber::with_default(capture.subscriber(filter), || {
+//!     tracing::info!("not pinned");
+//!     tracing::info!("pinned");
+//! });
+//! assert_eq!(capture.len(), 1);
+//! assert!(capture.contains(module_path!(), line!() - 3));
+//! ```
+//!
+//! [`assert_enabled!`]: crate::assert_enabled
+//! [`assert_disabled!`]: crate::assert_disabled
+//! [`LineFilter`]: crate::LineFilter
+//! [`mock_subscriber`]: https://docs.rs/tracing-line-filter/latest/tracing_line_filter/test_util/fn.mock_subscriber.html
+//! [`run_with_mock`]: https://docs.rs/tracing-line-filter/latest/tracing_line_filter/test_util/fn.run_with_mock.html
+//! [`tracing-mock`]: https://docs.rs/tracing-mock
+
+use crate::{CallsiteDesc, LineFilter};
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+use tracing_core::{span, Event, Level, Metadata, Subscriber};
+use tracing_subscriber::{
+    layer::{self, Layer, SubscriberExt},
+    registry::Registry,
+};
+
+/// Asserts that a [`LineFilter`] enables the spans and events on a line of a
+/// module.
+///
+/// `assert_enabled!(filter, module, line)` checks a `TRACE` event on line
+/// `line` of the module at `module`, and `assert_enabled!(filter, module,
+/// line, level)` checks an event at `level`, for entries that only enable
+/// some levels (such as `module@level` entries and `EnvFilter` directives).
+/// The location is checked as if by [`LineFilter::explain`], so the
+/// filter's own entries, deny entries, and `EnvFilter` are all considered,
+/// and a failed assertion's message says why the location was disabled.
+///
+/// Since the location has no file path, `file@line` entries never match it.
+///
+/// This macro is only available when the `test-util` feature flag is
+/// enabled. See [the `test_util` module](crate::test_util) for examples.
+///
+/// [`LineFilter`]: crate::LineFilter
+/// [`LineFilter::explain`]: crate::LineFilter::explain
+#[macro_export]
+macro_rules! assert_enabled {
+    ($filter:expr, $module:expr, $line:expr $(,)?) => {
+        $crate::test_util::__assert(&$filter, $module, $line, None, true)
+    };
+    ($filter:expr, $module:expr, $line:expr, $level:expr $(,)?) => {
+        $crate::test_util::__assert(&$filter, $module, $line, Some($level), true)
+    };
+}
+
+/// Asserts that a [`LineFilter`] disables the spans and events on a line of
+/// a module.
+///
+/// This is the opposite of [`assert_enabled!`], and takes the same
+/// arguments.
+///
+/// This macro is only available when the `test-util` feature flag is
+/// enabled.
+///
+/// [`LineFilter`]: crate::LineFilter
+/// [`assert_enabled!`]: crate::assert_enabled
+#[macro_export]
+macro_rules! assert_disabled {
+    ($filter:expr, $module:expr, $line:expr $(,)?) => {
+        $crate::test_util::__assert(&$filter, $module, $line, None, false)
+    };
+    ($filter:expr, $module:expr, $line:expr, $level:expr $(,)?) => {
+        $crate::test_util::__assert(&$filter, $module, $line, Some($level), false)
+    };
+}
+
+/// A [`Layer`] that records the spans and events that reach it.
+///
+/// Layered on top of a [`LineFilter`], which disables spans and events for
+/// the whole subscriber, a `Capture` sees exactly the spans and events that
+/// the filter allowed. Clones of a `Capture` share the same records, so a
+/// test can keep one clone and give the other to a subscriber, such as the
+/// one returned by [`subscriber`](Self::subscriber).
+///
+/// Each span is recorded when it is created, and each event when it is
+/// recorded, in order.
+#[derive(Clone, Default)]
+pub struct Capture {
+    records: Arc<Mutex<Vec<&'static Metadata<'static>>>>,
+}
+
+/// Returns a subscriber that filters spans and events with `filter`, and
+/// checks the ones it enables against the expectations of a `tracing-mock`
+/// layer, along with the handle for asserting that they were all met.
+///
+/// Since the filter disables spans and events for the whole subscriber, the
+/// mock layer only sees the ones that the filter allowed, so its
+/// expectations (and [`MockLayerBuilder::only`]) describe exactly what the
+/// filter enables.
+///
+/// This function is only available when the `tracing-mock` feature flag is
+/// enabled.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_line_filter::{test_util, LineFilter};
+/// use tracing_mock::{expect, layer};
+///
+/// let mut filter = LineFilter::default();
+/// filter.enable_by_mod(module_path!(), line!() + 8);
+/// let mock = layer::mock()
+///     .event(expect::event().with_fields(expect::msg("pinned")))
+///     .only();
+///
+/// let (subscriber, handle) = test_util::mock_subscriber(filter, mock);
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info!("not pinned");
+///     tracing::info!("pinned");
+/// });
+/// handle.assert_finished();
+/// ```
+///
+/// [`MockLayerBuilder::only`]: tracing_mock::layer::MockLayerBuilder::only
+#[cfg(feature = "tracing-mock")]
+pub fn mock_subscriber(
+    filter: LineFilter,
+    mock: tracing_mock::layer::MockLayerBuilder,
+) -> (
+    impl Subscriber + Send + Sync,
+    tracing_mock::subscriber::MockHandle,
+) {
+    let (layer, handle) = mock.run_with_handle();
+    (Registry::default().with(filter).with(layer), handle)
+}
+
+/// Runs `f` with a subscriber that filters spans and events with `filter`,
+/// and then asserts that the ones it enabled met all of `mock`'s
+/// expectations.
+///
+/// This is a shorthand for [`mock_subscriber`] followed by
+/// [`MockHandle::assert_finished`], for tests that don't need the handle.
+///
+/// This function is only available when the `tracing-mock` feature flag is
+/// enabled.
+///
+/// # Panics
+///
+/// If a span or event that `filter` enables doesn't match the next of
+/// `mock`'s expectations, or if any expectations are left once `f` returns.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_line_filter::{test_util, LineFilter};
+/// use tracing_mock::{expect, layer};
+///
+/// let filter: LineFilter = format!("{}@warn", module_path!()).parse().unwrap();
+/// let mock = layer::mock()
+///     .event(expect::event().at_level(tracing::Level::WARN))
+///     .event(expect::event().at_level(tracing::Level::ERROR))
+///     .only();
+///
+/// test_util::run_with_mock(filter, mock, || {
+///     tracing::info!("disabled");
+///     tracing::warn!("enabled");
+///     tracing::error!("also enabled");
+/// });
+/// ```
+///
+/// [`MockHandle::assert_finished`]: tracing_mock::subscriber::MockHandle::assert_finished
+#[cfg(feature = "tracing-mock")]
+#[track_caller]
+pub fn run_with_mock(
+    filter: LineFilter,
+    mock: tracing_mock::layer::MockLayerBuilder,
+    f: impl FnOnce(),
+) {
+    let (subscriber, handle) = mock_subscriber(filter, mock);
+    tracing::subscriber::with_default(subscriber, f);
+    handle.assert_finished();
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn __assert(filter: &LineFilter, module: &str, line: u32, level: Option<Level>, enabled: bool) {
+    let level = level.unwrap_or(Level::TRACE);
+    let explanation = filter.explain(CallsiteDesc::event(module, line, level).metadata());
+    if explanation.is_enabled() != enabled {
+        panic!(
+            "expected {} events on `{}@{}` to be {}, but they are {}: {}",
+            level,
+            module,
+            line,
+            if enabled { "enabled" } else { "disabled" },
+            if enabled { "disabled" } else { "enabled" },
+            explanation,
+        );
+    }
+}
+
+// === impl Capture ===
+
+impl Capture {
+    /// Returns a new `Capture` with no records.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a subscriber that filters spans and events with `filter`, and
+    /// records the ones it enables in this `Capture`.
+    pub fn subscriber(&self, filter: LineFilter) -> impl Subscriber + Send + Sync {
+        Registry::default().with(filter).with(self.clone())
+    }
+
+    /// Returns the metadata of the recorded spans and events, in the order
+    /// they were recorded.
+    pub fn metadata(&self) -> Vec<&'static Metadata<'static>> {
+        self.lock().clone()
+    }
+
+    /// Returns `true` if a span or event on line `line` of the module at
+    /// `module` was recorded.
+    pub fn contains(&self, module: &str, line: u32) -> bool {
+        self.lock()
+            .iter()
+            .any(|metadata| metadata.module_path() == Some(module) && metadata.line() == Some(line))
+    }
+
+    /// Returns the number of recorded spans and events.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns `true` if no spans or events were recorded.
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Removes all of the records.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<&'static Metadata<'static>>> {
+        self.records.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<S: Subscriber> Layer<S> for Capture {
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, _: &span::Id, _: layer::Context<'_, S>) {
+        self.lock().push(attrs.metadata());
+    }
+
+    fn on_event(&self, event: &Event<'_>, _: layer::Context<'_, S>) {
+        self.lock().push(event.metadata());
+    }
+}
+
+impl fmt::Debug for Capture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.lock().iter().map(|metadata| {
+                format!(
+                    "{}@{}",
+                    crate::module_key(metadata),
+                    metadata.line().unwrap_or_default(),
+                )
+            }))
+            .finish()
+    }
+}
@@ -0,0 +1,7 @@
+// `std::time::Instant::now` and `SystemTime::now` panic on
+// `wasm32-unknown-unknown`, so browser builds use `web-time`'s clocks
+// instead, which are `std`'s clocks on every other target.
+#[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
+pub(crate) use std::time::{Instant, SystemTime, UNIX_EPOCH};
+#[cfg(all(target_family = "wasm", target_os = "unknown"))]
+pub(crate) use web_time::{Instant, SystemTime, UNIX_EPOCH};
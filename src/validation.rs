@@ -0,0 +1,85 @@
+use crate::{validate_path, BadPath, LineFilter};
+use std::path::Path;
+
+/// How strictly a [`LineFilter`] checks the file paths in its entries.
+///
+/// With [`Strict`] validation, which is the default, the paths passed to
+/// [`enable_by_file`] and the filter's other file-based methods must be
+/// absolute paths to Rust source code files, since those are the only paths
+/// that [`std::file!()`] reports for crates built outside of a workspace, and
+/// a path that breaks these rules is usually a mistake. With [`Relaxed`]
+/// validation, any path is accepted as long as it is valid UTF-8 (or [lossy
+/// paths] are allowed), and is compared with the paths in spans' and
+/// events' metadata as-is. This lets quick scripts pin workspace crates'
+/// files by the relative paths that `file!()` reports for them, such as
+/// `src/db.rs`, at the cost of silently accepting paths that can never
+/// match.
+///
+/// A relaxed filter's [`Display`] output may include relative paths, which
+/// only parse again with [`try_add_directives`] on a relaxed filter.
+///
+/// # Examples
+///
+/// ```
+/// use tracing_line_filter::{LineFilter, Validation};
+///
+/// let mut filter = LineFilter::default();
+/// assert!(filter.enable_by_file("src/db.rs", 42).is_err());
+///
+/// filter
+///     .with_validation(Validation::Relaxed)
+///     .enable_by_file_or_panic("src/db.rs", 42)
+///     .add_directives("src/db.rs@200");
+/// assert_eq!(filter.to_string(), "src/db.rs@42,src/db.rs@200");
+/// ```
+///
+/// [`Strict`]: Validation::Strict
+/// [`Relaxed`]: Validation::Relaxed
+/// [`enable_by_file`]: LineFilter::enable_by_file
+/// [lossy paths]: LineFilter::allow_lossy_paths
+/// [`Display`]: std::fmt::Display
+/// [`try_add_directives`]: LineFilter::try_add_directives
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Validation {
+    /// File paths must be absolute paths to Rust source code files.
+    #[default]
+    Strict,
+    /// File paths may be any valid UTF-8 path.
+    Relaxed,
+}
+
+impl LineFilter {
+    /// Sets how strictly this filter checks the file paths in its entries.
+    ///
+    /// This applies to the paths passed to [`enable_by_file`],
+    /// [`deny_by_file`], [`with_files`], and their panicking `_or_panic`
+    /// variants, and to the `File` directives added by [`add_directive`],
+    /// [`try_add_directives`], [`define_profile`], and [`set_config`].
+    /// Entries that were already added are kept either way.
+    ///
+    /// [`enable_by_file`]: Self::enable_by_file
+    /// [`deny_by_file`]: Self::deny_by_file
+    /// [`with_files`]: Self::with_files
+    /// [`add_directive`]: Self::add_directive
+    /// [`try_add_directives`]: Self::try_add_directives
+    /// [`define_profile`]: Self::define_profile
+    /// [`set_config`]: Self::set_config
+    pub fn with_validation(&mut self, validation: Validation) -> &mut Self {
+        self.validation = validation;
+        self
+    }
+}
+
+// === impl Validation ===
+
+impl Validation {
+    /// Checks that `file` can be used in an entry, returning it as a string.
+    pub(crate) fn check_path(self, file: &Path) -> Result<&str, BadPath> {
+        match self {
+            Validation::Strict => validate_path(file),
+            Validation::Relaxed => file
+                .to_str()
+                .ok_or_else(|| BadPath::new(file, "file paths must be valid utf-8")),
+        }
+    }
+}
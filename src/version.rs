@@ -0,0 +1,181 @@
+use crate::{FilterConfig, ParseError, Warning, WarningKind};
+
+/// The version of the config format written by this version of the crate.
+const CURRENT: u32 = 2;
+
+/// A config that has been migrated to the current format by
+/// [`FilterConfig::migrate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Migration {
+    config: String,
+    from: u32,
+    /// The number of the line in the original config that the migrated
+    /// config's second line (the first one after its version line) came
+    /// from.
+    first_line: usize,
+    warnings: Vec<Warning>,
+}
+
+impl FilterConfig {
+    /// The version of the config format that this version of
+    /// `tracing-line-filter` reads and writes.
+    ///
+    /// Configs written by this crate, such as by the `save` [`Command`],
+    /// start with a `#! version = N` line recording the version of the
+    /// format they were written in. Since the line starts with `#`, older
+    /// versions of the crate ignore it as a comment. Configs without the
+    /// line are treated as version 1.
+    ///
+    /// [`Command`]: crate::Command
+    pub const VERSION: u32 = CURRENT;
+
+    /// Migrates a config written in an older version of the config format
+    /// to the current version.
+    ///
+    /// Parsing a config (as a [`LineFilter`], a `FilterConfig`, or from a
+    /// file named by a command-line flag) migrates it automatically,
+    /// recording each of the migration's warnings as a `WARN` event, or
+    /// returning them as [`WarningKind::Deprecated`] warnings from
+    /// [`LineFilter::parse_with_warnings`]. Use this to find out what
+    /// changed, or to rewrite a saved config in the current format.
+    ///
+    /// The format has had the following versions:
+    ///
+    /// 1. The original format.
+    /// 2. Adds `[profile.NAME]` headers and `when = { ... }` clauses.
+    ///    A line containing only a bracketed name, which version 1 read
+    ///    as an [`EnvFilter`] span directive, is now a profile header, so
+    ///    these lines are rewritten with an explicit level (`[my_span]`
+    ///    becomes `[my_span]=trace`, which means the same thing).
+    ///    `[profile.NAME]` lines are treated as profile headers even in
+    ///    version 1 configs, since profiles are far more likely than spans
+    ///    named `profile.NAME`. The same goes for the `[startup]` header,
+    ///    added later, so that hand-written configs, which rarely have a
+    ///    version line, can use it.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if `config`'s version line is invalid, or
+    /// names a version newer than [`FilterConfig::VERSION`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_line_filter::FilterConfig;
+    ///
+    /// let migration = FilterConfig::migrate("[request]\nmy_crate::db@142\n").unwrap();
+    /// assert_eq!(migration.from_version(), 1);
+    /// assert_eq!(
+    ///     migration.config(),
+    ///     "#! version = 2\n[request]=trace\nmy_crate::db@142\n"
+    /// );
+    /// assert_eq!(migration.warnings().len(), 1);
+    /// ```
+    ///
+    /// [`LineFilter`]: crate::LineFilter
+    /// [`LineFilter::parse_with_warnings`]: crate::LineFilter::parse_with_warnings
+    /// [`EnvFilter`]: tracing_subscriber::EnvFilter
+    pub fn migrate(config: &str) -> Result<Migration, ParseError> {
+        let (from, body) = read_version(config)?;
+        // The number of the body's first line in `config`.
+        let first_line = if body.len() < config.len() { 2 } else { 1 };
+        let mut migration = Migration {
+            config: format!("{}\n", stamp()),
+            from,
+            first_line,
+            warnings: Vec::new(),
+        };
+        for (i, line) in body.split_inclusive('\n').enumerate() {
+            let code = line.split('#').next().unwrap_or_default().trim();
+            if from < 2 && is_span_line(code) {
+                let migrated = line.replacen(code, &format!("{}=trace", code), 1);
+                migration.warnings.push(Warning::new(
+                    first_line + i,
+                    WarningKind::Deprecated,
+                    format!(
+                        "`{}` is now written `{}=trace`, since lines containing only a \
+                         bracketed name are profile headers",
+                        code, code
+                    ),
+                ));
+                migration.config.push_str(&migrated);
+            } else {
+                migration.config.push_str(line);
+            }
+        }
+        Ok(migration)
+    }
+}
+
+/// Returns the line that stamps a config with the current format version.
+pub(crate) fn stamp() -> String {
+    format!("#! version = {}", CURRENT)
+}
+
+/// Returns a config's version, and the config with its version line
+/// removed.
+fn read_version(config: &str) -> Result<(u32, &str), ParseError> {
+    let (first, rest) = match config.split_once('\n') {
+        Some(split) => split,
+        None => (config, ""),
+    };
+    let version = first
+        .trim()
+        .strip_prefix("#!")
+        .and_then(|rest| rest.trim_start().strip_prefix("version"))
+        .and_then(|rest| rest.trim_start().strip_prefix('='));
+    let version = match version {
+        Some(version) => version.trim(),
+        None => return Ok((1, config)),
+    };
+    match version.parse::<u32>() {
+        Ok(version) if (1..=CURRENT).contains(&version) => Ok((version, rest)),
+        Ok(version) if version > CURRENT => Err(ParseError::syntax(
+            first.trim(),
+            "this config was written by a newer version of `tracing-line-filter`",
+        )),
+        _ => Err(ParseError::syntax(
+            first.trim(),
+            "expected a version number",
+        )),
+    }
+}
+
+/// Returns `true` if `line` contains only a bracketed name that isn't a
+/// section header, which version 1 read as a span directive.
+fn is_span_line(line: &str) -> bool {
+    line.starts_with('[')
+        && line.ends_with(']')
+        && !line.starts_with("[profile.")
+        && line != "[startup]"
+}
+
+// === impl Migration ===
+
+impl Migration {
+    /// Returns the migrated config, starting with a line stamping it with
+    /// the current version.
+    pub fn config(&self) -> &str {
+        &self.config
+    }
+
+    /// Returns the version of the format the config was written in.
+    pub fn from_version(&self) -> u32 {
+        self.from
+    }
+
+    /// Returns a [`WarningKind::Deprecated`] warning describing each change
+    /// made to the config.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Returns the number of the line in the original config that line
+    /// `line` of the migrated config came from, or `None` if it is the
+    /// migrated config's version line.
+    ///
+    /// Both line numbers start at 1.
+    pub(crate) fn original_line(&self, line: usize) -> Option<usize> {
+        line.checked_sub(2).map(|offset| self.first_line + offset)
+    }
+}
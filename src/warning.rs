@@ -0,0 +1,84 @@
+use std::fmt;
+
+/// A problem found while parsing a config that didn't stop it from being
+/// parsed, returned by [`LineFilter::parse_with_warnings`] and
+/// [`FilterConfig::parse_with_warnings`].
+///
+/// Parsing a config with [`FromStr`] records each of these as a `WARN`
+/// event instead.
+///
+/// The `Display` implementation formats the warning with its line number,
+/// such as ``line 3: unknown `when` key `cluster`; the line is skipped``.
+///
+/// [`LineFilter::parse_with_warnings`]: crate::LineFilter::parse_with_warnings
+/// [`FilterConfig::parse_with_warnings`]: crate::FilterConfig::parse_with_warnings
+/// [`FromStr`]: std::str::FromStr
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Warning {
+    line: usize,
+    kind: WarningKind,
+    message: String,
+}
+
+/// What kind of problem a [`Warning`] is about.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WarningKind {
+    /// The config uses syntax from an older version of the config format,
+    /// which was migrated to the current version. See
+    /// [`FilterConfig::migrate`](crate::FilterConfig::migrate) for details.
+    Deprecated,
+    /// The config has a key that this version of the crate doesn't know
+    /// about, such as a `when` clause key added in a newer version. A line
+    /// with an unknown `when` key is skipped, as if its condition wasn't met,
+    /// and an unknown `#!` line is ignored.
+    UnknownKey,
+    /// A file path in one of the config's directives has `.` or `..`
+    /// components, which the paths reported by [`std::file!()`] usually
+    /// don't, so the directive probably never matches.
+    SuspiciousPath,
+}
+
+// === impl Warning ===
+
+impl Warning {
+    pub(crate) fn new(line: usize, kind: WarningKind, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /// Returns the number of the line in the config that this warning is
+    /// about, starting at 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns what kind of problem this warning is about.
+    pub fn kind(&self) -> WarningKind {
+        self.kind
+    }
+
+    /// Returns a description of the problem, without the line number.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Records this warning as a `WARN` event.
+    pub(crate) fn record(&self) {
+        tracing::warn!(
+            target: "tracing_line_filter",
+            line = self.line as u64,
+            "parsing config: {}",
+            self.message,
+        );
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
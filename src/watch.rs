@@ -0,0 +1,392 @@
+use crate::{audit, FilterConfig, FilterDiff, LineFilter};
+use std::{fmt, path::Path};
+#[cfg(feature = "tokio")]
+use std::{fs, io, path::PathBuf, time::Duration};
+#[cfg(feature = "tokio")]
+use tokio::{sync::watch, time::MissedTickBehavior};
+use tracing_core::Subscriber;
+use tracing_subscriber::reload;
+
+/// The transport that changes made by `from_watch` and `from_file` are
+/// recorded with.
+#[cfg(feature = "tokio")]
+const TRANSPORT: &str = "watch";
+
+#[cfg(feature = "tokio")]
+impl LineFilter {
+    /// Returns a filter whose directives follow the [`FilterConfig`]s sent
+    /// over a [`watch`] channel.
+    ///
+    /// The filter starts out with the channel's current config. This spawns
+    /// a task that applies each new config to the filter as it is sent,
+    /// using [`LineFilter::set_config`], until the sender is dropped or the
+    /// subscriber containing the filter is. The filter is wrapped in a
+    /// [`reload`] layer so that changes take effect immediately; its
+    /// [`Handle`] can still be used to change the filter in other ways.
+    ///
+    /// Each config that is applied is recorded as an audit event with the
+    /// `watch` transport, which includes the [`FilterDiff`] between the
+    /// filter's old directives and its new ones; see [`Command::apply`] for
+    /// details. Configs with an invalid `File` directive path are skipped:
+    /// the filter keeps its previous config, an `ERROR` audit event is
+    /// recorded with the error, and the failure is reported by the filter's
+    /// [`health_snapshot`] until a config is applied. Configs parsed from
+    /// strings never have invalid paths.
+    ///
+    /// This method is only available when the `tokio` feature flag is
+    /// enabled.
+    ///
+    /// # Panics
+    ///
+    /// This panics if it is called outside of a Tokio runtime.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tokio::sync::watch;
+    /// use tracing_line_filter::{FilterConfig, LineFilter};
+    /// use tracing_subscriber::prelude::*;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let config: FilterConfig = "my_crate::db@142".parse().unwrap();
+    /// let (tx, rx) = watch::channel(config);
+    ///
+    /// tracing_subscriber::registry()
+    ///     .with(tracing_subscriber::fmt::layer())
+    ///     .with(LineFilter::from_watch(rx))
+    ///     .init();
+    ///
+    /// // Later, when the application's config changes...
+    /// tx.send("my_crate::db@200,warn".parse().unwrap()).unwrap();
+    /// # }
+    /// ```
+    ///
+    /// [`watch`]: tokio::sync::watch
+    /// [`reload`]: tracing_subscriber::reload
+    /// [`Handle`]: tracing_subscriber::reload::Handle
+    /// [`Command::apply`]: crate::Command::apply
+    /// [`health_snapshot`]: Self::health_snapshot
+    pub fn from_watch<S>(mut rx: watch::Receiver<FilterConfig>) -> reload::Layer<Self, S>
+    where
+        S: Subscriber + 'static,
+    {
+        let mut filter = Self::new();
+        if let Err(error) = filter.set_config(&rx.borrow_and_update()) {
+            record_rejected(TRANSPORT, None, &error, None);
+        }
+        let (layer, handle) = reload::Layer::new(filter);
+
+        tokio::spawn(async move {
+            while rx.changed().await.is_ok() {
+                let config = rx.borrow_and_update().clone();
+                if !apply(&handle, TRANSPORT, &config, None) {
+                    // The subscriber was dropped.
+                    break;
+                }
+            }
+        });
+
+        layer
+    }
+
+    /// Returns a filter whose directives follow the config file at `path`,
+    /// which is read again every `interval`.
+    ///
+    /// The filter starts out with the file's current config, or with no
+    /// directives if the file doesn't exist yet. Whenever the file's
+    /// contents change, the new config is applied as if it were sent to
+    /// [`from_watch`], so each change is recorded as an audit event.
+    ///
+    /// If the file can't be read or parsed, the filter keeps the last config
+    /// that could be, rather than dropping its directives, until the file is
+    /// fixed. The same goes for a file that is removed once a config has been
+    /// read from it, such as while a ConfigMap volume is briefly unmounted:
+    /// only a file that has never existed means an empty config. An `ERROR`
+    /// audit event is recorded with the file's `path`, the
+    /// `error`, and (for a parse error) the `directive` that couldn't be
+    /// parsed, and the failure is reported by the filter's
+    /// [`health_snapshot`]. Warnings found while parsing the file, such as
+    /// unknown keys, are recorded as `WARN` events and don't stop it from
+    /// being applied.
+    ///
+    /// The file is polled rather than watched for changes, and `path` is
+    /// resolved again each time it is read, following any symbolic links.
+    /// This is what makes it work with Kubernetes ConfigMap volumes, which
+    /// update a mounted file by atomically switching a symbolic link in its
+    /// directory to a new copy, without changing the file that a watch on
+    /// `path` would see. Polling once every few seconds is cheap, since the
+    /// file is only parsed when it changes. The worker processes of a
+    /// pre-forking server can also use this to follow the config that their
+    /// primary process [publishes].
+    ///
+    /// This method is only available when the `tokio` feature flag is
+    /// enabled.
+    ///
+    /// # Panics
+    ///
+    /// This panics if it is called outside of a Tokio runtime, or if
+    /// `interval` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use tracing_line_filter::LineFilter;
+    /// use tracing_subscriber::prelude::*;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// tracing_subscriber::registry()
+    ///     .with(tracing_subscriber::fmt::layer())
+    ///     // A file from a ConfigMap mounted at `/etc/my-app`.
+    ///     .with(LineFilter::from_file("/etc/my-app/lines.conf", Duration::from_secs(5)))
+    ///     .init();
+    /// # }
+    /// ```
+    ///
+    /// [`from_watch`]: Self::from_watch
+    /// [`health_snapshot`]: Self::health_snapshot
+    /// [publishes]: Self::publish_to
+    pub fn from_file<S>(path: impl Into<PathBuf>, interval: Duration) -> reload::Layer<Self, S>
+    where
+        S: Subscriber + 'static,
+    {
+        let path = path.into();
+        let mut filter = Self::new();
+        let mut last = None;
+        match read_config(&path) {
+            Ok(text) => match text.as_deref().map(parse_config).transpose() {
+                Ok(config) => {
+                    let config = config.unwrap_or_default();
+                    filter.set_config(&config).expect("paths were validated");
+                    last = text;
+                }
+                Err(error) => {
+                    // The file is parsed again on the first tick, so the
+                    // error is recorded once the subscriber is set up.
+                    filter.reloaded(Some(error.to_string()));
+                }
+            },
+            Err(error) => filter.reloaded(Some(error.to_string())),
+        }
+        let (layer, handle) = reload::Layer::new(filter);
+
+        let mut ticks = tokio::time::interval(interval);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        tokio::spawn(async move {
+            // Only the first of several failed reads in a row is recorded.
+            let mut unreadable = false;
+            loop {
+                ticks.tick().await;
+                if handle.with_current(|_| ()).is_err() {
+                    // The subscriber was dropped.
+                    break;
+                }
+                let read = {
+                    let path = path.clone();
+                    tokio::task::spawn_blocking(move || read_config(&path)).await
+                };
+                let text = match read {
+                    Ok(Ok(None)) if last.is_some() => Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        "the config file was removed",
+                    )),
+                    Ok(read) => read,
+                    // The runtime is shutting down.
+                    Err(_) => break,
+                };
+                let text = match text {
+                    Ok(text) => text,
+                    Err(error) => {
+                        if !unreadable && !reject(&handle, TRANSPORT, Some(&path), &error, None) {
+                            break;
+                        }
+                        unreadable = true;
+                        continue;
+                    }
+                };
+                let recovered = std::mem::replace(&mut unreadable, false);
+                if text == last {
+                    // The file is back as it was, so the failure to read it
+                    // is over.
+                    if recovered && handle.modify(|filter| filter.reloaded(None)).is_err() {
+                        break;
+                    }
+                    continue;
+                }
+                let applied = match text.as_deref().map(parse_config).transpose() {
+                    Ok(config) => {
+                        apply(&handle, TRANSPORT, &config.unwrap_or_default(), Some(&path))
+                    }
+                    Err(error) => reject(
+                        &handle,
+                        TRANSPORT,
+                        Some(&path),
+                        &error,
+                        Some(error.directive()),
+                    ),
+                };
+                if !applied {
+                    // The subscriber was dropped.
+                    break;
+                }
+                last = text;
+            }
+        });
+
+        layer
+    }
+}
+
+/// Replaces the config of the filter behind `handle`, read from the file at
+/// `path` if there is one, and records the change as made over `transport`.
+/// Returns `false` if the subscriber containing the filter was dropped.
+pub(crate) fn apply<S>(
+    handle: &reload::Handle<LineFilter, S>,
+    transport: &str,
+    config: &FilterConfig,
+    path: Option<&Path>,
+) -> bool
+where
+    S: Subscriber,
+{
+    let mut result = Ok(());
+    let mut entries = (0, 0);
+    let mut evicted = Vec::new();
+    let mut diff = FilterDiff::default();
+    let modified = handle.modify(|filter| {
+        entries.0 = filter.entry_count();
+        let before = filter.diffable_directives();
+        result = filter.set_config(config).map(|_| ());
+        diff = FilterDiff::between(before, filter.diffable_directives());
+        entries.1 = filter.entry_count();
+        evicted = filter.take_evicted();
+    });
+    if modified.is_err() {
+        return false;
+    }
+    // Record events outside of `modify`, since the filter can't be used
+    // while it's being modified.
+    match result {
+        Ok(()) => audit::Change {
+            transport,
+            peer: None,
+            change: &format_args!("set config {} ({})", config, diff),
+            entries_before: entries.0,
+            entries_after: entries.1,
+            evicted: &evicted,
+        }
+        .record(),
+        // `set_config` has already recorded the failure for the filter's
+        // health snapshot.
+        Err(error) => record_rejected(transport, path, &error, None),
+    }
+    true
+}
+
+/// Records that the filter behind `handle` kept its config, because a new
+/// one couldn't be read or parsed over `transport`. Returns `false` if the
+/// subscriber containing the filter was dropped.
+pub(crate) fn reject<S>(
+    handle: &reload::Handle<LineFilter, S>,
+    transport: &str,
+    path: Option<&Path>,
+    error: &dyn fmt::Display,
+    directive: Option<&str>,
+) -> bool
+where
+    S: Subscriber,
+{
+    let error = error.to_string();
+    if handle
+        .modify(|filter| filter.reloaded(Some(error.clone())))
+        .is_err()
+    {
+        return false;
+    }
+    record_rejected(transport, path, &error, directive);
+    true
+}
+
+/// Reads the config file at `path`, returning `None` if it doesn't exist.
+#[cfg(feature = "tokio")]
+fn read_config(path: &Path) -> io::Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(Some(text)),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+pub(crate) fn parse_config(text: &str) -> Result<FilterConfig, crate::ParseError> {
+    let (config, warnings) = FilterConfig::parse_with_warnings(text)?;
+    for warning in &warnings {
+        warning.record();
+    }
+    Ok(config)
+}
+
+/// Records an `ERROR` audit event for a config that couldn't be applied.
+///
+/// This must not be called while the filter is being modified, since the
+/// event can't be filtered until the modification is done.
+fn record_rejected(
+    transport: &str,
+    path: Option<&Path>,
+    error: &dyn fmt::Display,
+    directive: Option<&str>,
+) {
+    tracing::error!(
+        target: audit::TARGET,
+        transport,
+        path = path.map(|path| tracing::field::display(path.display())),
+        directive,
+        %error,
+        "failed to reload the filter config; keeping the previous config",
+    );
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+    use tracing_subscriber::{prelude::*, Registry};
+
+    #[test]
+    fn a_removed_file_keeps_the_previous_config() {
+        let dir = std::env::temp_dir().join(format!("line-filter-watch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("lines.conf");
+        fs::write(&path, "m@1\n").unwrap();
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let layer = LineFilter::from_file::<Registry>(&path, Duration::from_millis(10));
+            let handle = layer.handle();
+            let _subscriber = tracing_subscriber::registry().with(layer);
+            let settle = || tokio::time::sleep(Duration::from_millis(100));
+            let current = || handle.with_current(|filter| filter.to_string()).unwrap();
+            let health = || handle.with_current(LineFilter::health_snapshot).unwrap();
+            settle().await;
+            assert_eq!(current(), "m@1");
+
+            fs::remove_file(&path).unwrap();
+            settle().await;
+            assert_eq!(current(), "m@1");
+            let error = health().last_reload().unwrap().error().unwrap().to_owned();
+            assert!(error.contains("removed"), "{}", error);
+
+            fs::write(&path, "m@1\n").unwrap();
+            settle().await;
+            assert!(health().last_reload().unwrap().error().is_none());
+
+            fs::write(&path, "m@2\n").unwrap();
+            settle().await;
+            assert_eq!(current(), "m@2");
+        });
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
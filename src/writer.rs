@@ -0,0 +1,112 @@
+use crate::LineFilter;
+use std::fmt;
+use tracing_core::{Metadata, Subscriber};
+use tracing_subscriber::{
+    fmt::{writer::EitherWriter, MakeWriter},
+    reload,
+};
+
+/// A [`MakeWriter`] that sends the spans and events that a [`LineFilter`]'s
+/// entries match to one writer, and everything else to another.
+///
+/// Pinned lines are usually enabled to debug a particular problem, and their
+/// output is easily lost among a program's regular logs. Using a
+/// `MatchedWriter` as an [`fmt`] layer's writer sends the output from the
+/// lines that the filter behind a [`reload`] handle enables with its
+/// `module@line`, `file@line`, `module@level`, and `module::**` entries to a
+/// dedicated writer, such as a debug log file, and everything else
+/// (including output enabled by the filter's [`EnvFilter`]) to the
+/// program's normal writer.
+///
+/// The filter's current entries are checked each time a writer is made, so
+/// changes made through the handle take effect immediately. Output is sent
+/// to the normal writer once the filter's entries [expire] or are
+/// [disabled], or if the subscriber containing the filter has been dropped.
+///
+/// # Examples
+///
+/// ```
+/// use std::{fs::File, sync::Mutex};
+/// use tracing_line_filter::{LineFilter, MatchedWriter};
+/// use tracing_subscriber::{prelude::*, reload};
+///
+/// # let dir = std::env::temp_dir();
+/// # let path = dir.join("pinned.log");
+/// let filter: LineFilter = "my_crate::db@142,info".parse().unwrap();
+/// let (filter, handle) = reload::Layer::new(filter);
+///
+/// // `my_crate::db@142` is written to `pinned.log`, and `INFO` events are
+/// // written to stdout.
+/// let pinned = Mutex::new(File::create(path).unwrap());
+/// let writer = MatchedWriter::new(handle, pinned, std::io::stdout);
+///
+/// tracing_subscriber::registry()
+///     .with(tracing_subscriber::fmt::layer().with_writer(writer))
+///     .with(filter)
+///     .init();
+/// ```
+///
+/// [`fmt`]: tracing_subscriber::fmt
+/// [`reload`]: tracing_subscriber::reload
+/// [`EnvFilter`]: tracing_subscriber::EnvFilter
+/// [expire]: LineFilter::expire_at
+/// [disabled]: LineFilter::disable_all
+pub struct MatchedWriter<M, D> {
+    /// Checks the filter behind the handle, which is boxed so that the
+    /// writer's type doesn't depend on the subscriber's.
+    is_matched: Box<dyn Fn(&Metadata<'_>) -> bool + Send + Sync>,
+    matched: M,
+    default: D,
+}
+
+impl<M, D> MatchedWriter<M, D> {
+    /// Returns a `MatchedWriter` that writes the output matched by the
+    /// entries of the filter behind `handle` to `matched`, and all other
+    /// output to `default`.
+    pub fn new<S>(handle: reload::Handle<LineFilter, S>, matched: M, default: D) -> Self
+    where
+        S: Subscriber,
+    {
+        let is_matched = move |metadata: &Metadata<'_>| {
+            handle
+                .with_current(|filter| filter.decide(metadata) == Some(true))
+                .unwrap_or(false)
+        };
+        Self {
+            is_matched: Box::new(is_matched),
+            matched,
+            default,
+        }
+    }
+}
+
+impl<'a, M, D> MakeWriter<'a> for MatchedWriter<M, D>
+where
+    M: MakeWriter<'a>,
+    D: MakeWriter<'a>,
+{
+    type Writer = EitherWriter<M::Writer, D::Writer>;
+
+    /// Returns the default writer, since there is no metadata to check
+    /// against the filter's entries.
+    fn make_writer(&'a self) -> Self::Writer {
+        EitherWriter::B(self.default.make_writer())
+    }
+
+    fn make_writer_for(&'a self, metadata: &Metadata<'_>) -> Self::Writer {
+        if (self.is_matched)(metadata) {
+            EitherWriter::A(self.matched.make_writer_for(metadata))
+        } else {
+            EitherWriter::B(self.default.make_writer_for(metadata))
+        }
+    }
+}
+
+impl<M: fmt::Debug, D: fmt::Debug> fmt::Debug for MatchedWriter<M, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MatchedWriter")
+            .field("matched", &self.matched)
+            .field("default", &self.default)
+            .finish_non_exhaustive()
+    }
+}